@@ -24,12 +24,23 @@ struct Args {
     /// Output file
     #[arg(short, long)]
     output: PathBuf,
+
+    /// Password of an encrypted input file
+    #[arg(long, default_value = "")]
+    password: String,
 }
 
 fn main() -> Result<(), PdfError> {
     let args = Args::parse();
     
-    let old_file = FileOptions::cached().open(&args.input).unwrap();
+    let old_file = match FileOptions::cached().password(args.password.as_bytes()).open(&args.input) {
+        Ok(file) => file,
+        Err(e) if e.is_invalid_password() => {
+            eprintln!("wrong password for {}", args.input.display());
+            std::process::exit(1);
+        }
+        Err(e) => return Err(e),
+    };
     let old_page = old_file.get_page(args.page).expect("no such page");
     
     let mut builder = PdfBuilder::new(FileOptions::cached());
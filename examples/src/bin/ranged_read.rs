@@ -0,0 +1,92 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use pdf::{
+    backend::{Backend, IndexRange},
+    error::{PdfError, Result},
+    file::FileOptions,
+};
+
+use clap::Parser;
+
+/// Open a PDF the way a web viewer would with HTTP range requests,
+/// printing each range that is fetched.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Input file, standing in for the remote file
+    #[arg(short, long)]
+    input: PathBuf,
+}
+
+/// Fetched ranges.
+///
+/// `Backend::read` returns slices that borrow the backend, so the data can't move or be freed
+/// while the backend lives. It is leaked to keep this example short. A long running program
+/// would put it in an arena owned by the backend instead.
+type Chunks = Mutex<Vec<(Range<usize>, &'static [u8])>>;
+
+/// A backend that fetches the ranges it is asked for.
+///
+/// `fetch` would send `Range: bytes=start-(end-1)`, the length comes from
+/// a `HEAD` request (`Content-Length`).
+struct RangedBackend<F> {
+    len: usize,
+    fetch: F,
+    chunks: Chunks,
+}
+impl<F: Fn(Range<usize>) -> std::io::Result<Vec<u8>>> RangedBackend<F> {
+    fn new(len: usize, fetch: F) -> Self {
+        RangedBackend { len, fetch, chunks: Mutex::new(vec![]) }
+    }
+}
+impl<F: Fn(Range<usize>) -> std::io::Result<Vec<u8>>> Backend for RangedBackend<F> {
+    fn read<T: IndexRange>(&self, range: T) -> Result<&[u8]> {
+        let range = range.to_range(self.len)?;
+        let mut chunks = self.chunks.lock().unwrap();
+        let known = chunks.iter().find(|(r, _)| r.start <= range.start && range.end <= r.end);
+        if let Some(&(ref r, data)) = known {
+            return Ok(&data[range.start - r.start .. range.end - r.start]);
+        }
+        let data = (self.fetch)(range.clone())?;
+        if data.len() != range.len() {
+            return Err(PdfError::Other { msg: format!("short read for {:?}", range) });
+        }
+        let data: &'static [u8] = Box::leak(data.into_boxed_slice());
+        chunks.push((range, data));
+        Ok(data)
+    }
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+fn main() -> Result<(), PdfError> {
+    let args = Args::parse();
+
+    let file = Mutex::new(std::fs::File::open(&args.input)?);
+    let len = file.lock().unwrap().metadata()?.len() as usize;
+    let fetched = AtomicUsize::new(0);
+    let backend = RangedBackend::new(len, |range: Range<usize>| {
+        println!("GET bytes={}-{}", range.start, range.end - 1);
+        fetched.fetch_add(range.len(), Ordering::Relaxed);
+        let mut file = file.lock().unwrap();
+        file.seek(SeekFrom::Start(range.start as u64))?;
+        let mut data = vec![0; range.len()];
+        file.read_exact(&mut data)?;
+        Ok(data)
+    });
+
+    let pdf = FileOptions::cached().load(backend)?;
+    if pdf.linearization().is_some() {
+        println!("linearized file");
+    }
+    let page = pdf.open_first_page_fast()?;
+    println!("first page: {:?}", page.media_box()?);
+    println!("fetched {} of {} bytes", fetched.load(Ordering::Relaxed), len);
+
+    Ok(())
+}
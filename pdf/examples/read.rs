@@ -50,6 +50,7 @@ fn main() -> Result<(), PdfError> {
         let page = page.unwrap();
         let resources = page.resources().unwrap();
         for (i, font) in resources.fonts.values().enumerate() {
+            let font = font.load(&resolver)?;
             let name = match &font.name {
                 Some(name) => name.as_str().into(),
                 None => i.to_string(),
@@ -70,7 +71,13 @@ fn main() -> Result<(), PdfError> {
             XObject::Image(ref im) => im,
             _ => continue,
         };
-        let (mut data, filter) = img.raw_image_data(&resolver)?;
+        // a single image filter can be passed through as-is
+        let (mut data, filter) = match img.inner.filters.as_slice() {
+            [filter @ StreamFilter::DCTDecode(_)] | [filter @ StreamFilter::JPXDecode] => {
+                (img.inner.raw_data(&resolver)?, Some(filter))
+            }
+            _ => img.raw_image_data(&resolver)?,
+        };
         let ext = match filter {
             Some(StreamFilter::DCTDecode(_)) => "jpeg",
             Some(StreamFilter::JBIG2Decode(_)) => "jbig2",
@@ -15,7 +15,21 @@ use std::ops::{
 
 pub const MAX_ID: u32 = 1_000_000;
 
+/// The data of a PDF file.
+///
+/// Implemented for everything that derefs to `[u8]`. Other implementations can load the data
+/// on demand, e.g. with HTTP range requests (`examples/src/bin/ranged_read.rs` shows one).
+/// `File` first reads the end of the file to find the cross-reference table, then the
+/// header and the newest cross-reference section, and after that only the ranges of the
+/// objects it needs. Older sections are read when an object is not in the newest one.
+/// Objects and cross-reference sections are read through a window that starts small and
+/// grows until the object fits, so a range `read` does not have to cover the rest of the
+/// file.
 pub trait Backend: Sized {
+    /// The bytes in `range`, which has to be within `0 .. self.len()`.
+    ///
+    /// The slice borrows `self`, so a backend that loads data lazily has to keep everything
+    /// it returned alive (and unchanged) until it is dropped.
     fn read<T: IndexRange>(&self, range: T) -> Result<&[u8]>;
     //fn write<T: IndexRange>(&mut self, range: T) -> Result<&mut [u8]>;
     fn len(&self) -> usize;
@@ -43,23 +57,41 @@ pub trait Backend: Sized {
         // `\nPOS\n%%EOF` where POS is the position encoded as base 10 integer.
         // u64::MAX has 20 digits + \n\n(2) + %%EOF(5) = 27 bytes max.
 
-        let mut lexer = Lexer::new(t!(self.read(..)));
-        lexer.set_pos_from_end(0);
-        t!(lexer.seek_substr_back(b"startxref"));
-        t!(lexer.next()).to::<usize>()
+        // usually within the last KB, but there may be garbage after `%%EOF`
+        let tail = self.len().saturating_sub(1024);
+        let find = |start: usize| -> Result<usize> {
+            let mut lexer = Lexer::new(t!(self.read(start ..)));
+            lexer.set_pos_from_end(0);
+            t!(lexer.seek_substr_back(b"startxref"));
+            t!(lexer.next()).to::<usize>()
+        };
+        match find(tail) {
+            Err(_) if tail > 0 => find(0),
+            r => r
+        }
     }
 
     /// Used internally by File, but could also be useful for applications that want to look at the raw PDF objects.
+    ///
+    /// Reads the whole `/Prev` chain. Returns the trailer of the newest section.
     fn read_xref_table_and_trailer(&self, start_offset: usize, resolve: &impl Resolve) -> Result<(XRefTable, Dictionary)> {
+        let (xref_offset, mut refs, trailer) = t!(self.read_newest_xref_and_trailer(start_offset, resolve));
+        if let Some(prev) = trailer.get("Prev") {
+            t!(self.read_older_xrefs(start_offset, xref_offset, t!(prev.as_usize()), &mut refs, resolve));
+        }
+        Ok((refs, trailer))
+    }
+
+    /// The newest cross-reference section (the one `startxref` points to) and its trailer,
+    /// along with its offset.
+    fn read_newest_xref_and_trailer(&self, start_offset: usize, resolve: &impl Resolve) -> Result<(usize, XRefTable, Dictionary)> {
         let xref_offset = t!(self.locate_xref_offset());
         let pos = t!(start_offset.checked_add(xref_offset).ok_or(PdfError::Invalid));
         if pos >= self.len() {
             bail!("XRef offset outside file bounds");
         }
 
-        let mut lexer = Lexer::with_offset(t!(self.read(pos ..)), pos);
-        
-        let (xref_sections, trailer) = t!(read_xref_and_trailer_at(&mut lexer, resolve));
+        let (xref_sections, trailer) = t!(parse_at(self, pos, XREF_WINDOW, |lexer| read_xref_and_trailer_at(lexer, resolve)));
         
         let highest_id = t!(trailer.get("Size")
             .ok_or_else(|| PdfError::MissingEntry {field: "Size".into(), typ: "XRefTable"})?
@@ -68,28 +100,30 @@ pub trait Backend: Sized {
         if highest_id > MAX_ID {
             bail!("too many objects");
         }
-        let mut refs = XRefTable::new(highest_id as ObjNr);
+        let mut refs = XRefTable::with_size(highest_id as ObjNr);
         for section in xref_sections {
             refs.add_entries_from(section)?;
         }
-        
-        let mut prev_trailer = {
-            match trailer.get("Prev") {
-                Some(p) => Some(t!(p.as_usize())),
-                None => None
-            }
-        };
+        Ok((xref_offset, refs, trailer))
+    }
+
+    /// Add the sections of the `/Prev` chain starting at `prev` to `refs`.
+    ///
+    /// `xref_offset` is the offset of the section `prev` was read from, to detect loops.
+    fn read_older_xrefs(&self, start_offset: usize, xref_offset: usize, prev: usize, refs: &mut XRefTable, resolve: &impl Resolve) -> Result<()> {
         trace!("READ XREF AND TABLE");
-        let mut seen = vec![];
+        let mut prev_trailer = Some(prev);
+        let mut seen = vec![xref_offset];
         while let Some(prev_xref_offset) = prev_trailer {
             if seen.contains(&prev_xref_offset) {
-                bail!("xref offsets loop");
+                // keep what has been read so far
+                warn!("/Prev loops back to the xref section at {}", prev_xref_offset);
+                break;
             }
             seen.push(prev_xref_offset);
 
             let pos = t!(start_offset.checked_add(prev_xref_offset).ok_or(PdfError::Invalid));
-            let mut lexer = Lexer::with_offset(t!(self.read(pos..)), pos);
-            let (xref_sections, trailer) = t!(read_xref_and_trailer_at(&mut lexer, resolve));
+            let (xref_sections, trailer) = t!(parse_at(self, pos, XREF_WINDOW, |lexer| read_xref_and_trailer_at(lexer, resolve)));
             
             for section in xref_sections {
                 refs.add_entries_from(section)?;
@@ -105,11 +139,32 @@ pub trait Backend: Sized {
                 }
             };
         }
-        Ok((refs, trailer))
+        Ok(())
     }
 }
 
 
+/// Initial size of the window cross-reference sections are parsed from.
+const XREF_WINDOW: usize = 64 * 1024;
+
+/// Run `parse` on the data at `pos`, starting with `window` bytes.
+///
+/// As long as `parse` runs out of data (`PdfError::EOF`) and the window does not reach
+/// the end of the file yet, it is retried with a window four times as large.
+pub(crate) fn parse_at<B: Backend, T>(backend: &B, pos: usize, mut window: usize, mut parse: impl FnMut(&mut Lexer) -> Result<T>) -> Result<T> {
+    if pos > backend.len() {
+        bail!("offset {} outside of the file", pos);
+    }
+    loop {
+        let end = pos.saturating_add(window).min(backend.len());
+        let mut lexer = Lexer::with_offset(t!(backend.read(pos .. end)), pos);
+        match parse(&mut lexer) {
+            Err(e) if e.is_eof() && end < backend.len() => window = window.saturating_mul(4),
+            r => return r
+        }
+    }
+}
+
 impl<T> Backend for T where T: Deref<Target=[u8]> { //+ DerefMut<Target=[u8]> {
     fn read<R: IndexRange>(&self, range: R) -> Result<&[u8]> {
         let r = t!(range.to_range(self.len()));
@@ -0,0 +1,167 @@
+//! The extent of the content of a page, see `Page::content_bbox`.
+
+use crate::content::{ContentState, FormXObject, Matrix, Op, Point, TextMode};
+use crate::error::*;
+use crate::object::*;
+use crate::text::{walk, ShownGlyph, TextFont, Visitor, WalkState};
+
+fn transform(m: &Matrix, x: f32, y: f32) -> (f32, f32) {
+    let p = m.apply(Point { x, y });
+    (p.x, p.y)
+}
+/// The box around `points`.
+fn bounds(points: impl IntoIterator<Item=(f32, f32)>) -> Option<Rectangle> {
+    points.into_iter().fold(None, |r: Option<Rectangle>, (x, y)| {
+        let p = Rectangle { left: x, bottom: y, right: x, top: y };
+        Some(r.map_or(p, |r| r.union(&p)))
+    })
+}
+/// The box around `r` transformed by `m`.
+fn transform_rect(m: &Matrix, r: &Rectangle) -> Rectangle {
+    let corners = [(r.left, r.bottom), (r.right, r.bottom), (r.left, r.top), (r.right, r.top)];
+    bounds(corners.iter().map(|&(x, y)| transform(m, x, y))).unwrap()
+}
+
+struct BBox {
+    bbox: Option<Rectangle>,
+    /// The current path in device space, and whether it is to become the clip.
+    path: Option<Rectangle>,
+    clip: bool,
+}
+impl BBox {
+    /// Add `r` (in device space) as far as it is visible within `clip`.
+    fn paint(&mut self, r: Rectangle, clip: Option<Rectangle>) {
+        if let Some(r) = clip.and_then(|clip| clip.intersection(&r)) {
+            self.bbox = Some(self.bbox.map_or(r, |b| b.union(&r)));
+        }
+    }
+    fn add_points(&mut self, points: &[Point], ctm: &Matrix) {
+        let r = bounds(points.iter().map(|p| transform(ctm, p.x, p.y)));
+        self.path = match (self.path, r) {
+            (Some(a), Some(b)) => Some(a.union(&b)),
+            (a, b) => a.or(b),
+        };
+    }
+}
+const UNIT: Rectangle = Rectangle { left: 0., bottom: 0., right: 1., top: 1. };
+
+impl Visitor for BBox {
+    /// The clip region in device space, `None` if nothing is visible.
+    type State = Option<Rectangle>;
+
+    fn op(&mut self, op: &Op, state: &mut WalkState<Option<Rectangle>>) -> Result<()> {
+        let ctm = state.content.ctm;
+        match *op {
+            Op::MoveTo { p } | Op::LineTo { p } => self.add_points(&[p], &ctm),
+            Op::CurveTo { c1, c2, p } => self.add_points(&[c1, c2, p], &ctm),
+            Op::Rect { rect } => self.add_points(&[
+                Point { x: rect.x, y: rect.y },
+                Point { x: rect.x + rect.width, y: rect.y + rect.height },
+                Point { x: rect.x, y: rect.y + rect.height },
+                Point { x: rect.x + rect.width, y: rect.y },
+            ], &ctm),
+            Op::Clip { .. } => self.clip = true,
+            ref op if op.ends_path() => {
+                if let Some(mut r) = self.path.take() {
+                    if matches!(op, Op::Stroke | Op::FillAndStroke { .. }) {
+                        // half the line width, in device space
+                        let w = state.content.line_width.max(1.) * (ctm.a * ctm.d - ctm.b * ctm.c).abs().sqrt() / 2.;
+                        r = Rectangle { left: r.left - w, bottom: r.bottom - w, right: r.right + w, top: r.top + w };
+                    }
+                    if !matches!(op, Op::EndPath) {
+                        self.paint(r, state.extra);
+                    }
+                    if self.clip {
+                        state.extra = state.extra.and_then(|c| c.intersection(&r));
+                    }
+                }
+                self.clip = false;
+            }
+            // a shading fills the clip region
+            Op::Shade { .. } => if let Some(clip) = state.extra {
+                self.paint(clip, Some(clip));
+            }
+            Op::InlineImage { .. } => self.paint(transform_rect(&ctm, &UNIT), state.extra),
+            _ => {}
+        }
+        Ok(())
+    }
+    fn glyph(&mut self, glyph: ShownGlyph, font: &TextFont, state: &WalkState<Option<Rectangle>>) {
+        if state.content.text.mode != TextMode::Invisible {
+            let trm = state.content.text_rendering_matrix();
+            let r = Rectangle { left: 0., bottom: font.descent / 1000., right: glyph.width / 1000., top: font.ascent / 1000. };
+            self.paint(transform_rect(&trm, &r), state.extra);
+        }
+    }
+    fn image(&mut self, state: &WalkState<Option<Rectangle>>) {
+        self.paint(transform_rect(&state.content.ctm, &UNIT), state.extra);
+    }
+    fn form(&mut self, form: &FormXObject, inner: &mut WalkState<Option<Rectangle>>) {
+        let bbox = transform_rect(&inner.content.ctm, &form.dict().bbox);
+        inner.extra = inner.extra.and_then(|c| c.intersection(&bbox));
+    }
+}
+
+/// The box around everything `ops` paint within `clip`, see `Page::content_bbox`.
+pub fn content_bbox(ops: &[Op], resources: &Resources, clip: Rectangle, resolve: &impl Resolve) -> Result<Option<Rectangle>> {
+    let mut bbox = BBox { bbox: None, path: None, clip: false };
+    let mut state = WalkState { content: ContentState::default(), font: None, extra: Some(clip.normalized()) };
+    walk(&mut bbox, ops, resources, &mut state, resolve, 8)?;
+    Ok(bbox.bbox)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::parse_ops;
+    use crate::parser::{parse, ParseFlags};
+
+    fn bbox(content: &[u8]) -> Option<Rectangle> {
+        let font = b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>";
+        let font = parse(font, &NoResolve, ParseFlags::ANY).unwrap();
+        let mut resources = Resources::default();
+        resources.fonts.insert("F1".into(), Lazy::from_primitive(font, &NoResolve).unwrap());
+        let font = b"<< /Type /Font /Subtype /Type0 /BaseFont /Test /Encoding /Identity-V
+            /DescendantFonts [ << /Type /Font /Subtype /CIDFontType2 /BaseFont /Test
+                /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >>
+                /FontDescriptor << /Type /FontDescriptor /FontName /Test /Flags 4
+                    /FontBBox [ 0 -200 1000 800 ] /ItalicAngle 0 >> >> ] >>";
+        let font = parse(font, &NoResolve, ParseFlags::ANY).unwrap();
+        resources.fonts.insert("F2".into(), Lazy::from_primitive(font, &NoResolve).unwrap());
+        let ops = parse_ops(content, &NoResolve).unwrap();
+        let page = Rectangle { left: 0., bottom: 0., right: 600., top: 800. };
+        content_bbox(&ops, &resources, page, &NoResolve).unwrap()
+    }
+
+    #[test]
+    fn paths_and_clips() {
+        assert_eq!(bbox(b""), None);
+        // an unpainted path and invisible text are no ink
+        assert_eq!(bbox(b"10 10 m 100 100 l n BT 3 Tr /F1 10 Tf (a) Tj ET"), None);
+
+        let r = |left, bottom, right, top| Some(Rectangle { left, bottom, right, top });
+        assert_eq!(bbox(b"q 2 0 0 2 10 20 cm 0 0 10 5 re f Q 100 100 10 10 re f"), r(10., 20., 110., 110.));
+        // clips apply to what follows, until restored
+        assert_eq!(bbox(b"q 0 0 50 50 re W n 20 20 100 100 re f Q"), r(20., 20., 50., 50.));
+        assert_eq!(bbox(b"q 0 0 50 50 re W n Q 20 20 100 100 re f"), r(20., 20., 120., 120.));
+        // content outside the page is clipped, a shading fills the clip
+        assert_eq!(bbox(b"-10 -10 20 20 re f"), r(0., 0., 10., 10.));
+        assert_eq!(bbox(b"q 10 10 20 30 re W n /Sh1 sh Q"), r(10., 10., 30., 40.));
+        // strokes extend by half the line width
+        assert_eq!(bbox(b"4 w 10 10 m 30 10 l S"), r(8., 8., 32., 12.));
+    }
+
+    #[test]
+    fn text() {
+        // Helvetica "a" is 556 wide, with the default ascent and descent
+        let b = bbox(b"BT /F1 10 Tf 100 200 Td (aa) Tj ET").unwrap();
+        let expected = Rectangle { left: 100., bottom: 198., right: 111.12, top: 208. };
+        for (a, b) in [(b.left, expected.left), (b.bottom, expected.bottom), (b.right, expected.right), (b.top, expected.top)] {
+            assert!((a - b).abs() < 1e-3, "{:?}", b);
+        }
+
+        // vertical fonts advance by the default /DW2 of -1000, glyphs are 1000 wide
+        assert_eq!(bbox(b"BT /F2 10 Tf 100 700 Td <00410042> Tj ET"), Some(Rectangle { left: 100., bottom: 688., right: 110., top: 708. }));
+        assert_eq!(bbox(b"BT /F2 10 Tf 100 700 Td [<0041> 500 <0042>] TJ ET"), Some(Rectangle { left: 100., bottom: 683., right: 110., top: 708. }));
+    }
+}
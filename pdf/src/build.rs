@@ -19,15 +19,25 @@ use crate::error::Result;
 use crate::parser::ParseFlags;
 use crate::primitive::Dictionary;
 use crate::primitive::Primitive;
+use crate::primitive::PdfString;
+use crate::primitive::Name;
+use crate::crypt::random_id;
+use crate::enc::{encode, LZWFlateParams};
+use crate::encoding::{BaseEncoding, Encoding};
+use crate::font::*;
+use crate::sfnt::Sfnt;
 
 #[derive(Default)]
 pub struct PageBuilder {
     pub ops: Vec<Op>,
+    /// Additional content stream parts, following `ops`.
+    pub parts: Vec<Stream<()>>,
     pub media_box: Option<Rectangle>,
     pub crop_box: Option<Rectangle>,
     pub trim_box: Option<Rectangle>,
     pub resources: Resources,
     pub rotate: i32,
+    pub user_unit: Option<f32>,
     pub metadata: Option<Primitive>,
     pub lgi: Option<Primitive>,
     pub vp: Option<Primitive>,
@@ -43,11 +53,13 @@ impl PageBuilder {
     pub fn from_page(page: &Page, resolve: &impl Resolve) -> Result<PageBuilder> {
         Ok(PageBuilder {
             ops: page.contents.as_ref().map(|c| c.operations(resolve)).transpose()?.unwrap_or_default(),
+            parts: vec![],
             media_box: Some(page.media_box()?),
             crop_box: Some(page.crop_box()?),
             trim_box: page.trim_box,
             resources: (**page.resources()?.data()).clone(),
-            rotate: page.rotate,
+            rotate: page.rotate(),
+            user_unit: page.user_unit,
             metadata: page.metadata.clone(),
             lgi: page.lgi.clone(),
             vp: page.vp.clone(),
@@ -70,11 +82,13 @@ impl PageBuilder {
 
         Ok(PageBuilder {
             ops,
+            parts: vec![],
             media_box: Some(page.media_box()?),
             crop_box: Some(page.crop_box()?),
             trim_box: page.trim_box,
             resources,
-            rotate: page.rotate,
+            rotate: page.rotate(),
+            user_unit: page.user_unit,
             metadata: page.metadata.deep_clone(cloner)?,
             lgi: page.lgi.deep_clone(cloner)?,
             vp: page.vp.deep_clone(cloner)?,
@@ -89,6 +103,55 @@ impl PageBuilder {
             right: width,
         });
     }
+    pub fn media_box(mut self, media_box: Rectangle) -> Self {
+        self.media_box = Some(media_box);
+        self
+    }
+    /// Add a font to the `/Font` resources of the page.
+    pub fn add_font(mut self, name: impl Into<Name>, font: impl Into<Lazy<Font>>) -> Self {
+        self.resources.fonts.insert(name.into(), font.into());
+        self
+    }
+    /// Add an XObject to the `/XObject` resources of the page.
+    pub fn add_xobject(mut self, name: impl Into<Name>, xobject: Ref<XObject>) -> Self {
+        self.resources.xobjects.insert(name.into(), xobject);
+        self
+    }
+    /// Add a color space to the `/ColorSpace` resources of the page.
+    pub fn add_color_space(mut self, name: impl Into<Name>, color_space: ColorSpace) -> Self {
+        self.resources.color_spaces.insert(name.into(), color_space);
+        self
+    }
+    /// Append the parts of `content` as separate content streams.
+    pub fn add_content(mut self, content: Content) -> Self {
+        self.parts.extend(content.parts);
+        self
+    }
+    /// Create the page as a child of `parent`. The resources are written as a new object.
+    pub fn build(self, parent: PagesRc, update: &mut impl Updater) -> Result<Page> {
+        let mut content = if self.ops.len() > 0 || self.parts.is_empty() {
+            Content::from_ops(self.ops)
+        } else {
+            Content { parts: vec![] }
+        };
+        content.parts.extend(self.parts);
+        let resources = update.create(self.resources)?.into();
+        Ok(Page {
+            parent,
+            contents: Some(content),
+            media_box: self.media_box,
+            crop_box: self.crop_box,
+            trim_box: self.trim_box,
+            resources: Some(resources),
+            rotate: self.rotate,
+            user_unit: self.user_unit,
+            metadata: self.metadata,
+            lgi: self.lgi,
+            vp: self.vp,
+            other: self.other,
+            annotations: Default::default(),
+        })
+    }
 }
 
 pub struct CatalogBuilder {
@@ -114,26 +177,12 @@ impl CatalogBuilder {
             kids,
             resources: None,
             media_box: None,
-            crop_box: None
+            crop_box: None,
+            rotate: None,
         }, update)?;
 
         for (page, promise) in self.pages.into_iter().zip(kids_promise) {
-            let content = Content::from_ops(page.ops);
-            let resources = update.create(page.resources)?.into();
-            let page = Page {
-                parent: tree.clone(),
-                contents: Some(content),
-                media_box: page.media_box,
-                crop_box: page.crop_box,
-                trim_box: page.trim_box,
-                resources: Some(resources),
-                rotate: page.rotate,
-                metadata: page.metadata,
-                lgi: page.lgi,
-                vp: page.vp,
-                other: page.other,
-                annotations: Default::default(),
-            };
+            let page = page.build(tree.clone(), update)?;
             update.fulfill(promise, PagesNode::Leaf(page))?;
         }
 
@@ -144,18 +193,219 @@ impl CatalogBuilder {
             dests: None,
             metadata: None,
             outlines: None,
+            on_open: None,
+            additional_actions: None,
             struct_tree_root: None,
             forms: None,
             page_labels: None,
+            optional_content: None,
         })
     }
 }
 
+/// Unicode values of the WinAnsiEncoding codes 0x80 to 0x9F, the others match Latin-1 (D.2)
+const WIN_ANSI_80: [Option<char>; 32] = [
+    Some('\u{20AC}'), None, Some('\u{201A}'), Some('\u{0192}'), Some('\u{201E}'), Some('\u{2026}'), Some('\u{2020}'), Some('\u{2021}'),
+    Some('\u{02C6}'), Some('\u{2030}'), Some('\u{0160}'), Some('\u{2039}'), Some('\u{0152}'), None, Some('\u{017D}'), None,
+    None, Some('\u{2018}'), Some('\u{2019}'), Some('\u{201C}'), Some('\u{201D}'), Some('\u{2022}'), Some('\u{2013}'), Some('\u{2014}'),
+    Some('\u{02DC}'), Some('\u{2122}'), Some('\u{0161}'), Some('\u{203A}'), Some('\u{0153}'), None, Some('\u{017E}'), Some('\u{0178}'),
+];
+
+fn win_ansi_char(code: u8) -> Option<char> {
+    match code {
+        0x80 ..= 0x9F => WIN_ANSI_80[code as usize - 0x80],
+        c => Some(c as char)
+    }
+}
+
+/// Embeds a TrueType or OpenType font program as a simple font.
+///
+/// The font uses `WinAnsiEncoding`, so strings shown with it are WinAnsi encoded (plain ASCII works).
+/// The whole font program is embedded, there is no subsetting.
+pub struct FontBuilder {
+    data: Vec<u8>,
+    name: Option<String>,
+}
+impl FontBuilder {
+    pub fn new(data: impl Into<Vec<u8>>) -> FontBuilder {
+        FontBuilder {
+            data: data.into(),
+            name: None
+        }
+    }
+    /// Use `name` as /BaseFont instead of the PostScript name in the font.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+    pub fn build(self, update: &mut impl Updater) -> Result<RcRef<Font>> {
+        let font = t!(Sfnt::parse(&self.data));
+        let scale = 1000. / font.units_per_em as f32;
+
+        let first_char = 32;
+        let last_char = 255;
+        let widths = (first_char ..= last_char)
+            .map(|code| match win_ansi_char(code).and_then(|c| font.glyph(c)) {
+                Some(gid) => font.advance(gid) as f32 * scale,
+                None => 0.
+            })
+            .collect();
+
+        let name = Name::from(self.name.or_else(|| font.postscript_name.clone()).unwrap_or_else(|| "Font".into()));
+
+        let mut flags = flags::Nonsymbolic;
+        if font.fixed_pitch {
+            flags |= flags::FixedPitch;
+        }
+        if font.italic_angle != 0. {
+            flags |= flags::Italic;
+        }
+
+        let filter = StreamFilter::FlateDecode(LZWFlateParams::default());
+        let program = encode(font.data(), &filter)?;
+        let (font_file2, font_file3) = if font.cff {
+            let stream = Stream::from_compressed(FontStream3 { subtype: FontTypeExt::OpenType }, program, vec![filter]);
+            (None, Some(update.create(stream)?))
+        } else {
+            (Some(update.create(Stream::from_compressed((), program, vec![filter]))?), None)
+        };
+
+        let [x_min, y_min, x_max, y_max] = font.bbox;
+        let weight = font.weight.unwrap_or(400) as f32;
+        let descriptor = FontDescriptor {
+            font_name: name.clone(),
+            font_family: None,
+            font_stretch: None,
+            font_weight: font.weight.map(|w| w as f32),
+            flags,
+            font_bbox: Rectangle {
+                left: x_min as f32 * scale,
+                bottom: y_min as f32 * scale,
+                right: x_max as f32 * scale,
+                top: y_max as f32 * scale,
+            },
+            italic_angle: font.italic_angle,
+            ascent: Some(font.ascent as f32 * scale),
+            descent: Some(font.descent as f32 * scale),
+            leading: font.line_gap as f32 * scale,
+            cap_height: Some(font.cap_height.unwrap_or(font.ascent) as f32 * scale),
+            xheight: font.x_height.unwrap_or(0) as f32 * scale,
+            // the usual estimate from the weight class
+            stem_v: 10. + 220. * (weight - 50.) / 900.,
+            stem_h: 0.,
+            avg_width: 0.,
+            max_width: 0.,
+            missing_width: font.advance(0) as f32 * scale,
+            font_file: None,
+            font_file2,
+            font_file3,
+            char_set: None,
+        };
+
+        let info = TFont {
+            base_font: Some(name.clone()),
+            first_char: Some(first_char as i32),
+            last_char: Some(last_char as i32),
+            widths: Some(widths),
+            font_descriptor: Some(descriptor),
+        };
+        let (subtype, data) = if font.cff {
+            (FontType::Type1, FontData::Type1(info))
+        } else {
+            (FontType::TrueType, FontData::TrueType(info))
+        };
+        update.create(Font {
+            subtype,
+            name: Some(name),
+            data,
+            encoding: Some(Encoding { base: BaseEncoding::WinAnsiEncoding, differences: HashMap::new() }),
+            to_unicode: None,
+            _other: Dictionary::new(),
+        })
+    }
+}
+
+/// Creates an image XObject from 8 bit samples, going from the top left row by row.
+///
+/// The color space follows the number of channels: gray, gray and alpha, RGB or RGBA.
+/// An alpha channel is written as soft mask (`/SMask`), unless it is fully opaque.
+pub struct ImageBuilder {
+    width: u32,
+    height: u32,
+    channels: usize,
+    data: Vec<u8>,
+    interpolate: bool,
+}
+impl ImageBuilder {
+    pub fn from_samples(width: u32, height: u32, channels: usize, data: &[u8]) -> Result<ImageBuilder> {
+        if !(1 ..= 4).contains(&channels) {
+            bail!("unsupported number of channels: {}", channels);
+        }
+        let expected = width as usize * height as usize * channels;
+        if data.len() != expected {
+            bail!("expected {} bytes of image data, got {}", expected, data.len());
+        }
+        Ok(ImageBuilder { width, height, channels, data: data.to_vec(), interpolate: false })
+    }
+    pub fn from_gray(width: u32, height: u32, data: &[u8]) -> Result<ImageBuilder> {
+        Self::from_samples(width, height, 1, data)
+    }
+    pub fn from_rgb(width: u32, height: u32, data: &[u8]) -> Result<ImageBuilder> {
+        Self::from_samples(width, height, 3, data)
+    }
+    pub fn from_rgba(width: u32, height: u32, data: &[u8]) -> Result<ImageBuilder> {
+        Self::from_samples(width, height, 4, data)
+    }
+    /// Set `/Interpolate`, asking viewers to smooth the image when scaling it up.
+    pub fn interpolate(mut self, interpolate: bool) -> Self {
+        self.interpolate = interpolate;
+        self
+    }
+    pub fn build(self, update: &mut impl Updater) -> Result<Ref<XObject>> {
+        let ImageBuilder { width, height, channels, data, interpolate } = self;
+        let has_alpha = channels % 2 == 0;
+        let color_channels = if has_alpha { channels - 1 } else { channels };
+        let (color, alpha): (Vec<u8>, Vec<u8>) = if has_alpha {
+            let color = data.chunks_exact(channels).flat_map(|px| px[.. color_channels].iter().copied()).collect();
+            let alpha = data.chunks_exact(channels).map(|px| px[color_channels]).collect();
+            (color, alpha)
+        } else {
+            (data, vec![])
+        };
+
+        let filter = StreamFilter::FlateDecode(LZWFlateParams::default());
+        let image_dict = |color_space| ImageDict {
+            width,
+            height,
+            color_space: Some(color_space),
+            bits_per_component: Some(8),
+            interpolate,
+            .. Default::default()
+        };
+        let smask = if alpha.iter().any(|&a| a != 255) {
+            let data = encode(&alpha, &filter)?;
+            Some(update.create(Stream::from_compressed(image_dict(ColorSpace::DeviceGray), data, vec![filter.clone()]))?.get_ref())
+        } else {
+            None
+        };
+        let color_space = if color_channels == 1 { ColorSpace::DeviceGray } else { ColorSpace::DeviceRGB };
+        let dict = ImageDict {
+            smask,
+            .. image_dict(color_space)
+        };
+        let data = encode(&color, &filter)?;
+        let inner = Stream::from_compressed(dict, data, vec![filter]);
+        Ok(update.create(XObject::Image(ImageXObject { inner }))?.get_ref())
+    }
+}
+
 pub struct PdfBuilder<SC, OC, L> {
     pub storage: Storage<Vec<u8>, SC, OC, L>,
     pub info: Option<InfoDict>,
     pub id: Option<[String; 2]>,
 
+    // (user password, owner password, permissions)
+    encryption: Option<(Vec<u8>, Vec<u8>, i32)>,
 }
 impl<SC, OC, L> PdfBuilder<SC, OC, L>
 where
@@ -168,7 +418,8 @@ where
         PdfBuilder {
             storage,
             info: None,
-            id: None
+            id: None,
+            encryption: None,
         }
     }
     pub fn info(mut self, info: InfoDict) -> Self {
@@ -179,14 +430,32 @@ where
         self.id = Some([a, b]);
         self
     }
+    /// Encrypt the document with AES-256.
+    ///
+    /// `permissions` is the value of /P in the encryption dictionary.
+    pub fn encrypt(mut self, user_password: &[u8], owner_password: &[u8], permissions: i32) -> Self {
+        self.encryption = Some((user_password.into(), owner_password.into(), permissions));
+        self
+    }
     pub fn build(mut self, catalog: CatalogBuilder) -> Result<Vec<u8>> {
+        let encrypt_dict = match self.encryption {
+            Some((ref user, ref owner, permissions)) => Some(self.storage.encrypt(user, owner, permissions)?),
+            None => None
+        };
         let catalog = catalog.build(&mut self.storage)?;
-        
+
+        let id = match self.id {
+            Some([ref a, ref b]) => vec![a.as_str().into(), b.as_str().into()],
+            None => {
+                let id = PdfString::new(random_id()?[..].into());
+                vec![id.clone(), id]
+            }
+        };
         let mut trailer = Trailer {
             root: self.storage.create(catalog)?,
-            encrypt_dict: None,
+            encrypt_dict,
             size: 0,
-            id: vec!["foo".into(), "bar".into()],
+            id,
             info_dict: self.info,
             prev_trailer_pos: None,
         };
@@ -194,8 +463,13 @@ where
         Ok(self.storage.into_inner())
     }
 }
+/// Copies objects from another file (`resolver`) into `updater`.
+///
+/// Every source object is copied once, and references to it map to the copy, so shared
+/// resources stay shared. Use one `Importer` for everything imported from the same file.
 pub struct Importer<'a, R, U> {
     resolver: R,
+    // source ref -> new ref
     map: HashMap<PlainRef, PlainRef>,
     updater: &'a mut U,
     rcrefs: HashMap<PlainRef, AnySync>,
@@ -358,31 +632,40 @@ impl<'a, R: Resolve, U: Updater> Cloner for Importer<'a, R, U> {
             return Ok(Ref::new(new_ref));
         }
         let obj = self.resolver.get(old)?;
-        let clone = obj.deep_clone(self)?;
 
-        let r = self.updater.create(clone)?;
-        self.map.insert(old.get_inner(), r.get_ref().get_inner());
+        // reserve the new object first, so references back to `old` map to it as well
+        let promise = self.updater.promise::<T>();
+        self.map.insert(old.get_inner(), promise.get_inner());
+        let clone = obj.deep_clone(self)?;
 
-        Ok(r.get_ref())
+        Ok(self.updater.fulfill(promise, clone)?.get_ref())
     }
     fn clone_plainref(&mut self, old: PlainRef) -> Result<PlainRef> {
         if let Some(&new_ref) = self.map.get(&old) {
             return Ok(new_ref);
         }
         let obj = self.resolver.resolve(old)?;
-        let clone = obj.deep_clone(self)?;
-
-        let new = self.updater.create(clone)?
-            .get_ref().get_inner();
 
+        let promise = self.updater.promise::<Primitive>();
+        let new = promise.get_inner();
         self.map.insert(old, new);
+        let clone = obj.deep_clone(self)?;
+        self.updater.fulfill(promise, clone)?;
 
         Ok(new)
     }
     fn clone_rcref<T: DeepClone + ObjectWrite + DataSize>(&mut self, old: &RcRef<T>) -> Result<RcRef<T>> {
         let old_ref = old.get_ref().get_inner();
         if let Some(&new_ref) = self.map.get(&old_ref) {
-            let arc = self.rcrefs.get(&new_ref).unwrap().clone().downcast()?;
+            let arc = match self.rcrefs.get(&new_ref) {
+                Some(arc) => arc.clone().downcast()?,
+                // cloned through a plain reference, which keeps no data around
+                None => {
+                    let arc = Arc::new(old.data().deep_clone(self)?);
+                    self.rcrefs.insert(new_ref, AnySync::new(arc.clone()));
+                    arc
+                }
+            };
             return Ok(RcRef::new(new_ref, arc));
         }
 
@@ -402,4 +685,134 @@ impl<'a, R: Resolve, U: Updater> Cloner for Importer<'a, R, U> {
         self.shared.insert(key, (AnySync::new_without_size(old.clone()), AnySync::new_without_size(new.clone())));
         Ok(new)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::FileOptions;
+
+    #[test]
+    fn build_font() {
+        let mut builder = PdfBuilder::new(FileOptions::uncached());
+        let font = FontBuilder::new(crate::sfnt::tests::test_font()).build(&mut builder.storage).unwrap();
+
+        let mut page = PageBuilder::default();
+        page.size(100., 100.);
+        page.resources.fonts.insert("F1".into(), font.into());
+        let data = builder.build(CatalogBuilder::from_pages(vec![page])).unwrap();
+
+        let file = FileOptions::uncached().load(data).unwrap();
+        let resolver = file.resolver();
+        let page = file.get_page(0).unwrap();
+        let font = page.resources().unwrap().fonts["F1"].load(&resolver).unwrap();
+        assert_eq!(font.name.as_deref(), Some("TestFont"));
+
+        let widths = font.widths(&resolver).unwrap().unwrap();
+        assert_eq!(widths.get(b'A' as usize), 600.);
+        assert_eq!(widths.get(b'B' as usize), 700.);
+        assert_eq!(widths.get(b'C' as usize), 0.);
+
+        let descriptor = font.info().unwrap().font_descriptor.as_ref().unwrap();
+        assert_eq!(descriptor.ascent, Some(800.));
+        assert_eq!(descriptor.data(&resolver).unwrap().unwrap().len(), crate::sfnt::tests::test_font().len());
+
+        let mapping = font.glyph_mapping(&resolver).unwrap();
+        assert_eq!(mapping.gid(b'A' as u32), Some(1));
+        assert_eq!(mapping.gid(b'B' as u32), Some(2));
+        assert_eq!(mapping.gid(b'C' as u32), None);
+    }
+
+    #[test]
+    fn page_builder() {
+        let mut builder = PdfBuilder::new(FileOptions::uncached());
+        let font = FontBuilder::new(crate::sfnt::tests::test_font()).build(&mut builder.storage).unwrap();
+
+        let mut page = PageBuilder::default()
+            .media_box(Rectangle { left: 0., bottom: 0., right: 200., top: 100. })
+            .add_font("F1", font.get_ref())
+            .add_color_space("CS0", ColorSpace::DeviceRGB)
+            .add_content(Content::from_ops(vec![Op::EndText]));
+        page.ops = vec![Op::BeginText];
+        let data = builder.build(CatalogBuilder::from_pages(vec![page])).unwrap();
+
+        let file = FileOptions::uncached().load(data).unwrap();
+        let resolver = file.resolver();
+        let page = file.get_page(0).unwrap();
+        assert_eq!(page.media_box().unwrap().right, 200.);
+
+        let resources = page.resources().unwrap();
+        assert!(resources.fonts["F1"].load(&resolver).is_ok());
+        assert!(matches!(resources.color_spaces["CS0"], ColorSpace::DeviceRGB));
+
+        let content = page.contents.as_ref().unwrap();
+        assert_eq!(content.parts.len(), 2);
+        let ops = content.operations(&resolver).unwrap();
+        assert!(matches!(ops[..], [Op::BeginText, Op::EndText]));
+    }
+
+    #[test]
+    fn image_builder() {
+        let mut builder = PdfBuilder::new(FileOptions::uncached());
+        let rgba = [255, 0, 0, 255, 0, 255, 0, 128];
+        let image = ImageBuilder::from_rgba(2, 1, &rgba).unwrap().build(&mut builder.storage).unwrap();
+        let gray = ImageBuilder::from_samples(1, 2, 2, &[7, 255, 9, 255]).unwrap().build(&mut builder.storage).unwrap();
+        assert!(ImageBuilder::from_rgb(2, 2, &rgba).is_err());
+
+        let page = PageBuilder::default().add_xobject("Im0", image).add_xobject("Im1", gray);
+        let data = builder.build(CatalogBuilder::from_pages(vec![page])).unwrap();
+
+        let file = FileOptions::uncached().load(data).unwrap();
+        let resolver = file.resolver();
+        let page = file.get_page(0).unwrap();
+        let resources = page.resources().unwrap();
+        let image = |name: &str| match *resolver.get(resources.xobjects[name]).unwrap() {
+            XObject::Image(ref image) => image.clone(),
+            _ => panic!("not an image")
+        };
+
+        let im0 = image("Im0");
+        assert!(matches!(im0.color_space, Some(ColorSpace::DeviceRGB)));
+        assert_eq!(&*im0.image_data(&resolver).unwrap(), &[255, 0, 0, 0, 255, 0]);
+        let smask = resolver.get(im0.smask.unwrap()).unwrap();
+        let smask: &Stream<ImageDict> = &smask;
+        assert_eq!(&*smask.data(&resolver).unwrap(), &[255, 128]);
+
+        // opaque, so no soft mask
+        let im1 = image("Im1");
+        assert!(matches!(im1.color_space, Some(ColorSpace::DeviceGray)));
+        assert_eq!(&*im1.image_data(&resolver).unwrap(), &[7, 9]);
+        assert!(im1.smask.is_none());
+    }
+
+    #[test]
+    fn import_shared_and_cyclic() {
+        let mut source = PdfBuilder::new(FileOptions::uncached());
+        let shared = source.storage.create(Primitive::Integer(42)).unwrap().get_ref().get_inner();
+        let a = source.storage.promise::<Primitive>();
+        let mut dict = Dictionary::new();
+        dict.insert("Prev", Primitive::Reference(a.get_inner()));
+        dict.insert("Shared", Primitive::Reference(shared));
+        let b = source.storage.create(Primitive::Dictionary(dict.clone())).unwrap().get_ref().get_inner();
+        dict.insert("Next", Primitive::Reference(b));
+        let a = source.storage.fulfill(a, Primitive::Dictionary(dict)).unwrap().get_ref().get_inner();
+
+        let mut target = PdfBuilder::new(FileOptions::uncached());
+        let source_resolver = source.storage.resolver();
+        let mut importer = Importer::new(source.storage.resolver(), &mut target.storage);
+        let new_a = importer.clone_plainref(a).unwrap();
+        assert_eq!(importer.clone_plainref(a).unwrap(), new_a);
+        let new_b = importer.clone_plainref(b).unwrap();
+        let new_shared = importer.clone_plainref(shared).unwrap();
+        let rc = importer.clone_rcref(&source_resolver.get(Ref::<Primitive>::new(shared)).unwrap()).unwrap();
+        assert_eq!(rc.get_ref().get_inner(), new_shared);
+        drop(importer);
+
+        let resolver = target.storage.resolver();
+        let get = |r, key: &str| resolver.resolve(r).unwrap().into_dictionary().unwrap()[key].clone().into_reference().unwrap();
+        assert_eq!(get(new_a, "Next"), new_b);
+        assert_eq!(get(new_b, "Prev"), new_a);
+        assert_eq!(get(new_a, "Shared"), new_shared);
+        assert_eq!(get(new_b, "Shared"), new_shared);
+        assert!(matches!(resolver.resolve(new_shared).unwrap(), Primitive::Integer(42)));
+    }
+}
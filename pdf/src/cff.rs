@@ -0,0 +1,387 @@
+//! Just enough of a CFF font program parser to find the charstring of a glyph and the
+//! subroutines it may call, including CID-keyed fonts.
+//!
+//! CFF data is found in `/FontFile3` streams (`/Type1C`, `/CIDFontType0C`) and in the
+//! `CFF ` table of OpenType fonts.
+
+use crate::error::*;
+use std::convert::TryFrom;
+
+fn u8_at(data: &[u8], pos: usize) -> Result<u8> {
+    data.get(pos).copied().ok_or(PdfError::EOF)
+}
+fn u16_at(data: &[u8], pos: usize) -> Result<u16> {
+    match data.get(pos .. pos.checked_add(2).ok_or(PdfError::EOF)?) {
+        Some(b) => Ok(u16::from_be_bytes([b[0], b[1]])),
+        None => Err(PdfError::EOF)
+    }
+}
+/// An offset of `size` bytes.
+fn offset_at(data: &[u8], pos: usize, size: usize) -> Result<usize> {
+    let bytes = data.get(pos .. pos.checked_add(size).ok_or(PdfError::EOF)?).ok_or(PdfError::EOF)?;
+    Ok(bytes.iter().fold(0, |n, &b| n << 8 | b as usize))
+}
+
+/// An INDEX: an array of variable length objects.
+#[derive(Debug, Clone, Copy)]
+pub struct Index<'a> {
+    data: &'a [u8],
+    count: usize,
+    off_size: usize,
+    /// position of the offset array
+    offsets: usize,
+}
+impl<'a> Index<'a> {
+    /// Parse the INDEX at `pos`, returning it and the position after it.
+    fn parse(data: &'a [u8], pos: usize) -> Result<(Index<'a>, usize)> {
+        let count = u16_at(data, pos)? as usize;
+        // u16_at checked that `pos + 2` does not overflow
+        if count == 0 {
+            return Ok((Index { data, count, off_size: 1, offsets: pos + 2 }, pos + 2));
+        }
+        let off_size = u8_at(data, pos + 2)? as usize;
+        if !(1 ..= 4).contains(&off_size) {
+            bail!("invalid INDEX offset size {}", off_size);
+        }
+        let index = Index { data, count, off_size, offsets: pos + 3 };
+        // the offset array has to fit, so that `offset` does not overflow
+        if data.len() < index.offsets + (count + 1) * off_size {
+            return Err(PdfError::EOF);
+        }
+        let end = index.offset(count)?;
+        if end > data.len() {
+            return Err(PdfError::EOF);
+        }
+        Ok((index, end))
+    }
+    /// Position of the start of item `i` (`count` for the end of the last one).
+    fn offset(&self, i: usize) -> Result<usize> {
+        // offsets are relative to the byte before the object data
+        let base = self.offsets + (self.count + 1) * self.off_size - 1;
+        base.checked_add(offset_at(self.data, self.offsets + i * self.off_size, self.off_size)?).ok_or(PdfError::EOF)
+    }
+    pub fn len(&self) -> usize {
+        self.count
+    }
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+    pub fn get(&self, i: usize) -> Option<&'a [u8]> {
+        if i >= self.count {
+            return None;
+        }
+        let (start, end) = (self.offset(i).ok()?, self.offset(i + 1).ok()?);
+        self.data.get(start .. end)
+    }
+    /// The subroutine a charstring calls with `callsubr`/`callgsubr` `n`, which is biased by
+    /// the number of subroutines.
+    pub fn subr(&self, n: i32) -> Option<&'a [u8]> {
+        let bias = match self.count {
+            0 ..= 1239 => 107,
+            1240 ..= 33899 => 1131,
+            _ => 32768,
+        };
+        self.get(usize::try_from(n.checked_add(bias)?).ok()?)
+    }
+}
+
+/// Parse a DICT into (operator, operands). Two-byte operators are `12 << 8 | b1`.
+fn parse_dict(mut data: &[u8]) -> Result<Vec<(u16, Vec<f64>)>> {
+    let mut entries = vec![];
+    let mut operands = vec![];
+    while let Some((&b0, rest)) = data.split_first() {
+        data = rest;
+        let byte = |data: &mut &[u8]| -> Result<u8> {
+            let (&b, rest) = data.split_first().ok_or(PdfError::EOF)?;
+            *data = rest;
+            Ok(b)
+        };
+        match b0 {
+            12 => {
+                let b1 = byte(&mut data)?;
+                entries.push((12 << 8 | b1 as u16, std::mem::take(&mut operands)));
+            }
+            0 ..= 21 => entries.push((b0 as u16, std::mem::take(&mut operands))),
+            28 => {
+                let v = i16::from_be_bytes([byte(&mut data)?, byte(&mut data)?]);
+                operands.push(v as f64);
+            }
+            29 => {
+                let v = i32::from_be_bytes([byte(&mut data)?, byte(&mut data)?, byte(&mut data)?, byte(&mut data)?]);
+                operands.push(v as f64);
+            }
+            30 => {
+                // a real number, as nibbles
+                let mut s = String::new();
+                'real: loop {
+                    let b = byte(&mut data)?;
+                    for nibble in [b >> 4, b & 15] {
+                        match nibble {
+                            0 ..= 9 => s.push((b'0' + nibble) as char),
+                            0xa => s.push('.'),
+                            0xb => s.push('E'),
+                            0xc => s.push_str("E-"),
+                            0xe => s.push('-'),
+                            0xf => break 'real,
+                            _ => bail!("invalid nibble in real number"),
+                        }
+                    }
+                }
+                operands.push(s.parse().map_err(|_| other!("invalid real number {}", s))?);
+            }
+            32 ..= 246 => operands.push(b0 as f64 - 139.),
+            247 ..= 250 => operands.push(((b0 as f64 - 247.) * 256. + byte(&mut data)? as f64) + 108.),
+            251 ..= 254 => operands.push(-((b0 as f64 - 251.) * 256. + byte(&mut data)? as f64) - 108.),
+            _ => bail!("invalid DICT byte {}", b0),
+        }
+    }
+    Ok(entries)
+}
+
+const CHAR_STRINGS: u16 = 17;
+const PRIVATE: u16 = 18;
+const SUBRS: u16 = 19;
+const ROS: u16 = 12 << 8 | 30;
+const FD_ARRAY: u16 = 12 << 8 | 36;
+const FD_SELECT: u16 = 12 << 8 | 37;
+
+fn operand(entries: &[(u16, Vec<f64>)], op: u16, i: usize) -> Option<usize> {
+    entries.iter().find(|(o, _)| *o == op)
+        .and_then(|(_, operands)| operands.get(i))
+        .filter(|&&v| v >= 0.)
+        .map(|&v| v as usize)
+}
+
+/// The local subroutines of the Private DICT that the entries (of a Top or Font DICT) point to.
+fn local_subrs<'a>(data: &'a [u8], entries: &[(u16, Vec<f64>)]) -> Result<Option<Index<'a>>> {
+    let (size, offset) = match (operand(entries, PRIVATE, 0), operand(entries, PRIVATE, 1)) {
+        (Some(size), Some(offset)) => (size, offset),
+        _ => return Ok(None)
+    };
+    // operands saturate, so the sums can overflow
+    let private = data.get(offset .. offset.checked_add(size).ok_or(PdfError::EOF)?).ok_or(PdfError::EOF)?;
+    match operand(&parse_dict(private)?, SUBRS, 0) {
+        // relative to the start of the Private DICT
+        Some(subrs) => Ok(Some(Index::parse(data, offset.checked_add(subrs).ok_or(PdfError::EOF)?)?.0)),
+        None => Ok(None)
+    }
+}
+
+/// Which Font DICT of a CID-keyed font each glyph uses.
+#[derive(Debug)]
+enum FdSelect<'a> {
+    /// one byte per glyph
+    Format0(&'a [u8]),
+    /// (first glyph, font dict) ranges, and the glyph after the last range
+    Format3(Vec<(u16, u8)>, u16),
+}
+impl<'a> FdSelect<'a> {
+    fn parse(data: &'a [u8], pos: usize, num_glyphs: usize) -> Result<FdSelect<'a>> {
+        match u8_at(data, pos)? {
+            // u8_at checked that `pos + 1` does not overflow
+            0 => Ok(FdSelect::Format0(data.get(pos + 1 ..).and_then(|d| d.get(.. num_glyphs)).ok_or(PdfError::EOF)?)),
+            3 => {
+                let num_ranges = u16_at(data, pos + 1)? as usize;
+                let ranges = (0 .. num_ranges)
+                    .map(|i| Ok((u16_at(data, pos + 3 + 3 * i)?, u8_at(data, pos + 5 + 3 * i)?)))
+                    .collect::<Result<Vec<_>>>()?;
+                let sentinel = u16_at(data, pos + 3 + 3 * num_ranges)?;
+                Ok(FdSelect::Format3(ranges, sentinel))
+            }
+            format => bail!("invalid FDSelect format {}", format)
+        }
+    }
+    fn font_dict(&self, gid: u16) -> Option<usize> {
+        match *self {
+            FdSelect::Format0(fds) => fds.get(gid as usize).map(|&fd| fd as usize),
+            FdSelect::Format3(ref ranges, sentinel) => {
+                if gid >= sentinel {
+                    return None;
+                }
+                let i = ranges.partition_point(|&(first, _)| first <= gid);
+                i.checked_sub(1).map(|i| ranges[i].1 as usize)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Kind<'a> {
+    Simple { local_subrs: Option<Index<'a>> },
+    /// CID-keyed: every Font DICT has its own Private DICT and subroutines
+    Cid { fd_select: FdSelect<'a>, local_subrs: Vec<Option<Index<'a>>> },
+}
+
+/// The program of a glyph: its charstring and the subroutines it can call.
+#[derive(Debug, Clone, Copy)]
+pub struct GlyphProgram<'a> {
+    pub charstring: &'a [u8],
+    pub global_subrs: Index<'a>,
+    /// From the Private DICT of the glyph: the font's, or for CID-keyed fonts the one selected
+    /// for the glyph by `FDSelect`.
+    pub local_subrs: Option<Index<'a>>,
+}
+
+/// The first font of a CFF font program.
+#[derive(Debug)]
+pub struct Cff<'a> {
+    char_strings: Index<'a>,
+    global_subrs: Index<'a>,
+    kind: Kind<'a>,
+}
+impl<'a> Cff<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Cff<'a>> {
+        if u8_at(data, 0)? != 1 {
+            bail!("not a CFF font program");
+        }
+        let header_size = u8_at(data, 2)? as usize;
+        let (_names, pos) = Index::parse(data, header_size)?;
+        let (top_dicts, pos) = Index::parse(data, pos)?;
+        let (_strings, pos) = Index::parse(data, pos)?;
+        let (global_subrs, _) = Index::parse(data, pos)?;
+
+        let top = parse_dict(top_dicts.get(0).ok_or_else(|| other!("CFF without fonts"))?)?;
+        let char_strings = operand(&top, CHAR_STRINGS, 0).ok_or_else(|| other!("CFF font without CharStrings"))?;
+        let (char_strings, _) = Index::parse(data, char_strings)?;
+
+        // the ROS operator makes the font CID-keyed
+        let kind = if top.iter().any(|(op, _)| *op == ROS) {
+            let fd_array = operand(&top, FD_ARRAY, 0).ok_or_else(|| other!("CID-keyed CFF font without FDArray"))?;
+            let fd_select = operand(&top, FD_SELECT, 0).ok_or_else(|| other!("CID-keyed CFF font without FDSelect"))?;
+            let (fd_array, _) = Index::parse(data, fd_array)?;
+            let local_subrs = (0 .. fd_array.len()).map(|i| {
+                let font_dict = fd_array.get(i).ok_or(PdfError::EOF)?;
+                local_subrs(data, &parse_dict(font_dict)?)
+            }).collect::<Result<_>>()?;
+            Kind::Cid { fd_select: FdSelect::parse(data, fd_select, char_strings.len())?, local_subrs }
+        } else {
+            Kind::Simple { local_subrs: local_subrs(data, &top)? }
+        };
+        Ok(Cff { char_strings, global_subrs, kind })
+    }
+
+    /// Whether the font is CID-keyed, so that glyphs are selected by CID.
+    pub fn is_cid(&self) -> bool {
+        matches!(self.kind, Kind::Cid { .. })
+    }
+    pub fn num_glyphs(&self) -> usize {
+        self.char_strings.len()
+    }
+
+    /// The charstring of glyph `gid` and the subroutines it uses.
+    pub fn glyph(&self, gid: u16) -> Option<GlyphProgram<'a>> {
+        let local_subrs = match self.kind {
+            Kind::Simple { local_subrs } => local_subrs,
+            Kind::Cid { ref fd_select, ref local_subrs } => *local_subrs.get(fd_select.font_dict(gid)?)?,
+        };
+        Some(GlyphProgram {
+            charstring: self.char_strings.get(gid as usize)?,
+            global_subrs: self.global_subrs,
+            local_subrs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(items: &[&[u8]]) -> Vec<u8> {
+        let mut out = (items.len() as u16).to_be_bytes().to_vec();
+        if items.is_empty() {
+            return out;
+        }
+        out.push(1);
+        let mut offset = 1;
+        out.push(offset);
+        for item in items {
+            offset += item.len() as u8;
+            out.push(offset);
+        }
+        items.iter().for_each(|item| out.extend_from_slice(item));
+        out
+    }
+    /// an integer operand, always five bytes
+    fn int(v: usize) -> Vec<u8> {
+        [&[29][..], &(v as i32).to_be_bytes()].concat()
+    }
+
+    /// A CID-keyed font with three glyphs: 0 and 1 use Font DICT 0, glyph 2 Font DICT 1.
+    fn cid_font(fd_select: &[u8]) -> Vec<u8> {
+        let header = [1, 0, 4, 1];
+        let names = index(&[b"Test"]);
+        let strings = index(&[]);
+        let global_subrs = index(&[b"\x0b"]);
+        let char_strings = index(&[b"\x0e", b"\x8b\x0a\x0e", b"\x8c\x0a\x0e"]);
+        // the Top DICT is 37 bytes, in an INDEX of 42
+        let top_len = 42;
+        let char_strings_pos = header.len() + names.len() + top_len + strings.len() + global_subrs.len();
+        let fd_select_pos = char_strings_pos + char_strings.len();
+        let fd_array_pos = fd_select_pos + fd_select.len();
+        // two font dicts of 11 bytes, then two private dicts of 6 bytes, each followed by its subrs
+        let fd_array_len = 2 + 1 + 3 + 2 * 11;
+        // the subrs follow the private dict
+        let private = [int(6), vec![SUBRS as u8]].concat();
+        let subrs = [index(&[b"\x01\x0b"]), index(&[b"\x02\x0b"])];
+        let private_pos = [fd_array_pos + fd_array_len, fd_array_pos + fd_array_len + 6 + subrs[0].len()];
+        let font_dict = |pos: usize| [int(6), int(pos), vec![PRIVATE as u8]].concat();
+        let fd_array = index(&[&font_dict(private_pos[0]), &font_dict(private_pos[1])]);
+        assert_eq!(fd_array.len(), fd_array_len);
+
+        let top = [
+            int(391), int(392), int(0), vec![12, 30],
+            int(char_strings_pos), vec![CHAR_STRINGS as u8],
+            int(fd_array_pos), vec![12, 36],
+            int(fd_select_pos), vec![12, 37],
+        ].concat();
+        let top = index(&[&top]);
+        assert_eq!(top.len(), top_len);
+
+        [
+            &header[..], &names, &top, &strings, &global_subrs, &char_strings, fd_select, &fd_array,
+            &private, &subrs[0], &private, &subrs[1],
+        ].concat()
+    }
+
+    #[test]
+    fn cid_keyed() {
+        // format 3: [0, 2) use 0, [2, 3) use 1
+        let data = cid_font(&[3, 0, 2, 0, 0, 0, 0, 2, 1, 0, 3]);
+        let cff = Cff::parse(&data).unwrap();
+        assert!(cff.is_cid());
+        assert_eq!(cff.num_glyphs(), 3);
+
+        let glyph = cff.glyph(1).unwrap();
+        assert_eq!(glyph.charstring, b"\x8b\x0a\x0e");
+        assert_eq!(glyph.global_subrs.subr(-107), Some(&b"\x0b"[..]));
+        assert_eq!(glyph.local_subrs.unwrap().subr(-107), Some(&b"\x01\x0b"[..]));
+        assert_eq!(cff.glyph(2).unwrap().local_subrs.unwrap().subr(-107), Some(&b"\x02\x0b"[..]));
+        assert_eq!(glyph.global_subrs.subr(i32::MAX), None);
+        assert!(cff.glyph(3).is_none());
+
+        // format 0: one byte per glyph
+        let data = cid_font(&[0, 1, 0, 1]);
+        let cff = Cff::parse(&data).unwrap();
+        assert_eq!(cff.glyph(0).unwrap().local_subrs.unwrap().get(0), Some(&b"\x02\x0b"[..]));
+        assert_eq!(cff.glyph(1).unwrap().local_subrs.unwrap().get(0), Some(&b"\x01\x0b"[..]));
+    }
+
+    #[test]
+    fn dict_operands() {
+        let dict = parse_dict(&[0x8b, 0xef, 0x27, 0xfa, 0x7c, 0xfe, 0x7c, 28, 0x27, 0x10, 30, 0xe2, 0xa2, 0x5f, 17]).unwrap();
+        assert_eq!(dict, [(17, vec![0., 100., -100., 1000., -1000., 10000., -2.25])]);
+    }
+
+    #[test]
+    fn huge_operands() {
+        // operands saturate to usize::MAX and must not overflow the position arithmetic
+        let data = [&int(0)[..], &[SUBRS as u8]].concat();
+        let private = |size: f64, offset: f64| vec![(PRIVATE, vec![size, offset])];
+        assert!(matches!(local_subrs(&data, &private(1e300, 1.)), Err(PdfError::EOF)));
+        assert!(matches!(local_subrs(&data, &private(6., 1e300)), Err(PdfError::EOF)));
+        let huge_subrs = [&int(i32::MAX as usize)[..], &[SUBRS as u8]].concat();
+        assert!(matches!(local_subrs(&huge_subrs, &private(6., 0.)), Err(PdfError::EOF)));
+        assert!(matches!(Index::parse(&data, usize::MAX - 1), Err(PdfError::EOF)));
+        assert!(matches!(FdSelect::parse(&[0, 1], 0, usize::MAX), Err(PdfError::EOF)));
+    }
+}
@@ -0,0 +1,398 @@
+//! CMaps as used for `/ToUnicode` (PDF 32000-1:2008, 9.10.3) and the `/Encoding`
+//! of Type0 fonts (9.7.5).
+
+use crate::error::*;
+use crate::object::NoResolve;
+use crate::primitive::{Primitive, PdfString};
+use crate::parser::{Lexer, parse_with_lexer, ParseFlags};
+use crate::font::utf16be_to_string;
+use std::collections::HashMap;
+use istring::SmallString;
+
+/// The most codes a single `bfrange` may cover, the size of a two byte code space.
+const MAX_BF_RANGE: u32 = 0xFFFF;
+
+/// One `begincodespacerange` entry. A code matches if every byte lies within
+/// the bounds of the corresponding bytes of `low` and `high`.
+#[derive(Clone, Debug)]
+struct CodespaceRange {
+    low: Vec<u8>,
+    high: Vec<u8>,
+}
+impl CodespaceRange {
+    fn matches(&self, code: &[u8]) -> bool {
+        code.len() >= self.low.len() && self.low.iter().zip(&self.high).zip(code)
+            .all(|((&lo, &hi), &b)| lo <= b && b <= hi)
+    }
+}
+
+/// A `begincidrange` (or `begincidchar`) entry.
+#[derive(Clone, Debug)]
+struct CidRange {
+    len: u8,
+    low: u32,
+    high: u32,
+    cid: u32,
+}
+
+/// A parsed CMap, mapping variable-length character codes to text (`bfchar`, `bfrange`)
+/// and/or to CIDs (`cidchar`, `cidrange`).
+#[derive(Clone, Debug, Default)]
+pub struct CMap {
+    codespace: Vec<CodespaceRange>,
+    /// keyed by (code length, code)
+    unicode: HashMap<(u8, u32), SmallString>,
+    cids: Vec<CidRange>,
+    vertical: bool,
+}
+
+impl CMap {
+    /// Parse the content of a CMap stream.
+    ///
+    /// Malformed entries are skipped. If the CMap has no `begincodespacerange`, the
+    /// code lengths are taken from the source codes of the mappings. A CMap referenced
+    /// with `usecmap` has to be one of the [predefined](CMap::predefined) CMaps.
+    pub fn parse(data: &[u8]) -> Result<CMap> {
+        let mut cmap = CMap::default();
+        let mut lexer = Lexer::new(data);
+        let next_string = |lexer: &mut Lexer| match parse_with_lexer(lexer, &NoResolve, ParseFlags::STRING) {
+            Ok(Primitive::String(s)) => Some(s),
+            _ => None,
+        };
+        let mut last_name: Option<String> = None;
+        while let Ok(substr) = lexer.next() {
+            match substr.as_slice() {
+                b"usecmap" => {
+                    let name = last_name.take().ok_or_else(|| other!("usecmap without a CMap name"))?;
+                    let parent = CMap::predefined(&name)?;
+                    cmap.codespace.extend(parent.codespace);
+                    cmap.cids.splice(0 .. 0, parent.cids);
+                    for (key, unicode) in parent.unicode {
+                        cmap.unicode.entry(key).or_insert(unicode);
+                    }
+                }
+                b"/WMode" => {
+                    cmap.vertical = lexer.next()?.as_slice() == b"1";
+                }
+                name if name.starts_with(b"/") => {
+                    last_name = Some(String::from_utf8_lossy(&name[1 ..]).into_owned());
+                }
+                b"begincidchar" => {
+                    while let Some(src) = next_string(&mut lexer) {
+                        let Ok(cid) = lexer.next_as::<u32>() else { break };
+                        if let Some((len, code)) = code_key(src.as_bytes()) {
+                            cmap.cids.push(CidRange { len, low: code, high: code, cid });
+                        }
+                    }
+                }
+                b"begincidrange" => {
+                    while let Some(start) = next_string(&mut lexer) {
+                        let Some(end) = next_string(&mut lexer) else { break };
+                        let Ok(cid) = lexer.next_as::<u32>() else { break };
+                        match (code_key(start.as_bytes()), code_key(end.as_bytes())) {
+                            (Some((len, low)), Some((end_len, high))) if len == end_len && low <= high => {
+                                cmap.cids.push(CidRange { len, low, high, cid });
+                            }
+                            _ => warn!("invalid cidrange {start:?} {end:?}")
+                        }
+                    }
+                }
+                b"begincodespacerange" => {
+                    while let Some(low) = next_string(&mut lexer) {
+                        let Some(high) = next_string(&mut lexer) else { break };
+                        let (low, high) = (low.as_bytes().to_vec(), high.as_bytes().to_vec());
+                        if low.len() == high.len() && !low.is_empty() && low.len() <= 4 {
+                            cmap.codespace.push(CodespaceRange { low, high });
+                        }
+                    }
+                }
+                b"beginbfchar" => {
+                    while let Some(src) = next_string(&mut lexer) {
+                        let Some(dst) = next_string(&mut lexer) else { break };
+                        if let Some(key) = code_key(src.as_bytes()) {
+                            cmap.insert(key, dst.as_bytes());
+                        }
+                    }
+                }
+                b"beginbfrange" => loop {
+                    let Some(start) = next_string(&mut lexer) else { break };
+                    let Some(end) = next_string(&mut lexer) else { break };
+                    let dst = parse_with_lexer(&mut lexer, &NoResolve, ParseFlags::STRING | ParseFlags::ARRAY);
+                    let (Some((len, start)), Some((end_len, end))) = (code_key(start.as_bytes()), code_key(end.as_bytes())) else {
+                        continue
+                    };
+                    if len != end_len || end < start {
+                        warn!("invalid bfrange {start:X} {end:X}");
+                        continue;
+                    }
+                    // every code of the range gets its own entry
+                    if end - start > MAX_BF_RANGE {
+                        warn!("bfrange {start:X} {end:X} is too large");
+                        continue;
+                    }
+                    match dst {
+                        Ok(Primitive::String(dst)) if !dst.as_bytes().is_empty() => {
+                            let mut dst = dst.as_bytes().to_vec();
+                            for code in start ..= end {
+                                cmap.insert((len, code), &dst);
+                                if !increment(&mut dst) {
+                                    break;
+                                }
+                            }
+                        }
+                        Ok(Primitive::Array(parts)) => {
+                            for (code, dst) in (start ..= end).zip(parts) {
+                                if let Primitive::String(dst) = dst {
+                                    cmap.insert((len, code), dst.as_bytes());
+                                }
+                            }
+                        }
+                        _ => break
+                    }
+                },
+                b"endcmap" => break,
+                _ => {}
+            }
+        }
+        if cmap.codespace.is_empty() {
+            let mut lengths: Vec<usize> = cmap.unicode.keys().map(|&(len, _)| len as usize)
+                .chain(cmap.cids.iter().map(|r| r.len as usize))
+                .collect();
+            lengths.sort_unstable();
+            lengths.dedup();
+            cmap.codespace = lengths.into_iter().map(|len| CodespaceRange { low: vec![0; len], high: vec![0xFF; len] }).collect();
+        }
+        Ok(cmap)
+    }
+
+    fn insert(&mut self, key: (u8, u32), dst: &[u8]) {
+        match utf16be_to_string(dst) {
+            Ok(s) => {
+                self.unicode.insert(key, s);
+            }
+            Err(_) => warn!("invalid unicode for code {:X} {dst:?}", key.1),
+        }
+    }
+
+    /// The text for the complete character code `code`.
+    pub fn lookup(&self, code: &[u8]) -> Option<&str> {
+        let key = code_key(code)?;
+        self.unicode.get(&key).map(|s| s.as_str())
+    }
+
+    /// One of the predefined CMaps of PDF 32000-1:2008, Table 118, by name.
+    ///
+    /// Bundled are `Identity-H`, `Identity-V` and the UCS-2 CMaps `UniJIS-UCS2-H`,
+    /// `UniGB-UCS2-H`, `UniCNS-UCS2-H` and `UniKS-UCS2-H`. Of the latter only the
+    /// Basic Latin range is included so far. Other names are an error; a copy of
+    /// such a file from Adobe's cmap-resources can be read with [`CMap::parse`].
+    pub fn predefined(name: &str) -> Result<CMap> {
+        let data: &[u8] = match name {
+            "Identity-H" | "Identity-V" => return Ok(CMap {
+                codespace: vec![CodespaceRange { low: vec![0, 0], high: vec![0xFF, 0xFF] }],
+                unicode: HashMap::new(),
+                cids: vec![CidRange { len: 2, low: 0, high: 0xFFFF, cid: 0 }],
+                vertical: name == "Identity-V",
+            }),
+            "UniJIS-UCS2-H" => include_bytes!("data/cmap/UniJIS-UCS2-H"),
+            "UniGB-UCS2-H" => include_bytes!("data/cmap/UniGB-UCS2-H"),
+            "UniCNS-UCS2-H" => include_bytes!("data/cmap/UniCNS-UCS2-H"),
+            "UniKS-UCS2-H" => include_bytes!("data/cmap/UniKS-UCS2-H"),
+            _ => bail!("the predefined CMap {} is not bundled", name)
+        };
+        CMap::parse(data)
+    }
+
+    /// The CID of the complete character code `code`.
+    ///
+    /// Later entries take precedence over earlier ones (and over those of a `usecmap` parent).
+    pub fn cid(&self, code: &[u8]) -> Option<u32> {
+        let (len, code) = code_key(code)?;
+        self.cids.iter().rev()
+            .find(|r| r.len == len && r.low <= code && code <= r.high)
+            .and_then(|r| r.cid.checked_add(code - r.low))
+    }
+
+    /// Whether the CMap is for vertical writing (`/WMode 1`).
+    pub fn is_vertical(&self) -> bool {
+        self.vertical
+    }
+
+    /// The length of the character code at the start of `data`.
+    ///
+    /// Follows the codespace ranges. Bytes that match no range are consumed
+    /// as the shortest code that matches their first byte (or a single byte).
+    pub fn code_len(&self, data: &[u8]) -> usize {
+        if data.is_empty() {
+            return 0;
+        }
+        if let Some(range) = self.codespace.iter().filter(|r| r.matches(data)).min_by_key(|r| r.low.len()) {
+            return range.low.len();
+        }
+        self.codespace.iter()
+            .filter(|r| r.low[0] <= data[0] && data[0] <= r.high[0])
+            .map(|r| r.low.len())
+            .min()
+            .unwrap_or(1)
+            .min(data.len())
+    }
+
+    /// Split a string shown with this CMap into character codes.
+    pub fn codes<'a>(&'a self, mut data: &'a [u8]) -> impl Iterator<Item=&'a [u8]> + 'a {
+        std::iter::from_fn(move || {
+            let len = self.code_len(data);
+            if len == 0 {
+                return None;
+            }
+            let (code, rest) = data.split_at(len);
+            data = rest;
+            Some(code)
+        })
+    }
+
+    /// Decode the text of a string shown with this CMap.
+    ///
+    /// Codes without a mapping are skipped.
+    pub fn decode(&self, s: &PdfString) -> String {
+        self.codes(s.as_bytes()).filter_map(|code| self.lookup(code)).collect()
+    }
+
+    /// All mappings, as (code length in bytes, code, text).
+    pub fn iter(&self) -> impl Iterator<Item=(usize, u32, &str)> {
+        self.unicode.iter().map(|(&(len, code), s)| (len as usize, code, s.as_str()))
+    }
+    pub fn len(&self) -> usize {
+        self.unicode.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.unicode.is_empty()
+    }
+}
+
+fn code_key(code: &[u8]) -> Option<(u8, u32)> {
+    match code.len() {
+        1 ..= 4 => Some((code.len() as u8, code.iter().fold(0, |n, &b| n << 8 | b as u32))),
+        _ => None
+    }
+}
+
+/// Increment a big-endian byte string by one, carrying into the preceding bytes.
+/// Returns false on overflow.
+fn increment(data: &mut [u8]) -> bool {
+    for b in data.iter_mut().rev() {
+        let (v, carry) = b.overflowing_add(1);
+        *b = v;
+        if !carry {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codespace_ranges() {
+        let cmap = CMap::parse(b"
+            /CIDInit /ProcSet findresource begin
+            12 dict begin
+            begincmap
+            2 begincodespacerange
+            <00> <80>
+            <8140> <FEFE>
+            endcodespacerange
+            2 beginbfchar
+            <41> <0041>
+            <8140> <3000>
+            endbfchar
+            1 beginbfrange
+            <8141> <8142> [<3001> <D83DDE00>]
+            endbfrange
+            endcmap
+        ").unwrap();
+        assert_eq!(cmap.lookup(b"A"), Some("A"));
+        assert_eq!(cmap.lookup(&[0x81, 0x40]), Some("\u{3000}"));
+        assert_eq!(cmap.lookup(&[0x81, 0x42]), Some("\u{1F600}"));
+        assert_eq!(cmap.lookup(&[0x00, 0x41]), None);
+
+        let codes: Vec<&[u8]> = cmap.codes(&[0x41, 0x81, 0x40, 0x81, 0x41, 0x20]).collect();
+        assert_eq!(codes, [&[0x41][..], &[0x81, 0x40], &[0x81, 0x41], &[0x20]]);
+        assert_eq!(cmap.decode(&PdfString::new(vec![0x41, 0x81, 0x41, 0x41].into())), "A\u{3001}A");
+    }
+
+    #[test]
+    fn bfrange_carry() {
+        let cmap = CMap::parse(b"
+            1 begincodespacerange <0000> <FFFF> endcodespacerange
+            2 beginbfrange
+            <0010> <0012> <00FF>
+            <0020> <0021> <0066006C>
+            endbfrange
+        ").unwrap();
+        assert_eq!(cmap.lookup(&[0x00, 0x10]), Some("\u{FF}"));
+        assert_eq!(cmap.lookup(&[0x00, 0x11]), Some("\u{100}"));
+        assert_eq!(cmap.lookup(&[0x00, 0x12]), Some("\u{101}"));
+        assert_eq!(cmap.lookup(&[0x00, 0x21]), Some("fm"));
+        assert_eq!(cmap.len(), 5);
+    }
+
+    #[test]
+    fn bfrange_too_large() {
+        let cmap = CMap::parse(b"
+            2 beginbfrange
+            <00000000> <FFFFFFFF> <0041>
+            <00010000> <00010001> <0042>
+            endbfrange
+        ").unwrap();
+        assert_eq!(cmap.len(), 2);
+        assert_eq!(cmap.lookup(&[0, 1, 0, 1]), Some("C"));
+    }
+
+    #[test]
+    fn no_codespace() {
+        let cmap = CMap::parse(b"1 beginbfchar <0003> <0020> endbfchar").unwrap();
+        let codes: Vec<&[u8]> = cmap.codes(&[0, 3, 0, 3]).collect();
+        assert_eq!(codes, [&[0, 3][..], &[0, 3]]);
+    }
+
+    #[test]
+    fn cid_ranges() {
+        let identity = CMap::predefined("Identity-V").unwrap();
+        assert!(identity.is_vertical());
+        assert_eq!(identity.cid(&[0x12, 0x34]), Some(0x1234));
+        assert_eq!(identity.cid(&[0x12]), None);
+        assert!(CMap::predefined("GBK-EUC-H").is_err());
+
+        // "A" is CID 34 of all four character collections
+        for name in ["UniJIS-UCS2-H", "UniGB-UCS2-H", "UniCNS-UCS2-H", "UniKS-UCS2-H"] {
+            let cmap = CMap::predefined(name).unwrap();
+            assert!(!cmap.is_vertical());
+            assert_eq!(cmap.cid(&[0x00, 0x41]), Some(34), "{}", name);
+            assert_eq!(cmap.cid(&[0x4E, 0x00]), None, "{}", name);
+        }
+        // JIS-Roman has the yen sign in place of the backslash, so U+005C is not in its range
+        let jis = CMap::predefined("UniJIS-UCS2-H").unwrap();
+        assert_eq!(jis.cid(&[0x00, 0x5C]), None);
+        assert_eq!(jis.cid(&[0x00, 0x5D]), Some(62));
+
+        let cmap = CMap::parse(b"
+            /CMapName /Test-H def
+            /WMode 0 def
+            /Identity-H usecmap
+            1 begincodespacerange <00> <7F> endcodespacerange
+            1 begincidrange <20> <7E> 1 endcidrange
+            1 begincidchar <0041> 500 endcidchar
+            endcmap
+        ").unwrap();
+        assert!(!cmap.is_vertical());
+        assert_eq!(cmap.cid(b"!"), Some(2));
+        assert_eq!(cmap.cid(&[0x00, 0x41]), Some(500));
+        assert_eq!(cmap.cid(&[0x00, 0x42]), Some(0x42));
+        let codes: Vec<&[u8]> = cmap.codes(&[0x41, 0x80, 0x41]).collect();
+        assert_eq!(codes, [&[0x41][..], &[0x80, 0x41]]);
+
+        assert!(CMap::parse(b"/Foo usecmap").is_err());
+    }
+}
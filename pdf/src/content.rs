@@ -10,7 +10,6 @@ use crate::error::*;
 use crate::object::*;
 use crate::parser::{Lexer, parse_with_lexer, ParseFlags};
 use crate::primitive::*;
-use crate::enc::StreamFilter;
 use crate as pdf;
 
 /// Represents a PDF content stream - a `Vec` of `Operator`s
@@ -173,7 +172,7 @@ fn inline_image(lexer: &mut Lexer, resolve: &impl Resolve) -> Result<Arc<ImageXO
         ]
     ), resolve)).transpose()?;
     let decode = dict.get("Decode").map(|p| Object::from_primitive(p.clone(), resolve)).transpose()?;
-    let decode_parms = dict.get("DecodeParms").map(|p| p.clone().resolve(resolve)?.into_dictionary()).transpose()?.unwrap_or_default();
+    let decode_parms = dict.remove("DecodeParms").unwrap_or(Primitive::Null);
     let filter = dict.remove("Filter").map(|p| expand_abbr(p,
         &[
             ("AHx", "ASCIIHexDecode"),
@@ -186,10 +185,7 @@ fn inline_image(lexer: &mut Lexer, resolve: &impl Resolve) -> Result<Arc<ImageXO
         ]
     ));
     let filters = match filter {
-        Some(Primitive::Array(parts)) => parts.into_iter()
-            .map(|p| p.as_name().and_then(|kind| StreamFilter::from_kind_and_params(kind, decode_parms.clone(), resolve)))
-            .collect::<Result<_>>()?,
-        Some(Primitive::Name(kind)) => vec![StreamFilter::from_kind_and_params(&kind, decode_parms, resolve)?],
+        Some(p @ Primitive::Array(_)) | Some(p @ Primitive::Name(_)) => filters_from_primitive(p, decode_parms, resolve)?,
         None => vec![],
         _ => bail!("invalid filter")
     };
@@ -224,6 +220,10 @@ fn inline_image(lexer: &mut Lexer, resolve: &impl Resolve) -> Result<Arc<ImageXO
 struct OpBuilder {
     last: Point,
     compability_section: bool,
+    /// open `q`
+    save_depth: usize,
+    /// open `BMC`/`BDC`
+    marked_depth: usize,
     ops: Vec<Op>
 }
 impl OpBuilder {
@@ -231,12 +231,16 @@ impl OpBuilder {
         OpBuilder {
             last: Point { x: 0., y: 0. },
             compability_section: false,
+            save_depth: 0,
+            marked_depth: 0,
             ops: Vec::new()
         }
     }
     fn parse(&mut self, data: &[u8], resolve: &impl Resolve) -> Result<()> {
         let mut lexer = Lexer::new(data);
         let mut buffer = Vec::with_capacity(5);
+        let options = resolve.options();
+        let mut n_ops = 0;
 
         loop {
             let backup_pos = lexer.get_pos();
@@ -254,13 +258,24 @@ impl OpBuilder {
                     lexer.set_pos(backup_pos);
                     let op = t!(lexer.next());
                     let operator = t!(op.as_str(), op);
+                    n_ops += 1;
+                    if let Some(max) = options.max_content_ops {
+                        if n_ops > max {
+                            return Err(PdfError::ContentTooManyOps { max });
+                        }
+                    }
                     match self.add(operator, buffer.drain(..), &mut lexer, resolve) {
                         Ok(()) => {},
-                        Err(e) if resolve.options().allow_invalid_ops => {
+                        Err(e) if options.allow_invalid_ops => {
                             warn!("OP Err: {:?}", e);
                         },
                         Err(e) => return Err(e),
                     }
+                    if let Some(max) = options.max_content_nesting {
+                        if self.save_depth.max(self.marked_depth) > max {
+                            return Err(PdfError::ContentNestingTooDeep { max });
+                        }
+                    }
                 }
             }
             match lexer.get_pos().cmp(&data.len()) {
@@ -269,6 +284,9 @@ impl OpBuilder {
                 Ordering::Equal => break
             }
         }
+        if self.save_depth > 0 || self.marked_depth > 0 {
+            warn!("content stream ends with {} open q and {} open marked content", self.save_depth, self.marked_depth);
+        }
         Ok(())
     }
     fn add(&mut self, op: &str, mut args: impl Iterator<Item=Primitive>, lexer: &mut Lexer, resolve: &impl Resolve) -> Result<()> {
@@ -288,17 +306,28 @@ impl OpBuilder {
                 push(Op::FillAndStroke { winding: EvenOdd });
             }
             "B*"  => push(Op::FillAndStroke { winding: EvenOdd }),
-            "BDC" => push(Op::BeginMarkedContent {
-                tag: name(&mut args)?,
-                properties: Some(args.next().ok_or(PdfError::NoOpArg)?)
-            }),
+            "BDC" => {
+                let tag = name(&mut args)?;
+                let properties = args.next().ok_or(PdfError::NoOpArg)?;
+                match properties {
+                    Primitive::Name(group) if tag.as_str() == "OC" => push(Op::BeginOptionalContent { group: group.into() }),
+                    properties => push(Op::BeginMarkedContent { tag, properties: Some(properties) })
+                }
+                self.marked_depth += 1;
+            }
             "BI"  => push(Op::InlineImage { image: inline_image(lexer, resolve)? }),
-            "BMC" => push(Op::BeginMarkedContent {
-                tag: name(&mut args)?,
-                properties: None
-            }),
+            "BMC" => {
+                push(Op::BeginMarkedContent {
+                    tag: name(&mut args)?,
+                    properties: None
+                });
+                self.marked_depth += 1;
+            }
             "BT"  => push(Op::BeginText),
-            "BX"  => self.compability_section = true,
+            "BX"  => {
+                push(Op::BeginCompat);
+                self.compability_section = true;
+            }
             "c"   => {
                 points!(args, c1, c2, p);
                 push(Op::CurveTo { c1, c2, p });
@@ -322,8 +351,14 @@ impl OpBuilder {
                 let phase = args.next().ok_or(PdfError::NoOpArg)?.as_number()?;
                 push(Op::Dash { pattern, phase });
             }
-            "d0"  => {}
-            "d1"  => {}
+            "d0"  => {
+                numbers!(args, wx, wy);
+                push(Op::SetCharWidth { wx, wy });
+            }
+            "d1"  => {
+                numbers!(args, wx, wy, left, bottom, right, top);
+                push(Op::SetCacheDevice { wx, wy, bbox: Rectangle { left, bottom, right, top } });
+            }
             "Do" | "Do0" => {
                 names!(args, name);
                 push(Op::XObject { name });
@@ -333,9 +368,15 @@ impl OpBuilder {
                 properties: Some(args.next().ok_or(PdfError::NoOpArg)?)
             }),
             "EI"  => bail!("Parse Error. Unexpected 'EI'"),
-            "EMC" => push(Op::EndMarkedContent),
+            "EMC" => {
+                self.marked_depth = self.marked_depth.checked_sub(1).ok_or(PdfError::ContentUnbalanced { op: "EMC" })?;
+                push(Op::EndMarkedContent);
+            }
             "ET"  => push(Op::EndText),
-            "EX"  => self.compability_section = false,
+            "EX"  => {
+                push(Op::EndCompat);
+                self.compability_section = false;
+            }
             "f" |
             "F"   => push(Op::Fill { winding: NonZero }),
             "f*"  => push(Op::Fill { winding: EvenOdd }),
@@ -386,8 +427,14 @@ impl OpBuilder {
             "M"   => push(Op::MiterLimit { limit: number(&mut args)? }),
             "MP"  => push(Op::MarkedContentPoint { tag: name(&mut args)?, properties: None }),
             "n"   => push(Op::EndPath),
-            "q"   => push(Op::Save),
-            "Q"   => push(Op::Restore),
+            "q"   => {
+                push(Op::Save);
+                self.save_depth += 1;
+            }
+            "Q"   => {
+                self.save_depth = self.save_depth.checked_sub(1).ok_or(PdfError::ContentUnbalanced { op: "Q" })?;
+                push(Op::Restore);
+            }
             "re"  => push(Op::Rect { rect: rect(&mut args)? }),
             "RG"  => push(Op::StrokeColor { color: Color::Rgb(rgb(&mut args)?) }),
             "rg"  => push(Op::FillColor { color: Color::Rgb(rgb(&mut args)?) }),
@@ -408,9 +455,7 @@ impl OpBuilder {
             "sc" | "scn" => {
                 push(Op::FillColor { color: Color::Other(args.collect()) });
             }
-            "sh"  => {
-
-            }
+            "sh"  => push(Op::Shade { name: name(&mut args)? }),
             "T*"  => push(Op::TextNewline),
             "Tc"  => push(Op::CharSpacing { char_space: number(&mut args)? }),
             "Td"  => push(Op::MoveTextPosition { translation: point(&mut args)? }),
@@ -529,6 +574,11 @@ impl FormXObject {
         t!(ops.parse(&data, resolve));
         Ok(ops.ops)
     }
+    /// The resources to use for the operations: the form's own `/Resources`, or else those
+    /// of where it is painted (`outer`), which older files rely on.
+    pub fn resources<'a>(&'a self, outer: &'a Resources) -> &'a Resources {
+        self.dict().resources.as_deref().unwrap_or(outer)
+    }
 }
 impl Object for FormXObject {
     /// Convert primitive to Self
@@ -568,6 +618,11 @@ pub fn serialize_ops(mut ops: &[Op]) -> Result<Vec<u8>> {
                 serialize_name(tag, f)?;
                 writeln!(f, " BMC")?;
             }
+            Op::BeginOptionalContent { ref group } => {
+                write!(f, "/OC ")?;
+                serialize_name(group, f)?;
+                writeln!(f, " BDC")?;
+            }
             Op::MarkedContentPoint { ref tag, properties: Some(ref name) } => {
                 serialize_name(tag, f)?;
                 write!(f, " ")?;
@@ -730,6 +785,12 @@ pub fn serialize_ops(mut ops: &[Op]) -> Result<Vec<u8>> {
                 }
                 writeln!(f, "] TJ")?;
             },
+            Op::SetCharWidth { wx, wy } => writeln!(f, "{} {} d0", wx, wy)?,
+            Op::SetCacheDevice { wx, wy, bbox } => {
+                writeln!(f, "{} {} {} {} {} {} d1", wx, wy, bbox.left, bbox.bottom, bbox.right, bbox.top)?;
+            }
+            Op::BeginCompat => writeln!(f, "BX")?,
+            Op::EndCompat => writeln!(f, "EX")?,
             Op::InlineImage { image: _ } => unimplemented!(),
             Op::XObject { ref name } => {
                 serialize_name(name, f)?;
@@ -741,6 +802,23 @@ pub fn serialize_ops(mut ops: &[Op]) -> Result<Vec<u8>> {
     Ok(data)
 }
 
+/// The operations of a Type 3 glyph procedure as they should be painted.
+///
+/// After `d1` the glyph is only a shape, painted in the current text fill color, so its
+/// color operators are dropped. Glyphs starting with `d0` keep their own colors.
+pub fn type3_glyph_ops(ops: Vec<Op>) -> Vec<Op> {
+    let mut shape_only = false;
+    ops.into_iter().filter(|op| match op {
+        Op::SetCacheDevice { .. } => {
+            shape_only = true;
+            true
+        }
+        Op::StrokeColor { .. } | Op::FillColor { .. } |
+        Op::StrokeColorSpace { .. } | Op::FillColorSpace { .. } => !shape_only,
+        _ => true
+    }).collect()
+}
+
 impl Content {
     pub fn from_ops(operations: Vec<Op>) -> Self {
         let data = serialize_ops(&operations).unwrap();
@@ -752,11 +830,16 @@ impl Content {
 
 impl ObjectWrite for Content {
     fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
-        if self.parts.len() == 1 {
-            let obj = self.parts[0].to_primitive(update)?;
-            update.create(obj)?.to_primitive(update)
+        // content streams have to be indirect objects
+        let mut parts = Vec::with_capacity(self.parts.len());
+        for part in self.parts.iter() {
+            let obj = part.to_primitive(update)?;
+            parts.push(update.create(obj)?.to_primitive(update)?);
+        }
+        if parts.len() == 1 {
+            Ok(parts.pop().unwrap())
         } else {
-            self.parts.to_primitive(update)
+            Ok(Primitive::Array(parts))
         }
     }
 }
@@ -850,15 +933,34 @@ impl Display for ViewRect {
         write!(f, "{} {} {} {}", self.x, self.y, self.width, self.height)
     }
 }
+impl ViewRect {
+    /// The subpath that `re` appends to the current path (8.5.2.1): a move to the
+    /// corner (x, y), three lines and a close.
+    ///
+    /// The direction follows the signs of width and height, which matters for the
+    /// nonzero winding rule. Renderers should add these segments to the path being
+    /// built, so the fill rule of the painting operator applies to all subpaths alike.
+    pub fn subpath(&self) -> [Op; 5] {
+        let ViewRect { x, y, width, height } = *self;
+        [
+            Op::MoveTo { p: Point { x, y } },
+            Op::LineTo { p: Point { x: x + width, y } },
+            Op::LineTo { p: Point { x: x + width, y: y + height } },
+            Op::LineTo { p: Point { x, y: y + height } },
+            Op::Close,
+        ]
+    }
+}
 #[cfg(feature = "euclid")]
 impl Into<euclid::Box2D<f32, PdfSpace>> for ViewRect {
     fn into(self) -> euclid::Box2D<f32, PdfSpace> {
         let ViewRect { x, y, width, height } = self;
 
-        assert!(width > 0.0);
-        assert!(height > 0.0);
+        // negative sizes only change the direction of the subpath
+        let (x0, x1) = if width < 0.0 { (x + width, x) } else { (x, x + width) };
+        let (y0, y1) = if height < 0.0 { (y + height, y) } else { (y, y + height) };
 
-        euclid::Box2D::new(euclid::Point2D::new(x, y), euclid::Point2D::new(x + width, y + height))
+        euclid::Box2D::new(euclid::Point2D::new(x0, y0), euclid::Point2D::new(x1, y1))
     }
 }
 #[cfg(feature = "euclid")]
@@ -902,6 +1004,50 @@ impl Default for Matrix {
         }
     }
 }
+impl Matrix {
+    /// The matrix that leaves points as they are, same as `Matrix::default()`.
+    pub fn identity() -> Matrix {
+        Matrix::default()
+    }
+
+    /// The transformation by `self`, then `other` (`self × other` in PDF notation).
+    ///
+    /// `cm` with `matrix` changes the CTM to `matrix.multiply(&ctm)`.
+    pub fn multiply(&self, other: &Matrix) -> Matrix {
+        Matrix {
+            a: self.a * other.a + self.b * other.c,
+            b: self.a * other.b + self.b * other.d,
+            c: self.c * other.a + self.d * other.c,
+            d: self.c * other.b + self.d * other.d,
+            e: self.e * other.a + self.f * other.c + other.e,
+            f: self.e * other.b + self.f * other.d + other.f,
+        }
+    }
+
+    /// The inverse transformation, `None` if the matrix is singular.
+    pub fn invert(&self) -> Option<Matrix> {
+        let det = self.a * self.d - self.b * self.c;
+        if det == 0. || !det.is_finite() {
+            return None;
+        }
+        Some(Matrix {
+            a: self.d / det,
+            b: -self.b / det,
+            c: -self.c / det,
+            d: self.a / det,
+            e: (self.c * self.f - self.d * self.e) / det,
+            f: (self.b * self.e - self.a * self.f) / det,
+        })
+    }
+
+    /// Transform the point `p`.
+    pub fn apply(&self, p: Point) -> Point {
+        Point {
+            x: self.a * p.x + self.c * p.y + self.e,
+            y: self.b * p.x + self.d * p.y + self.f,
+        }
+    }
+}
 impl Object for Matrix {
     fn from_primitive(p: Primitive, _resolve: &impl Resolve) -> Result<Self> {
         matrix(&mut p.into_array()?.into_iter())
@@ -939,6 +1085,277 @@ pub enum Color {
     Cmyk(Cmyk),
     Other(Vec<Primitive>),
 }
+impl Color {
+    /// The pattern set by `scn` or `SCN` in a `/Pattern` color space, with the color components before it.
+    ///
+    /// Only uncolored tiling patterns (`PaintType` 2) have components, in the underlying color space
+    /// of the `/Pattern` color space. The name is a key of `Resources::pattern`.
+    /// `None` for any other color.
+    pub fn pattern(&self) -> Option<(Name, Vec<f32>)> {
+        match self {
+            Color::Other(args) => match args.split_last()? {
+                (Primitive::Name(name), components) => {
+                    let components = components.iter().map(|c| c.as_number().ok()).collect::<Option<_>>()?;
+                    Some((Name(name.clone()), components))
+                }
+                _ => None
+            },
+            _ => None
+        }
+    }
+}
+
+/// The current fill and stroke color spaces and colors, for finding the actual color of what is
+/// painted (e.g. of text).
+///
+/// Feed it the operations with `apply`. `q` and `Q` are not tracked, clone the state on `Op::Save`
+/// instead. Patterns have no single color, so `fill_rgb` and `stroke_rgb` give `None` for them.
+#[derive(Debug, Clone)]
+pub struct ColorState {
+    pub fill_space: ColorSpace,
+    pub fill: Color,
+    pub stroke_space: ColorSpace,
+    pub stroke: Color,
+}
+impl Default for ColorState {
+    fn default() -> ColorState {
+        ColorState {
+            fill_space: ColorSpace::DeviceGray,
+            fill: Color::Gray(0.),
+            stroke_space: ColorSpace::DeviceGray,
+            stroke: Color::Gray(0.),
+        }
+    }
+}
+impl ColorState {
+    /// Update the state for `op`, looking up named color spaces in `resources`.
+    ///
+    /// Setting a color space also sets its initial color (8.6.8): black, or a tint of 1 for
+    /// `Separation` and `DeviceN`.
+    pub fn apply(&mut self, op: &Op, resources: &Resources) -> Result<()> {
+        let device = |color: &Color| match color {
+            Color::Gray(_) => Some(ColorSpace::DeviceGray),
+            Color::Rgb(_) => Some(ColorSpace::DeviceRGB),
+            Color::Cmyk(_) => Some(ColorSpace::DeviceCMYK),
+            Color::Other(_) => None
+        };
+        match *op {
+            Op::FillColorSpace { ref name } => {
+                self.fill_space = t!(ColorState::lookup(name, resources));
+                self.fill = initial_color(&self.fill_space);
+            }
+            Op::StrokeColorSpace { ref name } => {
+                self.stroke_space = t!(ColorState::lookup(name, resources));
+                self.stroke = initial_color(&self.stroke_space);
+            }
+            Op::FillColor { ref color } => {
+                if let Some(cs) = device(color) {
+                    self.fill_space = cs;
+                }
+                self.fill = color.clone();
+            }
+            Op::StrokeColor { ref color } => {
+                if let Some(cs) = device(color) {
+                    self.stroke_space = cs;
+                }
+                self.stroke = color.clone();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn lookup(name: &Name, resources: &Resources) -> Result<ColorSpace> {
+        Ok(match name.as_str() {
+            "DeviceGray" => ColorSpace::DeviceGray,
+            "DeviceRGB" => ColorSpace::DeviceRGB,
+            "DeviceCMYK" => ColorSpace::DeviceCMYK,
+            "Pattern" => ColorSpace::Pattern,
+            _ => resources.color_spaces.get(name)
+                .ok_or_else(|| other!("color space {} not found", name.as_str()))?
+                .clone()
+        })
+    }
+
+    /// The fill color as sRGB (components in 0..1), see `ColorSpace::to_rgb`.
+    ///
+    /// `None` for patterns and if nothing is painted.
+    pub fn fill_rgb(&self) -> Result<Option<[f32; 3]>> {
+        color_to_rgb(&self.fill_space, &self.fill)
+    }
+
+    /// The stroke color as sRGB (components in 0..1), see `ColorSpace::to_rgb`.
+    ///
+    /// `None` for patterns and if nothing is painted.
+    pub fn stroke_rgb(&self) -> Result<Option<[f32; 3]>> {
+        color_to_rgb(&self.stroke_space, &self.stroke)
+    }
+}
+
+fn initial_color(cs: &ColorSpace) -> Color {
+    match *cs {
+        ColorSpace::DeviceGray => Color::Gray(0.),
+        ColorSpace::DeviceRGB => Color::Rgb(Rgb { red: 0., green: 0., blue: 0. }),
+        ColorSpace::DeviceCMYK => Color::Cmyk(Cmyk { cyan: 0., magenta: 0., yellow: 0., key: 1. }),
+        ColorSpace::Separation(..) | ColorSpace::DeviceN { .. } => {
+            let n = cs.num_components().unwrap_or(1);
+            Color::Other(vec![Primitive::Number(1.); n])
+        }
+        _ => {
+            let n = cs.num_components().unwrap_or(0);
+            Color::Other(vec![Primitive::Integer(0); n])
+        }
+    }
+}
+
+fn color_to_rgb(cs: &ColorSpace, color: &Color) -> Result<Option<[f32; 3]>> {
+    let components = match *color {
+        Color::Gray(g) => vec![g],
+        Color::Rgb(Rgb { red, green, blue }) => vec![red, green, blue],
+        Color::Cmyk(Cmyk { cyan, magenta, yellow, key }) => vec![cyan, magenta, yellow, key],
+        Color::Other(ref args) => {
+            if matches!(cs, ColorSpace::Pattern) || color.pattern().is_some() {
+                return Ok(None);
+            }
+            args.iter().map(|p| p.as_number()).collect::<Result<_>>()?
+        }
+    };
+    cs.to_rgb(&components)
+}
+
+/// The text state parameters (9.3), part of the graphics state.
+#[derive(Debug, Clone)]
+pub struct TextState {
+    /// The font resource and the size set by `Tf`.
+    pub font: Option<(Name, f32)>,
+    pub char_space: f32,
+    pub word_space: f32,
+    /// `Tz` as a factor, 1 for 100%.
+    pub horiz_scale: f32,
+    pub leading: f32,
+    pub rise: f32,
+    pub mode: TextMode,
+}
+impl Default for TextState {
+    fn default() -> TextState {
+        TextState {
+            font: None,
+            char_space: 0.,
+            word_space: 0.,
+            horiz_scale: 1.,
+            leading: 0.,
+            rise: 0.,
+            mode: TextMode::Fill,
+        }
+    }
+}
+
+/// The part of the graphics state that doesn't need rendering: the transform, colors, line
+/// width and text state, plus the text and text line matrix.
+///
+/// Feed it the operations with `apply`. `q` and `Q` save and restore everything but the
+/// text matrices, as in a content stream. Showing text does not move the text matrix, as that
+/// needs the widths of the font.
+#[derive(Debug, Clone)]
+pub struct ContentState {
+    /// The current transformation matrix, from user space to the space `ContentState::new` was given.
+    pub ctm: Matrix,
+    pub colors: ColorState,
+    pub line_width: f32,
+    pub text: TextState,
+    pub text_matrix: Matrix,
+    pub line_matrix: Matrix,
+    stack: Vec<(Matrix, ColorState, f32, TextState)>,
+}
+impl Default for ContentState {
+    fn default() -> ContentState {
+        ContentState::new(Matrix::default())
+    }
+}
+impl ContentState {
+    /// The initial state with the transform `ctm`, e.g. of a form XObject.
+    pub fn new(ctm: Matrix) -> ContentState {
+        ContentState {
+            ctm,
+            colors: ColorState::default(),
+            line_width: 1.,
+            text: TextState::default(),
+            text_matrix: Matrix::default(),
+            line_matrix: Matrix::default(),
+            stack: vec![],
+        }
+    }
+    /// The state at the start of a form XObject painted in this state.
+    ///
+    /// `matrix` (the form's `/Matrix`) is applied to the CTM, and a `Q` in the form can't
+    /// restore a state saved outside of it.
+    pub fn begin_form(&self, matrix: Option<&Matrix>) -> ContentState {
+        ContentState {
+            ctm: matrix.map_or(self.ctm, |m| m.multiply(&self.ctm)),
+            colors: self.colors.clone(),
+            line_width: self.line_width,
+            text: self.text.clone(),
+            text_matrix: Matrix::default(),
+            line_matrix: Matrix::default(),
+            stack: vec![],
+        }
+    }
+    /// Update the state for `op`, looking up named color spaces in `resources`.
+    ///
+    /// A `Q` without matching `q` is ignored.
+    pub fn apply(&mut self, op: &Op, resources: &Resources) -> Result<()> {
+        let translate = |x: f32, y: f32| Matrix { e: x, f: y, ..Matrix::default() };
+        match *op {
+            Op::Save => self.stack.push((self.ctm, self.colors.clone(), self.line_width, self.text.clone())),
+            Op::Restore => if let Some((ctm, colors, line_width, text)) = self.stack.pop() {
+                self.ctm = ctm;
+                self.colors = colors;
+                self.line_width = line_width;
+                self.text = text;
+            }
+            Op::Transform { ref matrix } => self.ctm = matrix.multiply(&self.ctm),
+            Op::LineWidth { width } => self.line_width = width,
+            Op::FillColorSpace { .. } | Op::StrokeColorSpace { .. } | Op::FillColor { .. } | Op::StrokeColor { .. } => {
+                t!(self.colors.apply(op, resources));
+            }
+
+            Op::TextFont { ref name, size } => self.text.font = Some((name.clone(), size)),
+            Op::CharSpacing { char_space } => self.text.char_space = char_space,
+            Op::WordSpacing { word_space } => self.text.word_space = word_space,
+            Op::TextScaling { horiz_scale } => self.text.horiz_scale = horiz_scale / 100.,
+            Op::Leading { leading } => self.text.leading = leading,
+            Op::TextRise { rise } => self.text.rise = rise,
+            Op::TextRenderMode { mode } => self.text.mode = mode,
+
+            Op::BeginText => {
+                self.text_matrix = Matrix::default();
+                self.line_matrix = Matrix::default();
+            }
+            Op::MoveTextPosition { translation } => {
+                self.line_matrix = translate(translation.x, translation.y).multiply(&self.line_matrix);
+                self.text_matrix = self.line_matrix;
+            }
+            Op::SetTextMatrix { ref matrix } => {
+                self.line_matrix = *matrix;
+                self.text_matrix = *matrix;
+            }
+            Op::TextNewline => {
+                self.line_matrix = translate(0., -self.text.leading).multiply(&self.line_matrix);
+                self.text_matrix = self.line_matrix;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+    /// The transform from text space to the space of `ctm` for the current text matrix,
+    /// including the font size, horizontal scaling and rise.
+    pub fn text_rendering_matrix(&self) -> Matrix {
+        let size = self.text.font.as_ref().map_or(1., |&(_, size)| size);
+        Matrix { a: size * self.text.horiz_scale, d: size, f: self.text.rise, ..Matrix::default() }
+            .multiply(&self.text_matrix)
+            .multiply(&self.ctm)
+    }
+}
 
 #[derive(Debug, Copy, Clone, PartialEq, DataSize)]
 pub enum TextMode {
@@ -1002,6 +1419,13 @@ pub enum Op {
     /// generated by operators `BMC` and `BDC`
     BeginMarkedContent { tag: Name, properties: Option<Primitive> },
 
+    /// Begin a marked content sequence of optional content, `/OC /group BDC`.
+    /// 
+    /// `group` names an optional content group or membership dictionary in the
+    /// `/Properties` resources, see `Op::optional_content`.
+    /// Pairs with the following EndMarkedContent.
+    BeginOptionalContent { group: Name },
+
     /// End a marked content sequence.
     /// 
     /// Pairs with the previous BeginMarkedContent.
@@ -1038,6 +1462,12 @@ pub enum Op {
     /// operator: `sh`
     Shade { name: Name },
 
+    /// Intersect the clip with the current path.
+    ///
+    /// Like in the content stream, this comes before the op that ends the path (usually
+    /// `EndPath`, see `Op::ends_path`), and the clip takes effect after it.
+    ///
+    /// operators: `W`, `W*`
     Clip { winding: Winding },
 
     Save,
@@ -1092,6 +1522,141 @@ pub enum Op {
     XObject { name: Name },
 
     InlineImage { image: Arc<ImageXObject> },
+
+    /// Set the width of a Type 3 glyph, which also paints its color.
+    ///
+    /// operator: `d0`
+    SetCharWidth { wx: f32, wy: f32 },
+
+    /// Set the width and bounding box of a Type 3 glyph, which only paints a shape.
+    ///
+    /// operator: `d1`
+    SetCacheDevice { wx: f32, wy: f32, bbox: Rectangle },
+
+    /// Begin a compatibility section, in which unknown operators are ignored.
+    ///
+    /// operator: `BX`
+    BeginCompat,
+
+    /// End a compatibility section.
+    ///
+    /// operator: `EX`
+    EndCompat,
+}
+
+impl Op {
+    /// Whether the op paints or discards the current path, so that a preceding `Clip` applies.
+    pub fn ends_path(&self) -> bool {
+        matches!(self, Op::Stroke | Op::Fill { .. } | Op::FillAndStroke { .. } | Op::EndPath)
+    }
+    /// The property list of a `BDC` or `DP` operator.
+    ///
+    /// If the operand is a name, it is looked up in the `/Properties` dictionary of `resources`,
+    /// otherwise it has to be an inline dictionary. Returns `None` for all other operators
+    /// and for marked content without properties.
+    pub fn marked_content_properties(&self, resources: &Resources, resolve: &impl Resolve) -> Result<Option<Dictionary>> {
+        if let Op::BeginOptionalContent { ref group } = *self {
+            let dict = self.optional_content(resources)
+                .ok_or_else(|| PdfError::MissingEntry { typ: "Properties", field: group.as_str().into() })?;
+            return Ok(Some((**dict.data()).clone()));
+        }
+        let properties = match *self {
+            Op::BeginMarkedContent { properties: Some(ref p), .. } |
+            Op::MarkedContentPoint { properties: Some(ref p), .. } => p,
+            _ => return Ok(None)
+        };
+        match *properties {
+            Primitive::Name(ref name) => {
+                let dict = resources.properties.get(name.as_str())
+                    .ok_or_else(|| PdfError::MissingEntry { typ: "Properties", field: name.as_str().into() })?;
+                Ok(Some((**dict.data()).clone()))
+            }
+            ref p => Ok(Some(t!(p.clone().resolve(resolve)?.into_dictionary())))
+        }
+    }
+    /// The optional content group or membership dictionary of `Op::BeginOptionalContent`.
+    ///
+    /// `None` for other operators, or if the `/Properties` of `resources` have no such entry.
+    /// The reference of the group can be compared with those of an `OptionalContentConfig`.
+    pub fn optional_content<'a>(&self, resources: &'a Resources) -> Option<&'a MaybeRef<Dictionary>> {
+        match *self {
+            Op::BeginOptionalContent { ref group } => resources.properties.get(group),
+            _ => None
+        }
+    }
+}
+
+/// Remove content that is hidden in the optional content configuration `config`.
+///
+/// Marked content sequences `/OC /name BDC ... EMC` (`Op::BeginOptionalContent`) are dropped,
+/// including nested sequences, if the group or membership dictionary `/name` refers to is off.
+pub fn filter_optional_content(ops: Vec<Op>, resources: &Resources, config: &OptionalContentConfig, resolve: &impl Resolve) -> Result<Vec<Op>> {
+    let mut out = Vec::with_capacity(ops.len());
+    // one entry per open marked content sequence: whether it is visible
+    let mut stack: Vec<bool> = vec![];
+    let mut hidden = 0;
+    for op in ops {
+        match op {
+            Op::BeginOptionalContent { .. } => {
+                let visible = match op.optional_content(resources) {
+                    Some(dict) => optional_content_visible(dict, config, resolve)?,
+                    None => true
+                };
+                if !visible {
+                    hidden += 1;
+                }
+                stack.push(visible);
+                if hidden == 0 {
+                    out.push(op);
+                }
+            }
+            Op::BeginMarkedContent { .. } => {
+                stack.push(true);
+                if hidden == 0 {
+                    out.push(op);
+                }
+            }
+            Op::EndMarkedContent => {
+                let visible = stack.pop().unwrap_or(true);
+                if hidden == 0 {
+                    out.push(op);
+                }
+                if !visible {
+                    hidden -= 1;
+                }
+            }
+            op => if hidden == 0 {
+                out.push(op);
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn optional_content_visible(dict: &MaybeRef<Dictionary>, config: &OptionalContentConfig, resolve: &impl Resolve) -> Result<bool> {
+    if dict.get("Type").and_then(|t| t.as_name().ok()) != Some("OCMD") {
+        return Ok(match dict.as_ref() {
+            Some(r) => config.is_on(r.get_inner()),
+            None => true
+        });
+    }
+    // optional content membership dictionary
+    let groups: Vec<PlainRef> = match dict.get("OCGs") {
+        Some(&Primitive::Reference(r)) => match resolve.resolve(r)? {
+            Primitive::Array(arr) => arr.iter().map(|p| p.clone().into_reference()).collect::<Result<_>>()?,
+            _ => vec![r]
+        },
+        Some(Primitive::Array(arr)) => arr.iter().map(|p| p.clone().into_reference()).collect::<Result<_>>()?,
+        _ => return Ok(true)
+    };
+    let mut states = groups.iter().map(|&r| config.is_on(r));
+    let policy = dict.get("P").map(|p| p.as_name()).transpose()?.unwrap_or("AnyOn");
+    Ok(match policy {
+        "AllOn" => states.all(|on| on),
+        "AnyOff" => states.any(|on| !on),
+        "AllOff" => states.all(|on| !on),
+        _ => states.any(|on| on),
+    })
 }
 
 pub fn deep_clone_op(op: &Op, cloner: &mut impl Cloner, old_resources: &Resources, resources: &mut Resources) -> Result<Op> {
@@ -1110,6 +1675,14 @@ pub fn deep_clone_op(op: &Op, cloner: &mut impl Cloner, old_resources: &Resource
         Op::BeginMarkedContent { ref tag, ref properties } => {
             Ok(Op::BeginMarkedContent { tag: tag.clone(), properties: properties.deep_clone(cloner)? })
         }
+        Op::BeginOptionalContent { ref group } => {
+            if !resources.properties.contains_key(group) {
+                if let Some(dict) = old_resources.properties.get(group) {
+                    resources.properties.insert(group.clone(), dict.deep_clone(cloner)?);
+                }
+            }
+            Ok(Op::BeginOptionalContent { group: group.clone() })
+        }
         Op::TextFont { ref name, size } => {
             if !resources.fonts.contains_key(name) {
                 if let Some(f) = old_resources.fonts.get(name) {
@@ -1126,6 +1699,14 @@ pub fn deep_clone_op(op: &Op, cloner: &mut impl Cloner, old_resources: &Resource
             }
             Ok(Op::XObject { name: name.clone() })
         }
+        Op::Shade { ref name } => {
+            if !resources.shadings.contains_key(name) {
+                if let Some(sh) = old_resources.shadings.get(name) {
+                    resources.shadings.insert(name.clone(), sh.deep_clone(cloner)?);
+                }
+            }
+            Ok(Op::Shade { name: name.clone() })
+        }
         ref op => Ok(op.clone())
     }
 }
@@ -1151,4 +1732,380 @@ EI
         let mut lexer = Lexer::new(data);
         assert!(inline_image(&mut lexer, &NoResolve).is_ok()); 
     }
+
+    #[test]
+    fn inline_image_pixels() {
+        let image = |data: &[u8]| match parse_ops(data, &NoResolve).unwrap()[1] {
+            Op::InlineImage { ref image } => image.clone(),
+            ref op => panic!("{:?}", op)
+        };
+        // a red 2x2 square, flate compressed
+        let mut data = b"q BI /W 2 /H 2 /CS /RGB /BPC 8 /F /Fl ID ".to_vec();
+        data.extend_from_slice(b"\x78\x9c\xfb\xcf\xc0\xf0\x1f\x86\x00\x1d\xee\x03\xfd\nEI Q");
+        let pixels = image(&data).decode_pixels(&NoResolve, None).unwrap();
+        assert_eq!((pixels.width, pixels.height), (2, 2));
+        assert_eq!(pixels.data, [255, 0, 0, 255].repeat(4));
+
+        // 2 bit indexed from the resources, with an inverting decode array
+        let mut resources = Resources::default();
+        let cs = crate::parser::parse(b"[/Indexed /DeviceRGB 3 <000000 FF0000 00FF00 0000FF>]", &NoResolve, ParseFlags::ANY).unwrap();
+        resources.color_spaces.insert("CS0".into(), ColorSpace::from_primitive(cs, &NoResolve).unwrap());
+        let pixels = image(b"q BI /W 3 /H 1 /CS /CS0 /BPC 2 /D [3 0] ID \x1b\nEI Q").decode_pixels(&NoResolve, Some(&resources)).unwrap();
+        assert_eq!(pixels.data, [[0, 0, 255, 255], [0, 255, 0, 255], [255, 0, 0, 255]].concat());
+
+        // image masks
+        let pixels = image(b"q BI /W 3 /H 1 /IM true ID \xa0\nEI Q").decode_pixels(&NoResolve, None).unwrap();
+        assert_eq!(pixels.data, [[0, 0, 0, 0], [0, 0, 0, 255], [0, 0, 0, 0]].concat());
+        let pixels = image(b"q BI /W 3 /H 1 /IM true /D [1 0] ID \xa0\nEI Q").decode_pixels(&NoResolve, None).unwrap();
+        assert_eq!(pixels.data, [[0, 0, 0, 255], [0, 0, 0, 0], [0, 0, 0, 255]].concat());
+    }
+
+    #[test]
+    fn inline_image_filter_chains() {
+        use crate::enc::{LZWFlateParams, StreamFilter};
+        let image = |data: &[u8]| match parse_ops(data, &NoResolve).unwrap()[1] {
+            Op::InlineImage { ref image } => image.clone(),
+            ref op => panic!("{:?}", op)
+        };
+        let inline = |dict: &[u8], data: &[u8]| [b"q BI ", dict, b" ID ", data, b"\nEI Q"].concat();
+
+        // run length data with a byte past the 16x2 bitmap
+        let data = inline(b"/W 16 /H 2 /BPC 1 /CS /G /F /RL", b"\x04\xf0\x0f\xff\x00\xaa\x80");
+        let bitmap = image(&data);
+        assert_eq!(&*bitmap.image_data(&NoResolve).unwrap(), b"\xf0\x0f\xff\x00");
+        assert_eq!(bitmap.samples(&NoResolve, None).unwrap().len(), 32);
+
+        // flate followed by run length
+        let red = [255, 0, 0].repeat(4);
+        let rl = [&[11][..], &red, &[128]].concat();
+        let flate = StreamFilter::FlateDecode(LZWFlateParams::default());
+        let data = inline(b"/W 2 /H 2 /CS /RGB /BPC 8 /F [/Fl /RL]", &crate::enc::encode(&rl, &flate).unwrap());
+        assert_eq!(&*image(&data).image_data(&NoResolve).unwrap(), &red[..]);
+
+        // a PNG predictor from the abbreviated /DP
+        let params = LZWFlateParams { predictor: 12, n_components: 3, columns: 2, ..LZWFlateParams::default() };
+        let encoded = crate::enc::encode(&red, &StreamFilter::FlateDecode(params)).unwrap();
+        let data = inline(b"/W 2 /H 2 /CS /RGB /BPC 8 /F /Fl /DP << /Predictor 12 /Colors 3 /Columns 2 >>", &encoded);
+        let pixels = image(&data).decode_pixels(&NoResolve, None).unwrap();
+        assert_eq!(pixels.data, [255, 0, 0, 255].repeat(4));
+    }
+
+    #[test]
+    fn test_content_state() {
+        let ops = parse_ops(b"2 0 0 2 10 10 cm 1 0 0 rg 3 w q 1 0 0 1 5 0 cm 0 g 5 w /F1 12 Tf 14 TL Q BT 20 30 Td T* 50 Tz ET", &NoResolve).unwrap();
+        let resources = Resources::default();
+        let mut state = ContentState::default();
+        let mut states = vec![];
+        for op in &ops {
+            state.apply(op, &resources).unwrap();
+            states.push(state.clone());
+        }
+        // inside q ... Q
+        let inner = &states[8];
+        assert_eq!((inner.ctm.a, inner.ctm.e, inner.ctm.f), (2., 20., 10.));
+        assert_eq!(inner.colors.fill_rgb().unwrap(), Some([0., 0., 0.]));
+        assert_eq!((inner.line_width, inner.text.leading), (5., 14.));
+        assert_eq!(inner.text.font.as_ref().map(|(name, size)| (name.as_str(), *size)), Some(("F1", 12.)));
+
+        // restored
+        assert_eq!((state.ctm.a, state.ctm.e, state.ctm.f), (2., 10., 10.));
+        assert_eq!(state.colors.fill_rgb().unwrap(), Some([1., 0., 0.]));
+        assert_eq!((state.line_width, state.text.leading), (3., 0.));
+        assert!(state.text.font.is_none());
+        assert_eq!(state.text.horiz_scale, 0.5);
+        // Td, then T* with the leading of 0
+        assert_eq!((state.text_matrix.e, state.text_matrix.f), (20., 30.));
+        let trm = state.text_rendering_matrix();
+        assert_eq!((trm.a, trm.d, trm.e, trm.f), (1., 2., 50., 70.));
+
+        // an unbalanced Q is ignored
+        state.apply(&Op::Restore, &resources).unwrap();
+        assert_eq!(state.ctm.e, 10.);
+
+        // a form can't restore what was saved outside of it
+        state.apply(&Op::Save, &resources).unwrap();
+        let mut form = state.begin_form(Some(&Matrix { e: 1., ..Matrix::default() }));
+        assert_eq!((form.ctm.e, form.line_width), (12., 3.));
+        form.apply(&Op::Restore, &resources).unwrap();
+        assert_eq!(form.ctm.e, 12.);
+    }
+
+    #[test]
+    fn test_marked_content_properties() {
+        let mut oc = Dictionary::new();
+        oc.insert("Type", Name::from("OCG"));
+        let mut resources = Resources::default();
+        resources.properties.insert("oc1".into(), MaybeRef::Direct(Shared::new(oc)));
+
+        let ops = parse_ops(b"/OC /oc1 BDC EMC /Span << /ActualText (x) >> BDC EMC /OC /oc2 BDC EMC", &NoResolve).unwrap();
+        let props = ops[0].marked_content_properties(&resources, &NoResolve).unwrap().unwrap();
+        assert_eq!(props.get("Type").unwrap().as_name().unwrap(), "OCG");
+        assert!(ops[1].marked_content_properties(&resources, &NoResolve).unwrap().is_none());
+        let props = ops[2].marked_content_properties(&resources, &NoResolve).unwrap().unwrap();
+        assert!(props.contains_key("ActualText"));
+        assert!(ops[4].marked_content_properties(&resources, &NoResolve).is_err());
+    }
+
+    #[test]
+    fn test_optional_content_ops() {
+        let mut resources = Resources::default();
+        resources.properties.insert("oc1".into(), MaybeRef::Direct(Shared::new(Dictionary::new())));
+
+        let ops = parse_ops(b"/OC /oc1 BDC EMC /OC << /Type /OCMD >> BDC EMC /Layer /oc1 BDC EMC", &NoResolve).unwrap();
+        assert!(matches!(ops[0], Op::BeginOptionalContent { ref group } if group == "oc1"));
+        assert!(ops[0].optional_content(&resources).is_some());
+        // only named groups, and only with the /OC tag
+        assert!(matches!(ops[2], Op::BeginMarkedContent { ref tag, .. } if tag == "OC"));
+        assert!(matches!(ops[4], Op::BeginMarkedContent { ref tag, .. } if tag == "Layer"));
+        assert!(ops[4].optional_content(&resources).is_none());
+
+        let data = serialize_ops(&ops).unwrap();
+        assert!(std::str::from_utf8(&data).unwrap().starts_with("/OC /oc1 BDC\n"));
+        let ops2 = parse_ops(&data, &NoResolve).unwrap();
+        assert_eq!(format!("{:?}", ops), format!("{:?}", ops2));
+    }
+
+    #[test]
+    fn test_filter_optional_content() {
+        let group = PlainRef { id: 5, gen: 0 };
+        let mut resources = Resources::default();
+        resources.properties.insert("oc1".into(), MaybeRef::Indirect(RcRef::new(group, Shared::new(Dictionary::new()))));
+
+        let config = OptionalContentConfig {
+            name: None,
+            creator: None,
+            base_state: None,
+            on: vec![],
+            off: vec![Ref::new(group)],
+            order: None,
+            other: Dictionary::new(),
+        };
+        let ops = parse_ops(b"/OC /oc1 BDC /Span BMC 0 0 m EMC EMC 1 1 m /OC /oc2 BDC EMC", &NoResolve).unwrap();
+        let ops = filter_optional_content(ops, &resources, &config, &NoResolve).unwrap();
+        assert_eq!(ops.len(), 3);
+        assert!(matches!(ops[0], Op::MoveTo { p: Point { x, .. } } if x == 1.0));
+    }
+
+    #[test]
+    fn test_line_style_round_trip() {
+        let ops = parse_ops(b"[3 1.5] 0.5 d 1 J 2 j 4.5 M 0 0 m 10 10 l S [] 0 d", &NoResolve).unwrap();
+        let data = serialize_ops(&ops).unwrap();
+        let text = std::str::from_utf8(&data).unwrap();
+        for line in ["[3 1.5] 0.5 d", "1 J", "2 j", "4.5 M", "[] 0 d"] {
+            assert!(text.lines().any(|l| l == line), "missing {:?} in {:?}", line, text);
+        }
+        let ops2 = parse_ops(&data, &NoResolve).unwrap();
+        assert_eq!(format!("{:?}", ops), format!("{:?}", ops2));
+        assert!(matches!(ops2[..4], [
+            Op::Dash { ref pattern, phase: 0.5 },
+            Op::LineCap { cap: LineCap::Round },
+            Op::LineJoin { join: LineJoin::Bevel },
+            Op::MiterLimit { limit: 4.5 },
+        ] if pattern == &[3., 1.5]));
+    }
+
+    #[test]
+    fn test_limits() {
+        use crate::object::{ParseOptions, PlainRef};
+        use crate::enc::StreamFilter;
+
+        struct Limited(ParseOptions);
+        impl Resolve for Limited {
+            fn resolve_flags(&self, _: PlainRef, _: ParseFlags, _: usize) -> Result<Primitive> {
+                Err(PdfError::Reference)
+            }
+            fn get<T: Object+DataSize>(&self, _: Ref<T>) -> Result<RcRef<T>> {
+                Err(PdfError::Reference)
+            }
+            fn options(&self) -> &ParseOptions {
+                &self.0
+            }
+            fn get_data_or_decode(&self, _: PlainRef, _: std::ops::Range<usize>, _: &[StreamFilter]) -> Result<Arc<[u8]>> {
+                Err(PdfError::Reference)
+            }
+            fn stream_data(&self, _: PlainRef, _: std::ops::Range<usize>) -> Result<Arc<[u8]>> {
+                Err(PdfError::Reference)
+            }
+        }
+        let limited = |max_content_ops, max_content_nesting| Limited(ParseOptions {
+            max_content_ops,
+            max_content_nesting,
+            .. ParseOptions::strict()
+        });
+
+        let data = b"q q /P BMC 0 0 m EMC Q Q";
+        assert_eq!(parse_ops(data, &limited(Some(7), Some(2))).unwrap().len(), 7);
+        assert!(matches!(parse_ops(data, &limited(Some(6), None)), Err(PdfError::ContentTooManyOps { max: 6 })));
+        assert!(matches!(parse_ops(data, &limited(None, Some(1))), Err(PdfError::ContentNestingTooDeep { max: 1 })));
+
+        // an unbalanced Q is dropped, or an error if invalid ops are not allowed
+        let ops = parse_ops(b"q Q Q EMC", &NoResolve).unwrap();
+        assert!(matches!(ops[..], [Op::Save, Op::Restore]));
+        let strict = Limited(ParseOptions { allow_invalid_ops: false, .. ParseOptions::strict() });
+        assert!(matches!(parse_ops(b"q Q Q", &strict), Err(PdfError::ContentUnbalanced { op: "Q" })));
+    }
+
+    #[test]
+    fn test_type3_and_compat_ops() {
+        let data = b"500 0 d0 600 0 -10 -20 590 700 d1 /Sh1 sh BX 1 2 foo EX";
+        let ops = parse_ops(data, &NoResolve).unwrap();
+        assert!(matches!(ops[0], Op::SetCharWidth { wx, wy } if wx == 500. && wy == 0.));
+        assert!(matches!(ops[1], Op::SetCacheDevice { wx: 600., bbox: Rectangle { left, top, .. }, .. } if left == -10. && top == 700.));
+        assert!(matches!(ops[2], Op::Shade { ref name } if name == "Sh1"));
+        assert!(matches!(ops[3], Op::BeginCompat));
+        assert!(matches!(ops[4], Op::EndCompat));
+        assert_eq!(ops.len(), 5);
+
+        let data = serialize_ops(&ops).unwrap();
+        assert_eq!(std::str::from_utf8(&data).unwrap(), "500 0 d0\n600 0 -10 -20 590 700 d1\n/Sh1 sh\nBX\nEX\n");
+        assert_eq!(parse_ops(&data, &NoResolve).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_pattern_color() {
+        let ops = parse_ops(b"/P1 scn 0.5 0 1 /P2 SCN 0.5 sc", &NoResolve).unwrap();
+        let colors: Vec<_> = ops.iter().map(|op| match op {
+            Op::FillColor { color } | Op::StrokeColor { color } => color.pattern().map(|(name, c)| (name.as_str().to_owned(), c)),
+            _ => panic!("{:?}", op)
+        }).collect();
+        assert_eq!(colors, [Some(("P1".into(), vec![])), Some(("P2".into(), vec![0.5, 0., 1.])), None]);
+
+        let data = serialize_ops(&ops).unwrap();
+        assert_eq!(std::str::from_utf8(&data).unwrap(), "/P1 scn\n0.5 0 1 /P2 SCN\n0.5 scn\n");
+    }
+
+    #[test]
+    fn test_color_state() {
+        let separation = b"[/Separation /Spot /DeviceCMYK << /FunctionType 2 /Domain [0 1] /C0 [0 0 0 0] /C1 [0 1 1 0] /N 1 >>]";
+        let separation = ColorSpace::from_primitive(crate::parser::parse(separation, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        let mut resources = Resources::default();
+        resources.color_spaces.insert("CS0".into(), separation);
+
+        let ops = parse_ops(b"1 0 0 rg /CS0 CS 0.5 g /CS0 cs 0.5 sc /Pattern cs /P1 scn", &NoResolve).unwrap();
+        let mut state = ColorState::default();
+        let mut colors = vec![];
+        for op in &ops {
+            state.apply(op, &resources).unwrap();
+            colors.push((state.fill_rgb().unwrap(), state.stroke_rgb().unwrap()));
+        }
+        let red = Some([1., 0., 0.]);
+        assert_eq!(colors, [
+            (red, Some([0.; 3])),
+            // the initial tint of a separation is 1
+            (red, red),
+            (Some([0.5; 3]), red),
+            (red, red),
+            (Some([1., 0.5, 0.5]), red),
+            (None, red),
+            (None, red),
+        ]);
+        assert!(state.apply(&Op::FillColorSpace { name: "CS1".into() }, &resources).is_err());
+    }
+
+    #[test]
+    fn test_form_resources() {
+        let dict = crate::parser::parse(b"<< /XObject << /Im1 5 0 R >> >>", &NoResolve, crate::parser::ParseFlags::DICT).unwrap();
+        let own = Resources::from_dict(dict.into_dictionary().unwrap(), &NoResolve).unwrap();
+        let outer = Resources::default();
+
+        let form = |resources| FormXObject { stream: Stream::new(FormDict { resources, ..FormDict::default() }, vec![]) };
+        assert!(form(None).resources(&outer).xobjects.is_empty());
+        let with_own = form(Some(MaybeRef::Direct(Arc::new(own))));
+        assert_eq!(with_own.resources(&outer).xobjects["Im1"].get_inner().id, 5);
+    }
+
+    #[test]
+    fn test_matrix() {
+        let scale = Matrix { a: 2., d: 4., ..Matrix::identity() };
+        let translate = Matrix { e: 10., f: 20., ..Matrix::identity() };
+        let p = Point { x: 1., y: 1. };
+        // scaled first, then translated
+        let m = scale.multiply(&translate);
+        assert_eq!(m.apply(p), Point { x: 12., y: 24. });
+        assert_eq!(translate.multiply(&scale).apply(p), Point { x: 22., y: 84. });
+        assert_eq!(m.multiply(&Matrix::identity()), m);
+
+        let rotate = Matrix { a: 0., b: 1., c: -1., d: 0., e: 5., f: 0. };
+        let m = m.multiply(&rotate);
+        let inverse = m.invert().unwrap();
+        let q = inverse.apply(m.apply(Point { x: 3., y: -7. }));
+        assert!((q.x - 3.).abs() < 1e-5 && (q.y + 7.).abs() < 1e-5, "{:?}", q);
+
+        assert_eq!(Matrix { a: 1., b: 2., c: 2., d: 4., e: 1., f: 1. }.invert(), None);
+    }
+
+    #[test]
+    fn test_clip_ops() {
+        let data = b"0 0 10 10 re W n 5 5 m 9 9 l W* S";
+        let ops = parse_ops(data, &NoResolve).unwrap();
+        assert!(matches!(ops[..], [
+            Op::Rect { .. }, Op::Clip { winding: Winding::NonZero }, Op::EndPath,
+            Op::MoveTo { .. }, Op::LineTo { .. }, Op::Clip { winding: Winding::EvenOdd }, Op::Stroke,
+        ]));
+        assert!(ops[2].ends_path() && ops[6].ends_path() && !ops[1].ends_path());
+
+        let data = serialize_ops(&ops).unwrap();
+        assert!(std::str::from_utf8(&data).unwrap().ends_with("W\nn\n5 5 m\n9 9 l\nW*\nS\n"));
+    }
+
+    #[test]
+    fn test_type3_glyph_colors() {
+        let glyph = b"600 0 0 0 500 700 d1 1 0 0 rg /CS0 cs 0 0 500 700 re f";
+        let ops = type3_glyph_ops(parse_ops(glyph, &NoResolve).unwrap());
+        assert!(matches!(ops[..], [Op::SetCacheDevice { .. }, Op::Rect { .. }, Op::Fill { .. }]));
+
+        let glyph = b"600 0 d0 1 0 0 rg 0 0 500 700 re f";
+        let ops = type3_glyph_ops(parse_ops(glyph, &NoResolve).unwrap());
+        assert!(matches!(ops[1], Op::FillColor { color: Color::Rgb(_) }));
+        assert_eq!(ops.len(), 4);
+    }
+
+    #[test]
+    fn test_rect_subpath_winding() {
+        // a donut: outer square and a hole, the hole drawn in the opposite direction
+        let ops = parse_ops(b"0 0 10 10 re 8 2 -6 6 re f* 0 0 10 10 re 2 2 6 6 re f", &NoResolve).unwrap();
+
+        let mut paths = vec![];
+        let mut path = vec![];
+        for op in &ops {
+            match *op {
+                Op::Rect { rect } => path.extend(rect.subpath()),
+                Op::Fill { winding } => paths.push((winding, std::mem::take(&mut path))),
+                _ => panic!("unexpected {:?}", op)
+            }
+        }
+
+        // winding number of the point (5, 5), from a ray towards +x
+        let winding_number = |path: &[Op]| {
+            let (mut start, mut last, mut n) = (Point::default(), Point::default(), 0);
+            for op in path {
+                let p = match *op {
+                    Op::MoveTo { p } => {
+                        start = p;
+                        last = p;
+                        continue;
+                    }
+                    Op::LineTo { p } => p,
+                    Op::Close => start,
+                    _ => unreachable!()
+                };
+                if (last.y <= 5.) != (p.y <= 5.) && last.x > 5. {
+                    n += if p.y > last.y { 1 } else { -1 };
+                }
+                last = p;
+            }
+            n
+        };
+        let filled = |(winding, ref path): (Winding, Vec<Op>)| match (winding, winding_number(path)) {
+            (Winding::EvenOdd, n) => n % 2 != 0,
+            (Winding::NonZero, n) => n != 0,
+        };
+        let mut paths = paths.into_iter();
+        // the hole stays empty with either rule
+        assert!(!filled(paths.next().unwrap()));
+        // same direction: nonzero fills the hole, even-odd would not
+        let (_, path) = paths.next().unwrap();
+        assert!(filled((Winding::NonZero, path.clone())));
+        assert!(!filled((Winding::EvenOdd, path)));
+    }
 }
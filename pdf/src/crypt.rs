@@ -8,12 +8,14 @@ use sha2::{Digest, Sha256, Sha384, Sha512};
 use std::fmt;
 use std::collections::HashMap;
 use datasize::DataSize;
+use bitflags::bitflags;
 use crate::object::PlainRef;
-use crate::primitive::{Dictionary, PdfString, Name};
+use crate::primitive::{Dictionary, PdfString, PdfStream, StreamInner, Name, Primitive};
 use crate::error::{PdfError, Result};
 
 type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
 type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
 type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
 
 const PADDING: [u8; 32] = [
@@ -60,8 +62,31 @@ impl Rc4 {
     }
 }
 
+/// Fill `out` from the random number generator of the operating system.
+fn fill_random(out: &mut [u8]) -> Result<()> {
+    getrandom::fill(out).map_err(|e| other!("no random numbers available: {}", e))
+}
+
+/// Generate a random file identifier, suitable for the /ID entry of the trailer.
+pub fn random_id() -> Result<[u8; 16]> {
+    let mut id = [0; 16];
+    fill_random(&mut id)?;
+    Ok(id)
+}
+
+/// SASLprep the password and truncate it to 127 bytes (7.6.4.3.2).
+fn prepare_password(pass: &[u8]) -> Result<Vec<u8>> {
+    let password_unicode =
+        t!(String::from_utf8(pass.to_vec()).map_err(|_| PdfError::InvalidPassword));
+    let password_prepped =
+        t!(stringprep::saslprep(&password_unicode).map_err(|_| PdfError::InvalidPassword));
+    let mut password_encoded = password_prepped.as_bytes().to_vec();
+    password_encoded.truncate(127);
+    Ok(password_encoded)
+}
+
 /// 7.6.1 Table 20 + 7.6.3.2 Table 21
-#[derive(Object, Debug, Clone, DataSize)]
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
 pub struct CryptDict {
     #[pdf(key="O")]
     o: PdfString,
@@ -87,6 +112,9 @@ pub struct CryptDict {
     #[pdf(key="StmF")]
     default_crypt_filter: Option<Name>,
 
+    #[pdf(key="StrF")]
+    string_crypt_filter: Option<Name>,
+
     #[pdf(key="EncryptMetadata", default="true")]
     encrypt_metadata: bool,
 
@@ -96,11 +124,130 @@ pub struct CryptDict {
     #[pdf(key = "UE")]
     ue: Option<PdfString>,
 
+    #[pdf(key = "Perms")]
+    perms: Option<PdfString>,
+
     #[pdf(other)]
     _other: Dictionary
 }
+impl CryptDict {
+    /// Create an encryption dictionary for the standard security handler with
+    /// AES-256 (revision 6).
+    ///
+    /// `permissions` is the value of /P (Table 22). The returned `Decoder` holds the
+    /// new file key and can be used to encrypt the document.
+    pub fn aes_256(user_password: &[u8], owner_password: &[u8], permissions: i32) -> Result<(CryptDict, Decoder)> {
+        let user_password = prepare_password(user_password)?;
+        let owner_password = prepare_password(owner_password)?;
+
+        let mut file_key = [0u8; 32];
+        fill_random(&mut file_key)?;
+        let mut salts = [0u8; 32];
+        fill_random(&mut salts)?;
+        let (user_salts, owner_salts) = salts.split_at(16);
+
+        let wrap_key = |hash: [u8; 32]| {
+            let mut wrapped = file_key;
+            let zero_iv = [0u8; 16];
+            Aes256CbcEnc::new(&hash.into(), &zero_iv.into())
+                .encrypt_padded_mut::<NoPadding>(&mut wrapped, 32)
+                .unwrap();
+            wrapped
+        };
+
+        // Algorithm 8
+        let mut u = Decoder::revision_6_kdf(&user_password, &user_salts[..8], b"").to_vec();
+        u.extend_from_slice(user_salts);
+        let ue = wrap_key(Decoder::revision_6_kdf(&user_password, &user_salts[8..], b""));
+
+        // Algorithm 9
+        let mut o = Decoder::revision_6_kdf(&owner_password, &owner_salts[..8], &u).to_vec();
+        o.extend_from_slice(owner_salts);
+        let oe = wrap_key(Decoder::revision_6_kdf(&owner_password, &owner_salts[8..], &u));
+
+        // Algorithm 10
+        let mut perms = [0u8; 16];
+        fill_random(&mut perms[12..])?;
+        perms[..4].copy_from_slice(&permissions.to_le_bytes());
+        perms[4..8].copy_from_slice(&[0xff; 4]);
+        perms[8..12].copy_from_slice(b"Tadb");
+        let zero_iv = [0u8; 16];
+        Aes256CbcEnc::new(&file_key.into(), &zero_iv.into())
+            .encrypt_padded_mut::<NoPadding>(&mut perms, 16)
+            .unwrap();
+
+        let filter_name = Name::from("StdCF");
+        let mut crypt_filters = HashMap::new();
+        crypt_filters.insert(filter_name.clone(), CryptFilter {
+            method: CryptMethod::AESV3,
+            auth_event: AuthEvent::DocOpen,
+            length: Some(32),
+            _other: Dictionary::new(),
+        });
+        let dict = CryptDict {
+            o: PdfString::new(o.into()),
+            u: PdfString::new(u.into()),
+            r: 6,
+            p: permissions,
+            v: 5,
+            bits: 256,
+            crypt_filters,
+            default_crypt_filter: Some(filter_name.clone()),
+            string_crypt_filter: Some(filter_name),
+            encrypt_metadata: true,
+            oe: Some(PdfString::new(oe[..].into())),
+            ue: Some(PdfString::new(ue[..].into())),
+            perms: Some(PdfString::new(perms[..].into())),
+            _other: Dictionary::new(),
+        };
+        let decoder = Decoder::new(file_key.into(), 32, CryptMethod::AESV3, true);
+        Ok((dict, decoder))
+    }
+
+    /// The access permissions granted by /P.
+    pub fn permissions(&self) -> Permissions {
+        Permissions::from_p(self.p)
+    }
+}
+
+bitflags! {
+    /// User access permissions of the standard security handler (7.6.4.2 Table 22).
+    #[repr(transparent)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Permissions: u32 {
+        /// Print the document (possibly in degraded quality, see `PRINT_HIGH_QUALITY`).
+        const PRINT = 1 << 2;
+        /// Modify the contents of the document.
+        const MODIFY = 1 << 3;
+        /// Copy or extract text and graphics.
+        const COPY = 1 << 4;
+        /// Add or modify annotations and fill in form fields.
+        const ANNOTATE = 1 << 5;
+        /// Fill in existing form fields, even if `ANNOTATE` is not set.
+        const FILL_FORMS = 1 << 8;
+        /// Extract text and graphics for accessibility.
+        const EXTRACT_ACCESSIBILITY = 1 << 9;
+        /// Insert, rotate or delete pages and create bookmarks or thumbnails.
+        const ASSEMBLE = 1 << 10;
+        /// Print at full quality.
+        const PRINT_HIGH_QUALITY = 1 << 11;
+    }
+}
+impl Permissions {
+    /// Bits the spec requires to be set in /P (bits 7, 8 and 13-32).
+    const RESERVED: u32 = 0xFFFF_F0C0;
+
+    /// Decode the /P value. It is stored as a signed integer, with the reserved high bits set.
+    pub fn from_p(p: i32) -> Permissions {
+        Permissions::from_bits_truncate(p as u32)
+    }
+    /// Encode as a /P value, e.g. for `PdfBuilder::encrypt`.
+    pub fn to_p(self) -> i32 {
+        (self.bits() | Self::RESERVED) as i32
+    }
+}
 
-#[derive(Object, Debug, Clone, Copy, DataSize)]
+#[derive(Object, ObjectWrite, Debug, Clone, Copy, DataSize)]
 pub enum CryptMethod {
     None,
     V2,
@@ -108,13 +255,13 @@ pub enum CryptMethod {
     AESV3,
 }
 
-#[derive(Object, Debug, Clone, Copy, DataSize)]
+#[derive(Object, ObjectWrite, Debug, Clone, Copy, DataSize)]
 pub enum AuthEvent {
     DocOpen,
     EFOpen
 }
 
-#[derive(Object, Debug, Clone, DataSize)]
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
 #[pdf(Type="CryptFilter?")]
 pub struct CryptFilter {
     #[pdf(key="CFM", default="CryptMethod::None")]
@@ -388,15 +535,8 @@ impl Decoder {
             let owner_validation_salt = &o[32..40];
             let owner_key_salt = &o[40..48];
 
-            let password_unicode =
-                t!(String::from_utf8(pass.to_vec()).map_err(|_| PdfError::InvalidPassword));
-            let password_prepped =
-                t!(stringprep::saslprep(&password_unicode).map_err(|_| PdfError::InvalidPassword));
-            let mut password_encoded = password_prepped.as_bytes();
-
-            if password_encoded.len() > 127 {
-                password_encoded = &password_encoded[..127];
-            }
+            let password_encoded = prepare_password(pass)?;
+            let password_encoded = password_encoded.as_slice();
 
             let ue = t!(dict.ue.as_ref().ok_or_else(|| PdfError::MissingEntry {
                 typ: "Encrypt",
@@ -560,34 +700,19 @@ impl Decoder {
         match self.method {
             CryptMethod::None => unreachable!(),
             CryptMethod::V2 => {
-                // b)
-                let mut key = [0; 16 + 5];
-                let n = self.key().len();
-                key[..n].copy_from_slice(self.key());
-                key[n..n + 3].copy_from_slice(&id.id.to_le_bytes()[..3]);
-                key[n + 3..n + 5].copy_from_slice(&id.gen.to_le_bytes()[..2]);
-
-                // c)
-                let key = *md5::compute(&key[..n + 5]);
+                // b) and c)
+                let (key, n) = self.object_key(id, false);
 
                 // d)
-                Rc4::encrypt(&key[..(n + 5).min(16)], data);
+                Rc4::encrypt(&key[..n], data);
                 Ok(data)
             }
             CryptMethod::AESV2 => {
-                // b)
-                let mut key = [0; 32 + 5 + 4];
-                let n = std::cmp::min(self.key_size, 16);
-                key[..n].copy_from_slice(self.key());
-                key[n..n + 3].copy_from_slice(&id.id.to_le_bytes()[..3]);
-                key[n + 3..n + 5].copy_from_slice(&id.gen.to_le_bytes()[..2]);
-                key[n + 5..n + 9].copy_from_slice(b"sAlT");
-
-                // c)
-                let key = *md5::compute(&key[..n + 9]);
+                // b) and c)
+                let (key, n) = self.object_key(id, true);
 
                 // d)
-                let key = &key[..(n + 5).min(16)];
+                let key = &key[..n];
                 if data.len() < 16 {
                     return Err(PdfError::DecryptionFailure);
                 }
@@ -604,7 +729,7 @@ impl Decoder {
                 }
                 let (iv, ciphertext) = data.split_at_mut(16);
                 let cipher =
-                    t!(Aes256CbcDec::new_from_slices(self.key(), iv).map_err(|_| PdfError::DecryptionFailure));
+                    t!(Aes256CbcDec::new_from_slices(&self.key[..self.key_size], iv).map_err(|_| PdfError::DecryptionFailure));
                 Ok(t!(cipher
                     .decrypt_padded_mut::<Pkcs7>(ciphertext)
                     .map_err(|_| PdfError::DecryptionFailure)))
@@ -612,6 +737,120 @@ impl Decoder {
         }
     }
 }
+impl Decoder {
+    /// 7.6.2 Algorithm 1 b) and c): the key for the object `id` and its length
+    fn object_key(&self, id: PlainRef, aes: bool) -> ([u8; 16], usize) {
+        let mut key = [0; 16 + 5 + 4];
+        let n = self.key().len();
+        key[..n].copy_from_slice(self.key());
+        key[n..n + 3].copy_from_slice(&id.id.to_le_bytes()[..3]);
+        key[n + 3..n + 5].copy_from_slice(&id.gen.to_le_bytes()[..2]);
+        let len = if aes {
+            key[n + 5..n + 9].copy_from_slice(b"sAlT");
+            n + 9
+        } else {
+            n + 5
+        };
+        (*md5::compute(&key[..len]), (n + 5).min(16))
+    }
+
+    fn is_encrypted(&self, id: PlainRef) -> bool {
+        self.encrypt_indirect_object != Some(id)
+            && (self.encrypt_metadata || self.metadata_indirect_object != Some(id))
+    }
+
+    /// Encrypt the string or stream `data` of the object `id`. The inverse of `decrypt`.
+    pub fn encrypt(&self, id: PlainRef, data: &[u8]) -> Result<Vec<u8>> {
+        if !self.is_encrypted(id) {
+            return Ok(data.to_vec());
+        }
+        // AES: random IV, followed by the PKCS#7 padded ciphertext
+        let aes_buffer = || {
+            let mut buf = vec![0; 16 + data.len() + 16 - data.len() % 16];
+            fill_random(&mut buf[..16])?;
+            buf[16..16 + data.len()].copy_from_slice(data);
+            Ok::<_, PdfError>(buf)
+        };
+        match self.method {
+            CryptMethod::None => Ok(data.to_vec()),
+            CryptMethod::V2 => {
+                let (key, n) = self.object_key(id, false);
+                let mut data = data.to_vec();
+                Rc4::encrypt(&key[..n], &mut data);
+                Ok(data)
+            }
+            CryptMethod::AESV2 => {
+                let (key, n) = self.object_key(id, true);
+                let mut buf = aes_buffer()?;
+                let (iv, rest) = buf.split_at_mut(16);
+                let cipher = t!(Aes128CbcEnc::new_from_slices(&key[..n], iv).map_err(|_| PdfError::EncryptionFailure));
+                cipher.encrypt_padded_mut::<Pkcs7>(rest, data.len()).unwrap();
+                Ok(buf)
+            }
+            CryptMethod::AESV3 => {
+                let mut buf = aes_buffer()?;
+                let (iv, rest) = buf.split_at_mut(16);
+                let cipher = t!(Aes256CbcEnc::new_from_slices(&self.key[..self.key_size], iv).map_err(|_| PdfError::EncryptionFailure));
+                cipher.encrypt_padded_mut::<Pkcs7>(rest, data.len()).unwrap();
+                Ok(buf)
+            }
+        }
+    }
+
+    /// Encrypt all strings and the stream data of the indirect object `id`.
+    ///
+    /// Cross-reference streams are never encrypted (7.5.8.2).
+    pub fn encrypt_primitive(&self, id: PlainRef, p: &Primitive) -> Result<Primitive> {
+        Ok(match *p {
+            Primitive::String(ref s) => Primitive::String(PdfString::new(self.encrypt(id, s.as_bytes())?.into())),
+            Primitive::Array(ref parts) => Primitive::Array(
+                parts.iter().map(|p| self.encrypt_primitive(id, p)).collect::<Result<_>>()?
+            ),
+            Primitive::Dictionary(ref dict) => Primitive::Dictionary(self.encrypt_dict(id, dict)?),
+            Primitive::Stream(ref stream) => {
+                if stream.info.get("Type").and_then(|t| t.as_name().ok()) == Some("XRef") {
+                    return Ok(p.clone());
+                }
+                let mut info = self.encrypt_dict(id, &stream.info)?;
+                let inner = match stream.inner {
+                    StreamInner::Pending { .. } if identity_crypt_filter(&stream.info) => stream.inner.clone(),
+                    StreamInner::Pending { ref data } => {
+                        let data = self.encrypt(id, data)?;
+                        info.insert("Length", data.len() as i32);
+                        StreamInner::Pending { data: data.into() }
+                    }
+                    ref inner => inner.clone()
+                };
+                Primitive::Stream(PdfStream { info, inner })
+            }
+            ref p => p.clone()
+        })
+    }
+    fn encrypt_dict(&self, id: PlainRef, dict: &Dictionary) -> Result<Dictionary> {
+        let mut out = Dictionary::new();
+        for (k, v) in dict.iter() {
+            out.insert(k.clone(), self.encrypt_primitive(id, v)?);
+        }
+        Ok(out)
+    }
+}
+
+/// Whether the stream with dictionary `info` starts its filter chain with the `Identity`
+/// crypt filter (7.4.10), i.e. is stored unencrypted.
+fn identity_crypt_filter(info: &Dictionary) -> bool {
+    let first = |p: Option<&Primitive>| match p {
+        Some(Primitive::Array(parts)) => parts.first().cloned(),
+        p => p.cloned()
+    };
+    match first(info.get("Filter")) {
+        Some(Primitive::Name(ref name)) if name == "Crypt" => {}
+        _ => return false
+    }
+    match first(info.get("DecodeParms")) {
+        Some(Primitive::Dictionary(ref params)) => params.get("Name").is_none_or(|n| matches!(n, Primitive::Name(ref n) if n == "Identity")),
+        _ => true
+    }
+}
 impl fmt::Debug for Decoder {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Decoder")
@@ -623,6 +862,20 @@ impl fmt::Debug for Decoder {
 
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn permissions() {
+        use super::Permissions;
+
+        // all permissions except modify and copy
+        let p = Permissions::from_p(-28);
+        assert!(p.contains(Permissions::PRINT | Permissions::ANNOTATE | Permissions::PRINT_HIGH_QUALITY));
+        assert!(!p.intersects(Permissions::MODIFY | Permissions::COPY));
+        assert_eq!(p.to_p(), -28);
+        assert_eq!(Permissions::all().to_p(), -4);
+        assert_eq!(Permissions::from_p(-4), Permissions::all());
+        assert_eq!(Permissions::empty().to_p(), -3904);
+    }
+
     #[test]
     fn unencrypted_strings() {
         let data_prefix = b"%PDF-1.5\n\
@@ -6,23 +6,30 @@ use itertools::Itertools;
 use crate as pdf;
 use crate::error::*;
 use crate::object::{Object, Resolve, Stream};
-use crate::primitive::{Primitive, Dictionary};
+use crate::primitive::{Primitive, Dictionary, Name};
 use std::convert::{TryFrom, TryInto};
-use std::io::{Read, Write};
+use std::io::Read;
 use once_cell::sync::OnceCell;
 use datasize::DataSize;
 
 
+/// `/DecodeParms` of `LZWDecode` and `FlateDecode` (7.4.4.4 Table 8)
 #[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone)]
 pub struct LZWFlateParams {
+    /// 1 for no prediction (the default), 2 for TIFF predictor 2, 10 to 15 for PNG predictors.
     #[pdf(key="Predictor", default="1")]
     pub predictor: i32,
+    /// Color components per sample, 1 by default. Only used with a predictor.
     #[pdf(key="Colors", default="1")]
     pub n_components: i32,
+    /// Bits per color component, 8 by default. Only used with a predictor.
     #[pdf(key="BitsPerComponent", default="8")]
     pub bits_per_component: i32,
+    /// Samples per row, 1 by default. Only used with a predictor.
     #[pdf(key="Columns", default="1")]
     pub columns: i32,
+    /// Whether the LZW code length increases one code early (1, the default) or not (0).
+    /// Unused by `FlateDecode`.
     #[pdf(key="EarlyChange", default="1")]
     pub early_change: i32,
 }
@@ -38,49 +45,92 @@ impl Default for LZWFlateParams {
     }
 }
 
-#[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone)]
+/// `/DecodeParms` of `DCTDecode` (7.4.8 Table 13)
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone, Default)]
 pub struct DCTDecodeParams {
     // TODO The default value of ColorTransform is 1 if the image has three components and 0 otherwise.
     // 0:   No transformation.
     // 1:   If the image has three color components, transform RGB values to YUV before encoding and from YUV to RGB after decoding.
     //      If the image has four components, transform CMYK values to YUVK before encoding and from YUVK to CMYK after decoding.
     //      This option is ignored if the image has one or two color components.
+    /// The color transform, if given. Without it, it is 1 for three components and 0 otherwise
+    /// (unless the JPEG data says differently).
     #[pdf(key="ColorTransform")]
     pub color_transform: Option<i32>,
 }
 
+/// `/DecodeParms` of `CCITTFaxDecode` (7.4.6 Table 11)
 #[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone)]
 pub struct CCITTFaxDecodeParams {
+    /// The encoding: negative for Group 4, 0 (the default) for Group 3 1-D, positive for Group 3 2-D.
     #[pdf(key="K", default="0")]
     pub k: i32,
 
+    /// Whether rows end with end-of-line bit patterns, `false` by default.
     #[pdf(key="EndOfLine", default="false")]
     pub end_of_line: bool,
 
+    /// Whether rows start at a byte boundary, `false` by default.
     #[pdf(key="EncodedByteAlign", default="false")]
     pub encoded_byte_align: bool,
 
+    /// The width in pixels, 1728 by default.
     #[pdf(key="Columns", default="1728")]
     pub columns: u32,
 
+    /// The height in pixels, 0 (the default) if not known.
     #[pdf(key="Rows", default="0")]
     pub rows: u32,
 
+    /// Whether the data ends with an end-of-block pattern, `true` by default.
     #[pdf(key="EndOfBlock", default="true")]
     pub end_of_block: bool,
 
+    /// Whether 1 bits are black, `false` by default (0 is black).
     #[pdf(key="BlackIs1", default="false")]
     pub black_is_1: bool,
 
+    /// The number of damaged rows to accept, 0 by default.
     #[pdf(key="DamagedRowsBeforeError", default="0")]
     pub damaged_rows_before_error: u32,
 }
+impl Default for CCITTFaxDecodeParams {
+    fn default() -> CCITTFaxDecodeParams {
+        CCITTFaxDecodeParams {
+            k: 0,
+            end_of_line: false,
+            encoded_byte_align: false,
+            columns: 1728,
+            rows: 0,
+            end_of_block: true,
+            black_is_1: false,
+            damaged_rows_before_error: 0,
+        }
+    }
+}
 
-#[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone)]
+/// `/DecodeParms` of `JBIG2Decode` (7.4.7 Table 12)
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone, Default)]
 pub struct JBIG2DecodeParams {
+    /// The segments shared by several images, none by default.
     #[pdf(key="JBIG2Globals")]
     pub globals: Option<Stream<()>>
 }
+
+/// 7.4.10 Table 14
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize, DeepClone, Default)]
+pub struct CryptFilterParams {
+    /// The crypt filter to use, `Identity` if not set.
+    #[pdf(key="Name")]
+    pub name: Option<Name>,
+}
+impl CryptFilterParams {
+    /// Whether the stream is left unencrypted (the `Identity` crypt filter).
+    pub fn is_identity(&self) -> bool {
+        self.name.as_ref().is_none_or(|name| name == "Identity")
+    }
+}
+
 #[derive(Debug, Clone, DataSize, DeepClone)]
 pub enum StreamFilter {
     ASCIIHexDecode,
@@ -91,7 +141,7 @@ pub enum StreamFilter {
     DCTDecode (DCTDecodeParams),
     CCITTFaxDecode (CCITTFaxDecodeParams),
     JBIG2Decode(JBIG2DecodeParams),
-    Crypt,
+    Crypt(CryptFilterParams),
     RunLengthDecode
 }
 impl StreamFilter {
@@ -107,12 +157,43 @@ impl StreamFilter {
            "DCTDecode" => StreamFilter::DCTDecode (DCTDecodeParams::from_primitive(params, r)?),
            "CCITTFaxDecode" => StreamFilter::CCITTFaxDecode (CCITTFaxDecodeParams::from_primitive(params, r)?),
            "JBIG2Decode" => StreamFilter::JBIG2Decode(JBIG2DecodeParams::from_primitive(params, r)?),
-           "Crypt" => StreamFilter::Crypt,
+           "Crypt" => StreamFilter::Crypt(CryptFilterParams::from_primitive(params, r)?),
            "RunLengthDecode" => StreamFilter::RunLengthDecode,
            ty => bail!("Unrecognized filter type {:?}", ty),
        } 
        )
     }
+
+    /// The `/DecodeParms` of the filter, for filters that have them.
+    ///
+    /// One of `LZWFlateParams`, `DCTDecodeParams`, `CCITTFaxDecodeParams`, `JBIG2DecodeParams`
+    /// and `CryptFilterParams`, to get with `downcast_ref`.
+    pub fn params(&self) -> Option<&dyn std::any::Any> {
+        match self {
+            StreamFilter::LZWDecode(p) | StreamFilter::FlateDecode(p) => Some(p),
+            StreamFilter::DCTDecode(p) => Some(p),
+            StreamFilter::CCITTFaxDecode(p) => Some(p),
+            StreamFilter::JBIG2Decode(p) => Some(p),
+            StreamFilter::Crypt(p) => Some(p),
+            StreamFilter::ASCIIHexDecode | StreamFilter::ASCII85Decode | StreamFilter::JPXDecode | StreamFilter::RunLengthDecode => None,
+        }
+    }
+
+    /// The name of the filter, as used in `/Filter`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            StreamFilter::ASCIIHexDecode => "ASCIIHexDecode",
+            StreamFilter::ASCII85Decode => "ASCII85Decode",
+            StreamFilter::LZWDecode(_) => "LZWDecode",
+            StreamFilter::FlateDecode(_) => "FlateDecode",
+            StreamFilter::JPXDecode => "JPXDecode",
+            StreamFilter::DCTDecode(_) => "DCTDecode",
+            StreamFilter::CCITTFaxDecode(_) => "CCITTFaxDecode",
+            StreamFilter::JBIG2Decode(_) => "JBIG2Decode",
+            StreamFilter::Crypt(_) => "Crypt",
+            StreamFilter::RunLengthDecode => "RunLengthDecode",
+        }
+    }
 }
 
 #[inline]
@@ -273,12 +354,18 @@ fn inflate_bytes(data: &[u8]) -> Result<Vec<u8>> {
     Ok(decoded)
 }
 
+/// Bytes per pixel (at least one) and per row of the PNG predictors for `params`.
+fn predictor_layout(params: &LZWFlateParams) -> (usize, usize) {
+    let bits_per_pixel = params.n_components.max(1) as usize * params.bits_per_component.max(1) as usize;
+    let bpp = bits_per_pixel.div_ceil(8);
+    let stride = (params.columns.max(1) as usize * bits_per_pixel).div_ceil(8);
+    (bpp, stride)
+}
+
 pub fn flate_decode(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
 
     let predictor = params.predictor as usize;
-    let n_components = params.n_components as usize;
-    let columns = params.columns as usize;
-    let stride = columns * n_components;
+    let (bpp, stride) = predictor_layout(params);
 
 
     // First flate decode
@@ -295,7 +382,7 @@ pub fn flate_decode(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
     // Then unfilter (PNG)
     // For this, take the old out as input, and write output to out
 
-    if predictor > 10 {
+    if predictor >= 10 {
         let inp = decoded; // input buffer
         let rows = inp.len() / (stride+1);
         
@@ -321,7 +408,7 @@ pub fn flate_decode(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
                 let (prev, curr) = out.split_at_mut(out_off);
                 (&prev[last_out_off ..], &mut curr[.. stride])
             };
-            unfilter(predictor, n_components, prev_row, row_in, row_out);
+            unfilter(predictor, bpp, prev_row, row_in, row_out);
             
             last_out_off = out_off;
             
@@ -333,12 +420,50 @@ pub fn flate_decode(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
         Ok(decoded)
     }
 }
-fn flate_encode(data: &[u8]) -> Vec<u8> {
-    use libflate::deflate::Encoder;
-    let mut encoded = Vec::new();
-    let mut encoder = Encoder::new(&mut encoded);
-    encoder.write_all(data).unwrap();
-    encoded
+/// Deflate `data`, applying the PNG predictor of `params` first.
+///
+/// Predictors 10 to 14 use the PNG filter None, Sub, Up, Average or Paeth on every row,
+/// 15 picks the filter for each row that gives the smallest sum of (signed) differences.
+fn flate_encode(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
+    use self::PredictorType::*;
+
+    let method = match params.predictor {
+        1 => return Ok(deflate::deflate_bytes_zlib(data)),
+        10 => Some(NoFilter),
+        11 => Some(Sub),
+        12 => Some(Up),
+        13 => Some(Avg),
+        14 => Some(Paeth),
+        15 => None,
+        p => bail!("encoding with predictor {} is not supported", p)
+    };
+    let (bpp, stride) = predictor_layout(params);
+    if data.len() % stride != 0 {
+        bail!("{} bytes are not a whole number of rows of {} bytes", data.len(), stride);
+    }
+
+    let mut out = Vec::with_capacity(data.len() + data.len() / stride);
+    let null_row = vec![0; stride];
+    let mut row = vec![0; stride];
+    for (i, current) in data.chunks_exact(stride).enumerate() {
+        let previous = match i {
+            0 => &null_row[..],
+            _ => &data[(i - 1) * stride .. i * stride]
+        };
+        let method = method.unwrap_or_else(|| {
+            let mut cost = |method| {
+                row.copy_from_slice(current);
+                filter(method, bpp, previous, &mut row);
+                row.iter().map(|&b| (b as i8).unsigned_abs() as u32).sum::<u32>()
+            };
+            [NoFilter, Sub, Up, Avg, Paeth].iter().copied().min_by_key(|&m| cost(m)).unwrap()
+        });
+        row.copy_from_slice(current);
+        filter(method, bpp, previous, &mut row);
+        out.push(method as u8);
+        out.extend_from_slice(&row);
+    }
+    Ok(deflate::deflate_bytes_zlib(&out))
 }
 
 pub fn dct_decode(data: &[u8], _params: &DCTDecodeParams) -> Result<Vec<u8>> {
@@ -348,28 +473,35 @@ pub fn dct_decode(data: &[u8], _params: &DCTDecodeParams) -> Result<Vec<u8>> {
     Ok(pixels)
 }
 
+/// Decode LZW data with 8 bit literals: code 256 clears the table and 257 ends the data.
+///
+/// With `/EarlyChange 1` (the default) the code width grows one code early. Data that ends
+/// without an end-of-data code is accepted.
 pub fn lzw_decode(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
     use weezl::{BitOrder, decode::Decoder};
     let mut out = vec![];
 
-    let mut decoder = if params.early_change != 0 {
-        Decoder::with_tiff_size_switch(BitOrder::Msb, 9)
-    } else {
-        Decoder::new(BitOrder::Msb, 9)
+    let mut decoder = match params.early_change {
+        0 => Decoder::new(BitOrder::Msb, 8),
+        1 => Decoder::with_tiff_size_switch(BitOrder::Msb, 8),
+        n => bail!("invalid EarlyChange {}", n)
     };
 
     decoder
-        .into_stream(&mut out)
-        .decode_all(data).status?;
+        .into_vec(&mut out)
+        .decode(data).status
+        .map_err(|e| other!("LZW decoding failed: {:?}", e))?;
     Ok(out)
 }
 fn lzw_encode(data: &[u8], params: &LZWFlateParams) -> Result<Vec<u8>> {
     use weezl::{BitOrder, encode::Encoder};
-    if params.early_change != 0 {
-        bail!("encoding early_change != 0 is not supported");
-    }
+    let mut encoder = match params.early_change {
+        0 => Encoder::new(BitOrder::Msb, 8),
+        1 => Encoder::with_tiff_size_switch(BitOrder::Msb, 8),
+        n => bail!("invalid EarlyChange {}", n)
+    };
     let mut compressed = vec![];
-    Encoder::new(BitOrder::Msb, 9)
+    encoder
         .into_stream(&mut compressed)
         .encode_all(data).status?;
     Ok(compressed)
@@ -441,7 +573,8 @@ pub fn set_jbig2_decoder(f: Box<DecodeFn>) {
 }
 
 pub fn jpx_decode(data: &[u8]) -> Result<Vec<u8>> {
-    JPX_DECODER.get().ok_or_else(|| PdfError::Other { msg: "jp2k decoder not set".into()})?(data)
+    // without a decoder set, JPXDecode is not supported
+    JPX_DECODER.get().ok_or(PdfError::UnsupportedFilter { filter: "JPXDecode" })?(data)
 }
 pub fn jbig2_decode(data: &[u8], globals: &[u8]) -> Result<Vec<u8>> {
     let data = [
@@ -457,7 +590,7 @@ pub fn jbig2_decode(data: &[u8], globals: &[u8]) -> Result<Vec<u8>> {
         // end of stream
         &[0x00, 0x00, 0x00, 0x04, 0x33, 0x01, 0x00, 0x00, 0x00, 0x00],
     ].concat();
-    JBIG2_DECODER.get().ok_or_else(|| PdfError::Other { msg: "jbig2 decoder not set".into()})?(&data)
+    JBIG2_DECODER.get().ok_or(PdfError::UnsupportedFilter { filter: "JBIG2Decode" })?(&data)
 }
 
 pub fn decode(data: &[u8], filter: &StreamFilter) -> Result<Vec<u8>> {
@@ -468,8 +601,10 @@ pub fn decode(data: &[u8], filter: &StreamFilter) -> Result<Vec<u8>> {
         StreamFilter::FlateDecode(ref params) => flate_decode(data, params),
         StreamFilter::RunLengthDecode => run_length_decode(data),
         StreamFilter::DCTDecode(ref params) => dct_decode(data, params),
+        // decryption happens before the filters are applied
+        StreamFilter::Crypt(_) => Ok(data.to_vec()),
 
-        _ => bail!("unimplemented {filter:?}"),
+        _ => Err(PdfError::UnsupportedFilter { filter: filter.name() }),
     }
 }
 
@@ -478,8 +613,9 @@ pub fn encode(data: &[u8], filter: &StreamFilter) -> Result<Vec<u8>> {
         StreamFilter::ASCIIHexDecode => Ok(encode_hex(data)),
         StreamFilter::ASCII85Decode => Ok(encode_85(data)),
         StreamFilter::LZWDecode(ref params) => lzw_encode(data, params),
-        StreamFilter::FlateDecode (ref _params) => Ok(flate_encode(data)),
-        _ => unimplemented!(),
+        StreamFilter::FlateDecode(ref params) => flate_encode(data, params),
+        StreamFilter::Crypt(_) => Ok(data.to_vec()),
+        _ => Err(PdfError::UnsupportedFilter { filter: filter.name() }),
     }
 }
 
@@ -584,7 +720,6 @@ pub fn unfilter(filter: PredictorType, bpp: usize, prev: &[u8], inp: &[u8], out:
     }
 }
 
-#[allow(unused)]
 pub fn filter(method: PredictorType, bpp: usize, previous: &[u8], current: &mut [u8]) {
     use self::PredictorType::*;
     let len  = current.len();
@@ -603,7 +738,7 @@ pub fn filter(method: PredictorType, bpp: usize, previous: &[u8], current: &mut
         }
         Avg => {
             for i in (bpp..len).rev() {
-                current[i] = current[i].wrapping_sub(current[i - bpp].wrapping_add(previous[i]) / 2);
+                current[i] = current[i].wrapping_sub(((current[i - bpp] as u16 + previous[i] as u16) / 2) as u8);
             }
 
             for i in 0..bpp {
@@ -625,6 +760,23 @@ pub fn filter(method: PredictorType, bpp: usize, previous: &[u8], current: &mut
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::object::NoResolve;
+
+    #[test]
+    fn filter_params() {
+        let params = |dict: &[u8]| {
+            let p = crate::parser::parse(dict, &NoResolve, crate::parser::ParseFlags::ANY).unwrap();
+            LZWFlateParams::from_primitive(p, &NoResolve).unwrap()
+        };
+        let flate = StreamFilter::FlateDecode(params(b"<< /Predictor 12 /Columns 5 >>"));
+        let p = flate.params().unwrap().downcast_ref::<LZWFlateParams>().unwrap();
+        assert_eq!((p.predictor, p.columns, p.n_components, p.bits_per_component), (12, 5, 1, 8));
+        assert!(flate.params().unwrap().downcast_ref::<DCTDecodeParams>().is_none());
+        assert!(StreamFilter::ASCIIHexDecode.params().is_none());
+
+        let ccitt = CCITTFaxDecodeParams::default();
+        assert_eq!((ccitt.columns, ccitt.end_of_block), (1728, true));
+    }
 
     #[test]
     fn base_85() {
@@ -648,6 +800,52 @@ mod tests {
         */
     }
 
+    #[test]
+    fn lzw_early_change() {
+        // the example from the PDF reference
+        let data = [0x80, 0x0B, 0x60, 0x50, 0x22, 0x0C, 0x0C, 0x85, 0x01];
+        assert_eq!(lzw_decode(&data, &LZWFlateParams::default()).unwrap(), b"-----A---B");
+
+        let text: Vec<u8> = (0 .. 2000u32).map(|i| (i * i % 7 + i % 13) as u8).collect();
+        for early_change in [0, 1] {
+            let params = LZWFlateParams { early_change, ..LZWFlateParams::default() };
+            let encoded = lzw_encode(&text, &params).unwrap();
+            assert_eq!(lzw_decode(&encoded, &params).unwrap(), text);
+            // the code width changes at a different code, so the other setting garbles it
+            let other = LZWFlateParams { early_change: 1 - early_change, ..params };
+            assert_ne!(lzw_decode(&encoded, &other).ok(), Some(text.clone()));
+        }
+        // a missing end-of-data code: `-----A` and the start of the next code
+        assert_eq!(lzw_decode(&data[.. 6], &LZWFlateParams::default()).unwrap(), b"-----A");
+    }
+
+    #[test]
+    fn flate_predictors() {
+        // pseudo-random rows with some structure, so the predictors differ
+        let mut state = 0x2545_f491u32;
+        let data: Vec<u8> = (0 .. 3 * 17 * 20).map(|i| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (i % 50) as u8 * 5 + (state % 7) as u8
+        }).collect();
+        for predictor in [1, 10, 11, 12, 13, 14, 15] {
+            let params = LZWFlateParams { predictor, n_components: 3, columns: 17, ..LZWFlateParams::default() };
+            let filter = StreamFilter::FlateDecode(params);
+            let encoded = encode(&data, &filter).unwrap();
+            assert_eq!(decode(&encoded, &filter).unwrap(), data, "predictor {}", predictor);
+        }
+
+        // 16 bit samples filter whole pixels
+        let params = LZWFlateParams { predictor: 14, n_components: 2, bits_per_component: 16, columns: 5, ..LZWFlateParams::default() };
+        let filter = StreamFilter::FlateDecode(params);
+        let data = &data[.. 20 * 6];
+        assert_eq!(decode(&encode(data, &filter).unwrap(), &filter).unwrap(), data);
+
+        let params = LZWFlateParams { predictor: 12, columns: 7, ..LZWFlateParams::default() };
+        assert!(encode(&data[.. 8], &StreamFilter::FlateDecode(params)).is_err());
+    }
+
     #[test]
     fn run_length_decode_test() {
         let x = run_length_decode(&[254, b'a', 255, b'b', 2, b'c', b'b', b'c', 254, b'a', 128]).unwrap();
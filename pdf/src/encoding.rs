@@ -6,6 +6,11 @@ use crate::primitive::{Primitive, Dictionary};
 use crate::error::{Result};
 use datasize::DataSize;
 
+mod glyphlist;
+mod standard;
+
+pub use self::standard::{STANDARD_ENCODING, WIN_ANSI_ENCODING, MAC_ROMAN_ENCODING, SYMBOL_ENCODING, ZAPF_DINGBATS_ENCODING};
+
 #[derive(Debug, Clone, DataSize)]
 pub struct Encoding {
     pub base: BaseEncoding,
@@ -21,6 +26,8 @@ pub enum BaseEncoding {
     MacExpertEncoding,
     #[pdf(name = "Identity-H")]
     IdentityH,
+    #[pdf(name = "Identity-V")]
+    IdentityV,
     None,
 
     #[pdf(other)]
@@ -93,6 +100,18 @@ impl ObjectWrite for Encoding {
         }
     }
 }
+impl BaseEncoding {
+    /// The glyph name of each code, for the encodings with a fixed table.
+    pub fn glyph_names(&self) -> Option<&'static [Option<&'static str>; 256]> {
+        match self {
+            BaseEncoding::StandardEncoding => Some(&STANDARD_ENCODING),
+            BaseEncoding::WinAnsiEncoding => Some(&WIN_ANSI_ENCODING),
+            BaseEncoding::MacRomanEncoding => Some(&MAC_ROMAN_ENCODING),
+            BaseEncoding::SymbolEncoding => Some(&SYMBOL_ENCODING),
+            _ => None
+        }
+    }
+}
 impl Encoding {
     pub fn standard() -> Encoding {
         Encoding {
@@ -105,4 +124,90 @@ impl DeepClone for Encoding {
     fn deep_clone(&self, cloner: &mut impl pdf::object::Cloner) -> Result<Self> {
         Ok(self.clone())
     }
-}
\ No newline at end of file
+}
+/// Map a glyph name to Unicode, following the Adobe Glyph List Specification.
+///
+/// A suffix after the first `.` is ignored, `_` separates the components of a ligature.
+/// Each component is looked up in the glyph list or parsed as `uniXXXX[XXXX..]` or `uXXXX[XX]`.
+pub fn glyph_name_to_string(name: &str) -> Option<String> {
+    let name = name.split('.').next().unwrap();
+    let mut out = String::new();
+    for component in name.split('_') {
+        if let Ok(i) = glyphlist::GLYPH_LIST.binary_search_by(|&(n, _)| n.cmp(component)) {
+            out.push_str(glyphlist::GLYPH_LIST[i].1);
+        } else if let Some(hex) = component.strip_prefix("uni") {
+            if hex.len() == 0 || hex.len() % 4 != 0 {
+                return None;
+            }
+            for i in (0 .. hex.len()).step_by(4) {
+                out.push(parse_code_point(hex.get(i .. i + 4)?)?);
+            }
+        } else if let Some(hex) = component.strip_prefix('u') {
+            if !(4 ..= 6).contains(&hex.len()) {
+                return None;
+            }
+            out.push(parse_code_point(hex)?);
+        } else {
+            return None;
+        }
+    }
+    if out.is_empty() {
+        None
+    } else {
+        Some(out)
+    }
+}
+
+/// Map a glyph name to a single Unicode character. See `glyph_name_to_string`.
+///
+/// Returns `None` for names that map to several characters, like `f_f_i`.
+/// A ligature with its own code point (`ffi`) maps to that.
+pub fn glyph_name_to_unicode(name: &str) -> Option<char> {
+    let s = glyph_name_to_string(name)?;
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None
+    }
+}
+
+fn parse_code_point(hex: &str) -> Option<char> {
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    // char::from_u32 rejects surrogates
+    std::char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyph_list_sorted() {
+        assert!(glyphlist::GLYPH_LIST.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn glyph_names() {
+        assert_eq!(glyph_name_to_unicode("aacute"), Some('á'));
+        assert_eq!(glyph_name_to_unicode("A"), Some('A'));
+        assert_eq!(glyph_name_to_unicode("uni00E9"), Some('é'));
+        assert_eq!(glyph_name_to_unicode("u1F600"), Some('\u{1F600}'));
+        assert_eq!(glyph_name_to_unicode("a.sc"), Some('a'));
+        assert_eq!(glyph_name_to_unicode("fi"), Some('\u{FB01}'));
+        assert_eq!(glyph_name_to_unicode("f_i"), None);
+        assert_eq!(glyph_name_to_string("f_f_i").as_deref(), Some("ffi"));
+        assert_eq!(glyph_name_to_string("uni00660069").as_deref(), Some("fi"));
+        assert_eq!(glyph_name_to_unicode("afii10017"), Some('А'));
+        assert_eq!(glyph_name_to_unicode("Acircumflexacute"), Some('Ấ'));
+        assert_eq!(glyph_name_to_unicode("kokaithai"), Some('ก'));
+        assert_eq!(glyph_name_to_string("lamedholamhebrew").as_deref(), Some("\u{05DC}\u{05B9}"));
+        assert_eq!(glyph_name_to_unicode("lamedholamhebrew"), None);
+        assert_eq!(glyph_name_to_unicode("uniD800"), None);
+        assert_eq!(glyph_name_to_unicode("uni00E"), None);
+        assert_eq!(glyph_name_to_unicode("u110000"), None);
+        assert_eq!(glyph_name_to_unicode("g123"), None);
+        assert_eq!(glyph_name_to_unicode(".notdef"), None);
+    }
+}
@@ -0,0 +1,182 @@
+// The glyph names of the standard encodings and of the built-in encodings of Symbol and ZapfDingbats
+// (PDF 32000-1:2008, Annex D).
+
+/// StandardEncoding, the built-in encoding of the standard Latin-text Type 1 fonts.
+pub static STANDARD_ENCODING: [Option<&str>; 256] = [
+    /*   0 */ None, None, None, None, None, None, None, None,
+    /*   8 */ None, None, None, None, None, None, None, None,
+    /*  16 */ None, None, None, None, None, None, None, None,
+    /*  24 */ None, None, None, None, None, None, None, None,
+    /*  32 */ Some("space"), Some("exclam"), Some("quotedbl"), Some("numbersign"), Some("dollar"), Some("percent"), Some("ampersand"), Some("quoteright"),
+    /*  40 */ Some("parenleft"), Some("parenright"), Some("asterisk"), Some("plus"), Some("comma"), Some("hyphen"), Some("period"), Some("slash"),
+    /*  48 */ Some("zero"), Some("one"), Some("two"), Some("three"), Some("four"), Some("five"), Some("six"), Some("seven"),
+    /*  56 */ Some("eight"), Some("nine"), Some("colon"), Some("semicolon"), Some("less"), Some("equal"), Some("greater"), Some("question"),
+    /*  64 */ Some("at"), Some("A"), Some("B"), Some("C"), Some("D"), Some("E"), Some("F"), Some("G"),
+    /*  72 */ Some("H"), Some("I"), Some("J"), Some("K"), Some("L"), Some("M"), Some("N"), Some("O"),
+    /*  80 */ Some("P"), Some("Q"), Some("R"), Some("S"), Some("T"), Some("U"), Some("V"), Some("W"),
+    /*  88 */ Some("X"), Some("Y"), Some("Z"), Some("bracketleft"), Some("backslash"), Some("bracketright"), Some("asciicircum"), Some("underscore"),
+    /*  96 */ Some("quoteleft"), Some("a"), Some("b"), Some("c"), Some("d"), Some("e"), Some("f"), Some("g"),
+    /* 104 */ Some("h"), Some("i"), Some("j"), Some("k"), Some("l"), Some("m"), Some("n"), Some("o"),
+    /* 112 */ Some("p"), Some("q"), Some("r"), Some("s"), Some("t"), Some("u"), Some("v"), Some("w"),
+    /* 120 */ Some("x"), Some("y"), Some("z"), Some("braceleft"), Some("bar"), Some("braceright"), Some("asciitilde"), None,
+    /* 128 */ None, None, None, None, None, None, None, None,
+    /* 136 */ None, None, None, None, None, None, None, None,
+    /* 144 */ None, None, None, None, None, None, None, None,
+    /* 152 */ None, None, None, None, None, None, None, None,
+    /* 160 */ None, Some("exclamdown"), Some("cent"), Some("sterling"), Some("fraction"), Some("yen"), Some("florin"), Some("section"),
+    /* 168 */ Some("currency"), Some("quotesingle"), Some("quotedblleft"), Some("guillemotleft"), Some("guilsinglleft"), Some("guilsinglright"), Some("fi"), Some("fl"),
+    /* 176 */ None, Some("endash"), Some("dagger"), Some("daggerdbl"), Some("periodcentered"), None, Some("paragraph"), Some("bullet"),
+    /* 184 */ Some("quotesinglbase"), Some("quotedblbase"), Some("quotedblright"), Some("guillemotright"), Some("ellipsis"), Some("perthousand"), None, Some("questiondown"),
+    /* 192 */ None, Some("grave"), Some("acute"), Some("circumflex"), Some("tilde"), Some("macron"), Some("breve"), Some("dotaccent"),
+    /* 200 */ Some("dieresis"), None, Some("ring"), Some("cedilla"), None, Some("hungarumlaut"), Some("ogonek"), Some("caron"),
+    /* 208 */ Some("emdash"), None, None, None, None, None, None, None,
+    /* 216 */ None, None, None, None, None, None, None, None,
+    /* 224 */ None, Some("AE"), None, Some("ordfeminine"), None, None, None, None,
+    /* 232 */ Some("Lslash"), Some("Oslash"), Some("OE"), Some("ordmasculine"), None, None, None, None,
+    /* 240 */ None, Some("ae"), None, None, None, Some("dotlessi"), None, None,
+    /* 248 */ Some("lslash"), Some("oslash"), Some("oe"), Some("germandbls"), None, None, None, None,
+];
+
+/// WinAnsiEncoding (Windows code page 1252).
+pub static WIN_ANSI_ENCODING: [Option<&str>; 256] = [
+    /*   0 */ None, None, None, None, None, None, None, None,
+    /*   8 */ None, None, None, None, None, None, None, None,
+    /*  16 */ None, None, None, None, None, None, None, None,
+    /*  24 */ None, None, None, None, None, None, None, None,
+    /*  32 */ Some("space"), Some("exclam"), Some("quotedbl"), Some("numbersign"), Some("dollar"), Some("percent"), Some("ampersand"), Some("quotesingle"),
+    /*  40 */ Some("parenleft"), Some("parenright"), Some("asterisk"), Some("plus"), Some("comma"), Some("hyphen"), Some("period"), Some("slash"),
+    /*  48 */ Some("zero"), Some("one"), Some("two"), Some("three"), Some("four"), Some("five"), Some("six"), Some("seven"),
+    /*  56 */ Some("eight"), Some("nine"), Some("colon"), Some("semicolon"), Some("less"), Some("equal"), Some("greater"), Some("question"),
+    /*  64 */ Some("at"), Some("A"), Some("B"), Some("C"), Some("D"), Some("E"), Some("F"), Some("G"),
+    /*  72 */ Some("H"), Some("I"), Some("J"), Some("K"), Some("L"), Some("M"), Some("N"), Some("O"),
+    /*  80 */ Some("P"), Some("Q"), Some("R"), Some("S"), Some("T"), Some("U"), Some("V"), Some("W"),
+    /*  88 */ Some("X"), Some("Y"), Some("Z"), Some("bracketleft"), Some("backslash"), Some("bracketright"), Some("asciicircum"), Some("underscore"),
+    /*  96 */ Some("grave"), Some("a"), Some("b"), Some("c"), Some("d"), Some("e"), Some("f"), Some("g"),
+    /* 104 */ Some("h"), Some("i"), Some("j"), Some("k"), Some("l"), Some("m"), Some("n"), Some("o"),
+    /* 112 */ Some("p"), Some("q"), Some("r"), Some("s"), Some("t"), Some("u"), Some("v"), Some("w"),
+    /* 120 */ Some("x"), Some("y"), Some("z"), Some("braceleft"), Some("bar"), Some("braceright"), Some("asciitilde"), None,
+    /* 128 */ Some("Euro"), None, Some("quotesinglbase"), Some("florin"), Some("quotedblbase"), Some("ellipsis"), Some("dagger"), Some("daggerdbl"),
+    /* 136 */ Some("circumflex"), Some("perthousand"), Some("Scaron"), Some("guilsinglleft"), Some("OE"), None, Some("Zcaron"), None,
+    /* 144 */ None, Some("quoteleft"), Some("quoteright"), Some("quotedblleft"), Some("quotedblright"), Some("bullet"), Some("endash"), Some("emdash"),
+    /* 152 */ Some("tilde"), Some("trademark"), Some("scaron"), Some("guilsinglright"), Some("oe"), None, Some("zcaron"), Some("Ydieresis"),
+    /* 160 */ Some("space"), Some("exclamdown"), Some("cent"), Some("sterling"), Some("currency"), Some("yen"), Some("brokenbar"), Some("section"),
+    /* 168 */ Some("dieresis"), Some("copyright"), Some("ordfeminine"), Some("guillemotleft"), Some("logicalnot"), Some("hyphen"), Some("registered"), Some("macron"),
+    /* 176 */ Some("degree"), Some("plusminus"), Some("twosuperior"), Some("threesuperior"), Some("acute"), Some("mu"), Some("paragraph"), Some("periodcentered"),
+    /* 184 */ Some("cedilla"), Some("onesuperior"), Some("ordmasculine"), Some("guillemotright"), Some("onequarter"), Some("onehalf"), Some("threequarters"), Some("questiondown"),
+    /* 192 */ Some("Agrave"), Some("Aacute"), Some("Acircumflex"), Some("Atilde"), Some("Adieresis"), Some("Aring"), Some("AE"), Some("Ccedilla"),
+    /* 200 */ Some("Egrave"), Some("Eacute"), Some("Ecircumflex"), Some("Edieresis"), Some("Igrave"), Some("Iacute"), Some("Icircumflex"), Some("Idieresis"),
+    /* 208 */ Some("Eth"), Some("Ntilde"), Some("Ograve"), Some("Oacute"), Some("Ocircumflex"), Some("Otilde"), Some("Odieresis"), Some("multiply"),
+    /* 216 */ Some("Oslash"), Some("Ugrave"), Some("Uacute"), Some("Ucircumflex"), Some("Udieresis"), Some("Yacute"), Some("Thorn"), Some("germandbls"),
+    /* 224 */ Some("agrave"), Some("aacute"), Some("acircumflex"), Some("atilde"), Some("adieresis"), Some("aring"), Some("ae"), Some("ccedilla"),
+    /* 232 */ Some("egrave"), Some("eacute"), Some("ecircumflex"), Some("edieresis"), Some("igrave"), Some("iacute"), Some("icircumflex"), Some("idieresis"),
+    /* 240 */ Some("eth"), Some("ntilde"), Some("ograve"), Some("oacute"), Some("ocircumflex"), Some("otilde"), Some("odieresis"), Some("divide"),
+    /* 248 */ Some("oslash"), Some("ugrave"), Some("uacute"), Some("ucircumflex"), Some("udieresis"), Some("yacute"), Some("thorn"), Some("ydieresis"),
+];
+
+/// MacRomanEncoding, without the symbols that the Mac OS encoding adds.
+pub static MAC_ROMAN_ENCODING: [Option<&str>; 256] = [
+    /*   0 */ None, None, None, None, None, None, None, None,
+    /*   8 */ None, None, None, None, None, None, None, None,
+    /*  16 */ None, None, None, None, None, None, None, None,
+    /*  24 */ None, None, None, None, None, None, None, None,
+    /*  32 */ Some("space"), Some("exclam"), Some("quotedbl"), Some("numbersign"), Some("dollar"), Some("percent"), Some("ampersand"), Some("quotesingle"),
+    /*  40 */ Some("parenleft"), Some("parenright"), Some("asterisk"), Some("plus"), Some("comma"), Some("hyphen"), Some("period"), Some("slash"),
+    /*  48 */ Some("zero"), Some("one"), Some("two"), Some("three"), Some("four"), Some("five"), Some("six"), Some("seven"),
+    /*  56 */ Some("eight"), Some("nine"), Some("colon"), Some("semicolon"), Some("less"), Some("equal"), Some("greater"), Some("question"),
+    /*  64 */ Some("at"), Some("A"), Some("B"), Some("C"), Some("D"), Some("E"), Some("F"), Some("G"),
+    /*  72 */ Some("H"), Some("I"), Some("J"), Some("K"), Some("L"), Some("M"), Some("N"), Some("O"),
+    /*  80 */ Some("P"), Some("Q"), Some("R"), Some("S"), Some("T"), Some("U"), Some("V"), Some("W"),
+    /*  88 */ Some("X"), Some("Y"), Some("Z"), Some("bracketleft"), Some("backslash"), Some("bracketright"), Some("asciicircum"), Some("underscore"),
+    /*  96 */ Some("grave"), Some("a"), Some("b"), Some("c"), Some("d"), Some("e"), Some("f"), Some("g"),
+    /* 104 */ Some("h"), Some("i"), Some("j"), Some("k"), Some("l"), Some("m"), Some("n"), Some("o"),
+    /* 112 */ Some("p"), Some("q"), Some("r"), Some("s"), Some("t"), Some("u"), Some("v"), Some("w"),
+    /* 120 */ Some("x"), Some("y"), Some("z"), Some("braceleft"), Some("bar"), Some("braceright"), Some("asciitilde"), None,
+    /* 128 */ Some("Adieresis"), Some("Aring"), Some("Ccedilla"), Some("Eacute"), Some("Ntilde"), Some("Odieresis"), Some("Udieresis"), Some("aacute"),
+    /* 136 */ Some("agrave"), Some("acircumflex"), Some("adieresis"), Some("atilde"), Some("aring"), Some("ccedilla"), Some("eacute"), Some("egrave"),
+    /* 144 */ Some("ecircumflex"), Some("edieresis"), Some("iacute"), Some("igrave"), Some("icircumflex"), Some("idieresis"), Some("ntilde"), Some("oacute"),
+    /* 152 */ Some("ograve"), Some("ocircumflex"), Some("odieresis"), Some("otilde"), Some("uacute"), Some("ugrave"), Some("ucircumflex"), Some("udieresis"),
+    /* 160 */ Some("dagger"), Some("degree"), Some("cent"), Some("sterling"), Some("section"), Some("bullet"), Some("paragraph"), Some("germandbls"),
+    /* 168 */ Some("registered"), Some("copyright"), Some("trademark"), Some("acute"), Some("dieresis"), None, Some("AE"), Some("Oslash"),
+    /* 176 */ None, Some("plusminus"), None, None, Some("yen"), Some("mu"), None, None,
+    /* 184 */ None, None, None, Some("ordfeminine"), Some("ordmasculine"), None, Some("ae"), Some("oslash"),
+    /* 192 */ Some("questiondown"), Some("exclamdown"), Some("logicalnot"), None, Some("florin"), None, None, Some("guillemotleft"),
+    /* 200 */ Some("guillemotright"), Some("ellipsis"), Some("space"), Some("Agrave"), Some("Atilde"), Some("Otilde"), Some("OE"), Some("oe"),
+    /* 208 */ Some("endash"), Some("emdash"), Some("quotedblleft"), Some("quotedblright"), Some("quoteleft"), Some("quoteright"), Some("divide"), None,
+    /* 216 */ Some("ydieresis"), Some("Ydieresis"), Some("fraction"), Some("currency"), Some("guilsinglleft"), Some("guilsinglright"), Some("fi"), Some("fl"),
+    /* 224 */ Some("daggerdbl"), Some("periodcentered"), Some("quotesinglbase"), Some("quotedblbase"), Some("perthousand"), Some("Acircumflex"), Some("Ecircumflex"), Some("Aacute"),
+    /* 232 */ Some("Edieresis"), Some("Egrave"), Some("Iacute"), Some("Icircumflex"), Some("Idieresis"), Some("Igrave"), Some("Oacute"), Some("Ocircumflex"),
+    /* 240 */ None, Some("Ograve"), Some("Uacute"), Some("Ucircumflex"), Some("Ugrave"), Some("dotlessi"), Some("circumflex"), Some("tilde"),
+    /* 248 */ Some("macron"), Some("breve"), Some("dotaccent"), Some("ring"), Some("cedilla"), Some("hungarumlaut"), Some("ogonek"), Some("caron"),
+];
+
+/// The built-in encoding of the Symbol font (Annex D.5), also known as SymbolEncoding.
+pub static SYMBOL_ENCODING: [Option<&str>; 256] = [
+    /*   0 */ None, None, None, None, None, None, None, None,
+    /*   8 */ None, None, None, None, None, None, None, None,
+    /*  16 */ None, None, None, None, None, None, None, None,
+    /*  24 */ None, None, None, None, None, None, None, None,
+    /*  32 */ Some("space"), Some("exclam"), Some("universal"), Some("numbersign"), Some("existential"), Some("percent"), Some("ampersand"), Some("suchthat"),
+    /*  40 */ Some("parenleft"), Some("parenright"), Some("asteriskmath"), Some("plus"), Some("comma"), Some("minus"), Some("period"), Some("slash"),
+    /*  48 */ Some("zero"), Some("one"), Some("two"), Some("three"), Some("four"), Some("five"), Some("six"), Some("seven"),
+    /*  56 */ Some("eight"), Some("nine"), Some("colon"), Some("semicolon"), Some("less"), Some("equal"), Some("greater"), Some("question"),
+    /*  64 */ Some("congruent"), Some("Alpha"), Some("Beta"), Some("Chi"), Some("Delta"), Some("Epsilon"), Some("Phi"), Some("Gamma"),
+    /*  72 */ Some("Eta"), Some("Iota"), Some("theta1"), Some("Kappa"), Some("Lambda"), Some("Mu"), Some("Nu"), Some("Omicron"),
+    /*  80 */ Some("Pi"), Some("Theta"), Some("Rho"), Some("Sigma"), Some("Tau"), Some("Upsilon"), Some("sigma1"), Some("Omega"),
+    /*  88 */ Some("Xi"), Some("Psi"), Some("Zeta"), Some("bracketleft"), Some("therefore"), Some("bracketright"), Some("perpendicular"), Some("underscore"),
+    /*  96 */ Some("radicalex"), Some("alpha"), Some("beta"), Some("chi"), Some("delta"), Some("epsilon"), Some("phi"), Some("gamma"),
+    /* 104 */ Some("eta"), Some("iota"), Some("phi1"), Some("kappa"), Some("lambda"), Some("mu"), Some("nu"), Some("omicron"),
+    /* 112 */ Some("pi"), Some("theta"), Some("rho"), Some("sigma"), Some("tau"), Some("upsilon"), Some("omega1"), Some("omega"),
+    /* 120 */ Some("xi"), Some("psi"), Some("zeta"), Some("braceleft"), Some("bar"), Some("braceright"), Some("similar"), None,
+    /* 128 */ None, None, None, None, None, None, None, None,
+    /* 136 */ None, None, None, None, None, None, None, None,
+    /* 144 */ None, None, None, None, None, None, None, None,
+    /* 152 */ None, None, None, None, None, None, None, None,
+    /* 160 */ Some("Euro"), Some("Upsilon1"), Some("minute"), Some("lessequal"), Some("fraction"), Some("infinity"), Some("florin"), Some("club"),
+    /* 168 */ Some("diamond"), Some("heart"), Some("spade"), Some("arrowboth"), Some("arrowleft"), Some("arrowup"), Some("arrowright"), Some("arrowdown"),
+    /* 176 */ Some("degree"), Some("plusminus"), Some("second"), Some("greaterequal"), Some("multiply"), Some("proportional"), Some("partialdiff"), Some("bullet"),
+    /* 184 */ Some("divide"), Some("notequal"), Some("equivalence"), Some("approxequal"), Some("ellipsis"), Some("arrowvertex"), Some("arrowhorizex"), Some("carriagereturn"),
+    /* 192 */ Some("aleph"), Some("Ifraktur"), Some("Rfraktur"), Some("weierstrass"), Some("circlemultiply"), Some("circleplus"), Some("emptyset"), Some("intersection"),
+    /* 200 */ Some("union"), Some("propersuperset"), Some("reflexsuperset"), Some("notsubset"), Some("propersubset"), Some("reflexsubset"), Some("element"), Some("notelement"),
+    /* 208 */ Some("angle"), Some("gradient"), Some("registerserif"), Some("copyrightserif"), Some("trademarkserif"), Some("product"), Some("radical"), Some("dotmath"),
+    /* 216 */ Some("logicalnot"), Some("logicaland"), Some("logicalor"), Some("arrowdblboth"), Some("arrowdblleft"), Some("arrowdblup"), Some("arrowdblright"), Some("arrowdbldown"),
+    /* 224 */ Some("lozenge"), Some("angleleft"), Some("registersans"), Some("copyrightsans"), Some("trademarksans"), Some("summation"), Some("parenlefttp"), Some("parenleftex"),
+    /* 232 */ Some("parenleftbt"), Some("bracketlefttp"), Some("bracketleftex"), Some("bracketleftbt"), Some("bracelefttp"), Some("braceleftmid"), Some("braceleftbt"), Some("braceex"),
+    /* 240 */ None, Some("angleright"), Some("integral"), Some("integraltp"), Some("integralex"), Some("integralbt"), Some("parenrighttp"), Some("parenrightex"),
+    /* 248 */ Some("parenrightbt"), Some("bracketrighttp"), Some("bracketrightex"), Some("bracketrightbt"), Some("bracerighttp"), Some("bracerightmid"), Some("bracerightbt"), None,
+];
+
+/// The built-in encoding of the ZapfDingbats font (Annex D.6).
+pub static ZAPF_DINGBATS_ENCODING: [Option<&str>; 256] = [
+    /*   0 */ None, None, None, None, None, None, None, None,
+    /*   8 */ None, None, None, None, None, None, None, None,
+    /*  16 */ None, None, None, None, None, None, None, None,
+    /*  24 */ None, None, None, None, None, None, None, None,
+    /*  32 */ Some("space"), Some("a1"), Some("a2"), Some("a202"), Some("a3"), Some("a4"), Some("a5"), Some("a119"),
+    /*  40 */ Some("a118"), Some("a117"), Some("a11"), Some("a12"), Some("a13"), Some("a14"), Some("a15"), Some("a16"),
+    /*  48 */ Some("a105"), Some("a17"), Some("a18"), Some("a19"), Some("a20"), Some("a21"), Some("a22"), Some("a23"),
+    /*  56 */ Some("a24"), Some("a25"), Some("a26"), Some("a27"), Some("a28"), Some("a6"), Some("a7"), Some("a8"),
+    /*  64 */ Some("a9"), Some("a10"), Some("a29"), Some("a30"), Some("a31"), Some("a32"), Some("a33"), Some("a34"),
+    /*  72 */ Some("a35"), Some("a36"), Some("a37"), Some("a38"), Some("a39"), Some("a40"), Some("a41"), Some("a42"),
+    /*  80 */ Some("a43"), Some("a44"), Some("a45"), Some("a46"), Some("a47"), Some("a48"), Some("a49"), Some("a50"),
+    /*  88 */ Some("a51"), Some("a52"), Some("a53"), Some("a54"), Some("a55"), Some("a56"), Some("a57"), Some("a58"),
+    /*  96 */ Some("a59"), Some("a60"), Some("a61"), Some("a62"), Some("a63"), Some("a64"), Some("a65"), Some("a66"),
+    /* 104 */ Some("a67"), Some("a68"), Some("a69"), Some("a70"), Some("a71"), Some("a72"), Some("a73"), Some("a74"),
+    /* 112 */ Some("a203"), Some("a75"), Some("a204"), Some("a76"), Some("a77"), Some("a78"), Some("a79"), Some("a81"),
+    /* 120 */ Some("a82"), Some("a83"), Some("a84"), Some("a97"), Some("a98"), Some("a99"), Some("a100"), None,
+    /* 128 */ Some("a89"), Some("a90"), Some("a93"), Some("a94"), Some("a91"), Some("a92"), Some("a205"), Some("a85"),
+    /* 136 */ Some("a206"), Some("a86"), Some("a87"), Some("a88"), Some("a95"), Some("a96"), None, None,
+    /* 144 */ None, None, None, None, None, None, None, None,
+    /* 152 */ None, None, None, None, None, None, None, None,
+    /* 160 */ None, Some("a101"), Some("a102"), Some("a103"), Some("a104"), Some("a106"), Some("a107"), Some("a108"),
+    /* 168 */ Some("a112"), Some("a111"), Some("a110"), Some("a109"), Some("a120"), Some("a121"), Some("a122"), Some("a123"),
+    /* 176 */ Some("a124"), Some("a125"), Some("a126"), Some("a127"), Some("a128"), Some("a129"), Some("a130"), Some("a131"),
+    /* 184 */ Some("a132"), Some("a133"), Some("a134"), Some("a135"), Some("a136"), Some("a137"), Some("a138"), Some("a139"),
+    /* 192 */ Some("a140"), Some("a141"), Some("a142"), Some("a143"), Some("a144"), Some("a145"), Some("a146"), Some("a147"),
+    /* 200 */ Some("a148"), Some("a149"), Some("a150"), Some("a151"), Some("a152"), Some("a153"), Some("a154"), Some("a155"),
+    /* 208 */ Some("a156"), Some("a157"), Some("a158"), Some("a159"), Some("a160"), Some("a161"), Some("a163"), Some("a164"),
+    /* 216 */ Some("a196"), Some("a165"), Some("a192"), Some("a166"), Some("a167"), Some("a168"), Some("a169"), Some("a170"),
+    /* 224 */ Some("a171"), Some("a172"), Some("a173"), Some("a162"), Some("a174"), Some("a175"), Some("a176"), Some("a177"),
+    /* 232 */ Some("a178"), Some("a179"), Some("a193"), Some("a180"), Some("a199"), Some("a181"), Some("a200"), Some("a182"),
+    /* 240 */ None, Some("a201"), Some("a183"), Some("a184"), Some("a197"), Some("a185"), Some("a194"), Some("a198"),
+    /* 248 */ Some("a186"), Some("a195"), Some("a187"), Some("a188"), Some("a189"), Some("a190"), Some("a191"), None,
+];
@@ -1,4 +1,4 @@
-use crate::object::ObjNr;
+use crate::object::{ObjNr, PlainRef};
 use std::io;
 use std::error::Error;
 use crate::parser::ParseFlags;
@@ -57,6 +57,15 @@ pub enum PdfError {
     #[snafu(display("Parsing read past boundary of Contents."))]
     ContentReadPastBoundary,
 
+    #[snafu(display("Content stream has more than {} operators.", max))]
+    ContentTooManyOps { max: usize },
+
+    #[snafu(display("Content stream nesting is deeper than {}.", max))]
+    ContentNestingTooDeep { max: usize },
+
+    #[snafu(display("Unbalanced '{}' in content stream.", op))]
+    ContentUnbalanced { op: &'static str },
+
     #[snafu(display("Primitive not allowed"))]
     PrimitiveNotAllowed { allowed: ParseFlags, found: ParseFlags },
 
@@ -68,6 +77,9 @@ pub enum PdfError {
     #[snafu(display("Ascii85 tail error"))]
     Ascii85TailError,
 
+    #[snafu(display("Filter {} is not supported", filter))]
+    UnsupportedFilter { filter: &'static str },
+
     #[snafu(display("Failed to convert '{}' into PredictorType", n))]
     IncorrectPredictorType {n: u8},
 
@@ -81,6 +93,17 @@ pub enum PdfError {
         source: Box<PdfError>
     },
 
+    /// Decoding the object `r` as `expected` failed, for whatever reason. `found` describes
+    /// the primitive, with its `/Type` and `/Subtype`.
+    #[snafu(display("Can't decode object {} {} R ({}) as {}, caused by\n  {}", r.id, r.gen, found, expected, source))]
+    DecodeObject {
+        r: PlainRef,
+        expected: String,
+        found: String,
+        #[snafu(source)]
+        source: Box<PdfError>
+    },
+
     #[snafu(display("Field /{} is missing in dictionary for type {}.", field, typ))]
     MissingEntry {
         typ: &'static str,
@@ -134,6 +157,9 @@ pub enum PdfError {
     #[snafu(display("Decryption failure"))]
     DecryptionFailure,
 
+    #[snafu(display("Encryption failure"))]
+    EncryptionFailure,
+
     #[snafu(display("JPEG Error, caused by\n  {}", source))]
     Jpeg {
         #[snafu(source)]
@@ -162,6 +188,13 @@ pub enum PdfError {
         source: Box<PdfError>
     },
 
+    #[snafu(display("At byte offset {}, caused by\n  {}", offset, source))]
+    AtOffset {
+        offset: usize,
+        #[snafu(source)]
+        source: Box<PdfError>
+    },
+
     #[snafu(display("PostScriptParseError"))]
     PostScriptParse,
 
@@ -188,9 +221,56 @@ impl PdfError {
         match self {
             PdfError::EOF => true,
             PdfError::Try { ref source, .. } => source.is_eof(),
+            PdfError::AtOffset { ref source, .. } => source.is_eof(),
             _ => false
         }
     }
+    /// Whether the error is caused by a wrong password for an encrypted file.
+    pub fn is_invalid_password(&self) -> bool {
+        match self {
+            PdfError::InvalidPassword => true,
+            PdfError::Try { ref source, .. } => source.is_invalid_password(),
+            PdfError::AtOffset { ref source, .. } => source.is_invalid_password(),
+            PdfError::Shared { ref source } => source.is_invalid_password(),
+            PdfError::DecodeObject { ref source, .. } => source.is_invalid_password(),
+            _ => false
+        }
+    }
+    /// Whether the error is caused by a stream filter that can't be decoded (or encoded).
+    ///
+    /// Only the object using the filter is affected, so callers may skip it and go on.
+    pub fn is_unsupported_filter(&self) -> bool {
+        match self {
+            PdfError::UnsupportedFilter { .. } => true,
+            PdfError::Try { ref source, .. } => source.is_unsupported_filter(),
+            PdfError::AtOffset { ref source, .. } => source.is_unsupported_filter(),
+            PdfError::Shared { ref source } => source.is_unsupported_filter(),
+            PdfError::DecodeObject { ref source, .. } => source.is_unsupported_filter(),
+            _ => false
+        }
+    }
+    /// The byte offset in the file where the error occurred, if known.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            PdfError::AtOffset { offset, .. } => Some(*offset),
+            PdfError::Try { ref source, .. } | PdfError::FromPrimitive { ref source, .. } | PdfError::DecodeObject { ref source, .. } => source.offset(),
+            PdfError::Shared { ref source } => source.offset(),
+            _ => None
+        }
+    }
+}
+
+pub trait ResultExt<T> {
+    /// Record that the error occurred at `offset` in the file, unless an offset is already known.
+    fn with_offset(self, offset: usize) -> Result<T>;
+}
+impl<T> ResultExt<T> for Result<T> {
+    fn with_offset(self, offset: usize) -> Result<T> {
+        self.map_err(|e| match e.offset() {
+            Some(_) => e,
+            None => PdfError::AtOffset { offset, source: Box::new(e) }
+        })
+    }
 }
 datasize::non_dynamic_const_heap_size!(PdfError, 0);
 
@@ -1,22 +1,23 @@
 //! This is kind of the entry-point of the type-safe PDF functionality.
 use std::marker::PhantomData;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::path::Path;
 use std::io::Write;
 
 use crate as pdf;
 use crate::error::*;
 use crate::object::*;
-use crate::primitive::{Primitive, Dictionary, PdfString};
-use crate::backend::Backend;
+use crate::primitive::{Primitive, Dictionary, PdfString, PdfStream, StreamInner};
+use crate::backend::{Backend, parse_at};
 use crate::any::*;
 use crate::parser::{Lexer, parse_with_lexer};
 use crate::parser::{parse_indirect_object, parse, ParseFlags};
 use crate::xref::{XRef, XRefTable, XRefInfo};
 use crate::crypt::Decoder;
-use crate::crypt::CryptDict;
+use crate::crypt::{CryptDict, Permissions};
 use crate::enc::{StreamFilter, decode};
+use crate::build::PageBuilder;
 use std::ops::Range;
 use datasize::DataSize;
 
@@ -75,6 +76,11 @@ pub struct Storage<B, OC, SC, L> {
     changes:    HashMap<ObjNr, (Primitive, GenNr)>,
 
     refs:       XRefTable,
+    // Offset of the newest cross-reference section and its `/Prev`, while the older sections
+    // have not been merged into `refs`. They are read the first time an object is not found
+    // in `refs` (see `xref`).
+    older_xref: Option<(usize, usize)>,
+    older_refs: OnceLock<Result<XRefTable, Arc<PdfError>>>,
 
     decoder:    Option<Decoder>,
     options:    ParseOptions,
@@ -84,6 +90,14 @@ pub struct Storage<B, OC, SC, L> {
     // Position of the PDF header in the file.
     start_offset: usize,
 
+    // `/Info` of the trailer as read, which `Trailer` does not keep.
+    info_ref: Option<PlainRef>,
+
+    // signature dictionaries to be written, with the size of `/Contents` in bytes
+    signatures: HashMap<ObjNr, usize>,
+    // and where they have been written
+    signed: HashMap<ObjNr, SignatureRange>,
+
     log: L
 }
 
@@ -99,10 +113,15 @@ where
             stream_cache,
             changes: HashMap::new(),
             refs: XRefTable::new(0),
+            older_xref: None,
+            older_refs: OnceLock::new(),
             decoder: None,
             options: ParseOptions::strict(),
             backend: Vec::from(&b"%PDF-1.7\n"[..]),
             start_offset: 0,
+            info_ref: None,
+            signatures: HashMap::new(),
+            signed: HashMap::new(),
             log
         }
     }
@@ -122,15 +141,25 @@ where
         StorageResolver::new(self)
     }
     pub fn with_cache(backend: B, options: ParseOptions, object_cache: OC, stream_cache: SC, log: L) -> Result<Self> {
+        if let Some(max) = options.max_file_size {
+            if backend.len() > max {
+                bail!("file of {} bytes exceeds the maximum size of {} bytes", backend.len(), max);
+            }
+        }
         Ok(Storage {
             start_offset: backend.locate_start_offset()?,
             backend,
             refs: XRefTable::new(0),
+            older_xref: None,
+            older_refs: OnceLock::new(),
             cache: object_cache,
             stream_cache,
             changes: HashMap::new(),
             decoder: None,
             options,
+            info_ref: None,
+            signatures: HashMap::new(),
+            signed: HashMap::new(),
             log
         })
     }
@@ -138,8 +167,12 @@ where
         let data = self.backend.read(range)?;
 
         let mut data = Vec::from(data);
+        // a leading `Identity` crypt filter means the stream is not encrypted
+        let identity = matches!(filters.first(), Some(StreamFilter::Crypt(ref p)) if p.is_identity());
         if let Some(ref decoder) = self.decoder {
-            data = Vec::from(t!(decoder.decrypt(id, &mut data)));
+            if !identity {
+                data = Vec::from(t!(decoder.decrypt(id, &mut data)));
+            }
         }
         for filter in filters {
             data = t!(decode(&data, filter), filter);
@@ -154,8 +187,16 @@ where
     pub fn load_storage_and_trailer_password(&mut self, password: &[u8]) -> Result<Dictionary> {
 
         let resolver = StorageResolver::new(self);
-        let (refs, trailer) = t!(self.backend.read_xref_table_and_trailer(self.start_offset, &resolver));
+        let (xref_offset, refs, trailer) = t!(self.backend.read_newest_xref_and_trailer(self.start_offset, &resolver));
         self.refs = refs;
+        self.older_xref = match trailer.get("Prev") {
+            Some(prev) => Some((xref_offset, t!(prev.as_usize()))),
+            None => None
+        };
+        self.older_refs = OnceLock::new();
+        if let Some(&Primitive::Reference(r)) = trailer.get("Info") {
+            self.info_ref = Some(r);
+        }
 
         if let Some(crypt) = trailer.get("Encrypt") {
             let key = trailer
@@ -234,14 +275,97 @@ where
             }
         })
     }
+    /// The linearization parameter dictionary, if this is a linearized ("fast web view") file.
+    ///
+    /// It has to be the first object after the header. `None` is also returned if `/L` does not
+    /// match the length of the file, which means it was changed (e.g. updated incrementally)
+    /// after it was linearized and the hints can't be trusted.
+    pub fn linearization(&self) -> Option<Linearization> {
+        let (_, p) = parse_at(&self.backend, self.start_offset, OBJECT_WINDOW, |lexer| {
+            parse_indirect_object(lexer, &NoResolve, None, ParseFlags::DICT)
+        }).ok()?;
+        let dict = p.into_dictionary().ok()?;
+        dict.get("Linearized")?;
+        let linearization = Linearization::from_dict(dict, &NoResolve).ok()?;
+        if linearization.file_length != self.backend.len() - self.start_offset {
+            warn!("linearization dictionary does not match the file length");
+            return None;
+        }
+        Some(linearization)
+    }
+    /// Parse the object at offset `pos` (relative to the header).
+    fn parse_raw(&self, pos: usize, flags: ParseFlags, resolve: &impl Resolve) -> Result<Primitive> {
+        let pos = self.start_offset + pos;
+        let (_, p) = parse_at(&self.backend, pos, OBJECT_WINDOW, |lexer| {
+            parse_indirect_object(lexer, resolve, self.decoder.as_ref(), flags)
+        })?;
+        Ok(p)
+    }
+    /// The cross-reference entry of object `id`.
+    ///
+    /// Only the newest cross-reference section is read when the file is opened. The first
+    /// object that is not in it reads the older sections.
+    fn xref(&self, id: ObjNr) -> Result<XRef> {
+        match self.refs.get(id) {
+            Ok(XRef::Invalid) | Err(_) if self.older_xref.is_some() => t!(self.older_refs()).get(id),
+            r => r
+        }
+    }
+    fn older_refs(&self) -> Result<&XRefTable> {
+        let older = self.older_refs.get_or_init(|| {
+            let (xref_offset, prev) = self.older_xref.ok_or(PdfError::Invalid)?;
+            let mut refs = XRefTable::with_size(0);
+            let resolver = XRefResolver { backend: &self.backend, options: &self.options };
+            self.backend.read_older_xrefs(self.start_offset, xref_offset, prev, &mut refs, &resolver)?;
+            Ok(refs)
+        }.map_err(Arc::new));
+        older.as_ref().map_err(|e| PdfError::Shared { source: e.clone() })
+    }
+    /// Merge the older cross-reference sections into `refs`, before changing it.
+    fn load_older_xrefs(&mut self) -> Result<()> {
+        if self.older_xref.is_none() {
+            return Ok(());
+        }
+        t!(self.older_refs());
+        if let Some(Ok(older)) = self.older_refs.take() {
+            t!(self.refs.add_entries_from_table(&older));
+        }
+        self.older_xref = None;
+        Ok(())
+    }
+    /// The reference of object `id` if it is in use, either in the file or as a pending change.
+    fn object_ref(&self, id: ObjNr) -> Option<PlainRef> {
+        if let Some(&(_, gen)) = self.changes.get(&id) {
+            return Some(PlainRef { id, gen });
+        }
+        match self.xref(id).ok()? {
+            XRef::Raw { gen_nr, .. } => Some(PlainRef { id, gen: gen_nr }),
+            XRef::Stream { .. } => Some(PlainRef { id, gen: 0 }),
+            _ => None
+        }
+    }
+    /// Number of objects in use (not free).
+    pub fn object_count(&self) -> usize {
+        (0 .. self.refs.len() as ObjNr).filter(|&id| self.object_ref(id).is_some()).count()
+    }
+    /// Iterate over all objects in use, including those in object streams.
+    ///
+    /// Free entries are skipped. Objects that fail to load are yielded as `Err`.
+    pub fn objects(&self) -> impl Iterator<Item = (PlainRef, Result<Primitive>)> + '_ {
+        let resolver = StorageResolver::new(self);
+        (0 .. self.refs.len() as ObjNr)
+            .filter_map(move |id| self.object_ref(id))
+            .map(move |r| (r, resolver.resolve(r)))
+    }
     fn resolve_ref(&self, r: PlainRef, flags: ParseFlags, resolve: &impl Resolve) -> Result<Primitive> {
         match self.changes.get(&r.id) {
             Some((p, _)) => Ok((*p).clone()),
-            None => match t!(self.refs.get(r.id)) {
-                XRef::Raw {pos, ..} => {
-                    let mut lexer = Lexer::with_offset(t!(self.backend.read(self.start_offset + pos ..)), self.start_offset + pos);
-                    let p = t!(parse_indirect_object(&mut lexer, resolve, self.decoder.as_ref(), flags)).1;
-                    Ok(p)
+            None => match t!(self.xref(r.id)) {
+                XRef::Raw {pos, gen_nr} => {
+                    if gen_nr != r.gen {
+                        warn!("reference to {} {} R, but the current generation is {}", r.id, r.gen, gen_nr);
+                    }
+                    Ok(t!(self.parse_raw(pos, flags, resolve)))
                 }
                 XRef::Stream {stream_id, index} => {
                     if !flags.contains(ParseFlags::STREAM) {
@@ -280,6 +404,9 @@ impl<'a, B, OC, SC, L> StorageResolver<'a, B, OC, SC, L> {
     }
 }
 
+/// Initial size of the window objects are parsed from, see `backend::parse_at`.
+const OBJECT_WINDOW: usize = 16 * 1024;
+
 struct Defer<F: FnMut()>(F);
 impl<F: FnMut()> Drop for Defer<F> {
     fn drop(&mut self) {
@@ -302,6 +429,132 @@ where
     }
 
     fn get<T: Object+DataSize>(&self, r: Ref<T>) -> Result<RcRef<T>> {
+        self.get_with(r, || self.resolve(r.get_inner()))
+    }
+    /// Loads the objects in the order they appear in the file, with a single lexer
+    /// for all objects that are not compressed in an object stream.
+    ///
+    /// The lexer covers the range from the first to the last of those objects. One that
+    /// does not fit is read on its own.
+    fn get_many<T: Object+DataSize>(&self, refs: &[Ref<T>]) -> Result<Vec<RcRef<T>>> {
+        let storage = self.storage;
+        let raw = |id| match storage.xref(id) {
+            Ok(XRef::Raw { pos, gen_nr }) if !storage.changes.contains_key(&id) => Some((pos, gen_nr)),
+            _ => None
+        };
+        // objects in object streams are sorted by the position of their stream
+        let position = |r: &Ref<T>| {
+            let id = r.get_inner().id;
+            match storage.xref(id) {
+                _ if storage.changes.contains_key(&id) => (0, 0),
+                Ok(XRef::Raw { pos, .. }) => (pos, 0),
+                Ok(XRef::Stream { stream_id, index }) => (raw(stream_id).map_or(usize::MAX, |(pos, _)| pos), index + 1),
+                _ => (usize::MAX, 0)
+            }
+        };
+        let mut order: Vec<usize> = (0 .. refs.len()).collect();
+        order.sort_by_key(|&i| position(&refs[i]));
+
+        let span = || {
+            let mut positions = refs.iter().filter_map(|r| raw(r.get_inner().id)).map(|(pos, _)| pos);
+            let first = positions.next()?;
+            let last = positions.fold(first, usize::max);
+            let start = storage.start_offset.checked_add(first)?;
+            let end = storage.start_offset.checked_add(last)?.saturating_add(OBJECT_WINDOW).min(storage.backend.len());
+            Some(start .. end)
+        };
+        let mut window: Option<(Lexer, Range<usize>)> = None;
+        let mut objects = vec![None; refs.len()];
+        for i in order {
+            let r = refs[i];
+            let key = r.get_inner();
+            let obj = match raw(key.id) {
+                Some((pos, gen_nr)) => self.get_with(r, || {
+                    if gen_nr != key.gen {
+                        warn!("reference to {} {} R, but the current generation is {}", key.id, key.gen, gen_nr);
+                    }
+                    storage.log.load_object(key);
+                    if window.is_none() {
+                        let range = span().ok_or(PdfError::Invalid)?;
+                        window = Some((Lexer::with_offset(t!(storage.backend.read(range.clone())), range.start), range));
+                    }
+                    let (lexer, range) = window.as_mut().unwrap();
+                    match (storage.start_offset + pos).checked_sub(range.start) {
+                        Some(offset) if offset < range.len() => lexer.set_pos(offset),
+                        _ => return storage.parse_raw(pos, ParseFlags::ANY, self)
+                    };
+                    match parse_indirect_object(lexer, self, storage.decoder.as_ref(), ParseFlags::ANY) {
+                        Ok((_, p)) => Ok(p),
+                        Err(e) if e.is_eof() => storage.parse_raw(pos, ParseFlags::ANY, self),
+                        Err(e) => Err(e)
+                    }
+                })?,
+                None => self.get(r)?
+            };
+            objects[i] = Some(obj);
+        }
+        Ok(objects.into_iter().flatten().collect())
+    }
+    fn options(&self) -> &ParseOptions {
+        &self.storage.options
+    }
+    fn stream_data(&self, id: PlainRef, range: Range<usize>) -> Result<Arc<[u8]>> {
+        // only the crypt filter matters here, as it decides whether the data is encrypted
+        let crypt = match self.storage.decoder {
+            Some(_) => match t!(self.resolve(id)) {
+                Primitive::Stream(s) => {
+                    let info = t!(StreamInfo::<()>::from_primitive(Primitive::Dictionary(s.info), self));
+                    info.filters.into_iter().take(1).filter(|f| matches!(f, StreamFilter::Crypt(_))).collect()
+                }
+                _ => vec![]
+            },
+            None => vec![]
+        };
+        self.storage.decode(id, range, &crypt)
+    }
+
+    fn get_data_or_decode(&self, id: PlainRef, range: Range<usize>, filters: &[StreamFilter]) -> Result<Arc<[u8]>> {
+        self.storage.stream_cache.get_or_compute(id, || self.storage.decode(id, range, filters).map_err(Arc::new))
+        .map_err(|e| e.into())
+    }
+}
+/// Reads cross-reference streams of the older sections, which can't look up objects
+/// (as those may be in the section being read). Cross-reference streams are not encrypted.
+struct XRefResolver<'a, B> {
+    backend: &'a B,
+    options: &'a ParseOptions,
+}
+impl<B: Backend> Resolve for XRefResolver<'_, B> {
+    fn resolve_flags(&self, _: PlainRef, _: ParseFlags, _: usize) -> Result<Primitive> {
+        Err(PdfError::Reference)
+    }
+    fn get<T: Object+DataSize>(&self, _: Ref<T>) -> Result<RcRef<T>> {
+        Err(PdfError::Reference)
+    }
+    fn options(&self) -> &ParseOptions {
+        self.options
+    }
+    fn stream_data(&self, _: PlainRef, range: Range<usize>) -> Result<Arc<[u8]>> {
+        Ok(t!(self.backend.read(range)).into())
+    }
+    fn get_data_or_decode(&self, _: PlainRef, range: Range<usize>, filters: &[StreamFilter]) -> Result<Arc<[u8]>> {
+        let mut data = Vec::from(t!(self.backend.read(range)));
+        for filter in filters {
+            data = t!(decode(&data, filter), filter);
+        }
+        Ok(data.into())
+    }
+}
+
+impl<'a, B, OC, SC, L> StorageResolver<'a, B, OC, SC, L>
+where
+    B: Backend,
+    OC: Cache<Result<AnySync, Arc<PdfError>>>,
+    SC: Cache<Result<Arc<[u8]>, Arc<PdfError>>>,
+    L: Log
+{
+    /// `get`, with `parse` reading the object if it is not in the cache yet.
+    fn get_with<T: Object+DataSize>(&self, r: Ref<T>, parse: impl FnOnce() -> Result<Primitive>) -> Result<RcRef<T>> {
         let key = r.get_inner();
         self.storage.log.log_get(key);
         
@@ -319,12 +572,22 @@ where
         });
         
         let res = self.storage.cache.get_or_compute(key, || {
-            match self.resolve(key).and_then(|p| T::from_primitive(p, self)) {
+            let p = match parse() {
+                Ok(p) => p,
+                Err(e) => return Err(Arc::new(e))
+            };
+            match T::from_primitive(p, self) {
                 Ok(obj) => Ok(AnySync::new(Shared::new(obj))),
                 Err(e) => {
+                    // the primitive was consumed, read it again to describe it
                     let p = self.resolve(key);
                     warn!("failed to decode {p:?} as {}", std::any::type_name::<T>());
-                    Err(Arc::new(e))
+                    Err(Arc::new(PdfError::DecodeObject {
+                        r: key,
+                        expected: short_type_name::<T>(),
+                        found: p.as_ref().map_or_else(|_| "?".into(), describe_primitive),
+                        source: Box::new(e)
+                    }))
                 }
             }
         });
@@ -341,17 +604,6 @@ where
             Err(e) => Err(PdfError::Shared { source: e.clone()}),
         }
     }
-    fn options(&self) -> &ParseOptions {
-        &self.storage.options
-    }
-    fn stream_data(&self, id: PlainRef, range: Range<usize>) -> Result<Arc<[u8]>> {
-        self.storage.decode(id, range, &[])
-    }
-
-    fn get_data_or_decode(&self, id: PlainRef, range: Range<usize>, filters: &[StreamFilter]) -> Result<Arc<[u8]>> {
-        self.storage.stream_cache.get_or_compute(id, || self.storage.decode(id, range, filters).map_err(Arc::new))
-        .map_err(|e| e.into())
-    }
 }
 
 impl<B, OC, SC, L> Updater for Storage<B, OC, SC, L>
@@ -374,11 +626,12 @@ where
     fn update<T: ObjectWrite>(&mut self, old: PlainRef, obj: T) -> Result<RcRef<T>> {
         use std::collections::hash_map::Entry;
 
+        t!(self.load_older_xrefs());
         let r = match self.refs.get(old.id)? {
             XRef::Free { .. } => panic!(),
             XRef::Raw { gen_nr, .. } => PlainRef { id: old.id, gen: gen_nr },
-            XRef::Stream { .. } => return self.create(obj),
-            XRef::Promised => PlainRef { id: old.id, gen: 0 },
+            // written as a regular object on save, replacing the one in the object stream
+            XRef::Stream { .. } | XRef::Promised => PlainRef { id: old.id, gen: 0 },
             XRef::Invalid => panic!()
         };
         let primitive = obj.to_primitive(self)?;
@@ -426,40 +679,125 @@ where
     L: Log
 {
     pub fn save(&mut self, trailer: &mut Trailer) -> Result<&[u8]> {
+        self.save_with(trailer, &SaveOptions::default())
+    }
+    pub fn save_with(&mut self, trailer: &mut Trailer, options: &SaveOptions) -> Result<&[u8]> {
+        t!(self.load_older_xrefs());
+        if options.encryption == EncryptionMode::Off && self.decoder.is_some() {
+            t!(self.decrypt_all(trailer));
+        }
         // writing the trailer generates another id for the info dictionary
         trailer.size = (self.refs.len() + 2) as _;
         let trailer_dict = trailer.to_dict(self)?;
+        self.info_ref = match trailer_dict.get("Info") {
+            Some(&Primitive::Reference(r)) => Some(r),
+            _ => None
+        };
         
         let xref_promise = self.promise::<Stream<XRefInfo>>();
 
+        if options.gc {
+            let mut reachable = self.reachable(&trailer_dict);
+            reachable.insert(xref_promise.get_inner().id);
+            for id in 0 .. self.refs.len() as ObjNr {
+                if reachable.contains(&id) {
+                    continue;
+                }
+                match self.refs.get(id)? {
+                    XRef::Raw { gen_nr, .. } => self.refs.set(id, XRef::Free { next_obj_nr: 0, gen_nr: gen_nr + 1 }),
+                    XRef::Stream { .. } | XRef::Promised => self.refs.set(id, XRef::Free { next_obj_nr: 0, gen_nr: 1 }),
+                    _ => {}
+                }
+                self.changes.remove(&id);
+            }
+        }
+
+        // an update of an existing file may follow a `%%EOF` without line break
+        if !matches!(self.backend.last(), None | Some(b'\n') | Some(b'\r')) {
+            self.backend.push(b'\n');
+        }
+
         let mut changes: Vec<_> = self.changes.iter().collect();
         changes.sort_unstable_by_key(|&(id, _)| id);
 
+        // each object is serialized (and encrypted) completely before it is appended,
+        // so the offset in the xref is where its `N G obj` line actually starts.
+        // Like all offsets in the file, they are relative to the `%PDF` header.
+        let mut buf = Vec::new();
+        let mut offsets = Vec::with_capacity(changes.len());
+        // (id, placeholder of /Contents, placeholder of /ByteRange) in the backend
+        let mut placed = vec![];
         for &(&id, &(ref primitive, gen)) in changes.iter() {
-            let pos = self.backend.len();
-            self.refs.set(id, XRef::Raw { pos: pos as _, gen_nr: gen });
-            writeln!(self.backend, "{} {} obj", id, gen)?;
-            primitive.serialize(&mut self.backend)?;
-            writeln!(self.backend, "endobj")?;
+            buf.clear();
+            writeln!(buf, "{} {} obj", id, gen)?;
+            match self.decoder {
+                Some(ref decoder) => {
+                    let mut encrypted = decoder.encrypt_primitive(PlainRef { id, gen }, primitive)?;
+                    // the signature is over the encrypted file, it is not encrypted itself
+                    if let (true, Primitive::Dictionary(ref mut dict), Primitive::Dictionary(ref plain)) = (self.signatures.contains_key(&id), &mut encrypted, primitive) {
+                        if let Some(contents) = plain.get("Contents") {
+                            dict.insert("Contents", contents.clone());
+                        }
+                    }
+                    encrypted.serialize(&mut buf)?
+                }
+                None => primitive.serialize(&mut buf)?,
+            }
+            if let Some(&len) = self.signatures.get(&id) {
+                let (contents, byte_range) = signature_placeholders(len)?;
+                let find = |needle: &[u8]| buf.windows(needle.len()).position(|w| w == needle)
+                    .map(|pos| self.backend.len() + pos .. self.backend.len() + pos + needle.len())
+                    .ok_or_else(|| other!("signature placeholder of object {} not found", id));
+                placed.push((id, find(&contents)?, find(&byte_range)?));
+            }
+            // a number would run into `endobj` otherwise
+            writeln!(buf, "\nendobj")?;
+            offsets.push((id, gen, self.backend.len() - self.start_offset));
+            self.backend.extend_from_slice(&buf);
+        }
+        for (id, gen, pos) in offsets {
+            self.refs.set(id, XRef::Raw { pos, gen_nr: gen });
         }
 
-        let xref_pos = self.backend.len();
+        let xref_pos = self.backend.len() - self.start_offset;
         self.refs.set(xref_promise.get_inner().id, XRef::Raw { pos: xref_pos, gen_nr: 0 });
         // only write up to the xref stream obj id
         let stream = self.refs.write_stream(xref_promise.get_inner().id as usize + 1)?;
 
-        writeln!(self.backend, "{} {} obj", xref_promise.get_inner().id, 0)?;
+        buf.clear();
+        writeln!(buf, "{} {} obj", xref_promise.get_inner().id, 0)?;
         let mut xref_and_trailer = stream.to_pdf_stream(&mut NoUpdate)?;
         for (k, v) in trailer_dict.iter() {
+            // the xref stream has the actual /Size, which depends on whether an info dict was written
+            if k == "Size" {
+                continue;
+            }
             xref_and_trailer.info.insert(k.clone(), v.clone());
         }
 
-        xref_and_trailer.serialize(&mut self.backend)?;
-        writeln!(self.backend, "endobj")?;
+        xref_and_trailer.serialize(&mut buf)?;
+        writeln!(buf, "endobj")?;
+        write!(buf, "startxref\n{}\n%%EOF\n", xref_pos)?;
+        self.backend.extend_from_slice(&buf);
 
         let _ = self.fulfill(xref_promise, stream)?;
 
-        write!(self.backend, "\nstartxref\n{}\n%%EOF", xref_pos).unwrap();
+        // now that the length of the file is known
+        let len = self.backend.len();
+        for (id, contents, byte_range_pos) in placed {
+            let byte_range = [0, contents.start, contents.end, len - contents.end];
+            let text = format!("[0 {} {} {}", byte_range[1], byte_range[2], byte_range[3]);
+            let out = &mut self.backend[byte_range_pos];
+            out.fill(b' ');
+            out[.. text.len()].copy_from_slice(text.as_bytes());
+            *out.last_mut().unwrap() = b']';
+            self.backend[contents.start + 1 .. contents.end - 1].fill(b'0');
+
+            self.signatures.remove(&id);
+            // it is in the file now, as written
+            self.changes.remove(&id);
+            self.signed.insert(id, SignatureRange { byte_range, contents });
+        }
 
         // update trailer which may have change now.
         self.cache.clear();
@@ -467,6 +805,200 @@ where
 
         Ok(&self.backend)
     }
+
+    /// Read all objects of the file into `changes`, decrypted, and start over with an empty file,
+    /// so the next save writes all of them in the clear. The `/Encrypt` dictionary is dropped.
+    fn decrypt_all(&mut self, trailer: &mut Trailer) -> Result<()> {
+        let encrypt = trailer.encrypt_dict.take().map(|dict| dict.get_ref().get_inner().id);
+        let mut objects = vec![];
+        let mut free = vec![];
+        {
+            let resolver = StorageResolver::new(self);
+            for id in 0 .. self.refs.len() as ObjNr {
+                let gen = match self.refs.get(id)? {
+                    XRef::Raw { gen_nr, .. } => gen_nr,
+                    XRef::Stream { .. } => 0,
+                    _ => continue
+                };
+                if Some(id) == encrypt {
+                    free.push((id, gen));
+                    continue;
+                }
+                if self.changes.contains_key(&id) {
+                    continue;
+                }
+                let p = match resolver.resolve(PlainRef { id, gen }) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        warn!("can't read object {} {} R, dropping it: {}", id, gen, e);
+                        free.push((id, gen));
+                        continue;
+                    }
+                };
+                let p = match p {
+                    // these are written anew (or not at all)
+                    Primitive::Stream(ref s) if matches!(s.info.get("Type"), Some(Primitive::Name(t)) if t == "ObjStm" || t == "XRef") => {
+                        free.push((id, gen));
+                        continue;
+                    }
+                    Primitive::Stream(stream) => {
+                        let data = t!(stream.raw_data(&resolver));
+                        let mut info = stream.info;
+                        info.insert("Length", data.len() as i32);
+                        Primitive::Stream(PdfStream { info, inner: StreamInner::Pending { data } })
+                    }
+                    p => p
+                };
+                objects.push((id, gen, p));
+            }
+        }
+        for (id, gen) in free {
+            self.changes.remove(&id);
+            self.refs.set(id, XRef::Free { next_obj_nr: 0, gen_nr: gen + 1 });
+        }
+        for (id, gen, p) in objects {
+            self.changes.insert(id, (p, gen));
+        }
+
+        let header = self.backend.get(self.start_offset ..).unwrap_or_default();
+        let header = match header.iter().position(|&b| b == b'\n' || b == b'\r') {
+            Some(n) if header.starts_with(b"%PDF-") => &header[.. n],
+            _ => b"%PDF-1.7"
+        };
+        let mut backend = header.to_vec();
+        backend.push(b'\n');
+        self.backend = backend;
+        self.start_offset = 0;
+        self.decoder = None;
+        trailer.prev_trailer_pos = None;
+        Ok(())
+    }
+
+    /// Add the signature dictionary `dict` with room for `contents_len` bytes of signature in
+    /// `/Contents`, for signing the file elsewhere.
+    ///
+    /// `/Contents` and `/ByteRange` are set by the next save, and `signature_range` then tells
+    /// which bytes to sign. The signature goes into the file with `fill_signature`, after which
+    /// the file must not be saved again (another save appends an update that is not signed).
+    pub fn reserve_signature(&mut self, mut dict: Dictionary, contents_len: usize) -> Result<PlainRef> {
+        dict.insert("Contents", PdfString::new(vec![0xff; contents_len].into()));
+        dict.insert("ByteRange", Primitive::Array(vec![Primitive::Integer(0), Primitive::Integer(i32::MAX), Primitive::Integer(i32::MAX), Primitive::Integer(i32::MAX)]));
+        let r = self.create(dict)?.get_ref().get_inner();
+        self.signatures.insert(r.id, contents_len);
+        Ok(r)
+    }
+
+    /// Where the signature `r` (from `reserve_signature`) was written, `None` if it hasn't been yet.
+    pub fn signature_range(&self, r: PlainRef) -> Option<&SignatureRange> {
+        self.signed.get(&r.id)
+    }
+
+    /// Write `signature` (e.g. a DER encoded PKCS#7 object) into `/Contents` of the signature `r`.
+    ///
+    /// The rest of the placeholder stays zero.
+    pub fn fill_signature(&mut self, r: PlainRef, signature: &[u8]) -> Result<()> {
+        let range = self.signed.get(&r.id).ok_or_else(|| other!("object {} is not a saved signature", r.id))?;
+        let hex = &mut self.backend[range.contents.start + 1 .. range.contents.end - 1];
+        if signature.len() * 2 > hex.len() {
+            bail!("signature of {} bytes does not fit into {} bytes", signature.len(), hex.len() / 2);
+        }
+        for (out, b) in hex.chunks_exact_mut(2).zip(signature) {
+            out.copy_from_slice(format!("{:02X}", b).as_bytes());
+        }
+        Ok(())
+    }
+
+    /// Encrypt all objects written by subsequent saves with AES-256.
+    ///
+    /// The returned dictionary has to be set as `encrypt_dict` of the trailer, which
+    /// also needs an `id`. Objects that are already in the file are not rewritten,
+    /// so this is meant for new documents.
+    pub fn encrypt(&mut self, user_password: &[u8], owner_password: &[u8], permissions: i32) -> Result<RcRef<CryptDict>> {
+        let (dict, mut decoder) = CryptDict::aes_256(user_password, owner_password, permissions)?;
+        let dict = self.create(dict)?;
+        decoder.encrypt_indirect_object = Some(dict.get_ref().get_inner());
+        self.decoder = Some(decoder);
+        Ok(dict)
+    }
+
+    /// Object numbers of all objects that can be reached from the trailer.
+    ///
+    /// Object streams holding a reachable object are included as well.
+    fn reachable(&self, trailer: &Dictionary) -> HashSet<ObjNr> {
+        let resolver = StorageResolver::new(self);
+        let mut reachable = HashSet::new();
+        let mut stack = vec![];
+        trailer.values().for_each(|p| p.trace(&mut |r| stack.push(r)));
+
+        while let Some(r) = stack.pop() {
+            if !reachable.insert(r.id) {
+                continue;
+            }
+            if !self.changes.contains_key(&r.id) {
+                match self.xref(r.id) {
+                    Ok(XRef::Stream { stream_id, .. }) => stack.push(PlainRef { id: stream_id, gen: 0 }),
+                    Ok(XRef::Raw { .. }) => {}
+                    _ => continue
+                }
+            }
+            // references to missing objects are treated as null
+            if let Ok(p) = resolver.resolve(r) {
+                p.trace(&mut |r| stack.push(r));
+            }
+        }
+        reachable
+    }
+}
+
+/// The serialized `/Contents` and `/ByteRange` set by `Storage::reserve_signature`.
+fn signature_placeholders(len: usize) -> Result<(Vec<u8>, Vec<u8>)> {
+    let mut contents = vec![];
+    PdfString::new(vec![0xff; len].into()).serialize(&mut contents)?;
+    let byte_range = format!("[0 {} {} {}]", i32::MAX, i32::MAX, i32::MAX).into_bytes();
+    Ok((contents, byte_range))
+}
+
+/// Where a signature of `Storage::reserve_signature` is in the file, see `Storage::signature_range`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureRange {
+    /// The `/ByteRange` of the signature: offset and length of the data before `/Contents`
+    /// and of the data after it, up to the end of the file.
+    pub byte_range: [usize; 4],
+    /// The `/Contents` hex string, including `<` and `>`.
+    pub contents: Range<usize>,
+}
+impl SignatureRange {
+    /// The two parts of `data` that are signed.
+    pub fn signed_data<'a>(&self, data: &'a [u8]) -> [&'a [u8]; 2] {
+        let [a, b, c, d] = self.byte_range;
+        [&data[a .. a + b], &data[c .. c + d]]
+    }
+}
+
+/// Options for `File::save_to_with` and `Storage::save_with`
+#[derive(Debug, Default, Clone)]
+pub struct SaveOptions {
+    /// Drop all objects that are not reachable from the trailer.
+    ///
+    /// Unreachable objects are marked as free in the cross-reference table
+    /// and new unreachable objects are not written at all.
+    pub gc: bool,
+
+    /// Whether the file stays encrypted.
+    pub encryption: EncryptionMode,
+}
+
+/// See `SaveOptions::encryption`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptionMode {
+    /// Objects are encrypted like the file already is, or as set up by `Storage::encrypt`.
+    #[default]
+    Keep,
+    /// Write everything unencrypted and drop `/Encrypt` from the trailer.
+    ///
+    /// For an encrypted file this rewrites the whole file: all objects are read, decrypted
+    /// (strings and stream data), and written in the clear.
+    Off,
 }
 
 #[cfg(feature="cache")]
@@ -511,6 +1043,30 @@ where
         std::fs::write(path, self.storage.save(&mut self.trailer)?)?;
         Ok(())
     }
+    pub fn save_to_with(&mut self, path: impl AsRef<Path>, options: &SaveOptions) -> Result<()> {
+        std::fs::write(path, self.storage.save_with(&mut self.trailer, options)?)?;
+        Ok(())
+    }
+    /// Save the changes in memory, see `Storage::save_with`.
+    pub fn save_with(&mut self, options: &SaveOptions) -> Result<&[u8]> {
+        self.storage.save_with(&mut self.trailer, options)
+    }
+    /// The data of the file, including what was saved.
+    pub fn data(&self) -> &[u8] {
+        &self.storage.backend
+    }
+    /// See `Storage::reserve_signature`.
+    pub fn reserve_signature(&mut self, dict: Dictionary, contents_len: usize) -> Result<PlainRef> {
+        self.storage.reserve_signature(dict, contents_len)
+    }
+    /// See `Storage::signature_range`.
+    pub fn signature_range(&self, r: PlainRef) -> Option<&SignatureRange> {
+        self.storage.signature_range(r)
+    }
+    /// See `Storage::fill_signature`.
+    pub fn fill_signature(&mut self, r: PlainRef, signature: &[u8]) -> Result<()> {
+        self.storage.fill_signature(r, signature)
+    }
 }
 
 
@@ -628,6 +1184,32 @@ where
         &self.trailer.root
     }
 
+    /// The objects the trailer points to, as references.
+    ///
+    /// The `/Encrypt` dictionary is never encrypted, so `resolver().get(encrypt)` reads it as is.
+    pub fn trailer_typed(&self) -> TrailerRefs {
+        let trailer = &self.trailer;
+        TrailerRefs {
+            size: trailer.size,
+            root: trailer.root.get_ref(),
+            info: self.storage.info_ref.filter(|_| trailer.info_dict.is_some()).map(Ref::new),
+            encrypt: trailer.encrypt_dict.as_ref().map(|d| d.get_ref()),
+            id: trailer.id.clone(),
+        }
+    }
+
+    /// The PDF version as `(major, minor)`.
+    ///
+    /// From the `%PDF-` header, or the catalog's `/Version` if that is later. A header that
+    /// can't be read counts as version 1.0.
+    pub fn version(&self) -> (u8, u8) {
+        let start = self.storage.start_offset + b"%PDF-".len();
+        let header = self.storage.backend.read(start .. (start + 8).min(self.storage.backend.len()))
+            .ok().and_then(parse_version);
+        let catalog = self.trailer.root.version.as_ref().and_then(|v| parse_version(v.as_str().as_bytes()));
+        header.max(catalog).unwrap_or((1, 0))
+    }
+
     pub fn pages(&self) -> impl Iterator<Item=Result<PageRc>> + '_ {
         (0 .. self.num_pages()).map(move |n| self.get_page(n))
     }
@@ -635,11 +1217,61 @@ where
         self.trailer.root.pages.count
     }
 
+    /// Call `f` on every page, in parallel on the rayon thread pool, and collect the results
+    /// in page order.
+    ///
+    /// Each worker thread creates its own state with `init` (for example a font cache for
+    /// rendering) and passes it to `f` along with the page number and the loaded page.
+    #[cfg(feature = "rayon")]
+    pub fn pages_parallel<S, R>(
+        &self,
+        init: impl Fn() -> S + Sync + Send,
+        f: impl Fn(&mut S, u32, Result<PageRc>) -> R + Sync + Send,
+    ) -> Vec<R>
+    where
+        Self: Sync,
+        R: Send,
+    {
+        use rayon::prelude::*;
+        (0 .. self.num_pages()).into_par_iter()
+            .map_init(init, |state, n| f(state, n, self.get_page(n)))
+            .collect()
+    }
+
     pub fn get_page(&self, n: u32) -> Result<PageRc> {
         let resolver = StorageResolver::new(&self.storage);
         self.trailer.root.pages.page(&resolver, n)
     }
 
+    /// See `Storage::linearization`.
+    pub fn linearization(&self) -> Option<Linearization> {
+        self.storage.linearization()
+    }
+
+    /// The first page, read directly from the page object named in the linearization
+    /// dictionary instead of walking the page tree.
+    ///
+    /// Falls back to `get_page(0)` if the file is not linearized or the linearization data
+    /// does not agree with the rest of the file.
+    ///
+    /// Opening the file only reads the newest cross-reference section, which is the one of the
+    /// first page in a linearized file, so the main section at the end is only read if an
+    /// object of the first page is missing from it.
+    pub fn open_first_page_fast(&self) -> Result<PageRc> {
+        if let Some(linearization) = self.linearization() {
+            if linearization.first_page == 0 && linearization.num_pages == self.num_pages() {
+                let r = PlainRef { id: linearization.first_page_object as ObjNr, gen: 0 };
+                match PageRc::from_primitive(Primitive::Reference(r), &self.resolver()) {
+                    Ok(page) => return Ok(page),
+                    Err(e) => warn!("first page object {:?} of the linearized file is invalid: {:?}", r, e)
+                }
+            } else {
+                warn!("linearization dictionary does not match the page tree");
+            }
+        }
+        self.get_page(0)
+    }
+
     pub fn update_catalog(&mut self, catalog: Catalog) -> Result<()> {
         self.trailer.root = self.create(catalog)?;
         Ok(())
@@ -653,9 +1285,140 @@ where
         self.storage.scan()
     }
 
+    /// Number of objects in use, see `objects()`.
+    pub fn object_count(&self) -> usize {
+        self.storage.object_count()
+    }
+
+    /// Iterate over all indirect objects of the file.
+    pub fn objects(&self) -> impl Iterator<Item = (PlainRef, Result<Primitive>)> + '_ {
+        self.storage.objects()
+    }
+
     pub fn log(&self) -> &L {
         &self.storage.log
     }
+
+    /// The file identifier (`/ID` in the trailer), if it is present and well-formed.
+    pub fn id(&self) -> Option<[PdfString; 2]> {
+        match self.trailer.id[..] {
+            [ref a, ref b] => Some([a.clone(), b.clone()]),
+            _ => None
+        }
+    }
+
+    /// The access permissions of an encrypted file, `None` if the file is not encrypted.
+    pub fn permissions(&self) -> Option<Permissions> {
+        self.trailer.encrypt_dict.as_ref().map(|dict| dict.permissions())
+    }
+
+    /// Remove the page at `index` from the page tree.
+    ///
+    /// `/Kids` and `/Count` are updated up to the root. Intermediate nodes that
+    /// become empty are removed as well. The page object itself is left in the file.
+    pub fn remove_page(&mut self, index: u32) -> Result<()> {
+        let path = self.page_tree_path(index, false)?;
+        let mut remove = true;
+        for (node, i) in path.into_iter().rev() {
+            let mut tree = (*node).clone();
+            if remove {
+                tree.kids.remove(i);
+            }
+            tree.count -= 1;
+            remove = tree.kids.is_empty() && tree.parent.is_some();
+            self.update(node.get_ref().get_inner(), PagesNode::Tree(tree))?;
+        }
+        self.reload_catalog()
+    }
+
+    /// Build `page` and insert it so it becomes the page at `index`.
+    ///
+    /// `index` may be `num_pages()` to append the page. The page is added to the
+    /// node that holds the page currently at `index` (or the last page).
+    pub fn insert_page(&mut self, index: u32, page: PageBuilder) -> Result<PageRc> {
+        let path = self.page_tree_path(index, true)?;
+        let parent = path.last().unwrap().0.clone();
+        let page = page.build(parent, self)?;
+        let page = PageRc::create(page, self)?;
+
+        let last = path.len() - 1;
+        for (n, (node, i)) in path.into_iter().enumerate().rev() {
+            let mut tree = (*node).clone();
+            if n == last {
+                tree.kids.insert(i, page.get_ref());
+            }
+            tree.count += 1;
+            self.update(node.get_ref().get_inner(), PagesNode::Tree(tree))?;
+        }
+        self.reload_catalog()?;
+        Ok(page)
+    }
+
+    /// Replace the page at `index` by `page`, keeping its object number.
+    pub fn replace_page(&mut self, index: u32, page: PageBuilder) -> Result<PageRc> {
+        let path = self.page_tree_path(index, false)?;
+        let (parent, i) = path.last().cloned().unwrap();
+        let old = parent.kids[i];
+        let page = page.build(parent, self)?;
+        self.update(old.get_inner(), PagesNode::Leaf(page))?;
+        self.reload_catalog()?;
+        self.get_page(index)
+    }
+
+    /// The page tree nodes from the root to the node holding the page at `index`,
+    /// each with the position of the next node (or the page) in its `/Kids`.
+    ///
+    /// With `insert`, `index` may also be `num_pages()` and the position is where
+    /// a new page has to be inserted.
+    fn page_tree_path(&self, index: u32, insert: bool) -> Result<Vec<(PagesRc, usize)>> {
+        let resolver = self.resolver();
+        let max = self.num_pages();
+        if index > max || (index == max && !insert) {
+            return Err(PdfError::PageOutOfBounds { page_nr: index, max });
+        }
+        let mut path = vec![];
+        let mut node = self.trailer.root.pages.clone();
+        let mut index = index;
+        'descend: for _ in 0 .. 16 {
+            let mut pos = 0;
+            for (i, &kid) in node.kids.iter().enumerate() {
+                match *resolver.get(kid)? {
+                    PagesNode::Tree(ref tree) => {
+                        if index < pos + tree.count {
+                            path.push((node.clone(), i));
+                            node = PagesRc::from_primitive(Primitive::Reference(kid.get_inner()), &resolver)?;
+                            index -= pos;
+                            continue 'descend;
+                        }
+                        pos += tree.count;
+                    }
+                    PagesNode::Leaf(_) => {
+                        if pos == index {
+                            path.push((node, i));
+                            return Ok(path);
+                        }
+                        pos += 1;
+                    }
+                }
+            }
+            if insert && pos == index {
+                let len = node.kids.len();
+                path.push((node, len));
+                return Ok(path);
+            }
+            bail!("page tree /Count does not match its /Kids");
+        }
+        bail!("page tree depth exeeded");
+    }
+
+    /// Pick up changes to the page tree, which the catalog holds onto.
+    fn reload_catalog(&mut self) -> Result<()> {
+        self.storage.cache.clear();
+        let root = self.trailer.root.get_ref();
+        let root = StorageResolver::new(&self.storage).get(root)?;
+        self.trailer.root = root;
+        Ok(())
+    }
 }
 
 #[derive(Object, ObjectWrite, DataSize)]
@@ -679,6 +1442,53 @@ pub struct Trailer {
     pub id:                 Vec<PdfString>,
 }
 
+/// The entries of the trailer, with references instead of the objects. See `File::trailer_typed`.
+#[derive(Debug, Clone)]
+pub struct TrailerRefs {
+    pub size: i32,
+    pub root: Ref<Catalog>,
+    /// `None` if the document information dictionary is missing or was not read from the file.
+    pub info: Option<Ref<InfoDict>>,
+    pub encrypt: Option<Ref<CryptDict>>,
+    pub id: Vec<PdfString>,
+}
+
+/// Linearization parameter dictionary (PDF32000 Annex F.2)
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+pub struct Linearization {
+    /// Version of the linearization
+    #[pdf(key = "Linearized")]
+    pub version: f32,
+
+    /// Length of the file in bytes
+    #[pdf(key = "L")]
+    pub file_length: usize,
+
+    /// Offset and length of the primary hint stream, optionally followed by the overflow hint stream
+    #[pdf(key = "H")]
+    pub hint_streams: Vec<usize>,
+
+    /// Object number of the first page's page object
+    #[pdf(key = "O")]
+    pub first_page_object: u32,
+
+    /// Offset of the end of the first page
+    #[pdf(key = "E")]
+    pub first_page_end: usize,
+
+    /// Number of pages
+    #[pdf(key = "N")]
+    pub num_pages: u32,
+
+    /// Offset of the first entry in the main cross-reference table
+    #[pdf(key = "T")]
+    pub main_xref_offset: usize,
+
+    /// Index of the first page, if it is not the first one in the page tree
+    #[pdf(key = "P", default = "0")]
+    pub first_page: u32,
+}
+
 /*
 pub struct XRefStream {
     pub data: Vec<u8>,
@@ -700,3 +1510,175 @@ impl Object for XRefStream {
     }
 }
 */
+
+/// `major.minor`, followed by anything but another digit.
+fn parse_version(s: &[u8]) -> Option<(u8, u8)> {
+    let digits = |s: &[u8]| s.iter().take_while(|b| b.is_ascii_digit()).count();
+    let n = digits(s);
+    let major = std::str::from_utf8(&s[.. n]).ok()?.parse().ok()?;
+    let s = s[n ..].strip_prefix(b".")?;
+    let n = digits(s);
+    let minor = std::str::from_utf8(&s[.. n]).ok()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// `T` without module paths, like `Stream<ImageDict>`.
+fn short_type_name<T>() -> String {
+    let name = std::any::type_name::<T>();
+    let mut out = String::with_capacity(name.len());
+    for part in name.split_inclusive(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':')) {
+        out.push_str(part.rsplit("::").next().unwrap_or(part));
+    }
+    out
+}
+
+/// The kind of primitive, with the `/Type` and `/Subtype` of dictionaries and streams.
+fn describe_primitive(p: &Primitive) -> String {
+    let dict = match p {
+        Primitive::Dictionary(ref dict) => dict,
+        Primitive::Stream(ref stream) => &stream.info,
+        p => return p.get_debug_name().into()
+    };
+    let mut s = p.get_debug_name().to_string();
+    for key in ["Type", "Subtype"] {
+        if let Some(Primitive::Name(ref name)) = dict.get(key) {
+            s += &format!(" /{} /{}", key, name);
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_error() {
+        use crate::font::Font;
+
+        assert_eq!(short_type_name::<Stream<ImageDict>>(), "Stream<ImageDict>");
+        assert_eq!(short_type_name::<Option<Vec<u8>>>(), "Option<Vec<u8>>");
+
+        let mut file = FileOptions::uncached().storage();
+        let dict = crate::parser::parse(b"<< /Type /XObject /Subtype /Form >>", &NoResolve, ParseFlags::ANY).unwrap();
+        let r = file.create(dict).unwrap().get_ref().get_inner();
+        let err = file.resolver().get(Ref::<Font>::new(r)).map(|_| ()).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains(&format!("Can't decode object {} 0 R (Dictionary /Type /XObject /Subtype /Form) as Font", r.id)), "{}", msg);
+    }
+
+    #[test]
+    fn save_offsets() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../files/example.pdf");
+        // junk before the header, and no line break after `%%EOF`
+        let mut data = b"junk\n".to_vec();
+        data.extend_from_slice(std::fs::read(path).unwrap().trim_ascii_end());
+
+        let mut file = FileOptions::uncached().load(data).unwrap();
+        let r = file.create(Primitive::Integer(42)).unwrap().get_ref().get_inner();
+        let start = file.storage.start_offset;
+        assert_eq!(start, 5);
+
+        for _ in 0 .. 2 {
+            let written: Vec<ObjNr> = file.storage.changes.keys().copied().collect();
+            let data = file.storage.save(&mut file.trailer).unwrap().to_vec();
+            for id in written {
+                match file.storage.refs.get(id).unwrap() {
+                    XRef::Raw { pos, gen_nr } => {
+                        let header = format!("{} {} obj", id, gen_nr);
+                        assert!(data[start + pos ..].starts_with(header.as_bytes()), "wrong offset for {}", header);
+                    }
+                    xref => panic!("{:?} for object {}", xref, id)
+                }
+            }
+
+            assert!(data.ends_with(b"%%EOF\n"));
+            let tail = std::str::from_utf8(&data[data.len() - 30 ..]).unwrap();
+            let xref_pos: usize = tail.split("startxref\n").nth(1).unwrap().lines().next().unwrap().parse().unwrap();
+            let xref_header = data[start + xref_pos ..].split(|&b| b == b'\n').next().unwrap();
+            assert!(xref_header.ends_with(b" 0 obj"));
+            assert!(!data.windows(6).any(|w| w == b"%%EOF1"));
+
+            let reopened = FileOptions::uncached().load(data).unwrap();
+            assert!(matches!(reopened.resolver().resolve(r).unwrap(), Primitive::Integer(42)));
+            assert_eq!(reopened.num_pages(), 1);
+        }
+    }
+
+    const CATALOG: &str = "<< /Type /Catalog /Pages 2 0 R >>";
+    const PAGES: &str = "<< /Type /Pages /Kids [] /Count 0 >>";
+
+    /// Append a file section with `objects` (number, generation, object) and a classic xref
+    /// table to `data`, and return the offset of the table.
+    ///
+    /// `trailer` gets that offset and returns the entries of the trailer.
+    fn section(data: &mut Vec<u8>, objects: &[(ObjNr, GenNr, &str)], trailer: impl FnOnce(usize) -> String) -> usize {
+        let mut entries = String::from("0 1\n0000000000 65535 f \n");
+        for &(id, gen, obj) in objects {
+            entries += &format!("{} 1\n{:010} {:05} n \n", id, data.len(), gen);
+            data.extend_from_slice(format!("{} {} obj\n{}\nendobj\n", id, gen, obj).as_bytes());
+        }
+        let xref = data.len();
+        data.extend_from_slice(format!("xref\n{}trailer\n<< {} >>\nstartxref\n{}\n%%EOF\n", entries, trailer(xref), xref).as_bytes());
+        xref
+    }
+
+    #[test]
+    fn version() {
+        let file = |header: &str, catalog: &str| {
+            let mut data = format!("%PDF-{}\n", header).into_bytes();
+            let catalog = format!("<< /Type /Catalog /Pages 2 0 R {} >>", catalog);
+            section(&mut data, &[(1, 0, &catalog), (2, 0, PAGES)], |_| "/Size 3 /Root 1 0 R".into());
+            // offsets are relative to the header
+            let mut junk = b"junk before the header\n".to_vec();
+            junk.extend_from_slice(&data);
+            FileOptions::uncached().load(junk).unwrap()
+        };
+        assert_eq!(file("1.4", "").version(), (1, 4));
+        assert_eq!(file("1.4", "/Version /1.7").version(), (1, 7));
+        // /Version can't downgrade
+        assert_eq!(file("1.7", "/Version /1.3").version(), (1, 7));
+        assert_eq!(file("2.0", "").version(), (2, 0));
+        assert_eq!(file("x", "").version(), (1, 0));
+        assert_eq!(file("x", "/Version /1.5").version(), (1, 5));
+    }
+
+    #[test]
+    fn incremental_update() {
+        let mut data = b"%PDF-1.4\n".to_vec();
+        let xref = section(&mut data, &[(1, 0, CATALOG), (2, 0, PAGES), (3, 0, "(old)")], |_| "/Size 4 /Root 1 0 R".into());
+        // the update replaces object 3 with generation 1
+        section(&mut data, &[(3, 1, "(new)")], |_| format!("/Size 4 /Root 1 0 R /Prev {}", xref));
+
+        let file = FileOptions::uncached().load(data).unwrap();
+        let resolver = file.resolver();
+        let obj = resolver.resolve(PlainRef { id: 3, gen: 1 }).unwrap();
+        assert_eq!(obj.as_string().unwrap().as_bytes(), b"new");
+        assert!(matches!(file.storage.refs.get(3).unwrap(), XRef::Raw { gen_nr: 1, .. }));
+    }
+
+    #[test]
+    fn lazy_xref() {
+        let mut data = b"%PDF-1.4\n".to_vec();
+        let xref = section(&mut data, &[(1, 0, CATALOG), (2, 0, PAGES), (3, 0, "(old)")], |_| "/Size 4 /Root 1 0 R".into());
+        // the update has the catalog and the page tree, but not object 3
+        section(&mut data, &[(1, 0, CATALOG), (2, 0, PAGES)], |_| format!("/Size 4 /Root 1 0 R /Prev {}", xref));
+
+        let file = FileOptions::uncached().load(data).unwrap();
+        assert_eq!(file.num_pages(), 0);
+        assert!(file.storage.older_refs.get().is_none());
+        let obj = file.resolver().resolve(PlainRef { id: 3, gen: 0 }).unwrap();
+        assert_eq!(obj.as_string().unwrap().as_bytes(), b"old");
+        assert!(file.storage.older_refs.get().is_some());
+    }
+
+    #[test]
+    fn prev_loop() {
+        let mut data = b"%PDF-1.4\n".to_vec();
+        // the section is its own predecessor
+        section(&mut data, &[(1, 0, CATALOG), (2, 0, PAGES)], |xref| format!("/Size 3 /Root 1 0 R /Prev {}", xref));
+
+        let file = FileOptions::uncached().load(data).unwrap();
+        assert_eq!(file.num_pages(), 0);
+    }
+}
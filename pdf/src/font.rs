@@ -2,18 +2,22 @@ use crate as pdf;
 use crate::object::*;
 use crate::primitive::*;
 use crate::error::*;
-use crate::encoding::Encoding;
+use crate::encoding::{Encoding, BaseEncoding, STANDARD_ENCODING, SYMBOL_ENCODING, ZAPF_DINGBATS_ENCODING, glyph_name_to_unicode};
+use crate::type1::Type1Font;
+use crate::sfnt::Sfnt;
+use crate::cmap::CMap;
 use std::collections::HashMap;
 use std::fmt::Write;
-use crate::parser::{Lexer, parse_with_lexer, ParseFlags};
 use std::convert::TryInto;
 use std::sync::Arc;
 use istring::SmallString;
 use datasize::DataSize;
 use itertools::Itertools;
 
+mod standard;
+
 #[allow(non_upper_case_globals, dead_code)]
-mod flags {
+pub(crate) mod flags {
     pub const FixedPitch: u32    = 1 << 0;
     pub const Serif: u32         = 1 << 1;
     pub const Symbolic: u32      = 1 << 2;
@@ -61,7 +65,7 @@ pub enum FontData {
     Other(Dictionary),
 }
 
-#[derive(Debug, DataSize, DeepClone)]
+#[derive(Debug, Clone, DataSize, DeepClone)]
 pub enum CidToGidMap {
     Identity,
     Table(Vec<u16>)
@@ -179,20 +183,29 @@ impl ObjectWrite for Font {
 #[derive(Debug)]
 pub struct Widths {
     values: Vec<f32>,
-    default: f32,
+    missing_width: f32,
     first_char: usize
 }
+/// The largest CID.
+const MAX_CID: usize = 0xFFFF;
+
 impl Widths {
+    /// The width of `cid`, or the missing width if the font does not define it.
     pub fn get(&self, cid: usize) -> f32 {
         if cid < self.first_char {
-            self.default
+            self.missing_width
         } else {
-            self.values.get(cid - self.first_char).cloned().unwrap_or(self.default)
+            self.values.get(cid - self.first_char).cloned().unwrap_or(self.missing_width)
         }
     }
-    fn new(default: f32) -> Widths {
+    /// The width of codes outside of the defined range: `/MissingWidth` of the font
+    /// descriptor for simple fonts, `/DW` for CID fonts.
+    pub fn missing_width(&self) -> f32 {
+        self.missing_width
+    }
+    fn new(missing_width: f32) -> Widths {
         Widths {
-            default,
+            missing_width,
             values: Vec::new(),
             first_char: 0
         }
@@ -224,7 +237,7 @@ impl Widths {
         }
 
         if cid < self.first_char {
-            self.values.splice(0 .. 0, repeat(self.default).take(self.first_char - cid));
+            self.values.splice(0 .. 0, repeat(self.missing_width).take(self.first_char - cid));
             self.first_char = cid;
             self.values[0] = width;
             return;
@@ -232,7 +245,7 @@ impl Widths {
 
         if cid > self.values.len() + self.first_char {
             self.ensure_cid(cid);
-            self.values.extend(repeat(self.default).take(cid - self.first_char - self.values.len()));
+            self.values.extend(repeat(self.missing_width).take(cid - self.first_char - self.values.len()));
             self.values.push(width);
             return;
         }
@@ -240,7 +253,45 @@ impl Widths {
         self.values[cid - self.first_char] = width;
     }
 }
+/// A single entry of the vertical metrics.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct VerticalMetric {
+    /// vertical displacement `w1_y` (usually negative, i.e. downwards)
+    pub advance: f32,
+    /// position `(v_x, v_y)` of the vertical origin relative to the horizontal origin
+    pub origin: (f32, f32),
+}
+
+/// Vertical metrics of a CID font from `/DW2` and `/W2`, in glyph space units (1/1000 em).
+#[derive(Debug)]
+pub struct VerticalMetrics {
+    default_origin_y: f32,
+    default_advance: f32,
+    values: HashMap<usize, VerticalMetric>,
+}
+impl VerticalMetrics {
+    /// Look up the metrics for `cid`. `width` is the horizontal width of the glyph,
+    /// which determines `v_x` for glyphs that are not listed in `/W2`.
+    pub fn get(&self, cid: usize, width: f32) -> VerticalMetric {
+        match self.values.get(&cid) {
+            Some(&m) => m,
+            None => VerticalMetric {
+                advance: self.default_advance,
+                origin: (0.5 * width, self.default_origin_y),
+            }
+        }
+    }
+}
 impl Font {
+    /// The font descriptor, for Type0 fonts the one of the descendant font.
+    pub fn font_descriptor(&self) -> Option<&FontDescriptor> {
+        match self.data {
+            FontData::Type0(ref t) => t.descendant_fonts.first().and_then(|f| f.font_descriptor()),
+            FontData::CIDFontType0(ref c) | FontData::CIDFontType2(ref c) => Some(&c.font_descriptor),
+            FontData::Type1(ref t) | FontData::TrueType(ref t) => t.font_descriptor.as_ref(),
+            _ => None
+        }
+    }
     pub fn embedded_data(&self, resolve: &impl Resolve) -> Option<Result<Arc<[u8]>>> {
         match self.data {
             FontData::Type0(ref t) => t.descendant_fonts.get(0).and_then(|f| f.embedded_data(resolve)),
@@ -249,6 +300,18 @@ impl Font {
             _ => None
         }
     }
+    /// The encoding built into an embedded Type 1 font program (`/FontFile`), to fall back
+    /// to if the font dictionary has no `/Encoding`.
+    pub fn builtin_encoding(&self, resolve: &impl Resolve) -> Result<Option<[Option<String>; 256]>> {
+        let file = match self.data {
+            FontData::Type1(ref t) => t.font_descriptor.as_ref().and_then(|d| d.font_file.as_ref()),
+            _ => None
+        };
+        match file {
+            Some(file) => Ok(Type1Font::parse(&t!((**file).data(resolve)))?.builtin_encoding()),
+            None => Ok(None)
+        }
+    }
     pub fn is_cid(&self) -> bool {
         matches!(self.data, FontData::Type0(_) | FontData::CIDFontType0(_) | FontData::CIDFontType2(_))
     }
@@ -269,45 +332,94 @@ impl Font {
             _ => None
         }
     }
+    /// Whether the font program is part of the file.
+    ///
+    /// Type 3 fonts count as embedded, as their glyphs are defined in the font dictionary.
+    pub fn is_embedded(&self) -> bool {
+        match self.data {
+            FontData::Type0(ref t) => t.descendant_fonts.first().is_some_and(|f| f.is_embedded()),
+            FontData::CIDFontType0(ref c) | FontData::CIDFontType2(ref c) => c.font_descriptor.is_embedded(),
+            FontData::Type1(ref t) | FontData::TrueType(ref t) => t.font_descriptor.as_ref().is_some_and(|d| d.is_embedded()),
+            FontData::Other(_) => matches!(self.subtype, FontType::Type3),
+        }
+    }
+    /// The `/BaseFont` name without a subset tag (`ABCDEF+Helvetica` gives `Helvetica`),
+    /// to look up a substitute font by. Empty if the font has no name.
+    pub fn substitute_name(&self) -> &str {
+        self.name.as_ref().map_or("", |name| standard::strip_subset_tag(name.as_str()))
+    }
+    /// The standard 14 font this font refers to by `/BaseFont`, if any.
+    ///
+    /// Common aliases like `Arial` or `TimesNewRoman,Bold` map to their standard counterpart.
+    pub fn standard_font(&self) -> Option<&'static str> {
+        self.name.as_ref().and_then(|name| standard::standard_font_name(name.as_str()))
+    }
+    /// The widths of a simple font without `/Widths`, taken from the AFM metrics of the standard font.
+    fn standard_widths(&self, font: &str, missing_width: f32, resolve: &impl Resolve) -> Result<Widths> {
+        let mapping = self.glyph_mapping(resolve)?;
+        let values = (0 .. 256).map(|code| {
+            mapping.name(code).and_then(|name| standard::standard_width(font, name)).unwrap_or(missing_width)
+        }).collect();
+        Ok(Widths { missing_width, first_char: 0, values })
+    }
+    /// The glyph widths, in thousandths of a text space unit.
+    ///
+    /// Simple fonts without `/Widths` that refer to one of the standard 14 fonts get the widths
+    /// from the bundled font metrics (see `standard_font`).
     pub fn widths(&self, resolve: &impl Resolve) -> Result<Option<Widths>> {
         match self.data {
-            FontData::Type0(ref t0) => t0.descendant_fonts[0].widths(resolve),
+            FontData::Type0(ref t0) => t0.descendant_fonts.first()
+                .ok_or_else(|| other!("Type0 font without descendant font"))?
+                .widths(resolve),
             FontData::Type1(ref info) | FontData::TrueType(ref info) => {
+                let standard = self.standard_font().filter(|&font| standard::has_widths(font));
+                if let (None, Some(font)) = (&info.widths, standard) {
+                    let missing_width = info.font_descriptor.as_ref().map_or(0.0, |d| d.missing_width);
+                    return self.standard_widths(font, missing_width, resolve).map(Some);
+                }
                 match *info {
-                    TFont { first_char: Some(first), ref widths, .. } => Ok(Some(Widths {
-                        default: 0.0,
-                        first_char: first as usize,
-                        values: widths.as_ref().cloned().unwrap_or_default()
-                    })),
+                    TFont { first_char: Some(first), last_char, ref widths, ref font_descriptor, .. } => {
+                        let mut values = widths.as_ref().cloned().unwrap_or_default();
+                        if let Some(last) = last_char {
+                            values.truncate((last - first + 1).max(0) as usize);
+                        }
+                        Ok(Some(Widths {
+                            missing_width: font_descriptor.as_ref().map_or(0.0, |d| d.missing_width),
+                            first_char: first as usize,
+                            values
+                        }))
+                    }
                     _ => Ok(None)
                 }
             },
             FontData::CIDFontType0(ref cid) | FontData::CIDFontType2(ref cid) => {
+                // `/W` mixes `c [w1 w2 ...]` and `c_first c_last w`
                 let mut widths = Widths::new(cid.default_width);
                 let mut iter = cid.widths.iter();
                 while let Some(p) = iter.next() {
                     let c1 = p.as_usize()?;
-                    match iter.next() {
-                        Some(Primitive::Array(array)) => {
-                            widths.ensure_cid(c1 + array.len() - 1);
-                            for (i, w) in array.iter().enumerate() {
+                    let array = match iter.next() {
+                        Some(&Primitive::Reference(r)) => resolve.resolve(r)?,
+                        Some(p) => p.clone(),
+                        None => bail!("W array ends after {}", c1)
+                    };
+                    // CIDs are at most 0xFFFF, the widths of larger ones are dropped
+                    match array {
+                        Primitive::Array(array) => {
+                            if c1.saturating_add(array.len()) > MAX_CID + 1 {
+                                warn!("W entry {} with {} widths exceeds the largest CID", c1, array.len());
+                            }
+                            for (i, w) in array.iter().enumerate().take((MAX_CID + 1).saturating_sub(c1)) {
                                 widths.set(c1 + i, w.as_number()?);
                             }
                         },
-                        Some(&Primitive::Reference(r)) => {
-                            match resolve.resolve(r)? {
-                                Primitive::Array(array) => {
-                                    widths.ensure_cid(c1 + array.len() - 1);
-                                    for (i, w) in array.iter().enumerate() {
-                                        widths.set(c1 + i, w.as_number()?);
-                                    }
-                                }
-                                p => return Err(PdfError::Other { msg: format!("unexpected primitive in W array: {:?}", p) })
-                            }
-                        }
-                        Some(&Primitive::Integer(c2)) => {
+                        Primitive::Integer(_) => {
+                            let c2 = array.as_usize()?;
                             let w = try_opt!(iter.next()).as_number()?;
-                            for c in c1 ..= (c2 as usize) {
+                            if c2 > MAX_CID {
+                                warn!("W range {} {} exceeds the largest CID", c1, c2);
+                            }
+                            for c in c1 ..= c2.min(MAX_CID) {
                                 widths.set(c, w);
                             }
                         },
@@ -319,9 +431,186 @@ impl Font {
             _ => Ok(None)
         }
     }
+    /// Whether the font uses vertical writing mode (WMode 1).
+    ///
+    /// This is derived from the name of the predefined CMap in `/Encoding`.
+    pub fn is_vertical(&self) -> bool {
+        match self.encoding.as_ref().map(|e| &e.base) {
+            Some(BaseEncoding::IdentityV) => true,
+            Some(BaseEncoding::Other(ref name)) => name.ends_with("-V"),
+            _ => false
+        }
+    }
+    /// The CMap that maps the character codes of a Type0 font to CIDs, from the name
+    /// of a predefined CMap in `/Encoding`.
+    ///
+    /// Returns `None` for simple fonts and an error for CMaps that are not bundled,
+    /// see [`CMap::predefined`]. Embedded CMap streams are not supported yet.
+    pub fn cid_cmap(&self) -> Result<Option<CMap>> {
+        if !matches!(self.data, FontData::Type0(_)) {
+            return Ok(None);
+        }
+        match self.encoding.as_ref().map(|e| &e.base) {
+            Some(BaseEncoding::IdentityH) | None => CMap::predefined("Identity-H").map(Some),
+            Some(BaseEncoding::IdentityV) => CMap::predefined("Identity-V").map(Some),
+            Some(BaseEncoding::Other(ref name)) => CMap::predefined(name).map(Some),
+            Some(base) => bail!("{:?} is not a CMap", base),
+        }
+    }
+    /// Vertical metrics (`/DW2` and `/W2`) of a CID font.
+    pub fn vertical_metrics(&self, resolve: &impl Resolve) -> Result<Option<VerticalMetrics>> {
+        match self.data {
+            FontData::Type0(ref t0) => t0.descendant_fonts.first()
+                .ok_or_else(|| other!("Type0 font without descendant font"))?
+                .vertical_metrics(resolve),
+            FontData::CIDFontType0(ref cid) | FontData::CIDFontType2(ref cid) => {
+                let (v_y, w1_y) = match *cid.default_vertical_metrics {
+                    [v_y, w1_y] => (v_y, w1_y),
+                    ref a => bail!("invalid DW2 array {:?}", a)
+                };
+                let mut metrics = VerticalMetrics {
+                    default_origin_y: v_y,
+                    default_advance: w1_y,
+                    values: HashMap::new(),
+                };
+                let mut iter = cid.vertical_widths.iter();
+                while let Some(p) = iter.next() {
+                    let c1 = p.as_usize()?;
+                    match iter.next() {
+                        Some(p @ Primitive::Array(_)) | Some(p @ Primitive::Reference(_)) => {
+                            let array = p.clone().resolve(resolve)?.into_array()?;
+                            if c1.saturating_add(array.len() / 3) > MAX_CID + 1 {
+                                warn!("W2 entry {} with {} metrics exceeds the largest CID", c1, array.len() / 3);
+                            }
+                            for (i, chunk) in array.chunks_exact(3).enumerate().take((MAX_CID + 1).saturating_sub(c1)) {
+                                metrics.values.insert(c1 + i, VerticalMetric {
+                                    advance: chunk[0].as_number()?,
+                                    origin: (chunk[1].as_number()?, chunk[2].as_number()?),
+                                });
+                            }
+                        }
+                        Some(&Primitive::Integer(c2)) => {
+                            let advance = try_opt!(iter.next()).as_number()?;
+                            let v_x = try_opt!(iter.next()).as_number()?;
+                            let v_y = try_opt!(iter.next()).as_number()?;
+                            if c2 < 0 || (c2 as usize) < c1 {
+                                warn!("invalid W2 range {} {}", c1, c2);
+                                continue;
+                            }
+                            if c2 as usize > MAX_CID {
+                                warn!("W2 range {} {} exceeds the largest CID", c1, c2);
+                            }
+                            for c in c1 ..= (c2 as usize).min(MAX_CID) {
+                                metrics.values.insert(c, VerticalMetric { advance, origin: (v_x, v_y) });
+                            }
+                        }
+                        p => return Err(PdfError::Other { msg: format!("unexpected primitive in W2 array: {:?}", p) })
+                    }
+                }
+                Ok(Some(metrics))
+            }
+            _ => Ok(None)
+        }
+    }
     pub fn to_unicode(&self, resolve: &impl Resolve) -> Option<Result<ToUnicodeMap>> {
         self.to_unicode.as_ref().map(|s| (**s).data(resolve).and_then(|d| parse_cmap(&d)))
     }
+    /// The `/ToUnicode` CMap, keeping the code lengths of its codespace ranges.
+    pub fn to_unicode_cmap(&self, resolve: &impl Resolve) -> Option<Result<CMap>> {
+        self.to_unicode.as_ref().map(|s| (**s).data(resolve).and_then(|d| CMap::parse(&d)))
+    }
+    /// How the character codes of this font select glyphs of the embedded font program.
+    ///
+    /// For simple fonts, the glyph names come from `/Encoding`, falling back to the encoding
+    /// built into the font program and then to StandardEncoding, or the own encoding of Symbol and
+    /// ZapfDingbats if the font is one of those. `/Differences` without a
+    /// `/BaseEncoding` apply to the built-in encoding only if the font is symbolic. Glyph ids are looked up by
+    /// name in the Unicode cmap of embedded TrueType and OpenType programs.
+    pub fn glyph_mapping(&self, resolve: &impl Resolve) -> Result<GlyphMapping> {
+        match self.data {
+            FontData::Type0(ref t) => {
+                let font = t.descendant_fonts.first().ok_or_else(|| other!("Type0 font without descendant font"))?;
+                return font.glyph_mapping(resolve);
+            }
+            FontData::CIDFontType0(ref c) | FontData::CIDFontType2(ref c) => {
+                return Ok(GlyphMapping::Cid(c.cid_to_gid_map.clone()));
+            }
+            _ => {}
+        }
+
+        let base = self.encoding.as_ref().map(|e| &e.base);
+        // Symbol and ZapfDingbats have their own built-in encoding
+        let (standard, symbolic_font) = match self.standard_font() {
+            Some("Symbol") => (&SYMBOL_ENCODING, true),
+            Some("ZapfDingbats") => (&ZAPF_DINGBATS_ENCODING, true),
+            _ => (&STANDARD_ENCODING, false)
+        };
+        let standard = || standard.iter().map(|n| n.map(String::from)).collect();
+        let symbolic = symbolic_font || self.font_descriptor().is_some_and(|d| d.is_symbolic());
+        let mut names: Vec<Option<String>> = match base.and_then(|b| b.glyph_names()) {
+            Some(table) => table.iter().map(|n| n.map(String::from)).collect(),
+            // only `/Differences`: they apply to StandardEncoding, unless the font is symbolic
+            None if base == Some(&BaseEncoding::None) && !symbolic => standard(),
+            None => match self.builtin_encoding(resolve) {
+                Ok(Some(builtin)) => builtin.into(),
+                Ok(None) => standard(),
+                Err(e) => {
+                    warn!("can't read the built-in encoding: {:?}", e);
+                    standard()
+                }
+            }
+        };
+        if let Some(ref encoding) = self.encoding {
+            for (&code, name) in encoding.differences.iter() {
+                if let Some(slot) = names.get_mut(code as usize) {
+                    *slot = Some(name.as_str().into());
+                }
+            }
+        }
+
+        let data = self.embedded_data(resolve).transpose()?;
+        let sfnt = data.as_deref().and_then(|data| Sfnt::parse(data).ok());
+        let glyphs = names.into_iter().map(|name| {
+            let gid = match (&sfnt, &name) {
+                (Some(sfnt), Some(name)) => glyph_name_to_unicode(name).and_then(|c| sfnt.glyph(c)),
+                _ => None
+            };
+            SimpleGlyph { name, gid }
+        }).collect();
+        Ok(GlyphMapping::Simple(glyphs))
+    }
+}
+
+/// The result of `Font::glyph_mapping`.
+#[derive(Debug, Clone)]
+pub enum GlyphMapping {
+    /// A CID-keyed font. The codes are CIDs, which map to glyphs through the `/CIDToGIDMap`
+    /// (`None` for CFF based fonts, where the font program maps CIDs itself).
+    Cid(Option<CidToGidMap>),
+    /// A simple font, with one entry for each byte code.
+    Simple(Vec<SimpleGlyph>),
+}
+#[derive(Debug, Clone, Default)]
+pub struct SimpleGlyph {
+    pub name: Option<String>,
+    pub gid: Option<u16>,
+}
+impl GlyphMapping {
+    /// The glyph id selected by `code`, if known.
+    pub fn gid(&self, code: u32) -> Option<u16> {
+        match *self {
+            GlyphMapping::Cid(None) | GlyphMapping::Cid(Some(CidToGidMap::Identity)) => code.try_into().ok(),
+            GlyphMapping::Cid(Some(CidToGidMap::Table(ref table))) => table.get(code as usize).copied(),
+            GlyphMapping::Simple(ref glyphs) => glyphs.get(code as usize)?.gid,
+        }
+    }
+    /// The glyph name of `code` in a simple font.
+    pub fn name(&self, code: u32) -> Option<&str> {
+        match *self {
+            GlyphMapping::Simple(ref glyphs) => glyphs.get(code as usize)?.name.as_deref(),
+            GlyphMapping::Cid(_) => None,
+        }
+    }
 }
 #[derive(Object, ObjectWrite, Debug, DataSize, DeepClone)]
 pub struct TFont {
@@ -339,7 +628,7 @@ pub struct TFont {
     #[pdf(key="Widths")]
     pub widths: Option<Vec<f32>>,
 
-    #[pdf(key="FontDescriptor")]
+    #[pdf(key="FontDescriptor", indirect)]
     pub font_descriptor: Option<FontDescriptor>
 }
 
@@ -357,7 +646,7 @@ pub struct CIDFont {
     #[pdf(key="CIDSystemInfo")]
     pub system_info: Dictionary,
 
-    #[pdf(key="FontDescriptor")]
+    #[pdf(key="FontDescriptor", indirect)]
     pub font_descriptor: FontDescriptor,
 
     #[pdf(key="DW", default="1000.")]
@@ -366,6 +655,13 @@ pub struct CIDFont {
     #[pdf(key="W")]
     pub widths: Vec<Primitive>,
 
+    /// `[v_y w1_y]`: vertical origin and advance used for glyphs not listed in `/W2`.
+    #[pdf(key="DW2", default="vec![880., -1000.]")]
+    pub default_vertical_metrics: Vec<f32>,
+
+    #[pdf(key="W2")]
+    pub vertical_widths: Vec<Primitive>,
+
     #[pdf(key="CIDToGIDMap")]
     pub cid_to_gid_map: Option<CidToGidMap>,
 
@@ -441,6 +737,14 @@ pub struct FontDescriptor {
     pub char_set: Option<PdfString>
 }
 impl FontDescriptor {
+    /// Whether one of `/FontFile`, `/FontFile2` or `/FontFile3` is present.
+    pub fn is_embedded(&self) -> bool {
+        self.font_file.is_some() || self.font_file2.is_some() || self.font_file3.is_some()
+    }
+    /// Whether the Symbolic flag is set: the font uses glyphs outside the standard Latin set.
+    pub fn is_symbolic(&self) -> bool {
+        self.flags & flags::Symbolic != 0
+    }
     pub fn data(&self, resolve: &impl Resolve) -> Option<Result<Arc<[u8]>>> {
         if let Some(ref s) = self.font_file {
             Some((**s).data(resolve))
@@ -531,92 +835,12 @@ pub fn utf16be_to_string(data: &[u8]) -> pdf::error::Result<SmallString> {
         .map(|r| r.map_err(|_| PdfError::Utf16Decode))
         .collect()
 }
-fn parse_cid(s: &PdfString) -> Result<u16> {
-    let b = s.as_bytes();
-    match b.len() {
-        2 => Ok(u16::from_be_bytes(b.try_into().unwrap())),
-        1 => Ok(b[0] as u16),
-        _ => Err(PdfError::CidDecode),
-    }
-}
 fn parse_cmap(data: &[u8]) -> Result<ToUnicodeMap> {
-    let mut lexer = Lexer::new(data);
-    let mut map = ToUnicodeMap::new();
-    while let Ok(substr) = lexer.next() {
-        match substr.as_slice() {
-            b"beginbfchar" => loop {
-                let a = parse_with_lexer(&mut lexer, &NoResolve, ParseFlags::STRING);
-                if a.is_err() {
-                    break;
-                }
-                let b = parse_with_lexer(&mut lexer, &NoResolve, ParseFlags::STRING);
-                match (a, b) {
-                    (Ok(Primitive::String(cid_data)), Ok(Primitive::String(unicode_data))) => {
-                        let cid = parse_cid(&cid_data)?;
-                        let bytes = unicode_data.as_bytes();
-                        match utf16be_to_string(bytes) {
-                            Ok(unicode) => map.insert(cid, unicode),
-                            Err(_) => warn!("invalid unicode for cid {cid} {bytes:?}"),
-                        }
-                    }
-                    _ => break,
-                }
-            },
-            b"beginbfrange" => loop {
-                let a = parse_with_lexer(&mut lexer, &NoResolve, ParseFlags::STRING);
-                if a.is_err() {
-                    break;
-                }
-                let b = parse_with_lexer(&mut lexer, &NoResolve, ParseFlags::STRING);
-                let c = parse_with_lexer(&mut lexer, &NoResolve, ParseFlags::STRING | ParseFlags::ARRAY);
-                match (a, b, c) {
-                    (
-                        Ok(Primitive::String(cid_start_data)),
-                        Ok(Primitive::String(cid_end_data)),
-                        Ok(Primitive::String(unicode_data)),
-                    ) if unicode_data.data.len() > 0 => {
-                        let cid_start = parse_cid(&cid_start_data)?;
-                        let cid_end = parse_cid(&cid_end_data)?;
-                        let mut unicode_data = unicode_data.into_bytes();
-
-                        for cid in cid_start..=cid_end {
-                            match utf16be_to_string(&unicode_data) {
-                                Ok(unicode) => map.insert(cid, unicode),
-                                Err(_) => warn!("invalid unicode for cid {cid} {unicode_data:?}"),
-                            }
-                            let last = unicode_data.last_mut().unwrap();
-                            if *last < 255 {
-                                *last += 1;
-                            } else {
-                                break;
-                            }
-                        }
-                    }
-                    (
-                        Ok(Primitive::String(cid_start_data)),
-                        Ok(Primitive::String(cid_end_data)),
-                        Ok(Primitive::Array(unicode_data_arr)),
-                    ) => {
-                        let cid_start = parse_cid(&cid_start_data)?;
-                        let cid_end = parse_cid(&cid_end_data)?;
-
-                        for (cid, unicode_data) in (cid_start..=cid_end).zip(unicode_data_arr) {
-                            let bytes = unicode_data.as_string()?.as_bytes();
-                            match utf16be_to_string(bytes) {
-                                Ok(unicode) => map.insert(cid, unicode),
-                                Err(_) => warn!("invalid unicode for cid {cid} {bytes:?}"),
-                            }
-                        }
-                    }
-                    _ => break,
-                }
-            },
-            b"endcmap" => break,
-            _ => {}
-        }
-    }
-
-    Ok(map)
+    let cmap = CMap::parse(data)?;
+    let map = cmap.iter()
+        .filter(|&(len, _, _)| len <= 2)
+        .map(|(_, code, unicode)| (code as u16, unicode.into()));
+    Ok(ToUnicodeMap::create(map))
 }
 
 fn write_cid(w: &mut String, cid: u16) {
@@ -689,7 +913,196 @@ pub fn write_cmap(map: &ToUnicodeMap) -> String {
 #[cfg(test)]
 mod tests {
 
-    use crate::font::{utf16be_to_string, utf16be_to_char, utf16be_to_string_lossy};
+    use crate::font::{utf16be_to_string, utf16be_to_char, utf16be_to_string_lossy, Font, VerticalMetric};
+    use crate::object::{Object, NoResolve};
+    use crate::parser::{parse, ParseFlags};
+
+    #[test]
+    fn vertical_metrics() {
+        let data = b"<< /Type /Font /Subtype /CIDFontType0 /BaseFont /Foo
+            /CIDSystemInfo << >>
+            /FontDescriptor << /FontName /Foo /Flags 4 /FontBBox [0 0 1000 1000] /ItalicAngle 0 >>
+            /W2 [ 10 [ -900 500 800 ] 20 25 -500 250 880 ] >>";
+        let p = parse(data, &NoResolve, ParseFlags::ANY).unwrap();
+        let font = Font::from_primitive(p, &NoResolve).unwrap();
+        let metrics = font.vertical_metrics(&NoResolve).unwrap().unwrap();
+        assert_eq!(metrics.get(10, 1000.), VerticalMetric { advance: -900., origin: (500., 800.) });
+        assert_eq!(metrics.get(22, 1000.), VerticalMetric { advance: -500., origin: (250., 880.) });
+        assert_eq!(metrics.get(11, 600.), VerticalMetric { advance: -1000., origin: (300., 880.) });
+        assert!(!font.is_vertical());
+
+        // inverted ranges are skipped, CIDs beyond 0xFFFF dropped
+        let data = b"<< /Type /Font /Subtype /CIDFontType0 /BaseFont /Foo
+            /CIDSystemInfo << >>
+            /FontDescriptor << /FontName /Foo /Flags 4 /FontBBox [0 0 1000 1000] /ItalicAngle 0 >>
+            /W2 [ 5 -1 -500 250 880 9 3 -500 250 880 65535 2147483647 -700 1 2 65535 [ -800 1 2 -900 1 2 ] ] >>";
+        let font = Font::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        let metrics = font.vertical_metrics(&NoResolve).unwrap().unwrap();
+        assert_eq!(metrics.values.len(), 1);
+        assert_eq!(metrics.get(65535, 1000.).advance, -800.);
+    }
+
+    #[test]
+    fn cid_widths() {
+        let data = b"<< /Type /Font /Subtype /CIDFontType2 /BaseFont /Foo /CIDSystemInfo << >>
+            /FontDescriptor << /FontName /Foo /Flags 4 /FontBBox [0 0 1000 1000] /ItalicAngle 0 >>
+            /W [1 [500 600] 10 20 700 30 [] 40 35 100] >>";
+        let font = Font::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        let widths = font.widths(&NoResolve).unwrap().unwrap();
+        let get: Vec<f32> = [0, 1, 2, 3, 9, 10, 15, 20, 21, 30, 40].iter().map(|&cid| widths.get(cid)).collect();
+        assert_eq!(get, [1000., 500., 600., 1000., 1000., 700., 700., 700., 1000., 1000., 1000.]);
+        assert_eq!(widths.missing_width(), 1000.);
+
+        // CIDs beyond 0xFFFF are dropped instead of allocating widths for them
+        let data = b"<< /Type /Font /Subtype /CIDFontType2 /BaseFont /Foo /CIDSystemInfo << >>
+            /FontDescriptor << /FontName /Foo /Flags 4 /FontBBox [0 0 1000 1000] /ItalicAngle 0 >>
+            /W [65534 [500 600 700] 65535 2147483647 800 2000000000 [1 2] 2000000000 2000000001 3] >>";
+        let font = Font::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        let widths = font.widths(&NoResolve).unwrap().unwrap();
+        assert_eq!((widths.get(65534), widths.get(65535), widths.get(65536)), (500., 800., 1000.));
+        assert_eq!(widths.get(2000000000), 1000.);
+    }
+
+    #[test]
+    fn no_descendant_font() {
+        let data = b"<< /Type /Font /Subtype /Type0 /BaseFont /Foo /Encoding /Identity-H /DescendantFonts [] >>";
+        let font = Font::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        assert!(font.widths(&NoResolve).is_err());
+        assert!(font.vertical_metrics(&NoResolve).is_err());
+    }
+
+    #[test]
+    fn missing_width() {
+        let data = b"<< /Type /Font /Subtype /Type1 /BaseFont /Foo
+            /FirstChar 32 /LastChar 33 /Widths [ 250 300 400 ]
+            /FontDescriptor << /FontName /Foo /Flags 4 /FontBBox [0 0 1000 1000] /ItalicAngle 0 /MissingWidth 500 >> >>";
+        let font = Font::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        let widths = font.widths(&NoResolve).unwrap().unwrap();
+        assert_eq!(widths.missing_width(), 500.);
+        assert_eq!(widths.get(31), 500.);
+        assert_eq!(widths.get(33), 300.);
+        // beyond /LastChar, even though /Widths is longer
+        assert_eq!(widths.get(34), 500.);
+    }
+
+    #[test]
+    fn standard_widths() {
+        let data = b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>";
+        let font = Font::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        assert_eq!(font.standard_font(), Some("Helvetica"));
+        let widths = font.widths(&NoResolve).unwrap().unwrap();
+        assert_eq!(widths.get(b' ' as usize), 278.);
+        assert_eq!(widths.get(b'W' as usize), 944.);
+        assert_eq!(widths.get(b'\'' as usize), 191.);
+        assert_eq!(widths.get(0xE9), 556.);
+        assert_eq!(widths.get(0xED), 278.);
+        assert_eq!(widths.get(0x80), 0.);
+        assert_eq!(widths.get(0x97), 1000.);
+
+        let data = b"<< /Type /Font /Subtype /TrueType /BaseFont /ABCDEF+TimesNewRoman,Bold
+            /Encoding << /Differences [65 /m] >> >>";
+        let font = Font::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        assert_eq!(font.standard_font(), Some("Times-Bold"));
+        let widths = font.widths(&NoResolve).unwrap().unwrap();
+        assert_eq!(widths.get(b'A' as usize), 833.);
+        assert_eq!(widths.get(b'\'' as usize), 333.);
+
+        let data = b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier-Oblique >>";
+        let font = Font::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        assert_eq!(font.widths(&NoResolve).unwrap().unwrap().get(b'i' as usize), 600.);
+
+        // explicit /Widths win, and fonts without bundled widths are left alone
+        let data = b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /FirstChar 32 /LastChar 32 /Widths [ 100 ] >>";
+        let font = Font::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        assert_eq!(font.widths(&NoResolve).unwrap().unwrap().get(32), 100.);
+        let data = b"<< /Type /Font /Subtype /Type1 /BaseFont /Foo >>";
+        let font = Font::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        assert!(font.widths(&NoResolve).unwrap().is_none());
+
+        // Symbol and ZapfDingbats are looked up through their own encoding
+        let data = b"<< /Type /Font /Subtype /Type1 /BaseFont /Symbol >>";
+        let font = Font::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        assert_eq!(font.standard_font(), Some("Symbol"));
+        let widths = font.widths(&NoResolve).unwrap().unwrap();
+        assert_eq!(widths.get(b'a' as usize), 631.);
+        assert_eq!(widths.get(0xE5), 713.);
+        let data = b"<< /Type /Font /Subtype /Type1 /BaseFont /ZapfDingbats >>";
+        let font = Font::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        let widths = font.widths(&NoResolve).unwrap().unwrap();
+        assert_eq!(widths.get(b'!' as usize), 974.);
+        assert_eq!(widths.get(0xFE), 918.);
+    }
+
+    #[test]
+    fn substitute_name() {
+        let font = |data: &[u8]| Font::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        let helvetica = font(b"<< /Type /Font /Subtype /Type1 /BaseFont /ABCDEF+Helvetica >>");
+        assert_eq!(helvetica.substitute_name(), "Helvetica");
+        assert!(!helvetica.is_embedded());
+        // not a subset tag
+        assert_eq!(font(b"<< /Type /Font /Subtype /Type1 /BaseFont /ABC+Foo >>").substitute_name(), "ABC+Foo");
+        assert_eq!(font(b"<< /Type /Font /Subtype /Type1 /BaseFont /AbCDEF+Foo >>").substitute_name(), "AbCDEF+Foo");
+
+    }
+
+    #[test]
+    fn cid_cmap() {
+        let data = b"<< /Type /Font /Subtype /Type0 /BaseFont /Foo /Encoding /Identity-V /DescendantFonts [] >>";
+        let font = Font::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        let cmap = font.cid_cmap().unwrap().unwrap();
+        assert!(cmap.is_vertical());
+        assert_eq!(cmap.cid(&[1, 2]), Some(0x102));
+
+        let data = b"<< /Type /Font /Subtype /Type0 /BaseFont /Foo /Encoding /GBK-EUC-H /DescendantFonts [] >>";
+        let font = Font::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        assert!(font.cid_cmap().is_err());
+    }
+
+    #[test]
+    fn glyph_mapping() {
+        use crate::font::{GlyphMapping, CidToGidMap, FontData};
+        use crate::object::{Stream, RcRef, PlainRef};
+
+        let data = b"<< /Type /Font /Subtype /Type1 /BaseFont /Foo
+            /Encoding << /BaseEncoding /WinAnsiEncoding /Differences [65 /Bee /uni0043] >> >>";
+        let font = Font::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        let mapping = font.glyph_mapping(&NoResolve).unwrap();
+        assert_eq!(mapping.name(0x41), Some("Bee"));
+        assert_eq!(mapping.name(0x42), Some("uni0043"));
+        assert_eq!(mapping.name(0x44), Some("D"));
+        assert_eq!(mapping.name(0x80), Some("Euro"));
+        assert_eq!(mapping.gid(0x41), None);
+
+        // no /Encoding and nothing embedded: StandardEncoding
+        let data = b"<< /Type /Font /Subtype /Type1 /BaseFont /Foo >>";
+        let font = Font::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        assert_eq!(font.glyph_mapping(&NoResolve).unwrap().name(0x27), Some("quoteright"));
+
+        // only /Differences: on top of the built-in encoding if the font is symbolic
+        let program = &b"%!FontType1-1.0: Test\n/Encoding 256 array dup 65 /Alpha put dup 66 /Beta put readonly def\ncurrentfile eexec\n"[..];
+        // a program that can't be read falls back to StandardEncoding
+        for (program, flags, name) in [(program, 4, Some("Alpha")), (program, 32, Some("A")), (&b"garbage"[..], 4, Some("A"))] {
+            let data = format!("<< /Type /Font /Subtype /Type1 /BaseFont /Foo /Encoding << /Differences [66 /Bee] >>
+                /FontDescriptor << /FontName /Foo /Flags {} /FontBBox [0 0 1000 1000] /ItalicAngle 0 >> >>", flags);
+            let mut font = Font::from_primitive(parse(data.as_bytes(), &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+            if let FontData::Type1(ref mut t) = font.data {
+                let stream = Stream::new((), program.to_vec());
+                t.font_descriptor.as_mut().unwrap().font_file = Some(RcRef::new(PlainRef { id: 1, gen: 0 }, stream.into()));
+            }
+            let mapping = font.glyph_mapping(&NoResolve).unwrap();
+            assert_eq!(mapping.name(0x41), name);
+            assert_eq!(mapping.name(0x42), Some("Bee"));
+        }
+
+        let data = b"<< /Type /Font /Subtype /CIDFontType2 /BaseFont /Foo /CIDSystemInfo << >>
+            /FontDescriptor << /FontName /Foo /Flags 4 /FontBBox [0 0 1000 1000] /ItalicAngle 0 >>
+            /CIDToGIDMap /Identity >>";
+        let font = Font::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        assert!(font.cid_cmap().unwrap().is_none());
+        let mapping = font.glyph_mapping(&NoResolve).unwrap();
+        assert!(matches!(mapping, GlyphMapping::Cid(Some(CidToGidMap::Identity))));
+        assert_eq!(mapping.gid(300), Some(300));
+    }
     #[test]
     fn utf16be_to_string_quick() {
         let v = vec![0x20, 0x09];
@@ -0,0 +1,304 @@
+// Advance widths of the standard 14 fonts, from the Adobe Font Metrics (AFM) files.
+//
+// The Latin fonts cover the glyphs of StandardEncoding, WinAnsiEncoding and MacRomanEncoding.
+// Letters with accents are left out, they are as wide as the letter without them (see
+// `latin_width`). Courier is not listed, every glyph of it is 600 wide.
+
+/// The glyph names the width tables of the Latin fonts are indexed by.
+static GLYPHS: [&str; 170] = [
+    "space", "exclam", "quotedbl", "numbersign", "dollar", "percent", "ampersand", "quoteright",
+    "parenleft", "parenright", "asterisk", "plus", "comma", "hyphen", "period", "slash",
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+    "colon", "semicolon", "less", "equal", "greater", "question", "at",
+    "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M",
+    "N", "O", "P", "Q", "R", "S", "T", "U", "V", "W", "X", "Y", "Z",
+    "bracketleft", "backslash", "bracketright", "asciicircum", "underscore", "quoteleft",
+    "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m",
+    "n", "o", "p", "q", "r", "s", "t", "u", "v", "w", "x", "y", "z",
+    "braceleft", "bar", "braceright", "asciitilde", "quotesingle", "grave",
+    "exclamdown", "cent", "sterling", "fraction", "yen", "florin", "section", "currency",
+    "quotedblleft", "guillemotleft",
+    "guilsinglleft", "guilsinglright", "fi", "fl", "endash", "dagger", "daggerdbl",
+    "periodcentered", "paragraph", "bullet",
+    "quotesinglbase", "quotedblbase", "quotedblright", "guillemotright", "ellipsis", "perthousand",
+    "questiondown",
+    "acute", "circumflex", "tilde", "macron", "breve", "dotaccent", "dieresis", "ring", "cedilla",
+    "hungarumlaut", "ogonek", "caron",
+    "emdash", "AE", "ordfeminine", "Lslash", "Oslash", "OE", "ordmasculine", "ae", "dotlessi",
+    "lslash", "oslash", "oe", "germandbls",
+    "brokenbar", "copyright", "logicalnot", "registered", "degree", "plusminus", "twosuperior",
+    "threesuperior", "mu", "onesuperior",
+    "onequarter", "onehalf", "threequarters", "multiply", "divide", "Eth", "eth", "Thorn", "thorn",
+    "trademark", "minus",
+];
+
+static HELVETICA: [u16; 170] = [
+    278, 278, 355, 556, 556, 889, 667, 222,
+    333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556,
+    278, 278, 584, 584, 584, 556, 1015,
+    667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833,
+    722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611,
+    278, 278, 278, 469, 556, 222,
+    556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833,
+    556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500,
+    334, 260, 334, 584, 191, 333,
+    333, 556, 556, 167, 556, 556, 556, 556, 333, 556,
+    333, 333, 500, 500, 556, 556, 556, 278, 537, 350,
+    222, 333, 333, 556, 1000, 1000, 611,
+    333, 333, 333, 333, 333, 333, 333, 333, 333, 333, 333, 333,
+    1000, 1000, 370, 556, 778, 1000, 365, 889, 278, 222, 611, 944, 611,
+    260, 737, 584, 737, 400, 584, 333, 333, 556, 333,
+    834, 834, 834, 584, 584, 722, 556, 667, 556, 1000, 584,
+];
+
+static HELVETICA_BOLD: [u16; 170] = [
+    278, 333, 474, 556, 556, 889, 722, 278,
+    333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556,
+    333, 333, 584, 584, 584, 611, 975,
+    722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833,
+    722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611,
+    333, 278, 333, 584, 556, 278,
+    556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889,
+    611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500,
+    389, 280, 389, 584, 238, 333,
+    333, 556, 556, 167, 556, 556, 556, 556, 500, 556,
+    333, 333, 611, 611, 556, 556, 556, 278, 556, 350,
+    278, 500, 500, 556, 1000, 1000, 611,
+    333, 333, 333, 333, 333, 333, 333, 333, 333, 333, 333, 333,
+    1000, 1000, 370, 611, 778, 1000, 365, 889, 278, 278, 611, 944, 611,
+    280, 737, 584, 737, 400, 584, 333, 333, 611, 333,
+    834, 834, 834, 584, 584, 722, 611, 667, 611, 1000, 584,
+];
+
+static TIMES_ROMAN: [u16; 170] = [
+    250, 333, 408, 500, 500, 833, 778, 333,
+    333, 333, 500, 564, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    278, 278, 564, 564, 564, 444, 921,
+    722, 667, 667, 722, 611, 556, 722, 722, 333, 389, 722, 611, 889,
+    722, 722, 556, 722, 667, 556, 611, 722, 722, 944, 722, 722, 611,
+    333, 278, 333, 469, 500, 333,
+    444, 500, 444, 500, 444, 333, 500, 500, 278, 278, 500, 278, 778,
+    500, 500, 500, 500, 333, 389, 278, 500, 500, 722, 500, 500, 444,
+    480, 200, 480, 541, 180, 333,
+    333, 500, 500, 167, 500, 500, 500, 500, 444, 500,
+    333, 333, 556, 556, 500, 500, 500, 250, 453, 350,
+    333, 444, 444, 500, 1000, 1000, 444,
+    333, 333, 333, 333, 333, 333, 333, 333, 333, 333, 333, 333,
+    1000, 889, 276, 611, 722, 889, 310, 667, 278, 278, 500, 722, 500,
+    200, 760, 564, 760, 400, 564, 300, 300, 500, 300,
+    750, 750, 750, 564, 564, 722, 500, 556, 500, 980, 564,
+];
+
+static TIMES_BOLD: [u16; 170] = [
+    250, 333, 555, 500, 500, 1000, 833, 333,
+    333, 333, 500, 570, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    333, 333, 570, 570, 570, 500, 930,
+    722, 667, 722, 722, 667, 611, 778, 778, 389, 500, 778, 667, 944,
+    722, 778, 611, 778, 722, 556, 667, 722, 722, 1000, 722, 722, 667,
+    333, 278, 333, 581, 500, 333,
+    500, 556, 444, 556, 444, 333, 500, 556, 278, 333, 556, 278, 833,
+    556, 500, 556, 556, 444, 389, 333, 556, 500, 722, 500, 500, 444,
+    394, 220, 394, 520, 278, 333,
+    333, 500, 500, 167, 500, 500, 500, 500, 500, 500,
+    333, 333, 556, 556, 500, 500, 500, 250, 540, 350,
+    333, 500, 500, 500, 1000, 1000, 500,
+    333, 333, 333, 333, 333, 333, 333, 333, 333, 333, 333, 333,
+    1000, 1000, 300, 667, 778, 1000, 330, 722, 278, 278, 500, 722, 556,
+    220, 747, 570, 747, 400, 570, 300, 300, 556, 300,
+    750, 750, 750, 570, 570, 722, 500, 611, 556, 1000, 570,
+];
+
+static TIMES_ITALIC: [u16; 170] = [
+    250, 333, 420, 500, 500, 833, 778, 333,
+    333, 333, 500, 675, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    333, 333, 675, 675, 675, 500, 920,
+    611, 611, 667, 722, 611, 611, 722, 722, 333, 444, 667, 556, 833,
+    667, 722, 611, 722, 611, 500, 556, 722, 611, 833, 611, 556, 556,
+    389, 278, 389, 422, 500, 333,
+    500, 500, 444, 500, 444, 278, 500, 500, 278, 278, 444, 278, 722,
+    500, 500, 500, 500, 389, 389, 278, 500, 444, 667, 444, 444, 389,
+    400, 275, 400, 541, 214, 333,
+    389, 500, 500, 167, 500, 500, 500, 500, 556, 500,
+    333, 333, 500, 500, 500, 500, 500, 250, 523, 350,
+    333, 556, 556, 500, 889, 1000, 500,
+    333, 333, 333, 333, 333, 333, 333, 333, 333, 333, 333, 333,
+    889, 889, 276, 556, 722, 944, 310, 667, 278, 278, 500, 667, 500,
+    275, 760, 675, 760, 400, 675, 300, 300, 500, 300,
+    750, 750, 750, 675, 675, 722, 500, 611, 500, 980, 675,
+];
+
+static TIMES_BOLD_ITALIC: [u16; 170] = [
+    250, 389, 555, 500, 500, 833, 778, 333,
+    333, 333, 500, 570, 250, 333, 250, 278,
+    500, 500, 500, 500, 500, 500, 500, 500, 500, 500,
+    333, 333, 570, 570, 570, 500, 832,
+    667, 667, 667, 722, 667, 667, 722, 778, 389, 500, 667, 611, 889,
+    722, 722, 611, 722, 667, 556, 611, 722, 667, 889, 667, 611, 611,
+    333, 278, 333, 570, 500, 333,
+    500, 500, 444, 500, 444, 333, 500, 556, 278, 278, 500, 278, 778,
+    556, 500, 500, 500, 389, 389, 278, 556, 444, 667, 500, 444, 389,
+    348, 220, 348, 570, 278, 333,
+    389, 500, 500, 167, 500, 500, 500, 500, 500, 500,
+    333, 333, 556, 556, 500, 500, 500, 250, 500, 350,
+    333, 500, 500, 500, 1000, 1000, 500,
+    333, 333, 333, 333, 333, 333, 333, 333, 333, 333, 333, 333,
+    1000, 944, 266, 611, 722, 944, 300, 722, 278, 278, 500, 722, 500,
+    220, 747, 606, 747, 400, 570, 300, 300, 576, 300,
+    750, 750, 750, 570, 570, 722, 500, 611, 500, 1000, 606,
+];
+
+static SYMBOL: [(&str, u16); 190] = [
+    ("space", 250), ("exclam", 333), ("universal", 713), ("numbersign", 500), ("existential", 549),
+    ("percent", 833), ("ampersand", 778), ("suchthat", 439), ("parenleft", 333),
+    ("parenright", 333), ("asteriskmath", 500), ("plus", 549), ("comma", 250), ("minus", 549),
+    ("period", 250), ("slash", 278), ("zero", 500), ("one", 500), ("two", 500), ("three", 500),
+    ("four", 500), ("five", 500), ("six", 500), ("seven", 500), ("eight", 500), ("nine", 500),
+    ("colon", 278), ("semicolon", 278), ("less", 549), ("equal", 549), ("greater", 549),
+    ("question", 444), ("congruent", 549), ("Alpha", 722), ("Beta", 667), ("Chi", 722),
+    ("Delta", 612), ("Epsilon", 611), ("Phi", 763), ("Gamma", 603), ("Eta", 722), ("Iota", 333),
+    ("theta1", 631), ("Kappa", 722), ("Lambda", 686), ("Mu", 889), ("Nu", 722), ("Omicron", 722),
+    ("Pi", 768), ("Theta", 741), ("Rho", 556), ("Sigma", 592), ("Tau", 611), ("Upsilon", 690),
+    ("sigma1", 439), ("Omega", 768), ("Xi", 645), ("Psi", 795), ("Zeta", 611),
+    ("bracketleft", 333), ("therefore", 863), ("bracketright", 333), ("perpendicular", 658),
+    ("underscore", 500), ("radicalex", 500), ("alpha", 631), ("beta", 549), ("chi", 549),
+    ("delta", 494), ("epsilon", 439), ("phi", 521), ("gamma", 411), ("eta", 603), ("iota", 329),
+    ("phi1", 603), ("kappa", 549), ("lambda", 549), ("mu", 576), ("nu", 521), ("omicron", 549),
+    ("pi", 549), ("theta", 521), ("rho", 549), ("sigma", 603), ("tau", 439), ("upsilon", 576),
+    ("omega1", 713), ("omega", 686), ("xi", 493), ("psi", 686), ("zeta", 494), ("braceleft", 480),
+    ("bar", 200), ("braceright", 480), ("similar", 549), ("Euro", 750), ("Upsilon1", 620),
+    ("minute", 247), ("lessequal", 549), ("fraction", 167), ("infinity", 713), ("florin", 500),
+    ("club", 753), ("diamond", 753), ("heart", 753), ("spade", 753), ("arrowboth", 1042),
+    ("arrowleft", 987), ("arrowup", 603), ("arrowright", 987), ("arrowdown", 603), ("degree", 400),
+    ("plusminus", 549), ("second", 411), ("greaterequal", 549), ("multiply", 549),
+    ("proportional", 713), ("partialdiff", 494), ("bullet", 460), ("divide", 549),
+    ("notequal", 549), ("equivalence", 549), ("approxequal", 549), ("ellipsis", 1000),
+    ("arrowvertex", 603), ("arrowhorizex", 1000), ("carriagereturn", 658), ("aleph", 823),
+    ("Ifraktur", 686), ("Rfraktur", 795), ("weierstrass", 987), ("circlemultiply", 768),
+    ("circleplus", 768), ("emptyset", 823), ("intersection", 768), ("union", 768),
+    ("propersuperset", 713), ("reflexsuperset", 713), ("notsubset", 713), ("propersubset", 713),
+    ("reflexsubset", 713), ("element", 713), ("notelement", 713), ("angle", 768),
+    ("gradient", 713), ("registerserif", 790), ("copyrightserif", 790), ("trademarkserif", 890),
+    ("product", 823), ("radical", 549), ("dotmath", 250), ("logicalnot", 713), ("logicaland", 603),
+    ("logicalor", 603), ("arrowdblboth", 1042), ("arrowdblleft", 987), ("arrowdblup", 603),
+    ("arrowdblright", 987), ("arrowdbldown", 603), ("lozenge", 494), ("angleleft", 329),
+    ("registersans", 790), ("copyrightsans", 790), ("trademarksans", 786), ("summation", 713),
+    ("parenlefttp", 384), ("parenleftex", 384), ("parenleftbt", 384), ("bracketlefttp", 384),
+    ("bracketleftex", 384), ("bracketleftbt", 384), ("bracelefttp", 494), ("braceleftmid", 494),
+    ("braceleftbt", 494), ("braceex", 494), ("angleright", 329), ("integral", 274),
+    ("integraltp", 686), ("integralex", 686), ("integralbt", 686), ("parenrighttp", 384),
+    ("parenrightex", 384), ("parenrightbt", 384), ("bracketrighttp", 384), ("bracketrightex", 384),
+    ("bracketrightbt", 384), ("bracerighttp", 494), ("bracerightmid", 494), ("bracerightbt", 494),
+    ("apple", 790),
+];
+
+static ZAPF_DINGBATS: [(&str, u16); 202] = [
+    ("space", 278), ("a1", 974), ("a2", 961), ("a202", 974), ("a3", 980), ("a4", 719), ("a5", 789),
+    ("a119", 790), ("a118", 791), ("a117", 690), ("a11", 960), ("a12", 939), ("a13", 549),
+    ("a14", 855), ("a15", 911), ("a16", 933), ("a105", 911), ("a17", 945), ("a18", 974),
+    ("a19", 755), ("a20", 846), ("a21", 762), ("a22", 761), ("a23", 571), ("a24", 677),
+    ("a25", 763), ("a26", 760), ("a27", 759), ("a28", 754), ("a6", 494), ("a7", 552), ("a8", 537),
+    ("a9", 577), ("a10", 692), ("a29", 786), ("a30", 788), ("a31", 788), ("a32", 790),
+    ("a33", 793), ("a34", 794), ("a35", 816), ("a36", 823), ("a37", 789), ("a38", 841),
+    ("a39", 823), ("a40", 833), ("a41", 816), ("a42", 831), ("a43", 923), ("a44", 744),
+    ("a45", 723), ("a46", 749), ("a47", 790), ("a48", 792), ("a49", 695), ("a50", 776),
+    ("a51", 768), ("a52", 792), ("a53", 759), ("a54", 707), ("a55", 708), ("a56", 682),
+    ("a57", 701), ("a58", 826), ("a59", 815), ("a60", 789), ("a61", 789), ("a62", 707),
+    ("a63", 687), ("a64", 696), ("a65", 689), ("a66", 786), ("a67", 787), ("a68", 713),
+    ("a69", 791), ("a70", 785), ("a71", 791), ("a72", 873), ("a73", 761), ("a74", 762),
+    ("a203", 762), ("a75", 759), ("a204", 759), ("a76", 892), ("a77", 892), ("a78", 788),
+    ("a79", 784), ("a81", 438), ("a82", 138), ("a83", 277), ("a84", 415), ("a97", 392),
+    ("a98", 392), ("a99", 668), ("a100", 668), ("a89", 390), ("a90", 390), ("a93", 317),
+    ("a94", 317), ("a91", 276), ("a92", 276), ("a205", 509), ("a85", 509), ("a206", 410),
+    ("a86", 410), ("a87", 234), ("a88", 234), ("a95", 334), ("a96", 334), ("a101", 732),
+    ("a102", 544), ("a103", 544), ("a104", 910), ("a106", 667), ("a107", 760), ("a108", 760),
+    ("a112", 776), ("a111", 595), ("a110", 694), ("a109", 626), ("a120", 788), ("a121", 788),
+    ("a122", 788), ("a123", 788), ("a124", 788), ("a125", 788), ("a126", 788), ("a127", 788),
+    ("a128", 788), ("a129", 788), ("a130", 788), ("a131", 788), ("a132", 788), ("a133", 788),
+    ("a134", 788), ("a135", 788), ("a136", 788), ("a137", 788), ("a138", 788), ("a139", 788),
+    ("a140", 788), ("a141", 788), ("a142", 788), ("a143", 788), ("a144", 788), ("a145", 788),
+    ("a146", 788), ("a147", 788), ("a148", 788), ("a149", 788), ("a150", 788), ("a151", 788),
+    ("a152", 788), ("a153", 788), ("a154", 788), ("a155", 788), ("a156", 788), ("a157", 788),
+    ("a158", 788), ("a159", 788), ("a160", 894), ("a161", 838), ("a163", 1016), ("a164", 458),
+    ("a196", 748), ("a165", 924), ("a192", 748), ("a166", 918), ("a167", 927), ("a168", 928),
+    ("a169", 928), ("a170", 834), ("a171", 873), ("a172", 828), ("a173", 924), ("a162", 924),
+    ("a174", 917), ("a175", 930), ("a176", 931), ("a177", 463), ("a178", 883), ("a179", 836),
+    ("a193", 836), ("a180", 867), ("a199", 867), ("a181", 696), ("a200", 696), ("a182", 874),
+    ("a201", 874), ("a183", 760), ("a184", 946), ("a197", 771), ("a185", 865), ("a194", 771),
+    ("a198", 888), ("a186", 967), ("a195", 888), ("a187", 831), ("a188", 873), ("a189", 927),
+    ("a190", 970), ("a191", 918),
+];
+
+/// `name` without a subset tag (six uppercase letters and `+`, as in `ABCDEF+Helvetica`).
+pub fn strip_subset_tag(name: &str) -> &str {
+    match name.split_once('+') {
+        Some((tag, rest)) if tag.len() == 6 && tag.bytes().all(|b| b.is_ascii_uppercase()) => rest,
+        _ => name
+    }
+}
+
+/// The name of the standard font `name` refers to, including the common aliases
+/// (`Arial`, `TimesNewRoman,Bold`, ...). A subset tag (`ABCDEF+`) is ignored.
+pub fn standard_font_name(name: &str) -> Option<&'static str> {
+    Some(match strip_subset_tag(name) {
+        "Courier" | "CourierNew" | "CourierNewPSMT" => "Courier",
+        "Courier-Bold" | "CourierNew,Bold" | "CourierNew-Bold" | "CourierNewPS-BoldMT" => "Courier-Bold",
+        "Courier-Oblique" | "CourierNew,Italic" | "CourierNew-Italic" | "CourierNewPS-ItalicMT" => "Courier-Oblique",
+        "Courier-BoldOblique" | "CourierNew,BoldItalic" | "CourierNew-BoldItalic" | "CourierNewPS-BoldItalicMT" => "Courier-BoldOblique",
+        "Helvetica" | "Arial" | "ArialMT" => "Helvetica",
+        "Helvetica-Bold" | "Arial,Bold" | "Arial-Bold" | "Arial-BoldMT" => "Helvetica-Bold",
+        "Helvetica-Oblique" | "Arial,Italic" | "Arial-Italic" | "Arial-ItalicMT" => "Helvetica-Oblique",
+        "Helvetica-BoldOblique" | "Arial,BoldItalic" | "Arial-BoldItalic" | "Arial-BoldItalicMT" => "Helvetica-BoldOblique",
+        "Times-Roman" | "TimesNewRoman" | "TimesNewRomanPSMT" => "Times-Roman",
+        "Times-Bold" | "TimesNewRoman,Bold" | "TimesNewRoman-Bold" | "TimesNewRomanPS-BoldMT" => "Times-Bold",
+        "Times-Italic" | "TimesNewRoman,Italic" | "TimesNewRoman-Italic" | "TimesNewRomanPS-ItalicMT" => "Times-Italic",
+        "Times-BoldItalic" | "TimesNewRoman,BoldItalic" | "TimesNewRoman-BoldItalic" | "TimesNewRomanPS-BoldItalicMT" => "Times-BoldItalic",
+        "Symbol" => "Symbol",
+        "ZapfDingbats" => "ZapfDingbats",
+        _ => return None
+    })
+}
+
+/// The AFM width of `glyph` in the standard font `font` (as returned by `standard_font_name`).
+pub fn standard_width(font: &str, glyph: &str) -> Option<f32> {
+    let table = match font {
+        // every glyph of Courier has the same width
+        "Courier" | "Courier-Bold" | "Courier-Oblique" | "Courier-BoldOblique" => return Some(600.),
+        "Helvetica" | "Helvetica-Oblique" => &HELVETICA,
+        "Helvetica-Bold" | "Helvetica-BoldOblique" => &HELVETICA_BOLD,
+        "Times-Roman" => &TIMES_ROMAN,
+        "Times-Bold" => &TIMES_BOLD,
+        "Times-Italic" => &TIMES_ITALIC,
+        "Times-BoldItalic" => &TIMES_BOLD_ITALIC,
+        "Symbol" => return lookup(&SYMBOL, glyph),
+        "ZapfDingbats" => return lookup(&ZAPF_DINGBATS, glyph),
+        _ => return None
+    };
+    latin_width(table, glyph)
+}
+
+fn lookup(table: &[(&str, u16)], glyph: &str) -> Option<f32> {
+    table.iter().find(|&&(g, _)| g == glyph).map(|&(_, w)| w as f32)
+}
+
+/// Accents that are put on letters without changing their width.
+static ACCENTS: [&str; 9] = ["acute", "grave", "circumflex", "dieresis", "tilde", "ring", "cedilla", "caron", "ogonek"];
+
+fn latin_width(table: &[u16; 170], glyph: &str) -> Option<f32> {
+    if let Some(i) = GLYPHS.iter().position(|&g| g == glyph) {
+        return Some(table[i] as f32);
+    }
+    // `Eacute` is as wide as `E`, and `iacute` as `dotlessi`
+    let base = ACCENTS.iter().find_map(|a| glyph.strip_suffix(a)).filter(|b| b.len() == 1)?;
+    let base = if base == "i" { "dotlessi" } else { base };
+    GLYPHS.iter().position(|&g| g == base).map(|i| table[i] as f32)
+}
+
+/// Whether widths are bundled for the standard font `font`.
+pub fn has_widths(font: &str) -> bool {
+    standard_width(font, "space").is_some()
+}
@@ -16,9 +16,16 @@ pub mod backend;
 pub mod content;
 pub mod parser;
 pub mod font;
+pub mod cmap;
+pub mod text;
+pub mod bbox;
+pub mod svg;
 pub mod any;
 pub mod encoding;
 pub mod build;
+pub mod sfnt;
+pub mod type1;
+pub mod cff;
 
 // mod content;
 pub mod enc;
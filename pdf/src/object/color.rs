@@ -18,6 +18,91 @@ pub struct IccInfo {
     pub metadata: Option<Stream<()>>,
 }
 
+/// Parameters of a `CalGray` color space (Table 63).
+#[derive(Object, ObjectWrite, Debug, DataSize, Clone)]
+pub struct CalGray {
+    #[pdf(key="WhitePoint")]
+    pub white_point: [f32; 3],
+
+    #[pdf(key="BlackPoint")]
+    pub black_point: Option<[f32; 3]>,
+
+    #[pdf(key="Gamma", default="1.")]
+    pub gamma: f32,
+}
+impl CalGray {
+    /// Approximate sRGB value of the gray level `a`.
+    ///
+    /// `a` raised to `/Gamma` is the luminance relative to the white point, which is
+    /// encoded with the sRGB transfer curve. A gamma of 2.2 is close to `DeviceGray`.
+    pub fn to_rgb(&self, a: f32) -> [f32; 3] {
+        [srgb_encode(a.clamp(0., 1.).powf(self.gamma)); 3]
+    }
+}
+
+/// The sRGB transfer curve, from linear light to the encoded value.
+fn srgb_encode(c: f32) -> f32 {
+    let c = c.clamp(0., 1.);
+    if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    }
+}
+
+/// Parameters of a `CalRGB` color space (Table 64).
+#[derive(Object, ObjectWrite, Debug, DataSize, Clone)]
+pub struct CalRgb {
+    #[pdf(key="WhitePoint")]
+    pub white_point: [f32; 3],
+
+    #[pdf(key="BlackPoint")]
+    pub black_point: Option<[f32; 3]>,
+
+    #[pdf(key="Gamma")]
+    pub gamma: Option<[f32; 3]>,
+
+    /// `[XA YA ZA XB YB ZB XC YC ZC]`
+    #[pdf(key="Matrix")]
+    pub matrix: Option<[f32; 9]>,
+}
+impl CalRgb {
+    /// Approximate sRGB value of `abc`.
+    ///
+    /// With a `/Matrix`, the gamma corrected components are converted to XYZ and scaled from
+    /// `/WhitePoint` to D65. Without one, they are taken as linear sRGB. Either way the result
+    /// is encoded with the sRGB transfer curve.
+    pub fn to_rgb(&self, abc: [f32; 3]) -> [f32; 3] {
+        let mut linear = abc.map(|c| c.clamp(0., 1.));
+        if let Some([gr, gg, gb]) = self.gamma {
+            linear = [linear[0].powf(gr), linear[1].powf(gg), linear[2].powf(gb)];
+        }
+        let m = match self.matrix {
+            Some(ref m) => m,
+            None => return linear.map(srgb_encode)
+        };
+        let [a, b, c] = linear;
+        let xyz = [
+            m[0] * a + m[3] * b + m[6] * c,
+            m[1] * a + m[4] * b + m[7] * c,
+            m[2] * a + m[5] * b + m[8] * c,
+        ];
+
+        // von Kries style scaling to the D65 white point of sRGB
+        const D65: [f32; 3] = [0.9505, 1.0, 1.089];
+        let [x, y, z] = match self.white_point {
+            [xw, yw, zw] if xw > 0. && yw > 0. && zw > 0. => [xyz[0] * D65[0] / xw, xyz[1] / yw, xyz[2] * D65[2] / zw],
+            _ => xyz
+        };
+        let rgb = [
+             3.2406 * x - 1.5372 * y - 0.4986 * z,
+            -0.9689 * x + 1.8758 * y + 0.0415 * z,
+             0.0557 * x - 0.2040 * y + 1.0570 * z,
+        ];
+        rgb.map(srgb_encode)
+    }
+}
+
 #[derive(Debug, Clone, DeepClone)]
 pub enum ColorSpace {
     DeviceGray,
@@ -156,11 +241,174 @@ impl ColorSpace {
         }
     }
 }
+impl ColorSpace {
+    /// The parameters of a `CalGray` color space.
+    pub fn cal_gray(&self) -> Option<Result<CalGray>> {
+        match *self {
+            ColorSpace::CalGray(ref dict) => Some(CalGray::from_dict(dict.clone(), &NoResolve)),
+            _ => None
+        }
+    }
+    /// The parameters of a `CalRGB` color space.
+    pub fn cal_rgb(&self) -> Option<Result<CalRgb>> {
+        match *self {
+            ColorSpace::CalRGB(ref dict) => Some(CalRgb::from_dict(dict.clone(), &NoResolve)),
+            _ => None
+        }
+    }
+}
+/// The result of painting with a `Separation` color space, see `ColorSpace::separation_paint`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SeparationPaint {
+    /// The `/None` colorant: nothing is painted.
+    Nothing,
+    /// The `/All` colorant, which applies to every colorant of the output (e.g. registration
+    /// marks). `gray` is the `DeviceGray` value, a tint of 1 giving black.
+    All { gray: f32 },
+    /// Any other colorant: the components of the tint in the alternate color space.
+    Alternate(Vec<f32>),
+}
+
+impl ColorSpace {
+    /// What painting with `tint` in a `Separation` color space produces.
+    ///
+    /// The colorants `/None` and `/All` are special: the tint transform is not used for them.
+    /// Returns `None` for other color spaces.
+    pub fn separation_paint(&self, tint: f32) -> Option<Result<SeparationPaint>> {
+        let (name, function) = match *self {
+            ColorSpace::Separation(ref name, _, ref function) => (name, function),
+            _ => return None
+        };
+        let tint = tint.clamp(0., 1.);
+        Some(match name.as_str() {
+            "None" => Ok(SeparationPaint::Nothing),
+            "All" => Ok(SeparationPaint::All { gray: 1. - tint }),
+            _ => {
+                let mut out = vec![0.; function.output_dim()];
+                function.apply(&[tint], &mut out).map(|()| SeparationPaint::Alternate(out))
+            }
+        })
+    }
+}
+impl ColorSpace {
+    /// The number of components of a color in this color space.
+    ///
+    /// `None` for `Pattern`, named (resource) and unknown color spaces.
+    pub fn num_components(&self) -> Option<usize> {
+        match *self {
+            ColorSpace::DeviceGray | ColorSpace::CalGray(_) => Some(1),
+            ColorSpace::DeviceRGB | ColorSpace::CalRGB(_) => Some(3),
+            ColorSpace::DeviceCMYK | ColorSpace::CalCMYK(_) => Some(4),
+            ColorSpace::Indexed(..) | ColorSpace::Separation(..) => Some(1),
+            ColorSpace::DeviceN { ref names, .. } => Some(names.len()),
+            ColorSpace::Icc(ref s) => Some(s.info.info.components as usize),
+            ColorSpace::Pattern | ColorSpace::Named(_) | ColorSpace::Other(_) => None,
+        }
+    }
+
+    /// Approximate sRGB value (components in 0..1) of `color` in this color space.
+    ///
+    /// `Indexed` takes the index as its only component. ICC profiles are not applied, the
+    /// alternate color space (or the device space with the same number of components) is used.
+    /// Returns `None` if nothing is painted (the `/None` colorant of a `Separation`).
+    ///
+    /// Use `rgb_converter` to convert many colors of the same color space.
+    pub fn to_rgb(&self, color: &[f32]) -> Result<Option<[f32; 3]>> {
+        self.rgb_converter()?.to_rgb(color)
+    }
+
+    /// A converter to sRGB, see `to_rgb`.
+    ///
+    /// The parameters of calibrated color spaces, including those used as the base or
+    /// alternate color space, are parsed once here instead of for every color.
+    pub fn rgb_converter(&self) -> Result<RgbConverter<'_>> {
+        let cal = match *self {
+            ColorSpace::CalGray(ref dict) => Cal::Gray(t!(CalGray::from_dict(dict.clone(), &NoResolve))),
+            ColorSpace::CalRGB(ref dict) => Cal::Rgb(t!(CalRgb::from_dict(dict.clone(), &NoResolve))),
+            _ => Cal::None
+        };
+        let base = match *self {
+            ColorSpace::Indexed(ref base, ..) => Some(base),
+            ColorSpace::Separation(_, ref alt, _) | ColorSpace::DeviceN { ref alt, .. } => Some(alt),
+            ColorSpace::Icc(ref s) => s.info.info.alternate.as_ref(),
+            _ => None
+        };
+        let base = match base {
+            Some(cs) => Some(Box::new(cs.rgb_converter()?)),
+            None => None
+        };
+        Ok(RgbConverter { space: self, cal, base })
+    }
+}
+
+enum Cal {
+    None,
+    Gray(CalGray),
+    Rgb(CalRgb),
+}
+
+/// Converts colors of one color space to sRGB, see `ColorSpace::rgb_converter`.
+pub struct RgbConverter<'a> {
+    space: &'a ColorSpace,
+    cal: Cal,
+    /// the converter of the base or alternate color space
+    base: Option<Box<RgbConverter<'a>>>,
+}
+impl RgbConverter<'_> {
+    /// Approximate sRGB value of `color`, see `ColorSpace::to_rgb`.
+    pub fn to_rgb(&self, color: &[f32]) -> Result<Option<[f32; 3]>> {
+        let c = |i: usize| color.get(i).copied().unwrap_or(0.).clamp(0., 1.);
+        let cmyk = |c: f32, m: f32, y: f32, k: f32| [(1. - c) * (1. - k), (1. - m) * (1. - k), (1. - y) * (1. - k)];
+        match self.cal {
+            Cal::Gray(ref gray) => return Ok(Some(gray.to_rgb(c(0)))),
+            Cal::Rgb(ref rgb) => return Ok(Some(rgb.to_rgb([c(0), c(1), c(2)]))),
+            Cal::None => {}
+        }
+        if let Some(ref base) = self.base {
+            match *self.space {
+                ColorSpace::Indexed(ref base_space, hival, ref lookup) => {
+                    let n = base_space.num_components().ok_or_else(|| other!("invalid Indexed base {:?}", base_space))?;
+                    let index = (color.first().copied().unwrap_or(0.).round().max(0.) as usize).min(hival as usize);
+                    let entry = lookup.get(index * n .. (index + 1) * n)
+                        .ok_or(PdfError::Bounds { index: (index + 1) * n, len: lookup.len() })?;
+                    let base_color: Vec<f32> = entry.iter().map(|&b| b as f32 / 255.).collect();
+                    return base.to_rgb(&base_color);
+                }
+                ColorSpace::Separation(..) => return match self.space.separation_paint(c(0)).unwrap()? {
+                    SeparationPaint::Nothing => Ok(None),
+                    SeparationPaint::All { gray } => Ok(Some([gray; 3])),
+                    SeparationPaint::Alternate(alt_color) => base.to_rgb(&alt_color),
+                },
+                ColorSpace::DeviceN { ref tint, .. } => {
+                    let mut alt_color = vec![0.; tint.output_dim()];
+                    t!(tint.apply(color, &mut alt_color));
+                    return base.to_rgb(&alt_color);
+                }
+                _ => return base.to_rgb(color)
+            }
+        }
+        Ok(Some(match *self.space {
+            ColorSpace::DeviceGray => [c(0); 3],
+            ColorSpace::DeviceRGB => [c(0), c(1), c(2)],
+            ColorSpace::DeviceCMYK | ColorSpace::CalCMYK(_) => cmyk(c(0), c(1), c(2), c(3)),
+            ColorSpace::Icc(ref s) => match s.info.info.components {
+                1 => [c(0); 3],
+                3 => [c(0), c(1), c(2)],
+                4 => cmyk(c(0), c(1), c(2), c(3)),
+                n => bail!("ICC profile with {} components", n)
+            }
+            _ => bail!("can't convert {:?} to RGB", self.space)
+        }))
+    }
+}
 impl ObjectWrite for ColorSpace {
     fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
         match *self {
+            ColorSpace::DeviceGray => Ok(Primitive::name("DeviceGray")),
             ColorSpace::DeviceCMYK => Ok(Primitive::name("DeviceCMYK")),
             ColorSpace::DeviceRGB => Ok(Primitive::name("DeviceRGB")),
+            ColorSpace::Pattern => Ok(Primitive::name("Pattern")),
+            ColorSpace::Named(ref name) => Ok(Primitive::Name(name.0.clone())),
             ColorSpace::Indexed(ref  base, hival, ref lookup) => {
                 let base = base.to_primitive(update)?;
                 let hival = Primitive::Integer(hival.into());
@@ -171,6 +419,9 @@ impl ObjectWrite for ColorSpace {
                 };
                 Ok(Primitive::Array(vec![Primitive::name("Indexed"), base, hival, lookup]))
             }
+            ColorSpace::CalGray(ref dict) => Ok(Primitive::Array(vec![Primitive::name("CalGray"), dict.to_primitive(update)?])),
+            ColorSpace::CalRGB(ref dict) => Ok(Primitive::Array(vec![Primitive::name("CalRGB"), dict.to_primitive(update)?])),
+            ColorSpace::CalCMYK(ref dict) => Ok(Primitive::Array(vec![Primitive::name("CalCMYK"), dict.to_primitive(update)?])),
             ref p => {
                 dbg!(p);
                 unimplemented!()
@@ -178,3 +429,66 @@ impl ObjectWrite for ColorSpace {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, ParseFlags};
+
+    fn color_space(data: &[u8]) -> ColorSpace {
+        ColorSpace::from_primitive(parse(data, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap()
+    }
+
+    #[test]
+    fn cal_spaces() {
+        let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 0.03);
+
+        // the defaults are linear, so they are encoded with the sRGB curve
+        let gray = color_space(b"[/CalGray << /WhitePoint [0.9505 1 1.089] >>]").cal_gray().unwrap().unwrap();
+        assert!(close(gray.to_rgb(0.25), [0.537; 3]), "{:?}", gray.to_rgb(0.25));
+        assert!(close(gray.to_rgb(1.), [1.; 3]));
+        let rgb = color_space(b"[/CalRGB << /WhitePoint [0.9505 1 1.089] >>]").cal_rgb().unwrap().unwrap();
+        assert!(close(rgb.to_rgb([0.25, 0., 2.]), [0.537, 0., 1.]), "{:?}", rgb.to_rgb([0.25, 0., 2.]));
+
+        // gamma 2.2 is close to the device spaces, with and without a matrix
+        let gray = color_space(b"[/CalGray << /WhitePoint [0.9505 1 1.089] /Gamma 2.2 >>]").cal_gray().unwrap().unwrap();
+        assert!(close(gray.to_rgb(0.5), [0.5; 3]), "{:?}", gray.to_rgb(0.5));
+        let rgb = color_space(b"[/CalRGB << /WhitePoint [0.9505 1 1.089] /Gamma [2.2 2.2 2.2] >>]").cal_rgb().unwrap().unwrap();
+        assert!(close(rgb.to_rgb([0.5, 0.2, 0.8]), [0.5, 0.2, 0.8]), "{:?}", rgb.to_rgb([0.5, 0.2, 0.8]));
+
+        // sRGB primaries with the sRGB white point and gamma 2.2 are close to the input
+        let rgb = color_space(b"[/CalRGB << /WhitePoint [0.9505 1 1.089] /Gamma [2.2 2.2 2.2]
+            /Matrix [0.4124 0.2126 0.0193 0.3576 0.7152 0.1192 0.1805 0.0722 0.9505] >>]");
+        let rgb = rgb.cal_rgb().unwrap().unwrap();
+        for c in [[1., 1., 1.], [0.5, 0.2, 0.8], [0., 0., 0.]] {
+            let out = rgb.to_rgb(c);
+            assert!(close(out, c), "{:?} -> {:?}", c, out);
+        }
+        assert!(color_space(b"/DeviceGray").cal_rgb().is_none());
+
+        // an indexed base is parsed once by the converter
+        let indexed = color_space(b"[/Indexed [/CalGray << /WhitePoint [0.9505 1 1.089] /Gamma 2.2 >>] 1 <00ff>]");
+        let converter = indexed.rgb_converter().unwrap();
+        assert!(close(converter.to_rgb(&[1.]).unwrap().unwrap(), [1.; 3]));
+        assert_eq!(converter.to_rgb(&[0.]).unwrap(), indexed.to_rgb(&[0.]).unwrap());
+
+        // fixed length arrays
+        assert!(color_space(b"[/CalGray << /WhitePoint [0.9505 1] >>]").cal_gray().unwrap().is_err());
+        assert!(color_space(b"[/CalRGB << /WhitePoint [0.9505 1 1.089] /Matrix [1 0 0 0 1 0 0 0] >>]").cal_rgb().unwrap().is_err());
+        let p = rgb.white_point.to_primitive(&mut NoUpdate).unwrap();
+        assert_eq!(<[f32; 3]>::from_primitive(p, &NoResolve).unwrap(), [0.9505, 1., 1.089]);
+    }
+
+    #[test]
+    fn separation_paint() {
+        // tint transform to CMYK that would give cyan for every colorant
+        let function = b"<< /FunctionType 2 /Domain [0 1] /C0 [0 0 0 0] /C1 [1 0 0 0] /N 1 >>";
+        let cs = |name: &str| color_space(&[b"[/Separation /", name.as_bytes(), b" /DeviceCMYK ", &function[..], b"]"].concat());
+
+        assert_eq!(cs("None").separation_paint(1.).unwrap().unwrap(), SeparationPaint::Nothing);
+        assert_eq!(cs("All").separation_paint(0.75).unwrap().unwrap(), SeparationPaint::All { gray: 0.25 });
+        assert_eq!(cs("All").separation_paint(2.).unwrap().unwrap(), SeparationPaint::All { gray: 0. });
+        assert_eq!(cs("Spot").separation_paint(0.5).unwrap().unwrap(), SeparationPaint::Alternate(vec![0.5, 0., 0., 0.]));
+        assert!(color_space(b"/DeviceGray").separation_paint(1.).is_none());
+    }
+}
@@ -77,6 +77,7 @@ impl Function {
         match *self {
             Function::PostScript { ref domain, .. } => domain.len() / 2,
             Function::Sampled(ref f) => f.input.len(),
+            Function::Interpolated(_) => 1,
             _ => panic!()
         }
     }
@@ -84,9 +85,47 @@ impl Function {
         match *self {
             Function::PostScript { ref range, .. } => range.len() / 2,
             Function::Sampled(ref f) => f.output.len(),
+            Function::Interpolated(ref parts) => parts.len(),
             _ => panic!()
         }
     }
+    /// The domain of the first input.
+    pub fn domain(&self) -> Option<(f32, f32)> {
+        match *self {
+            Function::PostScript { ref domain, .. } => Some((*domain.first()?, *domain.get(1)?)),
+            Function::Sampled(ref f) => f.input.first().map(|i| i.domain),
+            Function::Interpolated(ref parts) => parts.first().map(|p| p.input_range),
+            _ => None
+        }
+    }
+    /// Precompute `samples` values over the domain of a function with a single input.
+    ///
+    /// The returned function interpolates linearly between them and is much cheaper to
+    /// evaluate than sampled or PostScript functions. Inputs are clamped to the domain.
+    pub fn to_lut(&self, samples: usize) -> Result<impl Fn(f32) -> Vec<f32>> {
+        let (x0, x1) = try_opt!(self.domain());
+        if self.input_dim() != 1 {
+            bail!("lookup tables need a function with one input, not {}", self.input_dim());
+        }
+        let samples = samples.max(2);
+        let n_out = self.output_dim();
+        if n_out == 0 {
+            bail!("lookup tables need a function with outputs");
+        }
+        let mut table = vec![0.; samples * n_out];
+        for (i, out) in table.chunks_exact_mut(n_out).enumerate() {
+            let x = x0 + (x1 - x0) * i as f32 / (samples - 1) as f32;
+            self.apply(&[x], out)?;
+        }
+        Ok(move |x: f32| {
+            let t = if x1 > x0 { ((x - x0) / (x1 - x0)).clamp(0., 1.) } else { 0. };
+            let pos = t * (samples - 1) as f32;
+            let i = (pos as usize).min(samples - 2);
+            let f = pos - i as f32;
+            let (a, b) = table[i * n_out ..].split_at(n_out);
+            a.iter().zip(b).map(|(&a, &b)| a + (b - a) * f).collect()
+        })
+    }
 }
 impl FromDict for Function {
     fn from_dict(dict: Dictionary, resolve: &impl Resolve) -> Result<Self> {
@@ -100,7 +139,8 @@ impl FromDict for Function {
                     (Some(range), _, _) => range.len() / 2,
                     (_, Some(c0), _) => c0.len(),
                     (_, _, Some(c1)) => c1.len(),
-                    _ => bail!("unknown dimensions")
+                    // C0 and C1 default to [0] and [1]
+                    _ => 1
                 };
                 let mut parts = Vec::with_capacity(n_dim);
                 let input_range = (raw.domain[0], raw.domain[1]);
@@ -352,7 +392,10 @@ pub struct InterpolatedFunctionDim {
     pub exponent: f32,
 }
 impl InterpolatedFunctionDim {
+    /// `c0 + x^exponent * (c1 - c0)` with `x` clamped to the domain and the result to the range.
     pub fn apply(&self, x: f32) -> f32 {
+        let (x0, x1) = self.input_range;
+        let x = x.min(x1).max(x0);
         let y = self.c0 + x.powf(self.exponent) * (self.c1 - self.c0);
         let (y0, y1) = self.output_range;
         y.min(y1).max(y0)
@@ -475,3 +518,54 @@ impl PsOp {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, ParseFlags};
+
+    #[test]
+    fn lut() {
+        let p = parse(b"<< /FunctionType 2 /Domain [0 2] /C0 [0 1] /C1 [1 0] /N 2 >>", &NoResolve, ParseFlags::ANY).unwrap();
+        let f = Function::from_primitive(p, &NoResolve).unwrap();
+        assert_eq!(f.domain(), Some((0., 2.)));
+        let lut = f.to_lut(65).unwrap();
+        for &x in &[0., 0.3, 1., 1.77, 2.] {
+            let mut out = [0.; 2];
+            f.apply(&[x], &mut out).unwrap();
+            let y = lut(x);
+            assert!(y.iter().zip(&out).all(|(a, b)| (a - b).abs() < 1e-3), "{} {:?} {:?}", x, y, out);
+        }
+        // clamped to the domain
+        assert_eq!(lut(-1.), lut(0.));
+        assert_eq!(lut(5.), lut(2.));
+
+        // no outputs
+        let p = parse(b"<< /FunctionType 2 /Domain [0 1] /C0 [] /C1 [] /N 1 >>", &NoResolve, ParseFlags::ANY).unwrap();
+        let f = Function::from_primitive(p, &NoResolve).unwrap();
+        assert_eq!(f.output_dim(), 0);
+        assert!(f.to_lut(65).is_err());
+    }
+
+    #[test]
+    fn exponential() {
+        let eval = |dict: &[u8], x: f32| {
+            let f = Function::from_primitive(parse(dict, &NoResolve, ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+            let mut out = [0.; 1];
+            f.apply(&[x], &mut out).unwrap();
+            out[0]
+        };
+        let square = b"<< /FunctionType 2 /Domain [0 1] /C0 [0.2] /C1 [1] /N 2 >>";
+        assert_eq!(eval(square, 0.), 0.2);
+        assert_eq!(eval(square, 1.), 1.);
+        assert!((eval(square, 0.5) - 0.4).abs() < 1e-6);
+        assert_eq!(eval(square, 2.), 1.);
+
+        // non-integer exponents, and the default C0 [0] and C1 [1]
+        let root = b"<< /FunctionType 2 /Domain [0 1] /N 0.5 >>";
+        assert!((eval(root, 0.25) - 0.5).abs() < 1e-6);
+        assert_eq!(eval(root, -1.), 0.);
+        let linear = b"<< /FunctionType 2 /Domain [0 1] /C0 [1] /C1 [0] /N 1 >>";
+        assert!((eval(linear, 0.25) - 0.75).abs() < 1e-6);
+    }
+}
@@ -37,6 +37,12 @@ pub struct ParseOptions {
     pub allow_xref_error: bool,
     pub allow_invalid_ops: bool,
     pub allow_missing_endobj: bool,
+    /// Maximum number of operators in a content stream.
+    pub max_content_ops: Option<usize>,
+    /// Maximum nesting depth of `q`/`Q` and of marked content in a content stream.
+    pub max_content_nesting: Option<usize>,
+    /// Maximum length of a file in bytes. Larger files are refused when they are loaded.
+    pub max_file_size: Option<usize>,
 }
 impl ParseOptions {
     pub const fn tolerant() -> Self {
@@ -45,6 +51,9 @@ impl ParseOptions {
             allow_xref_error: true,
             allow_invalid_ops: true,
             allow_missing_endobj: true,
+            max_content_ops: None,
+            max_content_nesting: None,
+            max_file_size: None,
         }
     }
     pub const fn strict() -> Self {
@@ -53,6 +62,9 @@ impl ParseOptions {
             allow_xref_error: false,
             allow_invalid_ops: true,
             allow_missing_endobj: false,
+            max_content_ops: None,
+            max_content_nesting: None,
+            max_file_size: None,
         }
     }
 }
@@ -63,7 +75,13 @@ pub trait Resolve: {
         self.resolve_flags(r, ParseFlags::ANY, 16)
     }
     fn get<T: Object+DataSize>(&self, r: Ref<T>) -> Result<RcRef<T>>;
+    /// Get all of `refs`, in the same order. Equivalent to calling `get` for each of them,
+    /// but implementations may load the objects more efficiently.
+    fn get_many<T: Object+DataSize>(&self, refs: &[Ref<T>]) -> Result<Vec<RcRef<T>>> {
+        refs.iter().map(|&r| self.get(r)).collect()
+    }
     fn options(&self) -> &ParseOptions;
+    /// Read the (decrypted) bytes of stream `id` in `range` without applying any filters.
     fn stream_data(&self, id: PlainRef, range: Range<usize>) -> Result<Arc<[u8]>>;
     fn get_data_or_decode(&self, id: PlainRef, range: Range<usize>, filters: &[StreamFilter]) -> Result<Arc<[u8]>>;
 }
@@ -495,6 +513,15 @@ impl<T> Default for Lazy<T> {
         }
     }
 }
+impl<T: Object> From<Ref<T>> for Lazy<T> {
+    fn from(value: Ref<T>) -> Self {
+        Lazy {
+            primitive: Primitive::Reference(value.inner),
+            cache: OnceCell::new(),
+            _marker: PhantomData
+        }
+    }
+}
 impl<T: Object> From<RcRef<T>> for Lazy<T> {
     fn from(value: RcRef<T>) -> Self {
         Lazy {
@@ -637,6 +664,35 @@ impl<T: Trace> Trace for Vec<T> {
         }
     }
 }
+/// An array of exactly `N` elements.
+impl<T: Object, const N: usize> Object for [T; N] {
+    fn from_primitive(p: Primitive, r: &impl Resolve) -> Result<Self> {
+        let arr = p.resolve(r)?.into_array()?;
+        if arr.len() != N {
+            bail!("expected array of length {} (found {})", N, arr.len());
+        }
+        let items = arr.into_iter().map(|p| T::from_primitive(p, r)).collect::<Result<Vec<T>>>()?;
+        Ok(items.try_into().unwrap_or_else(|_| unreachable!()))
+    }
+}
+impl<T: ObjectWrite, const N: usize> ObjectWrite for [T; N] {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        Primitive::array::<T, _, _, _>(self.iter(), update)
+    }
+}
+impl<T: DeepClone, const N: usize> DeepClone for [T; N] {
+    fn deep_clone(&self, cloner: &mut impl Cloner) -> Result<Self> {
+        let items = self.iter().map(|t| t.deep_clone(cloner)).collect::<Result<Vec<T>>>()?;
+        Ok(items.try_into().unwrap_or_else(|_| unreachable!()))
+    }
+}
+impl<T: Trace, const N: usize> Trace for [T; N] {
+    fn trace(&self, cb: &mut impl FnMut(PlainRef)) {
+        for i in self.iter() {
+            i.trace(cb);
+        }
+    }
+}
 /*
 pub struct Data(pub Vec<u8>);
 impl Object for Data {
@@ -694,6 +750,7 @@ impl Trace for Primitive {
             Primitive::Reference(r) => cb(r),
             Primitive::Array(ref parts) => parts.iter().for_each(|p| p.trace(cb)),
             Primitive::Dictionary(ref dict) => dict.values().for_each(|p| p.trace(cb)),
+            Primitive::Stream(ref s) => s.info.values().for_each(|p| p.trace(cb)),
             _ => ()
         }
     }
@@ -845,7 +902,32 @@ macro_rules! deep_clone_simple {
         )*
     )
 }
-deep_clone_simple!(f32, i32, u32, bool, Name, (), Date, PdfString, Rectangle, u8, Arc<[u8]>, Vec<u16>);
+deep_clone_simple!(f32, i32, u32, bool, Name, (), Date, PdfString, Rectangle, u8, usize, Arc<[u8]>, Vec<u16>);
+
+macro_rules! trace_simple {
+    ($($t:ty),*) => (
+        $(
+            impl Trace for $t {}
+        )*
+    )
+}
+trace_simple!(f32, i32, u32, usize, bool, Name, Date, PdfString, Rectangle, u8);
+
+impl Trace for Dictionary {
+    fn trace(&self, cb: &mut impl FnMut(PlainRef)) {
+        self.values().for_each(|p| p.trace(cb));
+    }
+}
+impl<V: Trace> Trace for HashMap<Name, V> {
+    fn trace(&self, cb: &mut impl FnMut(PlainRef)) {
+        self.values().for_each(|v| v.trace(cb));
+    }
+}
+impl<T> Trace for Lazy<T> {
+    fn trace(&self, cb: &mut impl FnMut(PlainRef)) {
+        self.primitive.trace(cb);
+    }
+}
 
 impl<A: DeepClone, B: DeepClone> DeepClone for (A, B) {
     fn deep_clone(&self, cloner: &mut impl Cloner) -> Result<Self> {
@@ -41,6 +41,7 @@ impl<I: Object> Stream<I> {
                 filters,
                 file: None,
                 file_filters: Vec::new(),
+                decoded_length: None,
                 info: i
             },
             inner_data: StreamData::Generated(data.into()),
@@ -52,6 +53,7 @@ impl<I: Object> Stream<I> {
                 filters: Vec::new(),
                 file: None,
                 file_filters: Vec::new(),
+                decoded_length: None,
                 info: i
             },
             inner_data: StreamData::Generated(data.into()),
@@ -64,6 +66,7 @@ impl<I: Object> Stream<I> {
                 filters: filters.clone(),
                 file: None,
                 file_filters: Vec::new(),
+                decoded_length: None,
                 info: i
             },
             inner_data: StreamData::Generated(data.into()),
@@ -91,6 +94,26 @@ impl<I: Object> Stream<I> {
         }
     }
 
+    /// The filters the data is encoded with (`/Filter` and `/DecodeParms`), in the order they are applied when decoding.
+    pub fn filters(&self) -> &[StreamFilter] {
+        &self.info.filters
+    }
+
+    /// The stream data as stored in the file, after decryption but before any filters are applied.
+    pub fn raw_data(&self, resolve: &impl Resolve) -> Result<Arc<[u8]>> {
+        match self.inner_data {
+            StreamData::Generated(ref data) => Ok(data.clone()),
+            StreamData::Original(ref file_range, id) => resolve.stream_data(id, file_range.clone())
+        }
+    }
+
+    /// The expected size of the decoded data, from `/DL`.
+    ///
+    /// Useful to pre-size buffers and for progress reporting, but not guaranteed to be correct.
+    pub fn decoded_len_hint(&self) -> Option<usize> {
+        self.info.decoded_length
+    }
+
     pub fn len(&self) -> usize {
         match self.inner_data {
             StreamData::Generated(ref data) => data.len(),
@@ -119,33 +142,20 @@ impl<I: ObjectWrite> Stream<I> {
             Primitive::Null => Dictionary::new(),
             p => bail!("stream info has to be a dictionary (found {:?})", p)
         };
-        let mut params = None;
+        let mut params = Vec::with_capacity(self.info.filters.len());
         if self.info.filters.len() > 0 {
             for f in self.info.filters.iter() {
-                if let Some(para) = match f {
-                    StreamFilter::LZWDecode(ref p) => Some(p.to_primitive(update)?),
-                    StreamFilter::FlateDecode(ref p) => Some(p.to_primitive(update)?),
-                    StreamFilter::DCTDecode(ref p) => Some(p.to_primitive(update)?),
-                    StreamFilter::CCITTFaxDecode(ref p) => Some(p.to_primitive(update)?),
-                    StreamFilter::JBIG2Decode(ref p) => Some(p.to_primitive(update)?),
-                    _ => None
-                } {
-                    assert!(params.is_none());
-                    params = Some(para);
-                }
+                params.push(match f {
+                    StreamFilter::LZWDecode(ref p) => p.to_primitive(update)?,
+                    StreamFilter::FlateDecode(ref p) => p.to_primitive(update)?,
+                    StreamFilter::DCTDecode(ref p) => p.to_primitive(update)?,
+                    StreamFilter::CCITTFaxDecode(ref p) => p.to_primitive(update)?,
+                    StreamFilter::JBIG2Decode(ref p) => p.to_primitive(update)?,
+                    StreamFilter::Crypt(ref p) => p.to_primitive(update)?,
+                    _ => Primitive::Null
+                });
             }
-            let mut filters = self.info.filters.iter().map(|filter| match filter {
-                StreamFilter::ASCIIHexDecode => "ASCIIHexDecode",
-                StreamFilter::ASCII85Decode => "ASCII85Decode",
-                StreamFilter::LZWDecode(ref _p) => "LZWDecode",
-                StreamFilter::FlateDecode(ref _p) => "FlateDecode",
-                StreamFilter::JPXDecode => "JPXDecode",
-                StreamFilter::DCTDecode(ref _p) => "DCTDecode",
-                StreamFilter::CCITTFaxDecode(ref _p) => "CCITTFaxDecode",
-                StreamFilter::JBIG2Decode(ref _p) => "JBIG2Decode",
-                StreamFilter::Crypt => "Crypt",
-                StreamFilter::RunLengthDecode => "RunLengthDecode",
-            })
+            let mut filters = self.info.filters.iter().map(StreamFilter::name)
             .map(|s| Primitive::Name(s.into()));
             match self.info.filters.len() {
                 0 => {},
@@ -157,9 +167,17 @@ impl<I: ObjectWrite> Stream<I> {
                 }
             }
         }
-        if let Some(para) = params {
+        // one entry per filter, unless none of the filters has params
+        if params.iter().any(|p| !matches!(p, Primitive::Null)) {
+            let para = match params.len() {
+                1 => params.pop().unwrap(),
+                _ => Primitive::Array(params)
+            };
             info.insert("DecodeParms", para);
         }
+        if let Some(len) = self.info.decoded_length {
+            info.insert("DL", Primitive::Integer(len as _));
+        }
 
         let inner = match self.inner_data {
             StreamData::Generated(ref data) => {
@@ -213,17 +231,9 @@ pub struct StreamInfo<I> {
     /// Filters to apply to external file specified in `file`.
     pub file_filters: Vec<StreamFilter>,
 
-    // TODO:
-    /*
-    /// Filters to apply to external file specified in `file`.
-    #[pdf(key="FFilter")]
-    file_filters: Vec<StreamFilter>,
-    #[pdf(key="FDecodeParms")]
-    file_decode_parms: Vec<DecodeParms>,
-    /// Number of bytes in the decoded stream
-    #[pdf(key="DL")]
-    dl: Option<usize>,
-    */
+    /// Number of bytes in the decoded stream (`/DL`). This is only a hint.
+    pub decoded_length: Option<usize>,
+
     // Specialized dictionary entries
     pub info: I,
 }
@@ -241,6 +251,7 @@ impl<I: Default> Default for StreamInfo<I> {
             filters: Vec::new(),
             file: None,
             file_filters: Vec::new(),
+            decoded_length: None,
             info: I::default(),
         }
     }
@@ -266,11 +277,8 @@ impl<T: Object> Object for StreamInfo<T> {
             dict.remove("Length").ok_or(PdfError::MissingEntry{ typ: "StreamInfo", field: "Length".into() })?,
             resolve)?;
 
-        let filters = Vec::<Name>::from_primitive(
+        let filters = filters_from_primitive(
             dict.remove("Filter").unwrap_or(Primitive::Null),
-            resolve)?;
-
-        let decode_params = Vec::<Option<Dictionary>>::from_primitive(
             dict.remove("DecodeParms").unwrap_or(Primitive::Null),
             resolve)?;
 
@@ -278,44 +286,40 @@ impl<T: Object> Object for StreamInfo<T> {
             dict.remove("F").unwrap_or(Primitive::Null),
             resolve)?;
 
-        let file_filters = Vec::<Name>::from_primitive(
+        let file_filters = filters_from_primitive(
             dict.remove("FFilter").unwrap_or(Primitive::Null),
-            resolve)?;
-
-        let file_decode_params = Vec::<Dictionary>::from_primitive(
             dict.remove("FDecodeParms").unwrap_or(Primitive::Null),
             resolve)?;
 
-
-        let mut new_filters = Vec::new();
-        let mut new_file_filters = Vec::new();
-
-        for (i, filter) in filters.iter().enumerate() {
-            let params = match decode_params.get(i) {
-                Some(Some(params)) => params.clone(),
-                _ => Dictionary::default(),
-            };
-            new_filters.push(StreamFilter::from_kind_and_params(filter, params, resolve)?);
-        }
-        for (i, filter) in file_filters.iter().enumerate() {
-            let params = match file_decode_params.get(i) {
-                Some(params) => params.clone(),
-                None => Dictionary::default(),
-            };
-            new_file_filters.push(StreamFilter::from_kind_and_params(filter, params, resolve)?);
-        }
+        let decoded_length = Option::<usize>::from_primitive(
+            dict.remove("DL").unwrap_or(Primitive::Null),
+            resolve)?;
 
         Ok(StreamInfo {
             // General
-            filters: new_filters,
+            filters,
             file,
-            file_filters: new_file_filters,
+            file_filters,
+            decoded_length,
             // Special
             info: T::from_primitive(Primitive::Dictionary (dict), resolve)?,
         })
     }
 }
 
+/// Builds the filter chain from a `/Filter` entry (a name or an array of names) and the
+/// matching `/DecodeParms` entry. For a chain of filters, the params are an array parallel
+/// to the filters, where `null` stands for filters without params.
+pub(crate) fn filters_from_primitive(filter: Primitive, params: Primitive, resolve: &impl Resolve) -> Result<Vec<StreamFilter>> {
+    let filters = Vec::<Name>::from_primitive(filter, resolve)?;
+    let params = Vec::<Option<Dictionary>>::from_primitive(params, resolve)?;
+
+    filters.iter().enumerate().map(|(i, filter)| {
+        let params = params.get(i).cloned().flatten().unwrap_or_default();
+        StreamFilter::from_kind_and_params(filter, params, resolve)
+    }).collect()
+}
+
 #[derive(Object, Default, Debug, DataSize)]
 #[pdf(Type = "ObjStm")]
 pub struct ObjStmInfo {
@@ -346,14 +350,26 @@ impl Object for ObjectStream {
     fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<ObjectStream> {
         let stream: Stream<ObjStmInfo> = Stream::from_primitive(p, resolve)?;
 
-        let mut offsets = Vec::new();
+        let data = stream.data(resolve)?;
+        let (num_objects, first) = (stream.info.num_objects, stream.info.first);
+        if first > data.len() {
+            bail!("object stream /First {} is beyond the end of its data ({} bytes)", first, data.len());
+        }
+        // every entry of the header takes at least four bytes ("1 0 ")
+        if num_objects > (first + 1) / 4 {
+            bail!("object stream /N {} does not fit in a header of {} bytes", num_objects, first);
+        }
+
+        let mut offsets = Vec::with_capacity(num_objects);
         {
             debug!("parsing stream");
-            let data = stream.data(resolve)?;
-            let mut lexer = Lexer::new(&data);
-            for _ in 0..(stream.info.num_objects as ObjNr) {
+            let mut lexer = Lexer::new(&data[.. first]);
+            for _ in 0 .. num_objects {
                 let _obj_nr = lexer.next()?.to::<ObjNr>()?;
                 let offset = lexer.next()?.to::<usize>()?;
+                if offset > data.len() - first {
+                    bail!("object stream offset {} is beyond the end of its data ({} bytes)", offset, data.len() - first);
+                }
                 offsets.push(offset);
             }
         }
@@ -389,3 +405,53 @@ impl ObjectStream {
         self.inner.data(resolve)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object_stream(n: usize, first: usize, data: &[u8]) -> Result<ObjectStream> {
+        let mut info = Dictionary::new();
+        info.insert("Type", Primitive::name("ObjStm"));
+        info.insert("N", Primitive::Integer(n as i32));
+        info.insert("First", Primitive::Integer(first as i32));
+        info.insert("Length", Primitive::Integer(data.len() as i32));
+        let stream = PdfStream { info, inner: StreamInner::Pending { data: data.into() } };
+        ObjectStream::from_primitive(Primitive::Stream(stream), &NoResolve)
+    }
+
+    #[test]
+    fn object_stream_header() {
+        let data = b"10 0 11 3 42 (a)";
+        let stream = object_stream(2, 10, data).unwrap();
+        assert_eq!(stream.n_objects(), 2);
+        let (data, range) = stream.get_object_slice(1, &NoResolve).unwrap();
+        assert_eq!(&data[range], b"(a)");
+
+        // a huge /N is refused before anything is parsed
+        assert!(object_stream(1 << 30, 10, b"10 0 11 3 42 (a)").is_err());
+        assert!(object_stream(2, 100, b"10 0 11 3 42 (a)").is_err());
+        assert!(object_stream(2, 10, b"10 0 11 99 42 (a)").is_err());
+        // entries after /First are not part of the header
+        assert!(object_stream(2, 5, b"10 0 11 3 42 (a)").is_err());
+    }
+
+    #[test]
+    fn unsupported_filter() {
+        let stream = |filter: &str| {
+            let mut info = Dictionary::new();
+            info.insert("Filter", crate::parser::parse(filter.as_bytes(), &NoResolve, crate::parser::ParseFlags::ANY).unwrap());
+            info.insert("Length", Primitive::Integer(5));
+            let stream = PdfStream { info, inner: StreamInner::Pending { data: b"6869>"[..].into() } };
+            Stream::<()>::from_primitive(Primitive::Stream(stream), &NoResolve).unwrap()
+        };
+        let hex = stream("/ASCIIHexDecode");
+        assert_eq!(&*hex.data(&NoResolve).unwrap(), b"hi");
+
+        let jpx = stream("[/ASCIIHexDecode /JPXDecode]");
+        let names: Vec<_> = jpx.filters().iter().map(StreamFilter::name).collect();
+        assert_eq!(names, ["ASCIIHexDecode", "JPXDecode"]);
+        let err = jpx.data(&NoResolve).unwrap_err();
+        assert!(err.is_unsupported_filter(), "{}", err);
+    }
+}
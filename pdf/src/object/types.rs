@@ -1,14 +1,15 @@
 //! Models of PDF types
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use datasize::DataSize;
 
 use crate as pdf;
 use crate::content::deep_clone_op;
 use crate::object::*;
 use crate::error::*;
-use crate::content::{Content, FormXObject, Matrix, parse_ops, serialize_ops, Op};
+use crate::content::{Content, FormXObject, Matrix, Point, parse_ops, serialize_ops, Op, Color, Rgb, Cmyk};
 use crate::font::Font;
+use crate::text::TextExtractOptions;
 use crate::enc::StreamFilter;
 
 /// Node in a page tree - type is either `Page` or `PageTree`
@@ -104,6 +105,9 @@ impl PagesRc {
     pub fn create(tree: PageTree, update: &mut impl Updater) -> Result<PagesRc> {
         Ok(PagesRc(update.create(PagesNode::Tree(tree))?))
     }
+    pub fn get_ref(&self) -> Ref<PagesNode> {
+        self.0.get_ref()
+    }
 }
 impl Object for PagesRc {
     fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<PagesRc> {
@@ -145,8 +149,15 @@ pub struct Catalog {
     #[pdf(key="Outlines")]
     pub outlines: Option<Outlines>,
 // Threads: array
-// OpenAction: array or dict
-// AA: dict
+
+    /// A destination array or an action dictionary, see `Catalog::open_action`.
+    #[pdf(key="OpenAction")]
+    pub on_open: Option<Primitive>,
+
+    /// Actions on closing, saving and printing the document, keyed by `/WC`, `/WS`, `/DS`, `/WP` and `/DP`.
+    #[pdf(key="AA")]
+    pub additional_actions: Option<Dictionary>,
+
 // URI: dict
 // AcroForm: dict
     #[pdf(key="AcroForm")]
@@ -164,7 +175,9 @@ pub struct Catalog {
 // SpiderInfo: dict
 // OutputIntents: array
 // PieceInfo: dict
-// OCProperties: dict
+    #[pdf(key="OCProperties")]
+    pub optional_content: Option<OptionalContentProperties>,
+
 // Perms: dict
 // Legal: dict
 // Requirements: array
@@ -172,6 +185,121 @@ pub struct Catalog {
 // NeedsRendering: bool
 }
 
+impl Catalog {
+    /// What to do when the document is opened (`/OpenAction`).
+    ///
+    /// A destination array is returned as `Action::Goto`.
+    pub fn open_action(&self, resolve: &impl Resolve) -> Result<Option<Action>> {
+        let Some(ref p) = self.on_open else {
+            return Ok(None);
+        };
+        match p.clone().resolve(resolve)? {
+            p @ Primitive::Array(_) => Ok(Some(Action::Goto(MaybeNamedDest::Direct(t!(Dest::from_primitive(p, resolve)))))),
+            p => Ok(Some(t!(Action::from_primitive(p, resolve))))
+        }
+    }
+
+    /// The page (and point) to show first, or the URI to open, like `Link::target`.
+    ///
+    /// `None` if there is no `/OpenAction`.
+    pub fn open_target(&self, resolve: &impl Resolve) -> Result<Option<LinkTarget>> {
+        let Some(ref p) = self.on_open else {
+            return Ok(None);
+        };
+        let p = p.clone().resolve(resolve)?;
+        let target = match p {
+            Primitive::Array(_) => target(Some(&p), None, resolve, self)?,
+            _ => target(None, Some(&p), resolve, self)?,
+        };
+        Ok(Some(target))
+    }
+
+    /// The decoded XMP packet of `/Metadata`, or `None` if the document has none.
+    pub fn metadata(&self, resolve: &impl Resolve) -> Result<Option<Arc<[u8]>>> {
+        let Some(r) = self.metadata else {
+            return Ok(None);
+        };
+        let stream = t!(resolve.get(r));
+        Ok(Some(t!(Stream::data(&stream, resolve))))
+    }
+
+    /// All optional content groups (layers) of the document, together with their
+    /// visibility in the default configuration.
+    pub fn optional_content(&self, resolve: &impl Resolve) -> Result<Vec<(RcRef<OptionalContentGroup>, bool)>> {
+        let oc = match self.optional_content {
+            Some(ref oc) => oc,
+            None => return Ok(vec![])
+        };
+        oc.groups.iter().map(|&r| {
+            let group = resolve.get(r)?;
+            Ok((group, oc.default_config.is_on(r.get_inner())))
+        }).collect()
+    }
+}
+
+/// `/OCProperties` of the catalog
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+pub struct OptionalContentProperties {
+    #[pdf(key="OCGs")]
+    pub groups: Vec<Ref<OptionalContentGroup>>,
+
+    /// the default viewing configuration
+    #[pdf(key="D")]
+    pub default_config: OptionalContentConfig,
+
+    #[pdf(key="Configs")]
+    pub configs: Vec<OptionalContentConfig>,
+}
+
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+#[pdf(Type = "OCG")]
+pub struct OptionalContentGroup {
+    #[pdf(key="Name")]
+    pub name: PdfString,
+
+    #[pdf(key="Intent")]
+    pub intent: Option<Primitive>,
+
+    #[pdf(key="Usage")]
+    pub usage: Option<Dictionary>,
+}
+
+/// Optional content configuration dictionary
+#[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
+pub struct OptionalContentConfig {
+    #[pdf(key="Name")]
+    pub name: Option<PdfString>,
+
+    #[pdf(key="Creator")]
+    pub creator: Option<PdfString>,
+
+    /// `ON`, `OFF` or `Unchanged`
+    #[pdf(key="BaseState")]
+    pub base_state: Option<Name>,
+
+    #[pdf(key="ON")]
+    pub on: Vec<Ref<OptionalContentGroup>>,
+
+    #[pdf(key="OFF")]
+    pub off: Vec<Ref<OptionalContentGroup>>,
+
+    #[pdf(key="Order")]
+    pub order: Option<Primitive>,
+
+    #[pdf(other)]
+    pub other: Dictionary,
+}
+impl OptionalContentConfig {
+    /// Whether the group `group` is visible in this configuration.
+    pub fn is_on(&self, group: PlainRef) -> bool {
+        let contains = |list: &[Ref<OptionalContentGroup>]| list.iter().any(|r| r.get_inner() == group);
+        match self.base_state.as_deref() {
+            Some("OFF") => contains(&self.on),
+            _ => !contains(&self.off)
+        }
+    }
+}
+
 #[derive(Object, ObjectWrite, Debug, Default, Clone, DataSize)]
 #[pdf(Type = "Pages?")]
 pub struct PageTree {
@@ -192,12 +320,29 @@ pub struct PageTree {
     
     #[pdf(key="CropBox")]
     pub crop_box:   Option<Rectangle>,
+
+    #[pdf(key="Rotate")]
+    pub rotate: Option<i32>,
 }
 impl PageTree {
+    /// Page `page_nr` of this tree.
+    ///
+    /// Only the subtrees on the way to the page are read, skipping others by their `/Count`.
+    /// If the counts turn out to be wrong, or `page_nr` is beyond the `/Count` of this tree,
+    /// the pages are counted one by one instead.
     pub fn page(&self, resolve: &impl Resolve, page_nr: u32) -> Result<PageRc> {
-        self.page_limited(resolve, page_nr, 16)
+        if page_nr < self.count {
+            if let Some(page) = self.page_limited(resolve, page_nr, 16)? {
+                return Ok(page);
+            }
+            warn!("/Count of the page tree is wrong, counting the pages instead");
+        }
+        let mut pos = 0;
+        self.page_linear(resolve, page_nr, &mut pos, 16)?
+            .ok_or(PdfError::PageOutOfBounds { page_nr, max: pos })
     }
-    fn page_limited(&self, resolve: &impl Resolve, page_nr: u32, depth: usize) -> Result<PageRc> {
+    /// `None` if the `/Count`s don't add up.
+    fn page_limited(&self, resolve: &impl Resolve, page_nr: u32, depth: usize) -> Result<Option<PageRc>> {
         if depth == 0 {
             bail!("page tree depth exeeded");
         }
@@ -206,6 +351,10 @@ impl PageTree {
             let node = resolve.get(kid)?;
             match *node {
                 PagesNode::Tree(ref tree) => {
+                    // every kid has at least one page
+                    if tree.kids.len() > tree.count as usize {
+                        return Ok(None);
+                    }
                     if (pos .. pos + tree.count).contains(&page_nr) {
                         return tree.page_limited(resolve, page_nr - pos, depth - 1);
                     }
@@ -213,13 +362,34 @@ impl PageTree {
                 }
                 PagesNode::Leaf(ref _page) => {
                     if pos == page_nr {
-                        return Ok(PageRc(node));
+                        return Ok(Some(PageRc(node)));
                     }
                     pos += 1;
                 }
             }
         }
-        Err(PdfError::PageOutOfBounds {page_nr, max: pos})
+        Ok(None)
+    }
+    /// Find page `page_nr`, with `pos` pages before this tree, ignoring `/Count`.
+    fn page_linear(&self, resolve: &impl Resolve, page_nr: u32, pos: &mut u32, depth: usize) -> Result<Option<PageRc>> {
+        if depth == 0 {
+            bail!("page tree depth exeeded");
+        }
+        for &kid in &self.kids {
+            let node = resolve.get(kid)?;
+            match *node {
+                PagesNode::Tree(ref tree) => if let Some(page) = tree.page_linear(resolve, page_nr, pos, depth - 1)? {
+                    return Ok(Some(page));
+                }
+                PagesNode::Leaf(_) => {
+                    if *pos == page_nr {
+                        return Ok(Some(PageRc(node)));
+                    }
+                    *pos += 1;
+                }
+            }
+        }
+        Ok(None)
     }
 
     /*
@@ -282,9 +452,14 @@ pub struct Page {
     #[pdf(key="Contents")]
     pub contents:   Option<Content>,
 
+    /// Use `rotate()` to include an inherited value.
     #[pdf(key="Rotate", default="0")]
     pub rotate: i32,
 
+    /// Size of a default user space unit in multiples of 1/72 inch. Use `user_unit()` to get the effective value.
+    #[pdf(key="UserUnit")]
+    pub user_unit: Option<f32>,
+
     #[pdf(key="Metadata")]
     pub metadata:   Option<Primitive>,
 
@@ -300,18 +475,6 @@ pub struct Page {
     #[pdf(other)]
     pub other: Dictionary,
 }
-fn inherit<'a, T: 'a, F>(mut parent: &'a PageTree, f: F) -> Result<Option<T>>
-    where F: Fn(&'a PageTree) -> Option<T>
-{
-    loop {
-        match (&parent.parent, f(parent)) {
-            (_, Some(t)) => return Ok(Some(t)),
-            (Some(ref p), None) => parent = p,
-            (None, None) => return Ok(None)
-        }
-    }
-}
-
 impl Page {
     pub fn new(parent: PagesRc) -> Page {
         Page {
@@ -322,6 +485,7 @@ impl Page {
             resources:  None,
             contents:   None,
             rotate:     0,
+            user_unit:  None,
             metadata:   None,
             lgi:        None,
             vp:         None,
@@ -329,29 +493,189 @@ impl Page {
             annotations: Default::default(),
         }
     }
+    /// Call `f` on the ancestors of this page, nearest first, until it returns a value.
+    ///
+    /// A `/Parent` loop is reported as an error.
+    fn inherit<'a, T, F>(&'a self, mut f: F) -> Result<Option<T>>
+        where F: FnMut(&'a PagesRc) -> Result<Option<T>>
+    {
+        let mut visited = HashSet::new();
+        let mut next = Some(&self.parent);
+        while let Some(node) = next {
+            let r = node.get_ref().get_inner();
+            if !visited.insert(r) {
+                bail!("the page tree has a cycle at {:?}", r);
+            }
+            if let Some(t) = f(node)? {
+                return Ok(Some(t));
+            }
+            next = node.parent.as_ref();
+        }
+        Ok(None)
+    }
     pub fn media_box(&self) -> Result<Rectangle> {
         match self.media_box {
             Some(b) => Ok(b),
-            None => inherit(&self.parent, |pt| pt.media_box)?
+            None => self.inherit(|pt| Ok(pt.media_box))?
                 .ok_or_else(|| PdfError::MissingEntry { typ: "Page", field: "MediaBox".into() })
         }
     }
+    /// The media box of the page, or US Letter (612 by 792) with a warning if neither the page
+    /// nor any of its ancestors have one.
+    pub fn media_box_or_letter(&self) -> Rectangle {
+        match self.media_box() {
+            Ok(b) => b,
+            Err(e) => {
+                warn!("{}, using US Letter", e);
+                Rectangle { left: 0., bottom: 0., right: 612., top: 792. }
+            }
+        }
+    }
     pub fn crop_box(&self) -> Result<Rectangle> {
         match self.crop_box {
             Some(b) => Ok(b),
-            None => match inherit(&self.parent, |pt| pt.crop_box)? {
+            None => match self.inherit(|pt| Ok(pt.crop_box))? {
                 Some(b) => Ok(b),
                 None => self.media_box()
             }
         }
     }
+    /// The `/Rotate` of this page or the nearest ancestor that has one, 0 if none do.
+    ///
+    /// A `/Rotate 0` on the page itself reads the same as a missing entry,
+    /// so the ancestors are asked in that case too.
+    pub fn rotate(&self) -> i32 {
+        if self.rotate != 0 {
+            return self.rotate;
+        }
+        match self.inherit(|pt| Ok(pt.rotate)) {
+            Ok(r) => r.unwrap_or(0),
+            Err(e) => {
+                warn!("{}", e);
+                0
+            }
+        }
+    }
+    /// Look up an inheritable entry (7.7.3.4) by key, for entries that have no field in `PageTree`.
+    ///
+    /// The page itself is checked first, then the raw dictionaries of the ancestor `Pages` nodes,
+    /// nearest first.
+    pub fn inherited<T: Object>(&self, key: &str, resolve: &impl Resolve) -> Result<Option<T>> {
+        if let Some(p) = t!(self.own_entry(key)) {
+            return Ok(Some(t!(T::from_primitive(p, resolve))));
+        }
+        self.inherit(|node| {
+            let mut dict = t!(t!(resolve.resolve(node.get_ref().get_inner())).into_dictionary());
+            match dict.remove(key) {
+                None | Some(Primitive::Null) => Ok(None),
+                Some(p) => Ok(Some(t!(T::from_primitive(p, resolve))))
+            }
+        })
+    }
+    /// The entry `key` of the page's own dictionary.
+    fn own_entry(&self, key: &str) -> Result<Option<Primitive>> {
+        let mut update = NoUpdate;
+        Ok(match key {
+            "MediaBox" => self.media_box.map(|b| b.to_primitive(&mut update)).transpose()?,
+            "CropBox" => self.crop_box.map(|b| b.to_primitive(&mut update)).transpose()?,
+            "Resources" => self.resources.as_ref().map(|r| r.to_primitive(&mut update)).transpose()?,
+            "Rotate" => Some(self.rotate).filter(|&r| r != 0).map(Primitive::Integer),
+            _ => self.other.get(key).cloned()
+        })
+    }
+    /// The `/UserUnit` of this page, 1.0 if not set.
+    pub fn user_unit(&self) -> f32 {
+        self.user_unit.unwrap_or(1.0)
+    }
+    /// Width and height of the visible page area (the crop box) in points (1/72 inch),
+    /// taking `/UserUnit` and `/Rotate` into account.
+    pub fn physical_size(&self) -> Result<(f32, f32)> {
+        let crop_box = self.crop_box()?;
+        let scale = self.user_unit();
+        let width = crop_box.width() * scale;
+        let height = crop_box.height() * scale;
+        match self.rotate().rem_euclid(360) {
+            90 | 270 => Ok((height, width)),
+            _ => Ok((width, height))
+        }
+    }
     pub fn resources(&self) -> Result<&MaybeRef<Resources>> {
         match self.resources {
             Some(ref r) => Ok(r),
-            None => inherit(&self.parent, |pt| pt.resources.as_ref())?
+            None => self.inherit(|pt| Ok(pt.resources.as_ref()))?
                 .ok_or_else(|| PdfError::MissingEntry { typ: "Page", field: "Resources".into() })
         }
     }
+    /// The text shown on this page, in content stream order.
+    ///
+    /// Spaces and line breaks are inserted where the glyphs are far enough apart, as set by `options`.
+    /// Text in form XObjects is included.
+    pub fn extract_text(&self, resolve: &impl Resolve, options: &TextExtractOptions) -> Result<String> {
+        let ops = match self.contents {
+            Some(ref contents) => t!(contents.operations(resolve)),
+            None => return Ok(String::new())
+        };
+        crate::text::extract_text(&ops, self.resources()?, options, resolve)
+    }
+    /// The box around everything drawn on the page: paths, text (as far as the font metrics
+    /// tell) and images, in default user space and clipped to the media box.
+    ///
+    /// `None` if the page paints nothing.
+    pub fn content_bbox(&self, resolve: &impl Resolve) -> Result<Option<Rectangle>> {
+        let ops = match self.contents {
+            Some(ref contents) => t!(contents.operations(resolve)),
+            None => return Ok(None)
+        };
+        crate::bbox::content_bbox(&ops, self.resources()?, self.media_box()?, resolve)
+    }
+    /// The paths drawn on the page as an SVG document, see `svg::content_svg`.
+    ///
+    /// This is not a full rendering: only paths are exported, text, images and shadings
+    /// are left out. The output covers the media box, one unit per point unless `transform` is given;
+    /// for example `Matrix { a: dpi / 72., d: dpi / 72., ..Matrix::default() }` scales it to `dpi`.
+    pub fn to_svg(&self, resolve: &impl Resolve, transform: Option<Matrix>) -> Result<String> {
+        let ops = match self.contents {
+            Some(ref contents) => t!(contents.operations(resolve)),
+            None => vec![]
+        };
+        crate::svg::content_svg(&ops, self.resources()?, self.media_box()?, transform.unwrap_or_default(), resolve)
+    }
+    /// The link annotations of the page with their targets.
+    ///
+    /// Destinations of `/Dest` and of `/GoTo` actions are resolved to page numbers, looking up
+    /// named destinations in `catalog`. Links that cannot be resolved have `LinkTarget::Other`.
+    pub fn links(&self, resolve: &impl Resolve, catalog: &Catalog) -> Result<Vec<Link>> {
+        let annotations = t!(self.annotations.load(resolve));
+        let mut links = vec![];
+        for annot in annotations.iter() {
+            if annot.subtype.as_str() != "Link" {
+                continue;
+            }
+            let Some(rect) = annot.rect else {
+                continue;
+            };
+            let target = link_target(annot, resolve, catalog).unwrap_or_else(|e| {
+                warn!("invalid link: {:?}", e);
+                LinkTarget::Other
+            });
+            links.push(Link { rect, target });
+        }
+        Ok(links)
+    }
+    /// The thumbnail image of this page (`/Thumb`), or `None` if the page has none.
+    ///
+    /// Thumbnails are image XObjects, but usually lack the `/Type` and `/Subtype` entries.
+    pub fn thumbnail(&self, resolve: &impl Resolve) -> Result<Option<ImageXObject>> {
+        let thumb = match self.other.get("Thumb") {
+            None | Some(Primitive::Null) => return Ok(None),
+            Some(p) => p.clone()
+        };
+        let mut stream = t!(PdfStream::from_primitive(thumb, resolve));
+        if !stream.info.contains_key("Subtype") {
+            stream.info.insert("Subtype", Primitive::name("Image"));
+        }
+        Ok(Some(t!(ImageXObject::from_stream(stream, resolve))))
+    }
 }
 impl SubType<PagesNode> for Page {}
 
@@ -379,7 +703,9 @@ pub struct Resources {
     #[pdf(key="Pattern")]
     pub pattern: HashMap<Name, Ref<Pattern>>,
 
-    // shading: Option<Shading>,
+    #[pdf(key="Shading")]
+    pub shadings: HashMap<Name, MaybeRef<Shading>>,
+
     #[pdf(key="XObject")]
     pub xobjects: HashMap<Name, Ref<XObject>>,
     // /XObject is a dictionary that map arbitrary names to XObjects
@@ -474,6 +800,85 @@ impl DeepClone for Pattern {
     }
 }
 
+/// The entries common to all shading dictionaries (Table 78).
+///
+/// Type specific entries like `/Function`, `/Coords` or `/Extend` are kept in `other`.
+#[derive(Object, ObjectWrite, DeepClone, Debug, DataSize, Clone)]
+pub struct ShadingDict {
+    #[pdf(key="ShadingType")]
+    pub shading_type: i32,
+
+    #[pdf(key="ColorSpace")]
+    pub color_space: ColorSpace,
+
+    /// Color of the area outside the shading's domain. Only used by `scn` with a
+    /// shading pattern, never by the `sh` operator.
+    #[pdf(key="Background")]
+    pub background: Option<Vec<f32>>,
+
+    /// Limits the painted area, in the shading's coordinate space.
+    #[pdf(key="BBox")]
+    pub bbox: Option<Rectangle>,
+
+    #[pdf(key="AntiAlias", default="false")]
+    pub anti_alias: bool,
+
+    #[pdf(other)]
+    pub other: Dictionary,
+}
+impl ShadingDict {
+    /// `/Background` as a color in the shading's color space.
+    pub fn background_color(&self) -> Option<Color> {
+        let c = self.background.as_ref()?;
+        Some(match (&self.color_space, &c[..]) {
+            (ColorSpace::DeviceGray, &[gray]) => Color::Gray(gray),
+            (ColorSpace::DeviceRGB, &[red, green, blue]) => Color::Rgb(Rgb { red, green, blue }),
+            (ColorSpace::DeviceCMYK, &[cyan, magenta, yellow, key]) => Color::Cmyk(Cmyk { cyan, magenta, yellow, key }),
+            _ => Color::Other(c.iter().map(|&n| Primitive::Number(n)).collect()),
+        })
+    }
+}
+
+/// A shading (`/Shading` resources and shading patterns). Types 4 to 7 are streams.
+#[derive(Debug, DataSize, Clone)]
+pub enum Shading {
+    Dict(Box<ShadingDict>),
+    Stream(Box<Stream<ShadingDict>>),
+}
+impl Shading {
+    pub fn dict(&self) -> &ShadingDict {
+        match *self {
+            Shading::Dict(ref d) => d,
+            Shading::Stream(ref s) => &s.info.info,
+        }
+    }
+}
+impl Object for Shading {
+    fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
+        match p.resolve(resolve)? {
+            Primitive::Dictionary(dict) => Ok(Shading::Dict(Box::new(t!(ShadingDict::from_dict(dict, resolve))))),
+            Primitive::Stream(s) => Ok(Shading::Stream(Box::new(t!(Stream::from_stream(s, resolve))))),
+            p => Err(PdfError::UnexpectedPrimitive { expected: "Dictionary or Stream", found: p.get_debug_name() })
+        }
+    }
+}
+impl ObjectWrite for Shading {
+    fn to_primitive(&self, update: &mut impl Updater) -> Result<Primitive> {
+        match self {
+            Shading::Dict(ref d) => d.to_primitive(update),
+            Shading::Stream(ref s) => s.to_primitive(update),
+        }
+    }
+}
+impl DeepClone for Shading {
+    fn deep_clone(&self, cloner: &mut impl Cloner) -> Result<Self> {
+        match *self {
+            Shading::Dict(ref d) => Ok(Shading::Dict(d.deep_clone(cloner)?)),
+            Shading::Stream(ref s) => Ok(Shading::Stream(s.deep_clone(cloner)?)),
+        }
+    }
+}
+
 #[derive(Object, ObjectWrite, DeepClone, Debug, DataSize, Copy, Clone)]
 pub enum LineCap {
     Butt = 0,
@@ -528,8 +933,15 @@ pub struct GraphicsStateParameters {
     // TR
     // TR2
     // HT
-    // FL
-    // SM
+
+    /// The flatness tolerance, like the `i` operator (`Op::Flatness`).
+    #[pdf(key="FL")]
+    pub flatness: Option<f32>,
+
+    /// The smoothness tolerance for shadings.
+    #[pdf(key="SM")]
+    pub smoothness: Option<f32>,
+
     // SA
 
     #[pdf(key="BM")]
@@ -554,6 +966,46 @@ pub struct GraphicsStateParameters {
     #[pdf(other)]
     _other: Dictionary
 }
+impl GraphicsStateParameters {
+    /// The soft mask set by `/SMask`.
+    ///
+    /// `None` if the entry is absent and the current mask stays, `Some(None)` for `/None`,
+    /// which removes the current soft mask.
+    pub fn soft_mask(&self, resolve: &impl Resolve) -> Result<Option<Option<SoftMask>>> {
+        match self.smask {
+            None => Ok(None),
+            Some(Primitive::Name(ref name)) if name == "None" => Ok(Some(None)),
+            Some(ref p) => Ok(Some(Some(t!(SoftMask::from_primitive(p.clone(), resolve))))),
+        }
+    }
+}
+
+#[derive(Object, ObjectWrite, DeepClone, Debug, DataSize, Copy, Clone, PartialEq)]
+pub enum SoftMaskType {
+    Alpha,
+    Luminosity,
+}
+
+/// A soft mask dictionary (Table 144)
+#[derive(Object, ObjectWrite, DeepClone, Debug, DataSize, Clone)]
+#[pdf(Type="Mask?")]
+pub struct SoftMask {
+    /// Whether the mask values are the alpha or the luminosity of `group`
+    #[pdf(key="S")]
+    pub subtype: SoftMaskType,
+
+    /// The transparency group XObject defining the mask
+    #[pdf(key="G")]
+    pub group: Ref<FormXObject>,
+
+    /// Backdrop color for `Luminosity` masks, in the group's color space
+    #[pdf(key="BC")]
+    pub backdrop: Option<Vec<f32>>,
+
+    /// A transfer function or `/Identity`
+    #[pdf(key="TR")]
+    pub transfer: Option<Primitive>,
+}
 
 #[derive(Object, Debug, DataSize, DeepClone)]
 #[pdf(is_stream)]
@@ -618,33 +1070,51 @@ impl ImageXObject {
 
     /// Decode everything except for the final image encoding (jpeg, jbig2, jp2k, ...)
     pub fn raw_image_data(&self, resolve: &impl Resolve) -> Result<(Arc<[u8]>, Option<&StreamFilter>)> {
-        match self.inner.inner_data {
-            StreamData::Generated(_) => Ok((self.inner.data(resolve)?, None)),
-            StreamData::Original(ref file_range, id) => {
-                let filters = self.inner.filters.as_slice();
-                // decode all non image filters
-                let end = filters.iter().rposition(|f| match f {
-                    StreamFilter::ASCIIHexDecode => false,
-                    StreamFilter::ASCII85Decode => false,
-                    StreamFilter::LZWDecode(_) => false,
-                    StreamFilter::RunLengthDecode => false,
-                    StreamFilter::Crypt => true,
-                    _ => true
-                }).unwrap_or(filters.len());
-                
-                let (normal_filters, image_filters) = filters.split_at(end);
-                let data = resolve.get_data_or_decode(id, file_range.clone(), normal_filters)?;
+        let filters = self.inner.filters.as_slice();
+        // decode all filters but a trailing image filter, e.g. `/FlateDecode` before `/RunLengthDecode`
+        let end = match filters.last() {
+            Some(
+                StreamFilter::DCTDecode(_) |
+                StreamFilter::CCITTFaxDecode(_) |
+                StreamFilter::JPXDecode |
+                StreamFilter::FlateDecode(_) |
+                StreamFilter::JBIG2Decode(_)
+            ) => filters.len() - 1,
+            _ => filters.len()
+        };
         
-                match image_filters {
-                    [] => Ok((data, None)),
-                    [StreamFilter::DCTDecode(_)] |
-                    [StreamFilter::CCITTFaxDecode(_)] |
-                    [StreamFilter::JPXDecode] |
-                    [StreamFilter::FlateDecode(_)] |
-                    [StreamFilter::JBIG2Decode(_)] => Ok((data, Some(&image_filters[0]))),
-                    _ => bail!("??? filters={:?}", image_filters)
+        let (normal_filters, image_filters) = filters.split_at(end);
+        let data = match self.inner.inner_data {
+            StreamData::Generated(ref data) => {
+                let mut data = data.clone();
+                for filter in normal_filters {
+                    data = t!(crate::enc::decode(&data, filter), filter).into();
                 }
+                data
             }
+            StreamData::Original(ref file_range, id) => resolve.get_data_or_decode(id, file_range.clone(), normal_filters)?,
+        };
+
+        match image_filters {
+            [] => Ok((data, None)),
+            [StreamFilter::DCTDecode(_)] |
+            [StreamFilter::CCITTFaxDecode(_)] |
+            [StreamFilter::JPXDecode] |
+            [StreamFilter::FlateDecode(_)] |
+            [StreamFilter::JBIG2Decode(_)] => Ok((data, Some(&image_filters[0]))),
+            _ => bail!("??? filters={:?}", image_filters)
+        }
+    }
+
+    /// The JPEG 2000 codestream of a `/JPXDecode` image, to be decoded elsewhere.
+    ///
+    /// `None` if the image is encoded differently. Pixels of JPX images are only available
+    /// (with `image_data` and `decode_pixels`) if a decoder has been set with `enc::set_jpx_decoder`,
+    /// otherwise they fail with `PdfError::UnsupportedFilter`.
+    pub fn as_jp2(&self, resolve: &impl Resolve) -> Result<Option<Arc<[u8]>>> {
+        match self.raw_image_data(resolve)? {
+            (data, Some(StreamFilter::JPXDecode)) => Ok(Some(data)),
+            _ => Ok(None)
         }
     }
 
@@ -652,7 +1122,7 @@ impl ImageXObject {
         let (data, filter) = self.raw_image_data(resolve)?;
         let filter = match filter {
             Some(f) => f,
-            None => return Ok(data)
+            None => return Ok(self.truncate_samples(data.to_vec()).into())
         };
         let mut data = match filter {
             StreamFilter::CCITTFaxDecode(ref params) => {
@@ -672,7 +1142,7 @@ impl ImageXObject {
                 let global_data = p.globals.as_ref().map(|s| s.data(resolve)).transpose()?;
                 jbig2_decode(&data, global_data.as_deref().unwrap_or_default())?
             },
-            StreamFilter::FlateDecode(ref p) => flate_decode(&data, p)?,
+            StreamFilter::FlateDecode(ref p) => self.truncate_samples(flate_decode(&data, p)?),
             _ => unreachable!()
         };
         if let Some(ref decode) = self.decode {
@@ -682,6 +1152,214 @@ impl ImageXObject {
         }
         Ok(data.into())
     }
+
+    /// Decode the image into RGBA pixels, applying the filters, the color space and `/Decode`.
+    ///
+    /// Inline images (`Op::InlineImage`) may name a color space of the `/ColorSpace` resources,
+    /// which are looked up in `resources`. Image masks give black where paint is applied and
+    /// transparent pixels elsewhere. `/Mask` and `/SMask` are not applied, see `soft_mask`.
+    pub fn decode_pixels(&self, resolve: &impl Resolve, resources: Option<&Resources>) -> Result<ImagePixels> {
+        let data = t!(self.image_data(resolve));
+        let (width, height) = (self.width as usize, self.height as usize);
+        // image_data already inverted 1 bit images with `/Decode [1 0]`
+        let inverted = self.bits_per_component == Some(1) && self.decode.as_deref() == Some(&[1.0, 0.0]);
+
+        if self.image_mask {
+            let painted = if !inverted && self.decode.as_deref() == Some(&[1.0, 0.0]) { 1 } else { 0 };
+            let row_len = width.div_ceil(8);
+            if data.len() < row_len * height {
+                bail!("image mask data too short: {} < {}", data.len(), row_len * height);
+            }
+            let mut out = Vec::with_capacity(width * height * 4);
+            for row in data.chunks_exact(row_len).take(height) {
+                for x in 0 .. width {
+                    let bit = (row[x / 8] >> (7 - x % 8)) & 1;
+                    out.extend_from_slice(&[0, 0, 0, if bit == painted { 255 } else { 0 }]);
+                }
+            }
+            return Ok(ImagePixels { width: self.width, height: self.height, data: out });
+        }
+
+        let bpc = self.sample_bits()?;
+        let color_space = self.sample_color_space(&data, resources)?;
+        let n = color_space.num_components().ok_or_else(|| other!("invalid image color space {:?}", color_space))?;
+        let row_len = (width * n * bpc).div_ceil(8);
+        if data.len() < row_len * height {
+            bail!("image data too short: {} < {}", data.len(), row_len * height);
+        }
+
+        let max = ((1u32 << bpc) - 1) as f32;
+        let decode: Vec<f32> = match self.decode {
+            Some(ref d) if !inverted && d.len() >= 2 * n => d.clone(),
+            _ if matches!(color_space, ColorSpace::Indexed(..)) => vec![0., max],
+            _ => [0., 1.].repeat(n),
+        };
+        let converter = t!(color_space.rgb_converter());
+        let rgba = |color: &[f32]| -> Result<[u8; 4]> {
+            Ok(match converter.to_rgb(color)? {
+                Some([r, g, b]) => [(r * 255.).round() as u8, (g * 255.).round() as u8, (b * 255.).round() as u8, 255],
+                None => [0; 4]
+            })
+        };
+        let sample = |row: &[u8], i: usize| -> u32 {
+            match bpc {
+                8 => row[i] as u32,
+                16 => u16::from_be_bytes([row[2 * i], row[2 * i + 1]]) as u32,
+                _ => {
+                    let bit = i * bpc;
+                    ((row[bit / 8] >> (8 - bpc - bit % 8)) as u32) & ((1 << bpc) - 1)
+                }
+            }
+        };
+        let value = |s: u32, c: usize| decode[2 * c] + s as f32 * (decode[2 * c + 1] - decode[2 * c]) / max;
+
+        // single components of up to 8 bits are converted once for every possible sample
+        let table = if n == 1 && bpc <= 8 {
+            Some((0 .. 1u32 << bpc).map(|s| rgba(&[value(s, 0)])).collect::<Result<Vec<_>>>()?)
+        } else {
+            None
+        };
+        let mut out = Vec::with_capacity(width * height * 4);
+        let mut color = vec![0.; n];
+        for row in data.chunks_exact(row_len).take(height) {
+            for x in 0 .. width {
+                match table {
+                    Some(ref table) => out.extend_from_slice(&table[sample(row, x) as usize]),
+                    None => {
+                        for (c, v) in color.iter_mut().enumerate() {
+                            *v = value(sample(row, x * n + c), c);
+                        }
+                        out.extend_from_slice(&rgba(&color)?);
+                    }
+                }
+            }
+        }
+        Ok(ImagePixels { width: self.width, height: self.height, data: out })
+    }
+
+    /// The samples of the image, one byte for every color component (or pixel of an image mask),
+    /// `width` pixels per row and without the padding at the end of rows.
+    ///
+    /// Samples of 1, 2 and 4 bits are expanded to a byte each but not scaled, so they range up to
+    /// `2^BitsPerComponent - 1` and the indices of `Indexed` images can be looked up directly.
+    /// Of 16 bit samples the high byte is kept. `/Decode` is not applied, except for the inversion
+    /// of 1 bit images done by `image_data`.
+    pub fn samples(&self, resolve: &impl Resolve, resources: Option<&Resources>) -> Result<Vec<u8>> {
+        let data = t!(self.image_data(resolve));
+        let (bpc, n) = if self.image_mask {
+            (1, 1)
+        } else {
+            let color_space = self.sample_color_space(&data, resources)?;
+            let n = color_space.num_components().ok_or_else(|| other!("invalid image color space {:?}", color_space))?;
+            (self.sample_bits()?, n)
+        };
+        unpack_samples(&data, bpc, self.width as usize * n, self.height as usize)
+    }
+
+    /// The soft mask of the image (`/SMask`), an image of its own that gives the alpha channel,
+    /// see `alpha`. It may have a different size than this image.
+    pub fn soft_mask(&self, resolve: &impl Resolve) -> Result<Option<ImageXObject>> {
+        match self.smask {
+            Some(r) => Ok(Some(ImageXObject { inner: (*t!(resolve.get(r))).clone() })),
+            None => Ok(None)
+        }
+    }
+
+    /// The samples of a soft mask image as alpha values, one byte per pixel from 0 (transparent)
+    /// to 255 (opaque), with its `/BitsPerComponent` and `/Decode` applied.
+    pub fn alpha(&self, resolve: &impl Resolve) -> Result<Vec<u8>> {
+        let samples = t!(self.samples(resolve, None));
+        let bpc = self.sample_bits()?;
+        // `samples` keeps the high byte of 16 bit samples
+        let max = ((1u32 << bpc.min(8)) - 1) as f32;
+        // image_data already inverted 1 bit images with `/Decode [1 0]`
+        let inverted = self.bits_per_component == Some(1) && self.decode.as_deref() == Some(&[1.0, 0.0]);
+        let (d0, d1) = match self.decode.as_deref() {
+            Some(&[d0, d1, ..]) if !inverted => (d0, d1),
+            _ => (0., 1.)
+        };
+        Ok(samples.iter().map(|&s| {
+            let a = d0 + s as f32 * (d1 - d0) / max;
+            (a.clamp(0., 1.) * 255.).round() as u8
+        }).collect())
+    }
+
+    /// Drop trailing bytes past the `width * height` samples, such as padding after
+    /// run length or predictor output. Left alone if the number of components is unknown.
+    fn truncate_samples(&self, mut data: Vec<u8>) -> Vec<u8> {
+        let (bpc, n) = if self.image_mask {
+            (1, Some(1))
+        } else {
+            (self.bits_per_component.unwrap_or(8) as usize, self.color_space.as_ref().and_then(|cs| cs.num_components()))
+        };
+        if let Some(n) = n {
+            let row_len = (self.width as usize * n * bpc).div_ceil(8);
+            data.truncate(row_len * self.height as usize);
+        }
+        data
+    }
+
+    /// Bits per component of the data returned by `image_data`.
+    fn sample_bits(&self) -> Result<usize> {
+        // DCT and JPX data is decoded to 8 bits per component
+        let decoded = matches!(self.inner.filters.last(), Some(StreamFilter::DCTDecode(_) | StreamFilter::JPXDecode));
+        let bpc = if decoded { 8 } else { self.bits_per_component.unwrap_or(8) as usize };
+        if !matches!(bpc, 1 | 2 | 4 | 8 | 16) {
+            bail!("invalid BitsPerComponent {}", bpc);
+        }
+        Ok(bpc)
+    }
+
+    /// The color space of `data` (as returned by `image_data`).
+    fn sample_color_space<'a>(&'a self, data: &[u8], resources: Option<&'a Resources>) -> Result<&'a ColorSpace> {
+        Ok(match self.color_space {
+            Some(ColorSpace::Named(ref name)) => resources
+                .and_then(|r| r.color_spaces.get(name))
+                .ok_or_else(|| other!("color space {} not found", name.as_str()))?,
+            Some(ref cs) => cs,
+            // only allowed for JPX, which carries its own
+            None => match data.len() / (self.width as usize * self.height as usize).max(1) {
+                1 => &ColorSpace::DeviceGray,
+                3 => &ColorSpace::DeviceRGB,
+                4 => &ColorSpace::DeviceCMYK,
+                n => bail!("image without color space and {} components", n)
+            }
+        })
+    }
+}
+
+/// Expand `rows` rows of `row_samples` samples of `bpc` bits to a byte per sample.
+///
+/// Every row starts at a byte boundary, the remaining bits of the last byte are padding.
+fn unpack_samples(data: &[u8], bpc: usize, row_samples: usize, rows: usize) -> Result<Vec<u8>> {
+    let row_len = (row_samples * bpc).div_ceil(8);
+    if data.len() < row_len * rows {
+        bail!("image data too short: {} < {}", data.len(), row_len * rows);
+    }
+    let mut out = Vec::with_capacity(row_samples * rows);
+    for row in data.chunks_exact(row_len.max(1)).take(rows) {
+        match bpc {
+            8 => out.extend_from_slice(row),
+            16 => out.extend(row.iter().step_by(2)),
+            _ => {
+                let mask = (1u8 << bpc) - 1;
+                out.extend((0 .. row_samples).map(|i| {
+                    let bit = i * bpc;
+                    (row[bit / 8] >> (8 - bpc - bit % 8)) & mask
+                }));
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Pixels of an image, see `ImageXObject::decode_pixels`.
+#[derive(Debug, Clone)]
+pub struct ImagePixels {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA with 8 bits per channel, row by row from the top.
+    pub data: Vec<u8>,
 }
 
 #[derive(Object, Debug, DataSize, DeepClone, ObjectWrite)]
@@ -940,6 +1618,108 @@ pub struct SignatureReferenceDictionary {
 }
 
 
+/// A link annotation, see `Page::links`.
+#[derive(Debug, Clone)]
+pub struct Link {
+    pub rect: Rectangle,
+    pub target: LinkTarget,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkTarget {
+    Uri(String),
+    /// A page of the document, and the point of it to show at the top left, if given.
+    GoTo { page: u32, point: Option<Point> },
+    Other,
+}
+
+fn link_target(annot: &Annot, resolve: &impl Resolve, catalog: &Catalog) -> Result<LinkTarget> {
+    target(annot.other.get("Dest"), annot.other.get("A"), resolve, catalog)
+}
+
+/// Where a destination `dest`, or else `action`, goes.
+fn target(dest: Option<&Primitive>, action: Option<&Primitive>, resolve: &impl Resolve, catalog: &Catalog) -> Result<LinkTarget> {
+    let dest = match (dest, action) {
+        (Some(dest), _) => dest.clone(),
+        (None, Some(action)) => {
+            let action = t!(action.clone().resolve(resolve)?.into_dictionary());
+            match action.get("S").map(|s| s.as_name()).transpose()? {
+                Some("URI") => {
+                    let uri = try_opt!(action.get("URI")).clone().resolve(resolve)?;
+                    return Ok(LinkTarget::Uri(uri.to_string_lossy()?));
+                }
+                Some("GoTo") => try_opt!(action.get("D")).clone(),
+                _ => return Ok(LinkTarget::Other)
+            }
+        }
+        (None, None) => return Ok(LinkTarget::Other)
+    };
+    let dest = match dest.resolve(resolve)? {
+        Primitive::Name(name) => named_dest(catalog, name.as_bytes(), resolve)?,
+        Primitive::String(name) => named_dest(catalog, name.as_bytes(), resolve)?,
+        p => Some(t!(Dest::from_primitive(p, resolve)))
+    };
+    let Some(Dest { page: Some(page), view }) = dest else {
+        return Ok(LinkTarget::Other);
+    };
+    let point = match view {
+        DestView::XYZ { left: None, top: None, .. } => None,
+        DestView::XYZ { left, top, .. } => Some(Point { x: left.unwrap_or(0.), y: top.unwrap_or(0.) }),
+        DestView::FitH { top } | DestView::FitBH { top } => Some(Point { x: 0., y: top }),
+        DestView::FitV { left } => Some(Point { x: left, y: 0. }),
+        DestView::FitR(rect) => Some(Point { x: rect.left, y: rect.top }),
+        DestView::Fit | DestView::FitB => None,
+    };
+    Ok(LinkTarget::GoTo { page: page_nr(page.get_inner(), resolve)?, point })
+}
+
+/// Look up a named destination in the `/Dests` name tree and the older `/Dests` dictionary.
+fn named_dest(catalog: &Catalog, name: &[u8], resolve: &impl Resolve) -> Result<Option<Dest>> {
+    if let Some(tree) = catalog.names.as_ref().and_then(|n| n.dests.as_ref()) {
+        let mut found = None;
+        tree.walk(resolve, &mut |key, dest| if key.as_bytes() == name && found.is_none() {
+            found = Some(dest.clone());
+        })?;
+        if let Some(dest) = found {
+            return Ok(dest);
+        }
+    }
+    let key = std::str::from_utf8(name).ok();
+    match (catalog.dests.as_ref(), key) {
+        (Some(dests), Some(key)) => match dests.get(key) {
+            Some(p) => Ok(Some(t!(Dest::from_primitive(p.clone(), resolve)))),
+            None => Ok(None)
+        },
+        _ => Ok(None)
+    }
+}
+
+/// The number of the page `page`, counting the pages before it in the page tree.
+fn page_nr(page: PlainRef, resolve: &impl Resolve) -> Result<u32> {
+    let node = t!(resolve.get(Ref::<PagesNode>::new(page)));
+    let PagesNode::Leaf(ref leaf) = *node else {
+        bail!("destination is not a page");
+    };
+    let mut child = page;
+    let mut parent = Some(leaf.parent.clone());
+    let mut nr = 0;
+    for _ in 0 .. 16 {
+        let Some(tree) = parent else {
+            return Ok(nr);
+        };
+        let pos = tree.kids.iter().position(|kid| kid.get_inner() == child)
+            .ok_or_else(|| other!("page {:?} not found in its parent", child))?;
+        for &kid in &tree.kids[.. pos] {
+            nr += match *t!(resolve.get(kid)) {
+                PagesNode::Tree(ref t) => t.count,
+                PagesNode::Leaf(_) => 1,
+            };
+        }
+        child = tree.get_ref().get_inner();
+        parent = tree.parent.clone();
+    }
+    bail!("page tree depth exeeded")
+}
+
 #[derive(Object, ObjectWrite, Debug, Clone, DataSize)]
 #[pdf(Type="Annot?")]
 pub struct Annot {
@@ -982,6 +1762,62 @@ pub struct Annot {
     #[pdf(other)]
     pub other: Dictionary,
 }
+impl Annot {
+    /// The normal appearance (`/AP /N`) of the annotation.
+    ///
+    /// If it has several appearance states, the one selected by `/AS` is returned.
+    /// `None` if there is no appearance stream (for the current state).
+    pub fn normal_appearance(&self, resolve: &impl Resolve) -> Result<Option<FormXObject>> {
+        let Some(ref ap) = self.appearance_streams else {
+            return Ok(None);
+        };
+        let normal = t!(resolve.get(ap.normal));
+        Ok(normal.get(self.appearance_state.as_ref()).cloned())
+    }
+
+    /// Whether the `Hidden` or `NoView` flag is set, so the annotation should not be displayed.
+    pub fn is_hidden(&self) -> bool {
+        self.annot_flags & (1 << 1 | 1 << 5) != 0
+    }
+
+    /// The transformation from the form space of `appearance` to user space (12.5.5).
+    ///
+    /// The appearance's `/BBox`, transformed by its `/Matrix`, is fitted onto `/Rect`.
+    /// Returns `None` if the annotation has no `/Rect`.
+    pub fn appearance_matrix(&self, appearance: &FormXObject) -> Result<Option<Matrix>> {
+        let Some(rect) = self.rect else {
+            return Ok(None);
+        };
+        let dict = appearance.dict();
+        let m = match dict.matrix {
+            Some(ref p) => t!(Matrix::from_primitive(p.clone(), &NoResolve)),
+            None => Matrix::default()
+        };
+        let bbox = dict.bbox;
+        let corners = [(bbox.left, bbox.bottom), (bbox.left, bbox.top), (bbox.right, bbox.bottom), (bbox.right, bbox.top)];
+        let (mut x0, mut y0, mut x1, mut y1) = (f32::INFINITY, f32::INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for (x, y) in corners {
+            let (x, y) = (m.a * x + m.c * y + m.e, m.b * x + m.d * y + m.f);
+            x0 = x0.min(x);
+            y0 = y0.min(y);
+            x1 = x1.max(x);
+            y1 = y1.max(y);
+        }
+        let scale = |from: f32, to: f32| if from > 0. { to / from } else { 1. };
+        let sx = scale(x1 - x0, (rect.right - rect.left).abs());
+        let sy = scale(y1 - y0, (rect.top - rect.bottom).abs());
+        let tx = rect.left.min(rect.right) - x0 * sx;
+        let ty = rect.bottom.min(rect.top) - y0 * sy;
+        Ok(Some(Matrix {
+            a: m.a * sx,
+            b: m.b * sy,
+            c: m.c * sx,
+            d: m.d * sy,
+            e: m.e * sx + tx,
+            f: m.f * sy + ty,
+        }))
+    }
+}
 
 #[derive(Object, ObjectWrite, Debug, DataSize, Clone)]
 pub struct FieldDictionary {
@@ -1030,9 +1866,64 @@ pub struct FieldDictionary {
     #[pdf(key="Subtype")]
     pub subtype: Option<Name>,
 
+    /// The appearance state, if the field is merged with its widget annotation.
+    #[pdf(key="AS")]
+    pub appearance_state: Option<Name>,
+
     #[pdf(other)]
     pub other: Dictionary
 }
+impl FieldDictionary {
+    /// Whether the check box or radio button is on.
+    ///
+    /// Reads `/AS` of a widget, or else `/V`, which is a state name for these fields.
+    /// Any state other than `/Off` is on. `None` if this isn't a check box or radio button.
+    pub fn checkbox_state(&self) -> Option<bool> {
+        match self.typ {
+            // push buttons have no state
+            Some(FieldType::Button) if self.flags & (1 << 16) == 0 => {}
+            // a widget of a button
+            None if self.appearance_state.is_some() => {}
+            _ => return None
+        }
+        let state = match (&self.appearance_state, &self.value) {
+            (Some(state), _) => state.as_str(),
+            (None, Primitive::Name(ref v)) => v.as_str(),
+            _ => "Off"
+        };
+        Some(state != "Off")
+    }
+
+    /// The names of the on states, which are the export values of a check box or radio button.
+    ///
+    /// These are the keys of `/AP /N` other than `/Off`, of the field and its widgets.
+    /// A check box usually has one, a radio button group one per button.
+    pub fn on_states(&self, resolve: &impl Resolve) -> Result<Vec<Name>> {
+        let mut states = vec![];
+        self.add_on_states(&mut states, resolve)?;
+        for &kid in &self.kids {
+            t!(resolve.get(kid)).add_on_states(&mut states, resolve)?;
+        }
+        Ok(states)
+    }
+    fn add_on_states(&self, states: &mut Vec<Name>, resolve: &impl Resolve) -> Result<()> {
+        let Some(ap) = self.other.get("AP") else {
+            return Ok(());
+        };
+        let ap = t!(ap.clone().resolve(resolve)).into_dictionary()?;
+        // unlike for other annotations, /N is a dictionary of states, usually a direct one
+        if let Some(normal) = ap.get("N") {
+            if let Primitive::Dictionary(normal) = t!(normal.clone().resolve(resolve)) {
+                for name in normal.keys() {
+                    if name.as_str() != "Off" && !states.contains(name) {
+                        states.push(name.clone());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
 
 #[derive(Object, ObjectWrite, Debug, DataSize, Clone, DeepClone)]
 pub struct AppearanceStreams {
@@ -1051,6 +1942,18 @@ pub enum AppearanceStreamEntry {
     Single(FormXObject),
     Dict(HashMap<Name, AppearanceStreamEntry>)
 }
+impl AppearanceStreamEntry {
+    /// The appearance for the given state (`/AS`). A single stream is used for all states.
+    pub fn get(&self, state: Option<&Name>) -> Option<&FormXObject> {
+        match self {
+            AppearanceStreamEntry::Single(s) => Some(s),
+            AppearanceStreamEntry::Dict(d) => match d.get(state?)? {
+                AppearanceStreamEntry::Single(s) => Some(s),
+                AppearanceStreamEntry::Dict(_) => None,
+            }
+        }
+    }
+}
 impl Object for AppearanceStreamEntry {
     fn from_primitive(p: Primitive, resolve: &impl Resolve) -> Result<Self> {
         match p.resolve(resolve)? {
@@ -1653,7 +2556,7 @@ pub struct Outlines {
 /// (ur x , ll y ).
 /// Also see Table 74, key BBox definition Pg 221
 /// defining top, left, bottom, right labeling
-#[derive(Debug, Copy, Clone, DataSize, Default)]
+#[derive(Debug, Copy, Clone, PartialEq, DataSize, Default)]
 pub struct Rectangle {
     pub left:   f32,
     pub bottom: f32,
@@ -1663,6 +2566,58 @@ pub struct Rectangle {
 #[deprecated]
 pub type Rect = Rectangle;
 
+impl Rectangle {
+    /// The same rectangle with `left <= right` and `bottom <= top`.
+    ///
+    /// PDF allows any two diagonally opposite corners, so `[x1 y1 x0 y0]` is the same rectangle as `[x0 y0 x1 y1]`.
+    pub fn normalized(&self) -> Rectangle {
+        Rectangle {
+            left:   self.left.min(self.right),
+            bottom: self.bottom.min(self.top),
+            right:  self.left.max(self.right),
+            top:    self.bottom.max(self.top),
+        }
+    }
+    pub fn width(&self) -> f32 {
+        (self.right - self.left).abs()
+    }
+    pub fn height(&self) -> f32 {
+        (self.top - self.bottom).abs()
+    }
+    /// The smallest rectangle containing both `self` and `other`.
+    pub fn union(&self, other: &Rectangle) -> Rectangle {
+        let (a, b) = (self.normalized(), other.normalized());
+        Rectangle {
+            left:   a.left.min(b.left),
+            bottom: a.bottom.min(b.bottom),
+            right:  a.right.max(b.right),
+            top:    a.top.max(b.top),
+        }
+    }
+    /// The area covered by both `self` and `other`, `None` if they do not overlap.
+    ///
+    /// Rectangles that only share an edge intersect in an empty rectangle.
+    pub fn intersection(&self, other: &Rectangle) -> Option<Rectangle> {
+        let (a, b) = (self.normalized(), other.normalized());
+        let r = Rectangle {
+            left:   a.left.max(b.left),
+            bottom: a.bottom.max(b.bottom),
+            right:  a.right.min(b.right),
+            top:    a.top.min(b.top),
+        };
+        if r.left <= r.right && r.bottom <= r.top {
+            Some(r)
+        } else {
+            None
+        }
+    }
+    /// Whether the point `(x, y)` lies inside the rectangle or on its border.
+    pub fn contains_point(&self, x: f32, y: f32) -> bool {
+        let r = self.normalized();
+        r.left <= x && x <= r.right && r.bottom <= y && y <= r.top
+    }
+}
+
 impl Object for Rectangle {
     fn from_primitive(p: Primitive, r: &impl Resolve) -> Result<Self> {
         let arr = p.resolve(r)?.into_array()?;
@@ -1777,14 +2732,14 @@ pub enum StructType {
     Other(String),
 }
 
-#[derive(Object, ObjectWrite, Debug, DataSize)]
+#[derive(Object, ObjectWrite, Debug, DataSize, Trace)]
 pub enum Trapped {
     True,
     False,
     Unknown,
 }
 
-#[derive(Object, ObjectWrite, Debug, DataSize, Default)]
+#[derive(Object, ObjectWrite, Debug, DataSize, Default, Trace)]
 pub struct InfoDict {
     #[pdf(key="Title")]
     pub title: Option<PdfString>,
@@ -1818,6 +2773,21 @@ pub struct InfoDict {
 mod tests {
     use super::*;
 
+    #[test]
+    fn page_tree_cycle() {
+        let node = |id, parent| PagesRc(RcRef::new(PlainRef { id, gen: 0 }, Shared::new(
+            PagesNode::Tree(PageTree { parent, count: 1, ..PageTree::default() })
+        )));
+        let root = node(1, None);
+        let mid = node(2, Some(root));
+        // a second copy of object 1 that has `mid` as its parent
+        let looped = node(1, Some(mid));
+        let page = Page::new(looped);
+        assert!(page.media_box().is_err());
+        assert!(page.inherited::<i32>("Bar", &NoResolve).is_err());
+        assert_eq!(page.rotate(), 0);
+    }
+
     #[test]
     fn parse_struct_type() {
         assert!(matches!(
@@ -1834,6 +2804,85 @@ mod tests {
         }
     }
 
+    #[test]
+    fn derive_trace() {
+        #[derive(Trace)]
+        struct Foo {
+            a: Ref<Page>,
+            b: Option<Dictionary>,
+            #[pdf(skip)]
+            _c: Ref<Page>,
+        }
+        #[derive(Trace)]
+        enum Bar {
+            A(Foo),
+            B { x: i32, y: MaybeRef<Dictionary> },
+            C,
+        }
+        let r = |id| PlainRef { id, gen: 0 };
+        let mut dict = Dictionary::new();
+        dict.insert("X", Primitive::Reference(r(2)));
+        let foo = Foo { a: Ref::new(r(1)), b: Some(dict), _c: Ref::new(r(3)) };
+
+        let mut refs = vec![];
+        Bar::A(foo).trace(&mut |r| refs.push(r.id));
+        assert_eq!(refs, [1, 2]);
+
+        refs.clear();
+        let y = MaybeRef::Indirect(RcRef::new(r(4), Shared::new(Dictionary::new())));
+        Bar::B { x: 0, y }.trace(&mut |r| refs.push(r.id));
+        Bar::C.trace(&mut |r| refs.push(r.id));
+        assert_eq!(refs, [4]);
+    }
+
+    #[test]
+    fn shading_background() {
+        let data = b"<< /ShadingType 2 /ColorSpace /DeviceRGB /Coords [0 0 1 0] /BBox [0 0 10 20]
+            /Background [1 0.5 0] /Function 3 0 R >>";
+        let p = crate::parser::parse(data, &NoResolve, crate::parser::ParseFlags::ANY).unwrap();
+        let shading = Shading::from_primitive(p, &NoResolve).unwrap();
+        let dict = shading.dict();
+        assert_eq!(dict.shading_type, 2);
+        assert_eq!(dict.bbox.map(|r| (r.right, r.top)), Some((10., 20.)));
+        assert!(matches!(dict.background_color(), Some(Color::Rgb(Rgb { red, green, blue })) if (red, green, blue) == (1., 0.5, 0.)));
+        assert!(dict.other.contains_key("Coords"));
+    }
+
+    #[test]
+    fn annot_appearance_matrix() {
+        let p = crate::parser::parse(b"<< /Subtype /Widget /Rect [100 120 140 100] /F 2 >>", &NoResolve, crate::parser::ParseFlags::ANY).unwrap();
+        let annot = Annot::from_primitive(p, &NoResolve).unwrap();
+        assert!(annot.is_hidden());
+
+        let mut dict = FormDict { bbox: Rectangle { left: 0., bottom: 0., right: 10., top: 20. }, ..FormDict::default() };
+        // rotated by 90°
+        dict.matrix = Some(vec![0, 1, -1, 0, 0, 0].into_iter().map(Primitive::Integer).collect::<Vec<_>>().into());
+        let form = FormXObject { stream: Stream::new(dict, vec![]) };
+        let m = annot.appearance_matrix(&form).unwrap().unwrap();
+        assert_eq!((m.a, m.b, m.c, m.d, m.e, m.f), (0., 2., -2., 0., 140., 100.));
+        // the bbox corner (10, 20) ends up at the top left of the rect
+        assert_eq!((10. * m.a + 20. * m.c + m.e, 10. * m.b + 20. * m.d + m.f), (100., 120.));
+    }
+
+    #[test]
+    fn ext_g_state_soft_mask() {
+        let gs = |data: &[u8]| {
+            let p = crate::parser::parse(data, &NoResolve, crate::parser::ParseFlags::ANY).unwrap();
+            GraphicsStateParameters::from_primitive(p, &NoResolve).unwrap()
+        };
+        let state = gs(b"<< /Type /ExtGState /CA 0.5 /ca 0.25 /FL 2 /SM 0.02 >>");
+        assert_eq!((state.stroke_alpha, state.fill_alpha), (Some(0.5), Some(0.25)));
+        assert_eq!((state.flatness, state.smoothness), (Some(2.), Some(0.02)));
+        assert!(state.soft_mask(&NoResolve).unwrap().is_none());
+
+        assert!(matches!(gs(b"<< /SMask /None >>").soft_mask(&NoResolve), Ok(Some(None))));
+
+        let mask = gs(b"<< /SMask << /Type /Mask /S /Luminosity /G 7 0 R /BC [0] >> >>").soft_mask(&NoResolve).unwrap().unwrap().unwrap();
+        assert_eq!(mask.subtype, SoftMaskType::Luminosity);
+        assert_eq!(mask.group.get_inner().id, 7);
+        assert_eq!(mask.backdrop, Some(vec![0.]));
+    }
+
     #[test]
     fn test_field_type() {
         assert_eq!(
@@ -1841,4 +2890,81 @@ mod tests {
             FieldType::Text
         );
     }
+
+    #[test]
+    fn rectangle_ops() {
+        let rect = |left, bottom, right, top| Rectangle { left, bottom, right, top };
+        // given as [x1 y1 x0 y0]
+        let reversed = Rectangle::from_primitive(crate::parser::parse(b"[100 200 0 50]", &NoResolve, crate::parser::ParseFlags::ANY).unwrap(), &NoResolve).unwrap();
+        assert_eq!(reversed.normalized(), rect(0., 50., 100., 200.));
+        assert_eq!((reversed.width(), reversed.height()), (100., 150.));
+        assert!(reversed.contains_point(0., 200.));
+        assert!(!reversed.contains_point(50., 20.));
+
+        let other = rect(50., 0., 150., 100.);
+        assert_eq!(reversed.union(&other), rect(0., 0., 150., 200.));
+        assert_eq!(reversed.intersection(&other), Some(rect(50., 50., 100., 100.)));
+        assert_eq!(other.intersection(&rect(150., 100., 200., 200.)), Some(rect(150., 100., 150., 100.)));
+        assert_eq!(other.intersection(&rect(151., 0., 200., 200.)), None);
+    }
+
+    #[test]
+    fn jpx_image() {
+        use crate::enc::LZWFlateParams;
+
+        let image = |filters| ImageXObject {
+            inner: Stream::from_compressed(ImageDict { width: 1, height: 1, ..ImageDict::default() }, &b"0000000C6A502020>"[..], filters)
+        };
+        let jpx = image(vec![StreamFilter::ASCIIHexDecode, StreamFilter::JPXDecode]);
+        assert_eq!(&*jpx.as_jp2(&NoResolve).unwrap().unwrap(), b"\0\0\0\x0cjP  ");
+        // no JPX decoder is set in the tests
+        assert!(jpx.image_data(&NoResolve).unwrap_err().is_unsupported_filter());
+        assert!(jpx.decode_pixels(&NoResolve, None).unwrap_err().is_unsupported_filter());
+
+        let flate = image(vec![StreamFilter::FlateDecode(LZWFlateParams::default())]);
+        assert!(flate.as_jp2(&NoResolve).unwrap().is_none());
+    }
+
+    #[test]
+    fn emit_empty() {
+        #[derive(Object, ObjectWrite)]
+        struct Holder {
+            #[pdf(key="A")]
+            a: HashMap<Name, i32>,
+            #[pdf(key="B", emit_empty)]
+            b: HashMap<Name, i32>,
+        }
+        let holder = Holder { a: HashMap::new(), b: HashMap::new() };
+        let dict = holder.to_primitive(&mut NoUpdate).unwrap().into_dictionary().unwrap();
+        assert!(dict.get("A").is_none());
+        assert_eq!(dict.get("B"), Some(&Primitive::Dictionary(Dictionary::new())));
+
+        let b = HashMap::from([(Name::from("x"), 1)]);
+        let dict = Holder { a: HashMap::new(), b }.to_primitive(&mut NoUpdate).unwrap().into_dictionary().unwrap();
+        assert_eq!(dict["B"].clone().into_dictionary().unwrap()["x"], Primitive::Integer(1));
+        let holder = Holder::from_primitive(Primitive::Dictionary(dict), &NoResolve).unwrap();
+        assert_eq!((holder.a.len(), holder.b.len()), (0, 1));
+    }
+
+    #[test]
+    fn image_samples() {
+        // 3 pixels of 4 bits per row, every row starts at a new byte
+        let lookup: Arc<[u8]> = (0 .. 48).collect::<Vec<u8>>().into();
+        let dict = ImageDict {
+            width: 3,
+            height: 2,
+            color_space: Some(ColorSpace::Indexed(Box::new(ColorSpace::DeviceRGB), 15, lookup)),
+            bits_per_component: Some(4),
+            ..ImageDict::default()
+        };
+        let image = ImageXObject { inner: Stream::new(dict, &[0x12, 0x3f, 0xab, 0xcf][..]) };
+        assert_eq!(image.samples(&NoResolve, None).unwrap(), [1, 2, 3, 10, 11, 12]);
+
+        let pixels = image.decode_pixels(&NoResolve, None).unwrap();
+        assert_eq!(&pixels.data[4 .. 8], &[6, 7, 8, 255]);
+
+        assert_eq!(unpack_samples(&[0b1011_0000, 0b0100_0000], 1, 3, 2).unwrap(), [1, 0, 1, 0, 1, 0]);
+        assert_eq!(unpack_samples(&[0b1110_0100], 2, 4, 1).unwrap(), [3, 2, 1, 0]);
+        assert!(unpack_samples(&[0x12], 4, 3, 1).is_err());
+    }
 }
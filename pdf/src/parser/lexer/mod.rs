@@ -120,6 +120,9 @@ impl<'a> Lexer<'a> {
         let word = self.next()?;
         if word.equals(expected.as_bytes()) {
             Ok(())
+        } else if self.pos == self.buf.len() && expected.as_bytes().starts_with(word.as_slice()) {
+            // cut off at the end of the data
+            Err(PdfError::EOF)
         } else {
             Err(PdfError::UnexpectedLexeme {
                 pos: self.pos,
@@ -223,6 +226,12 @@ impl<'a> Lexer<'a> {
         self.pos
     }
 
+    /// The current position in the file, i.e. including the offset of the buffer.
+    #[inline]
+    pub fn file_pos(&self) -> usize {
+        self.file_offset + self.pos
+    }
+
     #[inline]
     pub fn new_substr(&self, mut range: Range<usize>) -> Substr<'a> {
         // if the range is backward, fix it
@@ -504,6 +513,15 @@ mod tests {
         assert_eq!(boundary(b"q\n", 1, is_whitespace), 2);
     }
 
+    #[test]
+    fn test_next_expect() {
+        assert!(Lexer::new(b"endobj").next_expect("endobj").is_ok());
+        // the data ends in the middle of the keyword
+        assert!(Lexer::new(b"endo").next_expect("endobj").unwrap_err().is_eof());
+        assert!(!Lexer::new(b"endo ").next_expect("endobj").unwrap_err().is_eof());
+        assert!(!Lexer::new(b"foo").next_expect("endobj").unwrap_err().is_eof());
+    }
+
     #[test]
     fn test_substr() {
         assert!(Substr::new("123", 0).is_real_number());
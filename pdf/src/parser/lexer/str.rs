@@ -61,9 +61,6 @@ impl<'a> StringLexer<'a> {
                     b')' => Some(b')'),
                     b'\n' => {
                         // ignore end-of-line marker
-                        if let Ok(b'\r') = self.peek_byte() {
-                            let _ = self.next_byte();
-                        }
                         self.next_lexeme()?
                     }
                     b'\r' => {
@@ -75,22 +72,24 @@ impl<'a> StringLexer<'a> {
                     }
                     b'\\' => Some(b'\\'),
 
-                    _ => {
-                        self.back()?;
-                        let _start = self.get_offset();
-                        let mut char_code: u16 = 0;
-
-                        // A character code must follow. 1-3 numbers.
-                        for _ in 0..3 {
-                            let c = self.peek_byte()?;
-                            if (b'0'..=b'7').contains(&c) {
-                                self.next_byte()?;
-                                char_code = char_code * 8 + (c - b'0') as u16;
-                            } else {
-                                break;
+                    b'0' ..= b'7' => {
+                        // 1-3 octal digits, the high-order overflow is ignored
+                        let mut char_code = c - b'0';
+                        for _ in 1..3 {
+                            match self.peek_byte()? {
+                                c @ b'0' ..= b'7' => {
+                                    self.next_byte()?;
+                                    char_code = char_code.wrapping_mul(8).wrapping_add(c - b'0');
+                                }
+                                _ => break
                             }
                         }
-                        Some(char_code as u8)
+                        Some(char_code)
+                    }
+                    // the backslash of an unknown escape is ignored
+                    _ => {
+                        self.back()?;
+                        self.next_lexeme()?
                     }
                 }
                 )
@@ -109,6 +108,14 @@ impl<'a> StringLexer<'a> {
                 }
             },
 
+            // an unescaped end-of-line marker is read as a line feed
+            b'\r' => {
+                if let Ok(b'\n') = self.peek_byte() {
+                    let _ = self.next_byte();
+                }
+                Ok(Some(b'\n'))
+            }
+
             c => Ok(Some(c))
 
         }
@@ -326,6 +333,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn escapes() {
+        let lex = |data: &[u8]| StringLexer::new(data).iter().map(Result::unwrap).collect::<Vec<u8>>();
+        assert_eq!(lex(b"\\101\\n\\\\)"), b"A\n\\");
+        // digits before a non-digit
+        assert_eq!(lex(b"\\1x\\01\\7)"), b"\x01x\x01\x07");
+        // a line feed and a carriage return are two line ends, the second one read as a line feed
+        assert_eq!(lex(b"a\\\n\rb)"), b"a\nb");
+        // a bare carriage return and CR LF are one line feed each, LF CR are two
+        assert_eq!(lex(b"a\rb\r\nc\n\rd)"), b"a\nb\nc\n\nd");
+        // the backslash of unknown escapes is dropped, also before delimiters
+        assert_eq!(lex(b"\\q\\\\\\)x)"), b"q\\)x");
+    }
+
     #[test]
     fn hex_test() {
         let input = b"901FA3>";
@@ -11,8 +11,13 @@ use crate::crypt::Decoder;
 /// Parses an Object starting at the current position of `lexer`. Almost as
 /// `Reader::parse_object`, but this function does not take `Reader`, at the expense that it
 /// cannot dereference 
-
+///
+/// Errors carry the offset of the object in the file, see [`PdfError::offset`].
 pub fn parse_indirect_object(lexer: &mut Lexer, r: &impl Resolve, decoder: Option<&Decoder>, flags: ParseFlags) -> Result<(PlainRef, Primitive)> {
+    let offset = lexer.file_pos();
+    parse_indirect_object_inner(lexer, r, decoder, flags).with_offset(offset)
+}
+fn parse_indirect_object_inner(lexer: &mut Lexer, r: &impl Resolve, decoder: Option<&Decoder>, flags: ParseFlags) -> Result<(PlainRef, Primitive)> {
     let id = PlainRef {
         id: t!(lexer.next()).to::<ObjNr>()?,
         gen: t!(lexer.next()).to::<GenNr>()?,
@@ -38,6 +43,10 @@ pub fn parse_indirect_object(lexer: &mut Lexer, r: &impl Resolve, decoder: Optio
     Ok((id, obj))
 }
 pub fn parse_indirect_stream(lexer: &mut Lexer, r: &impl Resolve, decoder: Option<&Decoder>) -> Result<(PlainRef, PdfStream)> {
+    let offset = lexer.file_pos();
+    parse_indirect_stream_inner(lexer, r, decoder).with_offset(offset)
+}
+fn parse_indirect_stream_inner(lexer: &mut Lexer, r: &impl Resolve, decoder: Option<&Decoder>) -> Result<(PlainRef, PdfStream)> {
     let id = PlainRef {
         id: t!(lexer.next()).to::<ObjNr>()?,
         gen: t!(lexer.next()).to::<GenNr>()?,
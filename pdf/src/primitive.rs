@@ -5,14 +5,18 @@ use std::sync::Arc;
 use std::{str, fmt, io};
 use std::ops::{Index, Range};
 use std::ops::Deref;
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use std::borrow::{Borrow, Cow};
+use std::hash::{Hash, Hasher};
 use indexmap::IndexMap;
 use itertools::Itertools;
 use istring::{SmallString, IBytes};
 use datasize::DataSize;
 
-#[derive(Clone, Debug, PartialEq)]
+/// Primitives compare structurally: dictionaries key-wise regardless of order, and references
+/// by `(id, gen)` without resolving them. `Integer` and `Number` are never equal to each other,
+/// and numbers are compared by their bit pattern, so `NaN == NaN` but `0.0 != -0.0`.
+#[derive(Clone, Debug)]
 pub enum Primitive {
     Null,
     Integer (i32),
@@ -41,6 +45,43 @@ impl DataSize for Primitive {
     }
 }
 
+impl PartialEq for Primitive {
+    fn eq(&self, other: &Primitive) -> bool {
+        use Primitive::*;
+        match (self, other) {
+            (Null, Null) => true,
+            (Integer(a), Integer(b)) => a == b,
+            (Number(a), Number(b)) => a.to_bits() == b.to_bits(),
+            (Boolean(a), Boolean(b)) => a == b,
+            (String(a), String(b)) => a == b,
+            (Stream(a), Stream(b)) => a == b,
+            (Dictionary(a), Dictionary(b)) => a == b,
+            (Array(a), Array(b)) => a == b,
+            (Reference(a), Reference(b)) => a == b,
+            (Name(a), Name(b)) => a == b,
+            _ => false
+        }
+    }
+}
+impl Eq for Primitive {}
+impl Hash for Primitive {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Primitive::Null => {}
+            Primitive::Integer(i) => i.hash(state),
+            Primitive::Number(n) => n.to_bits().hash(state),
+            Primitive::Boolean(b) => b.hash(state),
+            Primitive::String(ref s) => s.hash(state),
+            Primitive::Stream(ref s) => s.hash(state),
+            Primitive::Dictionary(ref d) => d.hash(state),
+            Primitive::Array(ref arr) => arr.hash(state),
+            Primitive::Reference(r) => r.hash(state),
+            Primitive::Name(ref s) => s.hash(state),
+        }
+    }
+}
+
 impl fmt::Display for Primitive {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -98,24 +139,38 @@ fn serialize_list(arr: &[Primitive], out: &mut impl io::Write) -> Result<()> {
     Ok(())
 }
 
+/// Write `/s`, with `#XX` escapes for delimiters, `#`, whitespace and bytes outside of printable ASCII (7.3.5).
 pub fn serialize_name(s: &str, out: &mut impl io::Write) -> Result<()> {
     write!(out, "/")?;
-    for b in s.chars() {
+    for &b in s.as_bytes() {
         match b {
-            '\\' | '(' | ')' => write!(out, r"\")?,
-            c if c > '~' => panic!("only ASCII"),
-            _ => ()
+            b'#' | b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%' => write!(out, "#{:02X}", b)?,
+            b'!' ..= b'~' => out.write_all(&[b])?,
+            _ => write!(out, "#{:02X}", b)?,
         }
-        write!(out, "{}", b)?;
     }
     Ok(())
 }
 
 /// Primitive Dictionary type.
-#[derive(Default, Clone, PartialEq)]
+#[derive(Default, Clone, PartialEq, Eq)]
 pub struct Dictionary {
     dict: IndexMap<Name, Primitive>
 }
+/// Independent of the order of the entries, like `PartialEq`.
+impl Hash for Dictionary {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        use std::collections::hash_map::DefaultHasher;
+
+        let sum = self.dict.iter().fold(0u64, |sum, entry| {
+            let mut hasher = DefaultHasher::new();
+            entry.hash(&mut hasher);
+            sum.wrapping_add(hasher.finish())
+        });
+        self.dict.len().hash(state);
+        sum.hash(state);
+    }
+}
 impl Dictionary {
     pub fn new() -> Dictionary {
         Dictionary { dict: IndexMap::new()}
@@ -135,6 +190,13 @@ impl Dictionary {
     pub fn iter(&self) -> impl Iterator<Item=(&Name, &Primitive)> {
         self.dict.iter()
     }
+    /// The entries sorted by the bytes of their keys, for output that doesn't depend on the
+    /// order they were inserted or read in.
+    pub fn entries_sorted(&self) -> impl Iterator<Item=(&Name, &Primitive)> {
+        let mut entries: Vec<_> = self.dict.iter().collect();
+        entries.sort_unstable_by(|a, b| a.0.as_str().as_bytes().cmp(b.0.as_str().as_bytes()));
+        entries.into_iter()
+    }
     pub fn remove(&mut self, key: &str) -> Option<Primitive> {
         self.dict.remove(key)
     }
@@ -170,6 +232,32 @@ impl Dictionary {
     pub fn append(&mut self, other: Dictionary) {
         self.dict.extend(other.dict);
     }
+    /// Look up a value through a sequence of keys, resolving references at each step.
+    ///
+    /// Returns `Ok(None)` if any key is absent (or null). All values but the last have to be
+    /// dictionaries or streams, whose dictionary is used.
+    pub fn get_path(&self, path: &[&str], resolve: &impl Resolve) -> Result<Option<Primitive>> {
+        let mut dict = Cow::Borrowed(self);
+        for (i, &key) in path.iter().enumerate() {
+            let value = match dict.get(key) {
+                Some(p) => t!(p.clone().resolve(resolve), key),
+                None => return Ok(None)
+            };
+            if i + 1 == path.len() {
+                return Ok(match value {
+                    Primitive::Null => None,
+                    p => Some(p)
+                });
+            }
+            dict = Cow::Owned(match value {
+                Primitive::Dictionary(dict) => dict,
+                Primitive::Stream(stream) => stream.info,
+                Primitive::Null => return Ok(None),
+                p => return Err(PdfError::UnexpectedPrimitive { expected: "Dictionary", found: p.get_debug_name() })
+            });
+        }
+        Ok(Some(Primitive::Dictionary(dict.into_owned())))
+    }
 }
 impl DataSize for Dictionary {
     const IS_DYNAMIC: bool = true;
@@ -202,7 +290,8 @@ impl Dictionary {
     fn serialize(&self, out: &mut impl io::Write) -> Result<()> {
         writeln!(out, "<<")?;
         for (key, val) in self.iter() {
-            write!(out, "{} ", key)?;
+            serialize_name(key, out)?;
+            write!(out, " ")?;
             val.serialize(out)?;
             writeln!(out)?;
         }
@@ -246,13 +335,13 @@ impl<'a> IntoIterator for &'a Dictionary {
 }
 
 /// Primitive Stream (as opposed to the higher-level `Stream`)
-#[derive(Clone, Debug, PartialEq, DataSize)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, DataSize)]
 pub struct PdfStream {
     pub info: Dictionary,
     pub (crate) inner: StreamInner,
 }
 
-#[derive(Clone, Debug, PartialEq, DataSize)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, DataSize)]
 pub enum StreamInner {
     InFile { id: PlainRef, file_range: Range<usize> },
     Pending { data: Arc<[u8]> },
@@ -429,6 +518,11 @@ impl PdfString {
             for &b in self.data.as_slice() {
                 match b {
                     b'\\' | b'(' | b')' => write!(out, r"\")?,
+                    // a raw CR would be read back as LF
+                    b'\r' => {
+                        write!(out, r"\r")?;
+                        continue;
+                    }
                     _ => ()
                 }
                 out.write_all(&[b])?;
@@ -551,7 +645,7 @@ impl Primitive {
     pub fn as_bool(&self) -> Result<bool> {
         match *self {
             Primitive::Boolean (b) => Ok(b),
-            ref p => unexpected_primitive!(Number, p.get_debug_name())
+            ref p => unexpected_primitive!(Boolean, p.get_debug_name())
         }
     }
     pub fn as_name(&self) -> Result<&str> {
@@ -610,8 +704,18 @@ impl Primitive {
         let s = self.as_string()?;
         s.to_string()
     }
-    pub fn into_stream(self, _r: &impl Resolve) -> Result<PdfStream> {
+    /// The stream, if the primitive already is one.
+    ///
+    /// Streams are usually referenced indirectly; use `into_stream` to follow a reference.
+    pub fn as_stream(&self) -> Result<&PdfStream> {
         match self {
+            Primitive::Stream(ref s) => Ok(s),
+            p => unexpected_primitive!(Stream, p.get_debug_name())
+        }
+    }
+    /// The stream, resolving the primitive first if it is a reference.
+    pub fn into_stream(self, r: &impl Resolve) -> Result<PdfStream> {
+        match self.resolve(r)? {
             Primitive::Stream (s) => Ok(s),
             p => unexpected_primitive!(Stream, p.get_debug_name())
         }
@@ -664,6 +768,51 @@ impl From<PlainRef> for Primitive {
         Primitive::Reference (x)
     }
 }
+/// Creates a String primitive (not a Name) holding the UTF-8 bytes of `x`.
+impl From<String> for Primitive {
+    fn from(x: String) -> Primitive {
+        Primitive::String (PdfString::from(x.as_str()))
+    }
+}
+
+// Owned conversions. None of these resolve references: a `Primitive::Reference`
+// is an error here and has to be resolved explicitly first.
+impl TryFrom<Primitive> for i32 {
+    type Error = PdfError;
+    fn try_from(p: Primitive) -> Result<i32> {
+        p.as_integer()
+    }
+}
+impl TryFrom<Primitive> for f32 {
+    type Error = PdfError;
+    fn try_from(p: Primitive) -> Result<f32> {
+        p.as_number()
+    }
+}
+impl TryFrom<Primitive> for bool {
+    type Error = PdfError;
+    fn try_from(p: Primitive) -> Result<bool> {
+        p.as_bool()
+    }
+}
+impl TryFrom<Primitive> for String {
+    type Error = PdfError;
+    fn try_from(p: Primitive) -> Result<String> {
+        (&p).try_into()
+    }
+}
+impl TryFrom<Primitive> for Vec<Primitive> {
+    type Error = PdfError;
+    fn try_from(p: Primitive) -> Result<Vec<Primitive>> {
+        p.into_array()
+    }
+}
+impl TryFrom<Primitive> for Dictionary {
+    type Error = PdfError;
+    fn try_from(p: Primitive) -> Result<Dictionary> {
+        p.into_dictionary()
+    }
+}
 impl<'a> TryInto<f32> for &'a Primitive {
     type Error = PdfError;
     fn try_into(self) -> Result<f32> {
@@ -833,9 +982,47 @@ impl ObjectWrite for Date {
 
 #[cfg(test)]
 mod tests {
-    use crate::{primitive::{PdfString, TimeRel}, object::{NoResolve, Object}};
+    use crate::{primitive::{Dictionary, PdfString, Primitive, TimeRel, serialize_name}, object::{NoResolve, Object}};
 
     use super::Date;
+    #[test]
+    fn entries_sorted() {
+        let mut a = Dictionary::new();
+        let mut b = Dictionary::new();
+        for key in ["Type", "b", "Length", "a"] {
+            a.insert(key, 1);
+        }
+        for key in ["a", "Length", "b", "Type"] {
+            b.insert(key, 1);
+        }
+        let keys = |d: &Dictionary| d.entries_sorted().map(|(k, _)| k.as_str().to_owned()).collect::<Vec<_>>();
+        assert_eq!(keys(&a), ["Length", "Type", "a", "b"]);
+        assert_eq!(keys(&a), keys(&b));
+    }
+
+    #[test]
+    fn name_escapes() {
+        use crate::parser::{parse, ParseFlags};
+
+        for name in ["A B", "a/b", "#1", "(x)[y]", "tab\tüber", "plain"] {
+            let mut data = vec![];
+            serialize_name(name, &mut data).unwrap();
+            let parsed = parse(&data, &NoResolve, ParseFlags::NAME).unwrap();
+            assert_eq!(parsed.as_name().unwrap(), name, "{:?}", std::str::from_utf8(&data));
+        }
+        let mut data = vec![];
+        Primitive::name("A B/#").serialize(&mut data).unwrap();
+        assert_eq!(data, b"/A#20B#2F#23");
+
+        // dictionary keys too
+        let mut dict = Dictionary::new();
+        dict.insert("a b", Primitive::name("c/d"));
+        let mut data = vec![];
+        Primitive::Dictionary(dict).serialize(&mut data).unwrap();
+        let parsed = parse(&data, &NoResolve, ParseFlags::DICT).unwrap().into_dictionary().unwrap();
+        assert_eq!(parsed["a b"].as_name().unwrap(), "c/d");
+    }
+
     #[test]
     fn utf16be_string() {
         let s = PdfString::new([0xfe, 0xff, 0x20, 0x09].as_slice().into());
@@ -894,4 +1081,69 @@ mod tests {
         };
         assert_eq!(d.unwrap(), d2);
     }
+
+    #[test]
+    fn conversions() {
+        use super::{Primitive, Dictionary};
+        use crate::object::PlainRef;
+        use std::convert::TryInto;
+
+        let n: i32 = Primitive::Integer(3).try_into().unwrap();
+        assert_eq!(n, 3);
+        let f: f32 = Primitive::Integer(3).try_into().unwrap();
+        assert_eq!(f, 3.);
+        let b: bool = Primitive::from(true).try_into().unwrap();
+        assert!(b);
+        let s: String = Primitive::from(String::from("mitä")).try_into().unwrap();
+        assert_eq!(s, "mitä");
+        let s: String = Primitive::name("Name").try_into().unwrap();
+        assert_eq!(s, "Name");
+        let v: Vec<Primitive> = Primitive::from(vec![Primitive::Null]).try_into().unwrap();
+        assert_eq!(v, [Primitive::Null]);
+        let d: Dictionary = Primitive::from(Dictionary::new()).try_into().unwrap();
+        assert!(d.is_empty());
+
+        // references are not resolved
+        let r = Primitive::Reference(PlainRef { id: 1, gen: 0 });
+        assert!(TryInto::<i32>::try_into(r.clone()).is_err());
+        assert!(TryInto::<Dictionary>::try_into(r).is_err());
+        assert!(TryInto::<bool>::try_into(Primitive::Integer(1)).is_err());
+    }
+
+    #[test]
+    fn structural_eq() {
+        use super::{Primitive, Dictionary};
+        use crate::object::PlainRef;
+        use std::collections::HashSet;
+
+        let mut a = Dictionary::new();
+        a.insert("Type", Primitive::name("Font"));
+        a.insert("Widths", vec![Primitive::Integer(500), Primitive::Number(f32::NAN)]);
+        let mut b = Dictionary::new();
+        b.insert("Widths", vec![Primitive::Integer(500), Primitive::Number(f32::NAN)]);
+        b.insert("Type", Primitive::name("Font"));
+        assert_eq!(a, b);
+
+        let set: HashSet<Primitive> = vec![Primitive::from(a), Primitive::from(b)].into_iter().collect();
+        assert_eq!(set.len(), 1);
+
+        assert_ne!(Primitive::Integer(1), Primitive::Number(1.));
+        assert_ne!(Primitive::Number(0.), Primitive::Number(-0.));
+        assert_eq!(Primitive::Reference(PlainRef { id: 3, gen: 0 }), Primitive::Reference(PlainRef { id: 3, gen: 0 }));
+        assert_ne!(Primitive::Reference(PlainRef { id: 3, gen: 0 }), Primitive::Reference(PlainRef { id: 3, gen: 1 }));
+    }
+
+    #[test]
+    fn stream_accessors() {
+        use super::*;
+        use crate::error::PdfError;
+
+        let stream = PdfStream { info: Dictionary::new(), inner: StreamInner::Pending { data: b"data".as_slice().into() } };
+        let p = Primitive::Stream(stream);
+        assert_eq!(p.as_stream().unwrap().raw_data(&NoResolve).unwrap().as_ref(), b"data");
+        assert!(p.into_stream(&NoResolve).is_ok());
+
+        assert!(matches!(Primitive::Integer(1).as_stream(), Err(PdfError::UnexpectedPrimitive { expected: "Stream", found: "Integer" })));
+        assert!(matches!(Primitive::Null.into_stream(&NoResolve), Err(PdfError::UnexpectedPrimitive { expected: "Stream", found: "Null" })));
+    }
 }
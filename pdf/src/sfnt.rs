@@ -0,0 +1,572 @@
+//! Just enough of a TrueType/OpenType parser to embed a font, and to read TrueType outlines.
+
+use crate::error::*;
+use std::convert::{TryFrom, TryInto};
+
+fn u16_at(data: &[u8], pos: usize) -> Result<u16> {
+    match data.get(pos .. pos + 2) {
+        Some(b) => Ok(u16::from_be_bytes(b.try_into().unwrap())),
+        None => Err(PdfError::EOF)
+    }
+}
+fn i16_at(data: &[u8], pos: usize) -> Result<i16> {
+    u16_at(data, pos).map(|v| v as i16)
+}
+fn u32_at(data: &[u8], pos: usize) -> Result<u32> {
+    match data.get(pos .. pos + 4) {
+        Some(b) => Ok(u32::from_be_bytes(b.try_into().unwrap())),
+        None => Err(PdfError::EOF)
+    }
+}
+
+/// The metrics of a font program, in font units.
+#[derive(Debug)]
+pub struct Sfnt<'a> {
+    data: &'a [u8],
+    /// true if the glyphs are CFF outlines ('OTTO')
+    pub cff: bool,
+    pub units_per_em: u16,
+    /// xMin, yMin, xMax, yMax
+    pub bbox: [i16; 4],
+    pub ascent: i16,
+    pub descent: i16,
+    pub line_gap: i16,
+    pub cap_height: Option<i16>,
+    pub x_height: Option<i16>,
+    pub weight: Option<u16>,
+    pub italic_angle: f32,
+    pub fixed_pitch: bool,
+    pub postscript_name: Option<String>,
+    advances: Vec<u16>,
+    cmap: Option<&'a [u8]>,
+    glyf: Option<&'a [u8]>,
+    loca: Option<&'a [u8]>,
+    /// `indexToLocFormat` 1: `loca` holds 32 bit offsets
+    long_loca: bool,
+}
+
+/// A point of a TrueType outline, in font units.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GlyphPoint {
+    pub x: f32,
+    pub y: f32,
+    /// false for the control point of a quadratic curve
+    pub on_curve: bool,
+}
+
+/// The outline of a glyph, with the points of composite glyphs assembled from their components.
+#[derive(Debug, Clone, Default)]
+pub struct Glyph {
+    pub contours: Vec<Vec<GlyphPoint>>,
+    /// The advance width, from the component with `USE_MY_METRICS` if there is one.
+    pub advance: u16,
+}
+impl Glyph {
+    fn points(&self) -> impl Iterator<Item=&GlyphPoint> {
+        self.contours.iter().flatten()
+    }
+}
+
+// flags of a composite glyph component
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+const USE_MY_METRICS: u16 = 0x0200;
+const SCALED_COMPONENT_OFFSET: u16 = 0x0800;
+
+/// How deep composite glyphs may be nested.
+const MAX_COMPONENT_DEPTH: usize = 8;
+/// How many points and components a glyph may have in total, counting every use of a component.
+const MAX_GLYPH_POINTS: usize = 0xFFFF;
+const MAX_GLYPH_COMPONENTS: usize = 0xFFFF;
+
+/// What is left of the limits while loading one glyph.
+struct GlyphBudget {
+    points: usize,
+    components: usize,
+}
+
+fn f2dot14_at(data: &[u8], pos: usize) -> Result<f32> {
+    i16_at(data, pos).map(|v| v as f32 / 16384.)
+}
+
+impl<'a> Sfnt<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Sfnt<'a>> {
+        let tag = u32_at(data, 0)?;
+        let cff = match &tag.to_be_bytes() {
+            b"OTTO" => true,
+            b"true" | [0, 1, 0, 0] => false,
+            _ => bail!("not a TrueType or OpenType font")
+        };
+        let num_tables = u16_at(data, 4)? as usize;
+        let mut tables = Vec::with_capacity(num_tables);
+        for i in 0 .. num_tables {
+            let record = 12 + 16 * i;
+            let tag = data.get(record .. record + 4).ok_or(PdfError::EOF)?;
+            let offset = u32_at(data, record + 8)? as usize;
+            let len = u32_at(data, record + 12)? as usize;
+            let table = data.get(offset .. offset + len).ok_or(PdfError::EOF)?;
+            tables.push((tag, table));
+        }
+        let table = |name: &[u8]| tables.iter().find(|&&(tag, _)| tag == name).map(|&(_, t)| t);
+        let require = |name: &'static str| table(name.as_bytes())
+            .ok_or_else(|| other!("font has no {} table", name));
+
+        let head = require("head")?;
+        let hhea = require("hhea")?;
+        let hmtx = require("hmtx")?;
+
+        let num_h_metrics = u16_at(hhea, 34)? as usize;
+        let advances = (0 .. num_h_metrics).map(|i| u16_at(hmtx, 4 * i)).collect::<Result<_>>()?;
+
+        let mut font = Sfnt {
+            data,
+            cff,
+            units_per_em: u16_at(head, 18)?,
+            bbox: [i16_at(head, 36)?, i16_at(head, 38)?, i16_at(head, 40)?, i16_at(head, 42)?],
+            ascent: i16_at(hhea, 4)?,
+            descent: i16_at(hhea, 6)?,
+            line_gap: i16_at(hhea, 8)?,
+            cap_height: None,
+            x_height: None,
+            weight: None,
+            italic_angle: 0.,
+            fixed_pitch: false,
+            postscript_name: None,
+            advances,
+            cmap: table(b"cmap"),
+            glyf: table(b"glyf"),
+            loca: table(b"loca"),
+            long_loca: i16_at(head, 50)? == 1,
+        };
+        if let Some(os2) = table(b"OS/2") {
+            font.weight = u16_at(os2, 4).ok();
+            if u16_at(os2, 0)? >= 2 {
+                font.x_height = i16_at(os2, 86).ok();
+                font.cap_height = i16_at(os2, 88).ok();
+            }
+        }
+        if let Some(post) = table(b"post") {
+            font.italic_angle = u32_at(post, 4)? as i32 as f32 / 65536.;
+            font.fixed_pitch = u32_at(post, 12)? != 0;
+        }
+        if let Some(name) = table(b"name") {
+            font.postscript_name = postscript_name(name);
+        }
+        Ok(font)
+    }
+
+    /// The complete font program.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The advance width of the glyph `gid`.
+    pub fn advance(&self, gid: u16) -> u16 {
+        let gid = (gid as usize).min(self.advances.len().saturating_sub(1));
+        self.advances.get(gid).copied().unwrap_or(0)
+    }
+
+    /// Look up the glyph for `c` in the Unicode cmap subtable.
+    pub fn glyph(&self, c: char) -> Option<u16> {
+        let cmap = self.cmap?;
+        let num_tables = u16_at(cmap, 2).ok()? as usize;
+        let mut best = None;
+        for i in 0 .. num_tables {
+            let record = 4 + 8 * i;
+            let platform = u16_at(cmap, record).ok()?;
+            let encoding = u16_at(cmap, record + 2).ok()?;
+            let offset = u32_at(cmap, record + 4).ok()? as usize;
+            let format = u16_at(cmap, offset).ok()?;
+            let unicode = matches!((platform, encoding), (0, _) | (3, 1) | (3, 10));
+            if unicode && (format == 4 || format == 12) && best.is_none_or(|(f, _)| f < format) {
+                best = Some((format, offset));
+            }
+        }
+        let (format, offset) = best?;
+        let table = cmap.get(offset ..)?;
+        match format {
+            4 => cmap_format_4(table, c as u32).ok()?,
+            _ => cmap_format_12(table, c as u32).ok()?,
+        }
+    }
+}
+
+impl<'a> Sfnt<'a> {
+    /// The data of glyph `gid` in the `glyf` table, empty for glyphs without outline.
+    fn glyph_data(&self, gid: u16) -> Result<&'a [u8]> {
+        let (glyf, loca) = match (self.glyf, self.loca) {
+            (Some(glyf), Some(loca)) => (glyf, loca),
+            _ => bail!("font has no glyf or loca table")
+        };
+        let offset = |i: usize| -> Result<usize> {
+            Ok(match self.long_loca {
+                true => u32_at(loca, 4 * i)? as usize,
+                false => 2 * u16_at(loca, 2 * i)? as usize,
+            })
+        };
+        let (start, end) = (offset(gid as usize)?, offset(gid as usize + 1)?);
+        if start > end {
+            bail!("invalid loca entry for glyph {}", gid);
+        }
+        glyf.get(start .. end).ok_or(PdfError::EOF)
+    }
+
+    /// The outline of glyph `gid`, `None` for fonts without TrueType outlines (CFF).
+    pub fn outline(&self, gid: u16) -> Result<Option<Glyph>> {
+        if self.glyf.is_none() {
+            return Ok(None);
+        }
+        let mut budget = GlyphBudget { points: MAX_GLYPH_POINTS, components: MAX_GLYPH_COMPONENTS };
+        self.load_glyph(gid, MAX_COMPONENT_DEPTH, &mut budget).map(Some)
+    }
+
+    fn load_glyph(&self, gid: u16, depth: usize, budget: &mut GlyphBudget) -> Result<Glyph> {
+        let data = self.glyph_data(gid)?;
+        let mut glyph = Glyph { contours: vec![], advance: self.advance(gid) };
+        if data.is_empty() {
+            return Ok(glyph);
+        }
+        match i16_at(data, 0)? {
+            n if n >= 0 => {
+                glyph.contours = simple_glyph(data, n as usize)?;
+                let points = glyph.points().count();
+                budget.points = match budget.points.checked_sub(points) {
+                    Some(left) => left,
+                    None => bail!("glyph {} has more than {} points", gid, MAX_GLYPH_POINTS)
+                };
+            }
+            _ if depth == 0 => bail!("composite glyphs nested too deep"),
+            _ => {
+                let mut pos = 10;
+                loop {
+                    let flags = u16_at(data, pos)?;
+                    budget.components = match budget.components.checked_sub(1) {
+                        Some(left) => left,
+                        None => bail!("glyph {} has more than {} components", gid, MAX_GLYPH_COMPONENTS)
+                    };
+                    let component = self.load_glyph(u16_at(data, pos + 2)?, depth - 1, budget)?;
+                    pos += 4;
+                    let (arg1, arg2) = match flags & ARG_1_AND_2_ARE_WORDS != 0 {
+                        true => {
+                            pos += 4;
+                            (i16_at(data, pos - 4)? as i32, i16_at(data, pos - 2)? as i32)
+                        }
+                        false => {
+                            pos += 2;
+                            let b = data.get(pos - 2 .. pos).ok_or(PdfError::EOF)?;
+                            // offsets are signed, point numbers unsigned
+                            match flags & ARGS_ARE_XY_VALUES != 0 {
+                                true => (b[0] as i8 as i32, b[1] as i8 as i32),
+                                false => (b[0] as i32, b[1] as i32),
+                            }
+                        }
+                    };
+                    // x' = a x + c y, y' = b x + d y
+                    let [a, b, c, d] = if flags & WE_HAVE_A_SCALE != 0 {
+                        pos += 2;
+                        let s = f2dot14_at(data, pos - 2)?;
+                        [s, 0., 0., s]
+                    } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                        pos += 4;
+                        [f2dot14_at(data, pos - 4)?, 0., 0., f2dot14_at(data, pos - 2)?]
+                    } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                        pos += 8;
+                        [f2dot14_at(data, pos - 8)?, f2dot14_at(data, pos - 6)?, f2dot14_at(data, pos - 4)?, f2dot14_at(data, pos - 2)?]
+                    } else {
+                        [1., 0., 0., 1.]
+                    };
+                    let transform = |p: &GlyphPoint| GlyphPoint { x: a * p.x + c * p.y, y: b * p.x + d * p.y, on_curve: p.on_curve };
+
+                    let (dx, dy) = if flags & ARGS_ARE_XY_VALUES != 0 {
+                        let (dx, dy) = (arg1 as f32, arg2 as f32);
+                        match flags & SCALED_COMPONENT_OFFSET != 0 {
+                            true => (a * dx + c * dy, b * dx + d * dy),
+                            false => (dx, dy),
+                        }
+                    } else {
+                        // align point arg2 of the component with point arg1 of what we have so far
+                        let parent = glyph.points().nth(arg1 as usize);
+                        let child = component.points().nth(arg2 as usize).map(transform);
+                        match (parent, child) {
+                            (Some(p), Some(c)) => (p.x - c.x, p.y - c.y),
+                            _ => bail!("invalid point numbers {} {} in composite glyph {}", arg1, arg2, gid)
+                        }
+                    };
+                    glyph.contours.extend(component.contours.iter().map(|contour| {
+                        contour.iter().map(|p| {
+                            let p = transform(p);
+                            GlyphPoint { x: p.x + dx, y: p.y + dy, ..p }
+                        }).collect()
+                    }));
+                    if flags & USE_MY_METRICS != 0 {
+                        glyph.advance = component.advance;
+                    }
+                    if flags & MORE_COMPONENTS == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(glyph)
+    }
+}
+
+/// The contours of a glyph with `num_contours` contours.
+fn simple_glyph(data: &[u8], num_contours: usize) -> Result<Vec<Vec<GlyphPoint>>> {
+    const ON_CURVE: u8 = 0x01;
+    const X_SHORT: u8 = 0x02;
+    const Y_SHORT: u8 = 0x04;
+    const REPEAT: u8 = 0x08;
+    const X_SAME_OR_POSITIVE: u8 = 0x10;
+    const Y_SAME_OR_POSITIVE: u8 = 0x20;
+
+    let end_points = (0 .. num_contours).map(|i| u16_at(data, 10 + 2 * i).map(|v| v as usize)).collect::<Result<Vec<_>>>()?;
+    let num_points = match end_points.last() {
+        Some(&last) => last + 1,
+        None => return Ok(vec![])
+    };
+    let instructions = 10 + 2 * num_contours;
+    let mut pos = instructions + 2 + u16_at(data, instructions)? as usize;
+    let byte = |pos: usize| data.get(pos).copied().ok_or(PdfError::EOF);
+
+    let mut flags = Vec::with_capacity(num_points);
+    while flags.len() < num_points {
+        let flag = byte(pos)?;
+        pos += 1;
+        let count = if flag & REPEAT != 0 {
+            pos += 1;
+            byte(pos - 1)? as usize + 1
+        } else {
+            1
+        };
+        flags.extend(std::iter::repeat_n(flag, count.min(num_points - flags.len())));
+    }
+
+    let mut coords = |short: u8, same_or_positive: u8| -> Result<Vec<f32>> {
+        let mut value = 0i32;
+        flags.iter().map(|&flag| {
+            if flag & short != 0 {
+                pos += 1;
+                let delta = byte(pos - 1)? as i32;
+                value += if flag & same_or_positive != 0 { delta } else { -delta };
+            } else if flag & same_or_positive == 0 {
+                pos += 2;
+                value += i16_at(data, pos - 2)? as i32;
+            }
+            Ok(value as f32)
+        }).collect()
+    };
+    let xs = coords(X_SHORT, X_SAME_OR_POSITIVE)?;
+    let ys = coords(Y_SHORT, Y_SAME_OR_POSITIVE)?;
+
+    let mut start = 0;
+    end_points.iter().map(|&end| {
+        if end < start || end >= num_points {
+            bail!("invalid contour end point {}", end);
+        }
+        let contour = (start ..= end).map(|i| GlyphPoint { x: xs[i], y: ys[i], on_curve: flags[i] & ON_CURVE != 0 }).collect();
+        start = end + 1;
+        Ok(contour)
+    }).collect()
+}
+
+fn cmap_format_4(table: &[u8], c: u32) -> Result<Option<u16>> {
+    if c > 0xFFFF {
+        return Ok(None);
+    }
+    let c = c as u16;
+    let seg_count = u16_at(table, 6)? as usize / 2;
+    let end_codes = 14;
+    let start_codes = end_codes + 2 * seg_count + 2;
+    let id_deltas = start_codes + 2 * seg_count;
+    let id_range_offsets = id_deltas + 2 * seg_count;
+    for seg in 0 .. seg_count {
+        if c > u16_at(table, end_codes + 2 * seg)? {
+            continue;
+        }
+        let start = u16_at(table, start_codes + 2 * seg)?;
+        if c < start {
+            return Ok(None);
+        }
+        let delta = u16_at(table, id_deltas + 2 * seg)?;
+        let range_offset = u16_at(table, id_range_offsets + 2 * seg)? as usize;
+        if range_offset == 0 {
+            return Ok(Some(c.wrapping_add(delta)));
+        }
+        let pos = id_range_offsets + 2 * seg + range_offset + 2 * (c - start) as usize;
+        return Ok(match u16_at(table, pos)? {
+            0 => None,
+            gid => Some(gid.wrapping_add(delta))
+        });
+    }
+    Ok(None)
+}
+
+fn cmap_format_12(table: &[u8], c: u32) -> Result<Option<u16>> {
+    let num_groups = u32_at(table, 12)? as usize;
+    for group in 0 .. num_groups {
+        let pos = 16 + 12 * group;
+        let start = u32_at(table, pos)?;
+        let end = u32_at(table, pos + 4)?;
+        if (start ..= end).contains(&c) {
+            // glyph ids beyond u16::MAX do not exist
+            let gid = u32_at(table, pos + 8)?.checked_add(c - start);
+            return Ok(gid.and_then(|gid| u16::try_from(gid).ok()));
+        }
+    }
+    Ok(None)
+}
+
+/// nameID 6, either from a Windows (UTF-16BE) or a Macintosh (Roman) record
+fn postscript_name(table: &[u8]) -> Option<String> {
+    let count = u16_at(table, 2).ok()? as usize;
+    let storage = u16_at(table, 4).ok()? as usize;
+    for i in 0 .. count {
+        let record = 6 + 12 * i;
+        let platform = u16_at(table, record).ok()?;
+        let name_id = u16_at(table, record + 6).ok()?;
+        if name_id != 6 {
+            continue;
+        }
+        let len = u16_at(table, record + 8).ok()? as usize;
+        let offset = storage + u16_at(table, record + 10).ok()? as usize;
+        let bytes = table.get(offset .. offset + len)?;
+        let name = match platform {
+            0 | 3 => String::from_utf16_lossy(&bytes.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect::<Vec<_>>()),
+            1 => bytes.iter().map(|&b| b as char).collect(),
+            _ => continue
+        };
+        return Some(name);
+    }
+    None
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A TrueType font with the glyphs .notdef, 'A' and 'B' at 1000 units per em.
+    ///
+    /// 'A' is a triangle, 'B' a composite of two (one scaled) 'A's, and glyph 3
+    /// a rotated 'B'.
+    pub(crate) fn test_font() -> Vec<u8> {
+        fn be(parts: &[u16]) -> Vec<u8> {
+            parts.iter().flat_map(|v| v.to_be_bytes()).collect()
+        }
+        let mut head = vec![0; 54];
+        head[18 .. 20].copy_from_slice(&1000u16.to_be_bytes());
+        head[36 .. 44].copy_from_slice(&be(&[0, (-200i16) as u16, 800, 900]));
+
+        let mut hhea = vec![0; 36];
+        hhea[4 .. 10].copy_from_slice(&be(&[800, (-200i16) as u16, 100]));
+        hhea[34 .. 36].copy_from_slice(&3u16.to_be_bytes());
+
+        let hmtx = be(&[500, 0, 600, 0, 700, 0]);
+        let cmap = [
+            be(&[0, 1, 3, 1]), vec![0, 0, 0, 12],
+            be(&[4, 32, 0, 4, 0, 0, 0, 0x42, 0xFFFF, 0, 0x41, 0xFFFF, (-0x40i16) as u16, 1, 0, 0]),
+        ].concat();
+        let mut post = vec![0; 32];
+        post[0 .. 4].copy_from_slice(&0x0003_0000u32.to_be_bytes());
+        let name = [be(&[0, 1, 18, 1, 0, 0, 6, 8, 0]), b"TestFont".to_vec()].concat();
+
+        let glyf = [
+            // (0, 0), (250, 0) short and with the same y, (125, 700) as words
+            be(&[1, 0, 0, 250, 700, 2, 0]), vec![0x31, 0x33, 0x01, 250], be(&[(-125i16) as u16, 700]),
+            // two words, scaled by 0.5 at (0, 300), and two bytes at (100, 0) with USE_MY_METRICS
+            be(&[(-1i16) as u16, 0, 0, 0, 0]),
+            be(&[0x0B | 0x20, 1, 0, 300, 0x2000]),
+            be(&[0x0202, 1]), vec![100, 0],
+            // glyph 2 turned by 90°
+            be(&[(-1i16) as u16, 0, 0, 0, 0]),
+            be(&[0x82, 2]), vec![0, 0], be(&[0, 0x4000, 0xC000, 0]),
+        ].concat();
+        let loca = be(&[0, 0, 11, 24, 36]);
+
+        let tables: [(&[u8; 4], Vec<u8>); 8] = [
+            (b"cmap", cmap), (b"glyf", glyf), (b"head", head), (b"hhea", hhea), (b"hmtx", hmtx), (b"loca", loca), (b"name", name), (b"post", post)
+        ];
+        let mut font = be(&[1, 0, tables.len() as u16, 0, 0, 0]);
+        let mut offset = 12 + 16 * tables.len();
+        for (tag, table) in tables.iter() {
+            font.extend_from_slice(&tag[..]);
+            font.extend_from_slice(&[0; 4]);
+            font.extend_from_slice(&(offset as u32).to_be_bytes());
+            font.extend_from_slice(&(table.len() as u32).to_be_bytes());
+            offset += table.len();
+        }
+        for (_, table) in tables.iter() {
+            font.extend_from_slice(table);
+        }
+        font
+    }
+
+    #[test]
+    fn parse_test_font() {
+        let data = test_font();
+        let font = Sfnt::parse(&data).unwrap();
+        assert!(!font.cff);
+        assert_eq!(font.units_per_em, 1000);
+        assert_eq!(font.bbox, [0, -200, 800, 900]);
+        assert_eq!((font.ascent, font.descent, font.line_gap), (800, -200, 100));
+        assert_eq!(font.postscript_name.as_deref(), Some("TestFont"));
+        assert_eq!(font.glyph('A'), Some(1));
+        assert_eq!(font.glyph('B'), Some(2));
+        assert_eq!(font.glyph('C'), None);
+        assert_eq!(font.advance(2), 700);
+        assert_eq!(font.advance(100), 700);
+        assert!(Sfnt::parse(b"%PDF-1.7").is_err());
+    }
+
+    #[test]
+    fn composite_glyphs() {
+        let data = test_font();
+        let font = Sfnt::parse(&data).unwrap();
+        let point = |x, y| GlyphPoint { x, y, on_curve: true };
+
+        assert!(font.outline(0).unwrap().unwrap().contours.is_empty());
+        let a = font.outline(1).unwrap().unwrap();
+        assert_eq!(a.contours, [[point(0., 0.), point(250., 0.), point(125., 700.)]]);
+        assert_eq!(a.advance, 600);
+
+        let b = font.outline(2).unwrap().unwrap();
+        assert_eq!(b.contours, [
+            [point(0., 300.), point(125., 300.), point(62.5, 650.)],
+            [point(100., 0.), point(350., 0.), point(225., 700.)],
+        ]);
+        // USE_MY_METRICS
+        assert_eq!(b.advance, 600);
+
+        // nested
+        let rotated = font.outline(3).unwrap().unwrap();
+        assert_eq!(rotated.contours[1], [point(0., 100.), point(0., 350.), point(-700., 225.)]);
+        assert_eq!(rotated.advance, 700);
+
+        // glyph 2 uses two components with three points each
+        let load = |points, components| font.load_glyph(2, MAX_COMPONENT_DEPTH, &mut GlyphBudget { points, components });
+        assert!(load(6, 2).is_ok());
+        assert!(load(5, 2).is_err());
+        assert!(load(6, 1).is_err());
+    }
+
+    #[test]
+    fn cmap_format_12_groups() {
+        let be = |parts: &[u32]| -> Vec<u8> { parts.iter().flat_map(|v| v.to_be_bytes()).collect() };
+        let table = [
+            vec![0, 12, 0, 0], be(&[52, 0, 3]),
+            be(&[0x41, 0x5A, 1]),
+            // the glyph ids leave u16, and then u32
+            be(&[0x1_0000, 0x1_0010, 0xFFF8]),
+            be(&[0x2_0000, 0x2_0010, 0xFFFF_FFF8]),
+        ].concat();
+        assert_eq!(cmap_format_12(&table, 0x42).unwrap(), Some(2));
+        assert_eq!(cmap_format_12(&table, 0x1_0007).unwrap(), Some(0xFFFF));
+        assert_eq!(cmap_format_12(&table, 0x1_0008).unwrap(), None);
+        assert_eq!(cmap_format_12(&table, 0x2_0008).unwrap(), None);
+        assert_eq!(cmap_format_12(&table, 0x30).unwrap(), None);
+    }
+}
@@ -0,0 +1,214 @@
+//! The paths of a page as SVG, see `Page::to_svg`.
+//!
+//! Only paths are exported: text, images and shadings are not drawn.
+
+use std::fmt::Write;
+
+use crate::content::{ContentState, Matrix, Op, Point, Winding};
+use crate::error::*;
+use crate::object::*;
+
+struct Svg {
+    out: String,
+    defs: String,
+    clips: usize,
+}
+impl Svg {
+    /// `clip_id` is the id of the `<clipPath>` for the current clip.
+    fn ops(&mut self, ops: &[Op], resources: &Resources, state: &mut ContentState, clip_id: &mut Option<usize>, resolve: &impl Resolve, depth: usize) -> Result<()> {
+        let mut stack = vec![];
+        // the current path in device space, and the winding rule if it is to become the clip
+        let mut path = String::new();
+        let mut clip = None;
+
+        for op in ops {
+            t!(state.apply(op, resources));
+            let p = |p: Point| state.ctm.apply(p);
+            match *op {
+                Op::Save => stack.push(*clip_id),
+                Op::Restore => if let Some(c) = stack.pop() {
+                    *clip_id = c;
+                }
+
+                Op::MoveTo { p: a } => {
+                    let a = p(a);
+                    let _ = write!(path, "M{} {}", a.x, a.y);
+                }
+                Op::LineTo { p: a } => {
+                    let a = p(a);
+                    let _ = write!(path, "L{} {}", a.x, a.y);
+                }
+                Op::CurveTo { c1, c2, p: a } => {
+                    let (c1, c2, a) = (p(c1), p(c2), p(a));
+                    let _ = write!(path, "C{} {} {} {} {} {}", c1.x, c1.y, c2.x, c2.y, a.x, a.y);
+                }
+                Op::Rect { rect } => {
+                    let corners = [(rect.x, rect.y), (rect.x + rect.width, rect.y), (rect.x + rect.width, rect.y + rect.height), (rect.x, rect.y + rect.height)];
+                    for (i, &(x, y)) in corners.iter().enumerate() {
+                        let a = p(Point { x, y });
+                        let _ = write!(path, "{}{} {}", if i == 0 { 'M' } else { 'L' }, a.x, a.y);
+                    }
+                    path.push('Z');
+                }
+                Op::Close => path.push('Z'),
+                Op::Clip { winding } => clip = Some(winding),
+                ref op if op.ends_path() => {
+                    let (fill, stroke) = match *op {
+                        Op::Fill { winding } => (Some(winding), false),
+                        Op::FillAndStroke { winding } => (Some(winding), true),
+                        Op::Stroke => (None, true),
+                        _ => (None, false),
+                    };
+                    if !path.is_empty() {
+                        if fill.is_some() || stroke {
+                            self.path(&path, fill, stroke, state, *clip_id)?;
+                        }
+                        if let Some(winding) = clip {
+                            let id = self.clips;
+                            self.clips += 1;
+                            let _ = write!(self.defs, r#"<clipPath id="clip{}"{}><path d="{}" clip-rule="{}"/></clipPath>"#,
+                                id, clip_attr(*clip_id), path, rule(winding));
+                            *clip_id = Some(id);
+                        }
+                    }
+                    path.clear();
+                    clip = None;
+                }
+
+                Op::XObject { ref name } => {
+                    let xobject = match resources.xobjects.get(name) {
+                        Some(&r) => t!(resolve.get(r)),
+                        None => {
+                            warn!("XObject {} not found", name.as_str());
+                            continue;
+                        }
+                    };
+                    if let XObject::Form(ref form) = *xobject {
+                        if depth == 0 {
+                            continue;
+                        }
+                        let matrix = form.dict().matrix.as_ref().map(|m| Matrix::from_primitive(m.clone(), resolve)).transpose();
+                        let mut inner = state.begin_form(t!(matrix).as_ref());
+                        let form_resources = form.resources(resources);
+                        let ops = t!(form.operations(resolve));
+                        self.ops(&ops, form_resources, &mut inner, &mut clip_id.clone(), resolve, depth - 1)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&mut self, d: &str, fill: Option<Winding>, stroke: bool, state: &ContentState, clip: Option<usize>) -> Result<()> {
+        let fill = match fill {
+            Some(winding) => t!(state.colors.fill_rgb()).map(|rgb| (rgb, winding)),
+            None => None
+        };
+        let stroke_rgb = stroke.then(|| state.colors.stroke_rgb()).transpose()?.flatten();
+        if fill.is_none() && stroke_rgb.is_none() {
+            return Ok(());
+        }
+        // without a fill attribute SVG fills with black
+        let fill = match fill {
+            Some((rgb, winding)) => format!(r#" fill="{}" fill-rule="{}""#, color(rgb), rule(winding)),
+            None => r#" fill="none""#.into()
+        };
+        let stroke = match stroke_rgb {
+            Some(rgb) => {
+                // the line width in device space
+                let m = &state.ctm;
+                let width = state.line_width * (m.a * m.d - m.b * m.c).abs().sqrt();
+                format!(r#" stroke="{}" stroke-width="{}""#, color(rgb), width)
+            }
+            None => String::new()
+        };
+        let _ = writeln!(self.out, r#"<path d="{}"{}{}{}/>"#, d, fill, stroke, clip_attr(clip));
+        Ok(())
+    }
+}
+
+fn rule(winding: Winding) -> &'static str {
+    match winding {
+        Winding::NonZero => "nonzero",
+        Winding::EvenOdd => "evenodd",
+    }
+}
+fn color([r, g, b]: [f32; 3]) -> String {
+    let c = |v: f32| (v * 255.).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", c(r), c(g), c(b))
+}
+fn clip_attr(clip: Option<usize>) -> String {
+    clip.map(|id| format!(r#" clip-path="url(#clip{})""#, id)).unwrap_or_default()
+}
+
+/// An SVG document of the paths `ops` paint within `page`, and nothing else: text, images
+/// and shadings are left out.
+///
+/// `transform` maps from the page, with the origin at the top left corner and y going down
+/// as in SVG, to the output. Paths painted with a color that can't be converted to RGB
+/// (a pattern, or the `/None` colorant) are not filled.
+pub fn content_svg(ops: &[Op], resources: &Resources, page: Rectangle, transform: Matrix, resolve: &impl Resolve) -> Result<String> {
+    let page = page.normalized();
+    let flip = Matrix { a: 1., b: 0., c: 0., d: -1., e: -page.left, f: page.top };
+    let base = flip.multiply(&transform);
+    let mut svg = Svg { out: String::new(), defs: String::new(), clips: 0 };
+    svg.ops(ops, resources, &mut ContentState::new(base), &mut None, resolve, 8)?;
+
+    let size = base.apply(Point { x: page.right, y: page.bottom });
+    let origin = base.apply(Point { x: page.left, y: page.top });
+    let (width, height) = ((size.x - origin.x).abs(), (size.y - origin.y).abs());
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+        w = width, h = height
+    );
+    if !svg.defs.is_empty() {
+        let _ = writeln!(out, "<defs>{}</defs>", svg.defs);
+    }
+    out.push_str(&svg.out);
+    out.push_str("</svg>\n");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::parse_ops;
+
+    fn svg(content: &[u8], transform: Matrix) -> String {
+        let ops = parse_ops(content, &NoResolve).unwrap();
+        let page = Rectangle { left: 0., bottom: 0., right: 200., top: 100. };
+        content_svg(&ops, &Resources::default(), page, transform, &NoResolve).unwrap()
+    }
+
+    #[test]
+    fn paths() {
+        let out = svg(b"1 0 0 rg 10 10 20 30 re f 0 0 1 RG 2 w 0 0 m 100 50 l S 50 50 m 60 60 l n", Matrix::default());
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines, [
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" viewBox="0 0 200 100">"#,
+            r##"<path d="M10 90L30 90L30 60L10 60Z" fill="#ff0000" fill-rule="nonzero"/>"##,
+            r##"<path d="M0 100L100 50" fill="none" stroke="#0000ff" stroke-width="2"/>"##,
+            "</svg>",
+        ]);
+    }
+
+    #[test]
+    fn clip_and_scale() {
+        let scale = Matrix { a: 2., d: 2., ..Matrix::default() };
+        let out = svg(b"q 0 0 50 50 re W n 0 0 100 100 re f Q", scale);
+        assert!(out.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="400" height="200""#), "{}", out);
+        assert!(out.contains(r#"<clipPath id="clip0"><path d="M0 200L100 200L100 100L0 100Z" clip-rule="nonzero"/></clipPath>"#), "{}", out);
+        assert!(out.contains(r##"<path d="M0 200L200 200L200 0L0 0Z" fill="#000000" fill-rule="nonzero" clip-path="url(#clip0)"/>"##), "{}", out);
+    }
+
+    #[test]
+    fn unpainted() {
+        // a pattern fill is not exported, so a path that is only filled with it is left out
+        let out = svg(b"/Pattern cs /P1 scn 0 0 10 10 re f 0 0 10 10 re B", Matrix::default());
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[1 .. lines.len() - 1], [
+            r##"<path d="M0 100L10 100L10 90L0 90Z" fill="none" stroke="#000000" stroke-width="1"/>"##,
+        ]);
+    }
+}
@@ -0,0 +1,413 @@
+//! Extracting the text of a page, see `Page::extract_text`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::cmap::CMap;
+use crate::content::{ContentState, FormXObject, Matrix, Op, TextDrawAdjusted};
+use crate::encoding::glyph_name_to_string;
+use crate::error::*;
+use crate::font::{Font, GlyphMapping, VerticalMetrics, Widths};
+use crate::object::*;
+use crate::primitive::Name;
+
+/// How `Page::extract_text` decides where spaces and line breaks go.
+///
+/// Both thresholds are in multiples of the current font size, in the coordinate system of
+/// the text; horizontal distances are also scaled by the horizontal scaling (`Tz`).
+#[derive(Debug, Clone, Copy)]
+pub struct TextExtractOptions {
+    /// A gap between the end of a glyph and the start of the next one on the same line of
+    /// at least this much inserts a space.
+    pub space_threshold: f32,
+
+    /// A move perpendicular to the baseline of at least this much inserts a line break.
+    pub line_threshold: f32,
+}
+impl Default for TextExtractOptions {
+    fn default() -> Self {
+        TextExtractOptions {
+            space_threshold: 0.15,
+            line_threshold: 0.5,
+        }
+    }
+}
+
+/// (code, widths, text) of each glyph of a string.
+pub(crate) type Glyphs<'a> = Box<dyn Iterator<Item=(&'a [u8], GlyphWidth, Option<String>)> + 'a>;
+
+/// The horizontal width of a glyph and, for vertical fonts, its vertical displacement
+/// `w1_y`, both in glyph space.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GlyphWidth {
+    pub(crate) width: f32,
+    pub(crate) vertical: Option<f32>,
+}
+
+/// What is needed to decode and place the glyphs of a font.
+pub(crate) struct TextFont {
+    widths: Option<Widths>,
+    /// Extent above and below the baseline, in glyph space.
+    pub(crate) ascent: f32,
+    pub(crate) descent: f32,
+    /// The CMap splitting strings into codes, for Type0 fonts.
+    cmap: Option<CMap>,
+    to_unicode: Option<CMap>,
+    /// Glyph names of simple fonts.
+    names: Option<GlyphMapping>,
+    /// `/W2` and `/DW2` of fonts with a vertical CMap (`/WMode 1`).
+    vertical: Option<VerticalMetrics>,
+}
+impl TextFont {
+    pub(crate) fn new(font: &Font, resolve: &impl Resolve) -> Result<TextFont> {
+        let cmap = match font.cid_cmap() {
+            Ok(cmap) => cmap,
+            Err(e) => {
+                warn!("{:?}, assuming two byte codes", e);
+                Some(CMap::predefined("Identity-H")?)
+            }
+        };
+        let to_unicode = font.to_unicode_cmap(resolve).transpose().unwrap_or_else(|e| {
+            warn!("invalid ToUnicode CMap: {:?}", e);
+            None
+        });
+        let names = match cmap {
+            Some(_) => None,
+            None => Some(font.glyph_mapping(resolve)?)
+        };
+        let vertical = match cmap {
+            Some(ref cmap) if cmap.is_vertical() => font.vertical_metrics(resolve).unwrap_or_else(|e| {
+                warn!("invalid vertical metrics: {:?}", e);
+                None
+            }),
+            _ => None
+        };
+        let descriptor = font.font_descriptor();
+        Ok(TextFont {
+            widths: font.widths(resolve)?,
+            // typical values if the font does not say
+            ascent: descriptor.and_then(|d| d.ascent).filter(|&a| a != 0.).unwrap_or(800.),
+            descent: descriptor.and_then(|d| d.descent).unwrap_or(-200.),
+            cmap,
+            to_unicode,
+            names,
+            vertical,
+        })
+    }
+    /// Whether glyphs are placed from top to bottom.
+    pub(crate) fn is_vertical(&self) -> bool {
+        self.vertical.is_some()
+    }
+    /// Split `data` into glyphs.
+    pub(crate) fn glyphs<'a>(&'a self, data: &'a [u8]) -> Glyphs<'a> {
+        let codes: Box<dyn Iterator<Item=&'a [u8]>> = match self.cmap {
+            Some(ref cmap) => Box::new(cmap.codes(data)),
+            None => Box::new(data.chunks(1)),
+        };
+        Box::new(codes.map(move |code| {
+            let cid = match self.cmap {
+                Some(ref cmap) => cmap.cid(code).map(|cid| cid as usize),
+                None => Some(code[0] as usize),
+            };
+            let width = match (&self.widths, cid) {
+                (Some(widths), Some(cid)) => widths.get(cid),
+                _ => 0.
+            };
+            let vertical = self.vertical.as_ref().map(|metrics| cid.map_or(0., |cid| metrics.get(cid, width).advance));
+            let text = match self.to_unicode.as_ref().and_then(|m| m.lookup(code)) {
+                Some(s) => Some(s.into()),
+                None => self.names.as_ref()
+                    .and_then(|names| names.name(code[0] as u32))
+                    .and_then(glyph_name_to_string)
+            };
+            (code, GlyphWidth { width, vertical }, text)
+        }))
+    }
+}
+
+/// A glyph shown by `walk`.
+pub(crate) struct ShownGlyph {
+    /// The width in glyph space.
+    pub(crate) width: f32,
+    /// How far the text matrix moves past it, `(tx, ty)` in text space. `tx` is 0 for
+    /// vertical fonts and `ty` for horizontal ones.
+    pub(crate) advance: (f32, f32),
+    pub(crate) text: Option<String>,
+}
+
+/// The state of `walk`. `extra` is whatever else the visitor needs saved by `q`.
+#[derive(Clone)]
+pub(crate) struct WalkState<S> {
+    pub(crate) content: ContentState,
+    /// The font selected by `Tf`.
+    pub(crate) font: Option<Arc<TextFont>>,
+    pub(crate) extra: S,
+}
+
+/// Receives the content `walk` goes through.
+pub(crate) trait Visitor {
+    /// Saved with `q` and restored with `Q`, like the graphics state.
+    type State: Clone;
+
+    /// Every operation but the ones showing text or painting an XObject, after `state` was
+    /// updated for it.
+    fn op(&mut self, _op: &Op, _state: &mut WalkState<Self::State>) -> Result<()> {
+        Ok(())
+    }
+    /// A glyph, before the text matrix is moved past it.
+    fn glyph(&mut self, _glyph: ShownGlyph, _font: &TextFont, _state: &WalkState<Self::State>) {}
+    /// An image XObject, which fills the unit square.
+    fn image(&mut self, _state: &WalkState<Self::State>) {}
+    /// The start of a form XObject, with `inner` the state it is painted in.
+    fn form(&mut self, _form: &FormXObject, _inner: &mut WalkState<Self::State>) {}
+}
+
+/// Go through `ops` and the form XObjects they paint, up to `depth` levels deep, loading
+/// the fonts and placing their glyphs.
+pub(crate) fn walk<V: Visitor>(visitor: &mut V, ops: &[Op], resources: &Resources, state: &mut WalkState<V::State>, resolve: &impl Resolve, depth: usize) -> Result<()> {
+    let mut fonts: HashMap<Name, Arc<TextFont>> = HashMap::new();
+    // what `ContentState` doesn't save itself
+    let mut stack = vec![];
+
+    for op in ops {
+        t!(state.content.apply(op, resources));
+        match *op {
+            Op::Save => stack.push((state.font.clone(), state.extra.clone())),
+            Op::Restore => if let Some((font, extra)) = stack.pop() {
+                state.font = font;
+                state.extra = extra;
+            }
+            Op::TextFont { ref name, .. } => {
+                state.font = match fonts.get(name) {
+                    Some(f) => Some(f.clone()),
+                    None => match resources.fonts.get(name) {
+                        Some(font) => {
+                            let font = t!(font.load(resolve));
+                            let font = Arc::new(t!(TextFont::new(&font, resolve)));
+                            fonts.insert(name.clone(), font.clone());
+                            Some(font)
+                        }
+                        None => {
+                            warn!("font {} not found", name.as_str());
+                            None
+                        }
+                    }
+                };
+            }
+            Op::TextDraw { ref text } => {
+                show(visitor, text.as_bytes(), state);
+                continue;
+            }
+            Op::TextDrawAdjusted { ref array } => {
+                for part in array {
+                    match *part {
+                        TextDrawAdjusted::Text(ref text) => show(visitor, text.as_bytes(), state),
+                        TextDrawAdjusted::Spacing(n) => {
+                            let text = &state.content.text;
+                            let size = text.font.as_ref().map_or(0., |&(_, size)| size);
+                            let m = match state.font.as_ref().is_some_and(|f| f.is_vertical()) {
+                                true => translate(0., -n / 1000. * size),
+                                false => translate(-n / 1000. * size * text.horiz_scale, 0.),
+                            };
+                            state.content.text_matrix = m.multiply(&state.content.text_matrix);
+                        }
+                    }
+                }
+                continue;
+            }
+            Op::XObject { ref name } => {
+                let xobject = match resources.xobjects.get(name) {
+                    Some(&r) => t!(resolve.get(r)),
+                    None => {
+                        warn!("XObject {} not found", name.as_str());
+                        continue;
+                    }
+                };
+                match *xobject {
+                    XObject::Image(_) => visitor.image(state),
+                    XObject::Form(ref form) if depth > 0 => {
+                        let matrix = form.dict().matrix.as_ref().map(|m| Matrix::from_primitive(m.clone(), resolve)).transpose();
+                        let mut inner = WalkState {
+                            content: state.content.begin_form(t!(matrix).as_ref()),
+                            font: state.font.clone(),
+                            extra: state.extra.clone(),
+                        };
+                        visitor.form(form, &mut inner);
+                        let form_resources = form.resources(resources);
+                        let ops = t!(form.operations(resolve));
+                        walk(visitor, &ops, form_resources, &mut inner, resolve, depth - 1)?;
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            _ => {}
+        }
+        visitor.op(op, state)?;
+    }
+    Ok(())
+}
+
+/// Show the glyphs of `data` with the current font, moving the text matrix past each.
+fn show<V: Visitor>(visitor: &mut V, data: &[u8], state: &mut WalkState<V::State>) {
+    let font = match state.font {
+        Some(ref font) => font.clone(),
+        None => return
+    };
+    for (code, GlyphWidth { width, vertical }, text) in font.glyphs(data) {
+        let t = &state.content.text;
+        let size = t.font.as_ref().map_or(0., |&(_, size)| size);
+        let word_space = if code == b" " { t.word_space } else { 0. };
+        // 9.4.4: vertical displacements are not scaled horizontally
+        let advance = match vertical {
+            Some(w1_y) => (0., w1_y / 1000. * size + t.char_space + word_space),
+            None => ((width / 1000. * size + t.char_space + word_space) * t.horiz_scale, 0.),
+        };
+        visitor.glyph(ShownGlyph { width, advance, text }, &font, state);
+        state.content.text_matrix = translate(advance.0, advance.1).multiply(&state.content.text_matrix);
+    }
+}
+
+/// Where the previous glyph ended, in user space.
+#[derive(Clone, Copy)]
+struct LastGlyph {
+    end: (f32, f32),
+    /// How far the text rise (`Ts`) moved it from the baseline of the line.
+    lift: (f32, f32),
+    /// The vectors along and across its baseline that are one (scaled) font size long.
+    along: (f32, f32),
+    across: (f32, f32),
+}
+
+struct Extractor<'a> {
+    options: &'a TextExtractOptions,
+    out: String,
+    last: Option<LastGlyph>,
+}
+
+pub(crate) fn translate(x: f32, y: f32) -> Matrix {
+    Matrix { e: x, f: y, ..Matrix::default() }
+}
+fn dot(a: (f32, f32), b: (f32, f32)) -> f32 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+impl Visitor for Extractor<'_> {
+    type State = ();
+
+    fn glyph(&mut self, glyph: ShownGlyph, font: &TextFont, state: &WalkState<()>) {
+        let state = &state.content;
+        let rise = state.text.rise;
+        let trm = state.text_rendering_matrix();
+        let origin = (trm.e, trm.f);
+        // vertical text runs downwards, and its lines from right to left
+        let (along, across) = match font.is_vertical() {
+            true => ((-trm.c, -trm.d), (-trm.a, -trm.b)),
+            false => ((trm.a, trm.b), (trm.c, trm.d)),
+        };
+        let tm = state.text_matrix.multiply(&state.ctm);
+        let lift = (rise * tm.c, rise * tm.d);
+        if let Some(ref text) = glyph.text {
+            self.separate(origin, lift, along);
+            self.out.push_str(text);
+        }
+        let m = translate(glyph.advance.0, glyph.advance.1 + rise).multiply(&tm);
+        self.last = Some(LastGlyph { end: (m.e, m.f), lift, along, across });
+    }
+}
+
+impl Extractor<'_> {
+    /// Insert a space or line break if the glyph at `origin` is far enough from the previous one.
+    ///
+    /// Glyphs are compared on the baseline without their rise (`lift`), so superscripts and
+    /// subscripts stay on their line.
+    fn separate(&mut self, origin: (f32, f32), lift: (f32, f32), along: (f32, f32)) {
+        let LastGlyph { end: (x, y), lift: last_lift, along: last_along, across: last_across } = match self.last {
+            Some(last) => last,
+            None => return
+        };
+        if self.out.is_empty() {
+            return;
+        }
+        let d = (origin.0 - lift.0 - (x - last_lift.0), origin.1 - lift.1 - (y - last_lift.1));
+        // distances in multiples of the (previous) font size
+        let len2 = |v: (f32, f32)| dot(v, v);
+        let dx = if len2(last_along) > 0. { dot(d, last_along) / len2(last_along) } else { 0. };
+        let dy = if len2(last_across) > 0. { dot(d, last_across) / len2(last_across) } else { 0. };
+        let rotated = dot(along, last_along) <= 0. && len2(along) > 0.;
+
+        if dy.abs() >= self.options.line_threshold || rotated {
+            if !self.out.ends_with('\n') {
+                self.out.push('\n');
+            }
+        } else if dx >= self.options.space_threshold && !self.out.ends_with(char::is_whitespace) {
+            self.out.push(' ');
+        }
+    }
+}
+
+/// The text of `ops`, see `Page::extract_text`.
+pub fn extract_text(ops: &[Op], resources: &Resources, options: &TextExtractOptions, resolve: &impl Resolve) -> Result<String> {
+    let mut extractor = Extractor { options, out: String::new(), last: None };
+    let mut state = WalkState { content: ContentState::default(), font: None, extra: () };
+    walk(&mut extractor, ops, resources, &mut state, resolve, 8)?;
+    Ok(extractor.out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::parse_ops;
+    use crate::parser::{parse, ParseFlags};
+
+    fn extract(content: &[u8], options: &TextExtractOptions) -> String {
+        let font = b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>";
+        let font = parse(font, &NoResolve, ParseFlags::ANY).unwrap();
+        let mut resources = Resources::default();
+        resources.fonts.insert("F1".into(), Lazy::from_primitive(font, &NoResolve).unwrap());
+        let ops = parse_ops(content, &NoResolve).unwrap();
+        extract_text(&ops, &resources, options, &NoResolve).unwrap()
+    }
+
+    #[test]
+    fn spaces_and_lines() {
+        let options = TextExtractOptions::default();
+        let content = b"BT /F1 12 Tf 10 700 Td (Hello) Tj 40 0 Td (World) Tj 0 -20 Td (Next) Tj ET";
+        assert_eq!(extract(content, &options), "Hello World\nNext");
+
+        // kerning does not split words, a large adjustment does
+        let content = b"BT /F1 10 Tf [(Ke) 30 (rn) -400 (ing)] TJ ET";
+        assert_eq!(extract(content, &options), "Kern ing");
+
+        // explicit spaces are kept, and not doubled
+        let content = b"BT /F1 10 Tf (a b) Tj 50 0 Td (c) Tj ET";
+        assert_eq!(extract(content, &options), "a b c");
+
+        // gaps are relative to the scaled font size
+        for content in [&b"BT /F1 10 Tf 50 Tz [(a) -200 (b) -100 (c)] TJ ET"[..], b"BT /F1 40 Tf 200 Tz [(a) -200 (b) -100 (c)] TJ ET"] {
+            assert_eq!(extract(content, &options), "a bc");
+        }
+    }
+
+    #[test]
+    fn rise_and_scaling() {
+        let options = TextExtractOptions::default();
+        // superscripts and subscripts stay on the line, in either direction
+        let content = b"BT /F1 10 Tf (x) Tj 7 Tf 4 Ts (2) Tj 0 Ts 10 Tf (y) Tj -3 Ts (i) Tj ET";
+        assert_eq!(extract(content, &options), "x2yi");
+        // a rise does not hide a move to the next line
+        let content = b"BT /F1 10 Tf 4 Ts (a) Tj 0 Ts 0 -12 Td (b) Tj ET";
+        assert_eq!(extract(content, &options), "a\nb");
+        // advances are scaled, so a scaled word does not run into the next one
+        let content = b"BT /F1 10 Tf 200 Tz (ab) Tj 100 Tz 23 0 Td (c) Tj ET";
+        assert_eq!(extract(content, &options), "abc");
+    }
+
+    #[test]
+    fn thresholds() {
+        let content = b"BT /F1 10 Tf [(a) -300 (b)] TJ 0 -4 Td (c) Tj ET";
+        assert_eq!(extract(content, &TextExtractOptions::default()), "a bc");
+        let options = TextExtractOptions { space_threshold: 0.5, line_threshold: 0.3 };
+        assert_eq!(extract(content, &options), "ab\nc");
+    }
+}
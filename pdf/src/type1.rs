@@ -0,0 +1,202 @@
+//! Just enough of a Type 1 font program parser to read its built-in encoding.
+
+use crate::error::*;
+use std::convert::TryInto;
+use crate::encoding::STANDARD_ENCODING;
+
+/// A Type 1 font program (as in `/FontFile`), split into the cleartext part and the
+/// eexec-decrypted private part.
+#[derive(Debug)]
+pub struct Type1Font {
+    cleartext: Vec<u8>,
+    private: Vec<u8>,
+}
+
+impl Type1Font {
+    /// Parse a font program, either in PFA form or with PFB segment headers.
+    pub fn parse(data: &[u8]) -> Result<Type1Font> {
+        let data = pfb_segments(data).unwrap_or_else(|| data.to_vec());
+        if !data.starts_with(b"%!") {
+            bail!("not a Type 1 font program");
+        }
+        let (cleartext, private) = match find(&data, b"eexec") {
+            Some(pos) => {
+                // a single whitespace (or CR LF) separates `eexec` from the encrypted bytes
+                let encrypted = &data[pos + 5 ..];
+                let encrypted = match encrypted {
+                    [b'\r', b'\n', ref rest @ ..] => rest,
+                    [b, ref rest @ ..] if b.is_ascii_whitespace() => rest,
+                    rest => rest
+                };
+                (data[.. pos].to_vec(), eexec_decrypt(encrypted))
+            }
+            None => (data, Vec::new())
+        };
+        Ok(Type1Font { cleartext, private })
+    }
+
+    /// The encoding defined by the font program itself (`/Encoding` in the font dictionary).
+    ///
+    /// `StandardEncoding` is expanded to the standard table. Returns `None` if the font
+    /// program has no (readable) encoding.
+    pub fn builtin_encoding(&self) -> Option<[Option<String>; 256]> {
+        parse_encoding(&self.cleartext).or_else(|| parse_encoding(&self.private))
+    }
+}
+
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Concatenate the ASCII and binary segments of a PFB file.
+fn pfb_segments(mut data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    while let [0x80, kind, ref rest @ ..] = *data {
+        if kind == 3 {
+            break;
+        }
+        let len = u32::from_le_bytes(rest.get(.. 4)?.try_into().ok()?) as usize;
+        out.extend_from_slice(rest.get(4 .. 4 + len)?);
+        data = &rest[4 + len ..];
+    }
+    if out.is_empty() {
+        None
+    } else {
+        Some(out)
+    }
+}
+
+/// Decrypt the private part (T1 spec 7.2), which may be hex encoded.
+fn eexec_decrypt(data: &[u8]) -> Vec<u8> {
+    let hex = data.len() >= 4 && data[.. 4].iter().all(|b| b.is_ascii_hexdigit());
+    let binary;
+    let data = if hex {
+        let digits: Vec<u8> = data.iter().filter_map(|&b| crate::enc::decode_nibble(b)).collect();
+        binary = digits.chunks_exact(2).map(|c| c[0] << 4 | c[1]).collect::<Vec<u8>>();
+        &binary[..]
+    } else {
+        data
+    };
+    let mut r: u16 = 55665;
+    data.iter().map(|&c| {
+        let p = c ^ (r >> 8) as u8;
+        r = (c as u16).wrapping_add(r).wrapping_mul(52845).wrapping_add(22719);
+        p
+    }).skip(4).collect()
+}
+
+/// Splits PostScript source into tokens. Only names, numbers, operators and
+/// brackets matter for the encoding, so strings and procedures are not parsed.
+struct Tokens<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+impl<'a> Iterator for Tokens<'a> {
+    type Item = &'a [u8];
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let is_delim = |b: u8| b"()<>[]{}/%".contains(&b);
+        loop {
+            match *self.data.get(self.pos)? {
+                b if b.is_ascii_whitespace() => self.pos += 1,
+                b'%' => {
+                    while self.data.get(self.pos).is_some_and(|&b| b != b'\n' && b != b'\r') {
+                        self.pos += 1;
+                    }
+                }
+                _ => break
+            }
+        }
+        let start = self.pos;
+        self.pos += 1;
+        if !is_delim(self.data[start]) || self.data[start] == b'/' {
+            while self.data.get(self.pos).is_some_and(|&b| !b.is_ascii_whitespace() && !is_delim(b)) {
+                self.pos += 1;
+            }
+        }
+        Some(&self.data[start .. self.pos])
+    }
+}
+
+fn parse_encoding(data: &[u8]) -> Option<[Option<String>; 256]> {
+    let mut tokens = Tokens { data, pos: 0 };
+    tokens.find(|&t| t == b"/Encoding")?;
+
+    let mut encoding: [Option<String>; 256] = std::array::from_fn(|_| None);
+    match tokens.next()? {
+        b"StandardEncoding" => {
+            for (slot, name) in encoding.iter_mut().zip(STANDARD_ENCODING.iter()) {
+                *slot = name.map(String::from);
+            }
+            return Some(encoding);
+        }
+        t if t.iter().all(u8::is_ascii_digit) => {}
+        _ => return None
+    }
+
+    // `dup <code> /<name> put` entries, up to the closing `def`
+    let mut window: [&[u8]; 4] = [b""; 4];
+    for token in tokens {
+        if token == b"def" {
+            break;
+        }
+        window = [window[1], window[2], window[3], token];
+        if let [b"dup", code, name, b"put"] = window {
+            let code = std::str::from_utf8(code).ok()?.parse::<u8>().ok()?;
+            let name = name.strip_prefix(b"/")?;
+            if name != b".notdef" {
+                encoding[code as usize] = Some(String::from_utf8_lossy(name).into());
+            }
+        }
+    }
+    Some(encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encrypt(data: &[u8]) -> Vec<u8> {
+        let mut r: u16 = 55665;
+        [&[0u8; 4][..], data].concat().iter().map(|&p| {
+            let c = p ^ (r >> 8) as u8;
+            r = (c as u16).wrapping_add(r).wrapping_mul(52845).wrapping_add(22719);
+            c
+        }).collect()
+    }
+
+    #[test]
+    fn builtin_encoding() {
+        let font = b"%!PS-AdobeFont-1.0: Test 001\n\
+            /Encoding 256 array\n\
+            0 1 255 {1 index exch /.notdef put} for\n\
+            dup 65 /Alpha put\n\
+            dup 66 /Beta put\n\
+            readonly def\n\
+            currentfile eexec\n";
+        let encoding = Type1Font::parse(font).unwrap().builtin_encoding().unwrap();
+        assert_eq!(encoding[65].as_deref(), Some("Alpha"));
+        assert_eq!(encoding[66].as_deref(), Some("Beta"));
+        assert_eq!(encoding.iter().flatten().count(), 2);
+
+        let font = b"%!FontType1-1.0: Test\n/Encoding StandardEncoding def\ncurrentfile eexec\n";
+        let encoding = Type1Font::parse(font).unwrap().builtin_encoding().unwrap();
+        assert_eq!(encoding[b'A' as usize].as_deref(), Some("A"));
+        assert_eq!(encoding[0o341].as_deref(), Some("AE"));
+        assert_eq!(encoding[0o200], None);
+
+        // encoding in the encrypted part, as a PFB file
+        let private = encrypt(b"/Encoding 256 array dup 32 /space put readonly def");
+        let mut pfb = Vec::new();
+        for (kind, segment) in [(1, &b"%!FontType1-1.0: Test\ncurrentfile eexec\n"[..]), (2, &private)] {
+            pfb.extend_from_slice(&[0x80, kind]);
+            pfb.extend_from_slice(&(segment.len() as u32).to_le_bytes());
+            pfb.extend_from_slice(segment);
+        }
+        pfb.extend_from_slice(&[0x80, 3]);
+        let encoding = Type1Font::parse(&pfb).unwrap().builtin_encoding().unwrap();
+        assert_eq!(encoding[32].as_deref(), Some("space"));
+
+        assert!(Type1Font::parse(b"%!FontType1-1.0: Test\n").unwrap().builtin_encoding().is_none());
+        assert!(Type1Font::parse(b"OTTO").is_err());
+    }
+}
@@ -62,6 +62,15 @@ impl XRefTable {
             entries,
         }
     }
+    /// An empty table for the sections of a file whose trailer has `/Size num_objects`.
+    ///
+    /// Unlike `new` this has no placeholder entry at `num_objects`, so all entries,
+    /// including one for object `num_objects` itself, come from the file.
+    pub fn with_size(num_objects: ObjNr) -> XRefTable {
+        XRefTable {
+            entries: vec![XRef::Invalid; num_objects as usize],
+        }
+    }
 
     pub fn iter(&self) -> impl Iterator<Item=u32> + '_ {
         self.entries.iter().enumerate()
@@ -106,20 +115,40 @@ impl XRefTable {
         (max_a, max_b)
     }
 
+    /// Merge the entries of `section` into the table.
+    ///
+    /// Sections have to be added from the most recent revision to the oldest (following `/Prev`):
+    /// entries that are already set come from a more recent revision and are kept, whatever
+    /// their generation number.
+    ///
+    /// /Size of the trailer is only a hint: the table grows to hold entries beyond it.
     pub fn add_entries_from(&mut self, section: XRefSection) -> Result<()> {
         for (i, &entry) in section.entries() {
-            if let Some(dst) = self.entries.get_mut(i) {
-                // Early return if the entry we have has larger or equal generation number
-                let should_be_updated = match *dst {
-                    XRef::Raw { gen_nr: gen, .. } | XRef::Free { gen_nr: gen, .. }
-                        => entry.get_gen_nr() > gen,
-                    XRef::Stream { .. } | XRef::Invalid
-                        => true,
-                    x => bail!("found {:?}", x)
-                };
-                if should_be_updated {
-                    *dst = entry;
-                }
+            self.add_entry(i, entry)?;
+        }
+        Ok(())
+    }
+    /// Merge the entries of a table of older sections, like `add_entries_from`.
+    pub fn add_entries_from_table(&mut self, older: &XRefTable) -> Result<()> {
+        for (i, &entry) in older.entries.iter().enumerate() {
+            if !matches!(entry, XRef::Invalid) {
+                self.add_entry(i, entry)?;
+            }
+        }
+        Ok(())
+    }
+    fn add_entry(&mut self, i: usize, entry: XRef) -> Result<()> {
+        if i >= self.entries.len() {
+            if i > crate::backend::MAX_ID as usize {
+                bail!("xref entry {} out of range", i);
+            }
+            self.entries.resize(i + 1, XRef::Invalid);
+        }
+        if let Some(dst) = self.entries.get_mut(i) {
+            match *dst {
+                XRef::Invalid => *dst = entry,
+                XRef::Raw { .. } | XRef::Free { .. } | XRef::Stream { .. } => {}
+                x => bail!("found {:?}", x)
             }
         }
         Ok(())
@@ -232,3 +261,57 @@ pub struct XRefInfo {
 // read_xref_table
 // read_xref_stream
 // read_xref_and_trailer_at
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_beyond_size() {
+        // /Size 27, but /Index ends with [28 2]
+        let mut table = XRefTable::with_size(27);
+        let mut section = XRefSection::new(28);
+        section.add_inuse_entry(100, 0);
+        section.add_inuse_entry(200, 0);
+        table.add_entries_from(section).unwrap();
+
+        assert_eq!(table.len(), 30);
+        assert!(matches!(table.get(28).unwrap(), XRef::Raw { pos: 100, .. }));
+        assert!(matches!(table.get(29).unwrap(), XRef::Raw { pos: 200, .. }));
+        assert!(matches!(table.get(27).unwrap(), XRef::Invalid));
+
+        // an entry for object /Size itself
+        let mut table = XRefTable::with_size(27);
+        let mut section = XRefSection::new(26);
+        section.add_inuse_entry(100, 0);
+        section.add_inuse_entry(200, 0);
+        table.add_entries_from(section).unwrap();
+
+        assert_eq!(table.len(), 28);
+        assert!(matches!(table.get(26).unwrap(), XRef::Raw { pos: 100, .. }));
+        assert!(matches!(table.get(27).unwrap(), XRef::Raw { pos: 200, .. }));
+    }
+
+    #[test]
+    fn most_recent_section_wins() {
+        let mut table = XRefTable::new(4);
+        // the update: 1 moved into an object stream, 2 at generation 1, 3 deleted
+        let mut update = XRefSection::new(1);
+        update.entries.push(XRef::Stream { stream_id: 5, index: 0 });
+        update.add_inuse_entry(500, 1);
+        update.add_free_entry(0, 1);
+        table.add_entries_from(update).unwrap();
+
+        let mut original = XRefSection::new(0);
+        original.add_free_entry(0, 65535);
+        original.add_inuse_entry(100, 0);
+        original.add_inuse_entry(200, 0);
+        original.add_inuse_entry(300, 0);
+        table.add_entries_from(original).unwrap();
+
+        assert!(matches!(table.get(0).unwrap(), XRef::Free { gen_nr: 65535, .. }));
+        assert!(matches!(table.get(1).unwrap(), XRef::Stream { stream_id: 5, index: 0 }));
+        assert!(matches!(table.get(2).unwrap(), XRef::Raw { pos: 500, gen_nr: 1 }));
+        assert!(matches!(table.get(3).unwrap(), XRef::Free { gen_nr: 1, .. }));
+    }
+}
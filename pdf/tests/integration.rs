@@ -41,6 +41,12 @@ fn open_file() {
     });
 }
 
+#[test]
+fn unencrypted_permissions() {
+    let file = run!(FileOptions::uncached().open(file_path("example.pdf")));
+    assert!(file.permissions().is_none());
+}
+
 #[cfg(feature="cache")]
 #[test]
 fn read_pages() {
@@ -56,6 +62,31 @@ fn read_pages() {
     }
 }
 
+#[cfg(feature = "rayon")]
+#[test]
+fn pages_parallel() {
+    for path in dir_pdfs(files()) {
+        let file = run!(FileOptions::cached().open(&path));
+        let sequential: Vec<_> = file.pages().map(|p| p.ok().map(|p| p.get_ref())).collect();
+        let parallel = file.pages_parallel(Vec::new, |seen: &mut Vec<u32>, n, page| {
+            seen.push(n);
+            page.ok().map(|p| p.get_ref())
+        });
+        assert_eq!(sequential, parallel, "{:?}", path);
+    }
+}
+
+/// Only a compile time check: files, pages and fonts are `Send + Sync`, so a renderer
+/// (which is not part of this crate) can keep them in a cache shared between threads.
+#[test]
+fn shareable_across_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<pdf::file::CachedFile<Vec<u8>>>();
+    assert_send_sync::<PageRc>();
+    assert_send_sync::<pdf::font::Font>();
+    assert_send_sync::<RcRef<pdf::font::Font>>();
+}
+
 #[test]
 fn user_password() {
     for path in dir_pdfs(file_path("password_protected")) {
@@ -124,3 +155,738 @@ fn parse_objects_from_stream() {
 }
 
 // TODO test decoding
+
+#[cfg(feature="cache")]
+#[test]
+fn raw_stream_data() {
+    use pdf::enc::decode;
+    let file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    let resolver = file.resolver();
+    let page = run!(file.get_page(0));
+    for part in page.contents.as_ref().unwrap().parts.iter() {
+        let raw = run!(part.raw_data(&resolver));
+        let mut decoded = raw.to_vec();
+        for filter in part.filters.iter() {
+            decoded = run!(decode(&decoded, filter));
+        }
+        assert_eq!(&*decoded, &*run!(part.data(&resolver)));
+    }
+}
+
+#[test]
+fn iterate_objects() {
+    let file = run!(FileOptions::uncached().open(file_path("xelatex.pdf")));
+    let mut count = 0;
+    for (r, p) in file.objects() {
+        assert!(r.id > 0);
+        run!(p);
+        count += 1;
+    }
+    assert_eq!(count, file.object_count());
+}
+
+#[test]
+fn save_gc() {
+    use pdf::file::SaveOptions;
+    use pdf::primitive::Primitive;
+
+    let mut file = run!(FileOptions::uncached().open(file_path("example.pdf")));
+    let orphan = run!(file.create(Primitive::Integer(42))).get_ref().get_inner();
+    let path = std::env::temp_dir().join("pdf_save_gc.pdf");
+    run!(file.save_to_with(&path, &SaveOptions { gc: true, ..SaveOptions::default() }));
+
+    let file = run!(FileOptions::uncached().open(&path));
+    assert!(file.resolver().resolve(orphan).is_err());
+    assert_eq!(file.num_pages(), 1);
+    run!(file.get_page(0));
+}
+
+#[test]
+fn encrypt_round_trip() {
+    use pdf::build::*;
+    use pdf::content::{Content, Op};
+    use pdf::primitive::PdfString;
+
+    let ops = vec![Op::BeginText, Op::TextDraw { text: "secret".into() }, Op::EndText];
+    let mut page = PageBuilder::from_content(Content::from_ops(ops), &NoResolve).unwrap();
+    page.media_box = Some(Rectangle { left: 0., bottom: 0., right: 100., top: 100. });
+    let info = InfoDict { title: Some(PdfString::from("hidden title")), ..InfoDict::default() };
+
+    let data = run!(PdfBuilder::new(FileOptions::uncached())
+        .info(info)
+        .encrypt(b"user", b"owner", -4)
+        .build(CatalogBuilder::from_pages(vec![page])));
+    assert!(!data.windows(6).any(|w| w == b"secret"));
+
+    for password in [&b"user"[..], b"owner"] {
+        let file = run!(FileOptions::uncached().password(password).load(&data[..]));
+        assert!(file.id().is_some());
+        assert_eq!(file.permissions(), Some(pdf::crypt::Permissions::from_p(-4)));
+        let title = file.trailer.info_dict.as_ref().unwrap().title.as_ref().unwrap();
+        assert_eq!(title.to_string_lossy(), "hidden title");
+        let page = run!(file.get_page(0));
+        let content = page.contents.as_ref().unwrap();
+        let ops = run!(content.operations(&file.resolver()));
+        assert!(matches!(ops[1], Op::TextDraw { ref text } if text.as_bytes() == b"secret"));
+    }
+    assert!(FileOptions::uncached().password(b"wrong").load(&data[..]).err().unwrap().is_invalid_password());
+}
+
+#[test]
+fn save_decrypted() {
+    use pdf::file::{EncryptionMode, SaveOptions};
+
+    let options = SaveOptions { encryption: EncryptionMode::Off, ..SaveOptions::default() };
+    for name in ["encrypted_rc4_rev2.pdf", "encrypted_aes_128.pdf", "encrypted_aes_256.pdf"] {
+        let mut file = run!(FileOptions::uncached().open(file_path(name)));
+        let ops = |file: &pdf::file::File<Vec<u8>, _, _, _>| -> Vec<String> {
+            let page = run!(file.get_page(0));
+            let ops = run!(page.contents.as_ref().unwrap().operations(&file.resolver()));
+            ops.iter().map(|op| format!("{:?}", op)).collect()
+        };
+        let expected = ops(&file);
+        assert!(!expected.is_empty());
+        let path = std::env::temp_dir().join(format!("pdf_decrypted_{}", name));
+        run!(file.save_to_with(&path, &options));
+
+        let data = run!(std::fs::read(&path));
+        assert!(data.starts_with(b"%PDF-"));
+        assert!(!data.windows(8).any(|w| w == b"/Encrypt"), "{}", name);
+        let file = run!(FileOptions::uncached().load(data));
+        assert!(file.trailer.encrypt_dict.is_none());
+        assert_eq!(ops(&file), expected, "{}", name);
+    }
+}
+
+#[test]
+fn signature_placeholder() {
+    use pdf::file::SaveOptions;
+    use pdf::primitive::{Dictionary, Name};
+
+    let mut file = run!(FileOptions::uncached().open(file_path("example.pdf")));
+    let mut dict = Dictionary::new();
+    dict.insert("Type", Name::from("Sig"));
+    dict.insert("Filter", Name::from("Adobe.PPKLite"));
+    dict.insert("SubFilter", Name::from("adbe.pkcs7.detached"));
+    let sig = run!(file.reserve_signature(dict, 16));
+    assert!(file.signature_range(sig).is_none());
+    let len = run!(file.save_with(&SaveOptions::default())).len();
+
+    let range = file.signature_range(sig).unwrap().clone();
+    let [a, b, c, d] = range.byte_range;
+    assert_eq!((a, b, c, c + d), (0, range.contents.start, range.contents.end, len));
+    let data = file.data();
+    assert_eq!(&data[range.contents.clone()], format!("<{}>", "0".repeat(32)).as_bytes());
+    let [before, after] = range.signed_data(data);
+    assert_eq!(before.len() + after.len() + 34, len);
+
+    assert!(file.fill_signature(sig, &[0; 17]).is_err());
+    run!(file.fill_signature(sig, &[0xab, 0xcd]));
+
+    let file = run!(FileOptions::uncached().load(file.data().to_vec()));
+    let dict = run!(run!(file.resolver().resolve(sig)).into_dictionary());
+    let mut contents = vec![0xab, 0xcd];
+    contents.resize(16, 0);
+    assert_eq!(dict["Contents"].as_string().unwrap().as_bytes(), &contents[..]);
+    let byte_range: Vec<usize> = dict["ByteRange"].as_array().unwrap().iter().map(|p| p.as_usize().unwrap()).collect();
+    assert_eq!(byte_range, [a, b, c, d]);
+}
+
+#[test]
+fn trailer_typed() {
+    let file = run!(FileOptions::uncached().open(file_path("example.pdf")));
+    let trailer = file.trailer_typed();
+    assert_eq!(trailer.root, file.trailer.root.get_ref());
+    assert!(trailer.encrypt.is_none());
+    let root = run!(file.resolver().get(trailer.root));
+    assert_eq!(root.pages.count, file.num_pages());
+
+    let file = run!(FileOptions::uncached().open(file_path("encrypted_rc4_rev2.pdf")));
+    let trailer = file.trailer_typed();
+    assert_eq!(trailer.id, file.trailer.id);
+    // the encryption dictionary itself is not decrypted
+    let crypt = run!(file.resolver().get(trailer.encrypt.unwrap()));
+    assert_eq!(format!("{:?}", *crypt), format!("{:?}", **file.trailer.encrypt_dict.as_ref().unwrap()));
+    assert!(trailer.info.is_none());
+
+    let mut file = run!(FileOptions::uncached().load(std::fs::read(file_path("xelatex.pdf")).unwrap()));
+    let info = run!(file.resolver().get(file.trailer_typed().info.unwrap()));
+    assert_eq!(info.creator, file.trailer.info_dict.as_ref().unwrap().creator);
+
+    // saving writes the info dictionary again, as another object
+    let old = file.trailer_typed().info.unwrap();
+    run!(file.save_with(&pdf::file::SaveOptions::default()));
+    let new = file.trailer_typed().info.unwrap();
+    assert_ne!(new.get_inner(), old.get_inner());
+    let info = run!(file.resolver().get(new));
+    assert_eq!(info.creator, file.trailer.info_dict.as_ref().unwrap().creator);
+}
+
+/// A PDF file of `objects`, numbered from 1, and the first being the catalog.
+fn pdf_from_objects(objects: &[&str]) -> Vec<u8> {
+    let mut data = b"%PDF-1.7\n".to_vec();
+    let mut offsets = vec![];
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(data.len());
+        data.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+    }
+    let xref = data.len();
+    data.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for pos in offsets {
+        data.extend_from_slice(format!("{:010} 00000 n \n", pos).as_bytes());
+    }
+    data.extend_from_slice(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).as_bytes());
+    data
+}
+
+#[test]
+fn page_tree_counts() {
+    let file = |counts: (u32, u32)| {
+        let root = format!("<< /Type /Pages /Kids [3 0 R 6 0 R] /Count {} /MediaBox [0 0 600 800] >>", counts.0);
+        let tree = format!("<< /Type /Pages /Parent 2 0 R /Kids [4 0 R 5 0 R] /Count {} >>", counts.1);
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>",
+            root.as_str(),
+            tree.as_str(),
+            "<< /Type /Page /Parent 3 0 R >>",
+            "<< /Type /Page /Parent 3 0 R >>",
+            "<< /Type /Page /Parent 2 0 R >>",
+        ];
+        run!(FileOptions::uncached().load(pdf_from_objects(&objects)))
+    };
+    // correct, undercounted and overcounted subtree
+    for counts in [(3, 2), (3, 1), (3, 4)] {
+        let file = file(counts);
+        let ids: Vec<_> = (0 .. 3).map(|n| run!(file.get_page(n)).get_ref().get_inner().id).collect();
+        assert_eq!(ids, [4, 5, 6], "{:?}", counts);
+        assert!(file.get_page(3).is_err());
+    }
+    // pages beyond the /Count of the root are still found
+    let file = file((2, 2));
+    assert_eq!(run!(file.get_page(2)).get_ref().get_inner().id, 6);
+    assert!(file.get_page(3).is_err());
+}
+
+#[test]
+fn page_svg() {
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 100] /Resources << >> /Contents 4 0 R >>",
+        "<< /Length 23 >>\nstream\n0 1 0 rg 0 0 10 10 re f\nendstream",
+    ];
+    let file = run!(FileOptions::uncached().load(pdf_from_objects(&objects)));
+    let resolver = file.resolver();
+    let page = run!(file.get_page(0));
+    let svg = run!(page.to_svg(&resolver, None));
+    assert!(svg.contains(r##"<path d="M0 100L10 100L10 90L0 90Z" fill="#00ff00""##), "{}", svg);
+    let scale = pdf::content::Matrix { a: 3., d: 3., ..Default::default() };
+    let svg = run!(page.to_svg(&resolver, Some(scale)));
+    assert!(svg.contains(r#"width="600" height="300""#), "{}", svg);
+}
+
+#[test]
+fn image_soft_mask() {
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [] /Count 0 >>",
+        "<< /Type /XObject /Subtype /Image /Width 2 /Height 1 /ColorSpace /DeviceGray /BitsPerComponent 8 /SMask 4 0 R /Length 2 >>\nstream\n\x00\x00\nendstream",
+        "<< /Type /XObject /Subtype /Image /Width 2 /Height 1 /ColorSpace /DeviceGray /BitsPerComponent 4 /Decode [1 0] /Length 1 >>\nstream\n\x0f\nendstream",
+    ];
+    let file = run!(FileOptions::uncached().load(pdf_from_objects(&objects)));
+    let resolver = file.resolver();
+    let image = run!(ImageXObject::from_primitive(run!(resolver.resolve(PlainRef { id: 3, gen: 0 })), &resolver));
+    let mask = run!(image.soft_mask(&resolver)).unwrap();
+    assert_eq!((mask.width, mask.height), (2, 1));
+    // inverted by /Decode
+    assert_eq!(run!(mask.alpha(&resolver)), [255, 0]);
+    assert!(run!(mask.soft_mask(&resolver)).is_none());
+}
+
+#[test]
+fn media_box_fallback() {
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>",
+        "<< /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 >>",
+        "<< /Type /Page /Parent 2 0 R >>",
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 100] >>",
+    ];
+    let file = run!(FileOptions::uncached().load(pdf_from_objects(&objects)));
+    let page = run!(file.get_page(0));
+    assert!(page.media_box().is_err());
+    let letter = page.media_box_or_letter();
+    assert_eq!((letter.right, letter.top), (612., 792.));
+    assert_eq!(run!(file.get_page(1)).media_box_or_letter().right, 200.);
+}
+
+#[test]
+fn page_links() {
+    use pdf::content::Point;
+
+    // pages 4 and 5 are in a nested page tree, 6 is the third page
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R /Dests << /old [6 0 R /FitH 500] >> /Names << /Dests << /Names [(chap) [5 0 R /Fit]] >> >> >>",
+        "<< /Type /Pages /Kids [3 0 R 6 0 R] /Count 3 /MediaBox [0 0 600 800] >>",
+        "<< /Type /Pages /Parent 2 0 R /Kids [4 0 R 5 0 R] /Count 2 >>",
+        "<< /Type /Page /Parent 3 0 R /Annots [
+            << /Type /Annot /Subtype /Link /Rect [0 0 10 10] /A << /S /URI /URI (https://example.com/) >> >>
+            << /Type /Annot /Subtype /Link /Rect [0 10 10 20] /Dest [6 0 R /XYZ 10 700 0] >>
+            << /Type /Annot /Subtype /Link /Rect [0 20 10 30] /A << /S /GoTo /D (chap) >> >>
+            << /Type /Annot /Subtype /Link /Rect [0 30 10 40] /Dest /old >>
+            << /Type /Annot /Subtype /Link /Rect [0 40 10 50] /A << /S /Launch /F (app) >> >>
+            << /Type /Annot /Subtype /Text /Rect [0 50 10 60] >>
+        ] >>",
+        "<< /Type /Page /Parent 3 0 R >>",
+        "<< /Type /Page /Parent 2 0 R >>",
+    ];
+    let file = run!(FileOptions::uncached().load(pdf_from_objects(&objects)));
+    let page = run!(file.get_page(0));
+    let links = run!(page.links(&file.resolver(), file.get_root()));
+    let targets: Vec<_> = links.iter().map(|l| l.target.clone()).collect();
+    assert_eq!(targets, [
+        LinkTarget::Uri("https://example.com/".into()),
+        LinkTarget::GoTo { page: 2, point: Some(Point { x: 10., y: 700. }) },
+        LinkTarget::GoTo { page: 1, point: None },
+        LinkTarget::GoTo { page: 2, point: Some(Point { x: 0., y: 500. }) },
+        LinkTarget::Other,
+    ]);
+    assert_eq!(links[1].rect, Rectangle { left: 0., bottom: 10., right: 10., top: 20. });
+}
+
+#[test]
+fn checkbox_states() {
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [4 0 R 5 0 R 8 0 R] >> >>",
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 /MediaBox [0 0 600 800] >>",
+        "<< /Type /Page /Parent 2 0 R /Annots [4 0 R 6 0 R 7 0 R 8 0 R] >>",
+        // a check box merged with its widget, exporting /Agree rather than /Yes
+        "<< /FT /Btn /T (agree) /V /Agree /Subtype /Widget /AS /Agree /AP << /N << /Agree 9 0 R /Off 9 0 R >> >> >>",
+        // a radio button group with its widgets as kids
+        "<< /FT /Btn /Ff 32768 /T (choice) /V /b /Kids [6 0 R 7 0 R] >>",
+        "<< /Parent 5 0 R /Subtype /Widget /AS /Off /AP << /N << /a 9 0 R /Off 9 0 R >> >> >>",
+        "<< /Parent 5 0 R /Subtype /Widget /AS /b /AP << /N << /b 9 0 R /Off 9 0 R >> >> >>",
+        "<< /FT /Btn /Ff 65536 /T (submit) /Subtype /Widget /AP << /N 9 0 R >> >>",
+        "<< /Subtype /Form /BBox [0 0 10 10] /Length 0 >>\nstream\n\nendstream",
+    ];
+    let file = run!(FileOptions::uncached().load(pdf_from_objects(&objects)));
+    let resolver = file.resolver();
+    let on_states = |field: &FieldDictionary| -> Vec<String> {
+        run!(field.on_states(&resolver)).iter().map(|name| name.as_str().into()).collect()
+    };
+    let fields = &file.get_root().forms.as_ref().unwrap().fields;
+    let (agree, choice, submit) = (&fields[0], &fields[1], &fields[2]);
+
+    assert_eq!(agree.checkbox_state(), Some(true));
+    assert_eq!(on_states(agree), ["Agree"]);
+
+    assert_eq!(on_states(choice), ["a", "b"]);
+    let kids: Vec<_> = choice.kids.iter().map(|&kid| run!(resolver.get(kid)).checkbox_state()).collect();
+    assert_eq!(kids, [Some(false), Some(true)]);
+
+    assert_eq!(submit.checkbox_state(), None);
+    assert!(on_states(submit).is_empty());
+}
+
+#[test]
+fn open_action() {
+    use pdf::content::Point;
+
+    let file = |open_action: &str| {
+        let catalog = format!("<< /Type /Catalog /Pages 2 0 R /OpenAction {} /Names << /Dests << /Names [(intro) [4 0 R /Fit]] >> >> >>", open_action);
+        let objects = [
+            catalog.as_str(),
+            "<< /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 /MediaBox [0 0 600 800] >>",
+            "<< /Type /Page /Parent 2 0 R >>",
+            "<< /Type /Page /Parent 2 0 R >>",
+            "<< /S /GoTo /D (intro) >>",
+        ];
+        run!(FileOptions::uncached().load(pdf_from_objects(&objects)))
+    };
+
+    let array = file("[4 0 R /XYZ 0 700 null]");
+    let catalog = array.get_root();
+    assert!(matches!(run!(catalog.open_action(&array.resolver())), Some(Action::Goto(MaybeNamedDest::Direct(Dest { view: DestView::XYZ { .. }, .. })))));
+    assert_eq!(run!(catalog.open_target(&array.resolver())), Some(LinkTarget::GoTo { page: 1, point: Some(Point { x: 0., y: 700. }) }));
+
+    // an indirect action dictionary with a named destination
+    let named = file("5 0 R");
+    let catalog = named.get_root();
+    assert!(matches!(run!(catalog.open_action(&named.resolver())), Some(Action::Goto(MaybeNamedDest::Named(_)))));
+    assert_eq!(run!(catalog.open_target(&named.resolver())), Some(LinkTarget::GoTo { page: 1, point: None }));
+
+    let uri = file("<< /S /URI /URI (https://example.com/) >>");
+    assert!(matches!(run!(uri.get_root().open_action(&uri.resolver())), Some(Action::Other(_))));
+    assert_eq!(run!(uri.get_root().open_target(&uri.resolver())), Some(LinkTarget::Uri("https://example.com/".into())));
+}
+
+#[test]
+fn dictionary_get_path() {
+    use pdf::primitive::Primitive;
+
+    let file = run!(FileOptions::uncached().open(file_path("example.pdf")));
+    let resolver = file.resolver();
+    let catalog = run!(run!(resolver.resolve(file.trailer.root.get_ref().get_inner())).into_dictionary());
+
+    assert!(matches!(run!(catalog.get_path(&["Pages", "Count"], &resolver)), Some(Primitive::Integer(1))));
+    assert!(matches!(run!(catalog.get_path(&["Pages", "Kids"], &resolver)), Some(Primitive::Array(_))));
+    assert!(run!(catalog.get_path(&["Pages", "Missing", "Count"], &resolver)).is_none());
+    assert!(catalog.get_path(&["Pages", "Count", "Kids"], &resolver).is_err());
+}
+
+#[test]
+fn per_filter_decode_parms() {
+    use pdf::enc::{encode, StreamFilter, LZWFlateParams};
+    use pdf::primitive::Primitive;
+
+    let info = run!(parse(b"<< /Length 0 /Filter [/ASCII85Decode /FlateDecode] /DecodeParms [null << /Predictor 12 /Columns 2 >>] >>", &NoResolve, ParseFlags::DICT));
+    let info = run!(StreamInfo::<()>::from_primitive(info, &NoResolve));
+    assert!(matches!(info.filters[0], StreamFilter::ASCII85Decode));
+    assert!(matches!(info.filters[1], StreamFilter::FlateDecode(LZWFlateParams { predictor: 12, columns: 2, .. })));
+
+    // two rows of two bytes, each prefixed by the PNG predictor tag 0 (None)
+    let data = run!(encode(&[0, 1, 2, 0, 3, 4], &StreamFilter::FlateDecode(LZWFlateParams::default())));
+    let data = run!(encode(&data, &StreamFilter::ASCII85Decode));
+    let stream = Stream::from_compressed((), data, info.filters.clone());
+    assert_eq!(&*run!(stream.data(&NoResolve)), &[1, 2, 3, 4]);
+
+    let written = run!(stream.to_pdf_stream(&mut NoUpdate));
+    match written.info.get("DecodeParms") {
+        Some(Primitive::Array(parms)) => {
+            assert!(matches!(parms[..], [Primitive::Null, Primitive::Dictionary(_)]));
+        }
+        p => panic!("unexpected DecodeParms {:?}", p)
+    }
+}
+
+#[test]
+fn decoded_len_hint() {
+    use pdf::primitive::Primitive;
+
+    let info = run!(parse(b"<< /Length 3 /Filter /ASCIIHexDecode /DL 1 >>", &NoResolve, ParseFlags::DICT));
+    let info = run!(StreamInfo::<()>::from_primitive(info, &NoResolve));
+    let mut stream = Stream::from_compressed((), &b"41>"[..], info.filters.clone());
+    assert_eq!(stream.decoded_len_hint(), None);
+
+    stream.info = info;
+    assert_eq!(stream.decoded_len_hint(), Some(1));
+    assert_eq!(&*run!(stream.data(&NoResolve)), b"A");
+    let written = run!(stream.to_pdf_stream(&mut NoUpdate));
+    assert!(matches!(written.info.get("DL"), Some(Primitive::Integer(1))));
+}
+
+#[test]
+fn page_thumbnail() {
+    use pdf::build::*;
+    use pdf::primitive::{Dictionary, PdfString, Primitive};
+
+    let mut builder = PdfBuilder::new(FileOptions::uncached());
+    let mut info = Dictionary::new();
+    info.insert("Width", 2);
+    info.insert("Height", 1);
+    info.insert("BitsPerComponent", 8);
+    info.insert("ColorSpace", vec![
+        Primitive::name("Indexed"), Primitive::name("DeviceGray"), 1.into(), PdfString::new(vec![0x00, 0xff].into()).into()
+    ]);
+    let thumb = run!(builder.storage.create(Stream::new(info, vec![1u8, 0])));
+
+    let mut page = PageBuilder::default().media_box(Rectangle { left: 0., bottom: 0., right: 100., top: 100. });
+    page.other.insert("Thumb", Primitive::Reference(thumb.get_ref().get_inner()));
+    let data = run!(builder.build(CatalogBuilder::from_pages(vec![page, PageBuilder::default()])));
+
+    let file = run!(FileOptions::uncached().load(data));
+    let resolver = file.resolver();
+    let thumb = run!(run!(file.get_page(0)).thumbnail(&resolver)).unwrap();
+    assert_eq!((thumb.width, thumb.height), (2, 1));
+    assert!(matches!(thumb.color_space, Some(ColorSpace::Indexed(ref base, 1, ref lookup)) if matches!(**base, ColorSpace::DeviceGray) && lookup[..] == [0x00, 0xff]));
+    assert_eq!(&*run!(thumb.image_data(&resolver)), &[1, 0]);
+
+    assert!(run!(run!(file.get_page(1)).thumbnail(&resolver)).is_none());
+}
+
+#[cfg(feature="cache")]
+#[test]
+fn get_many() {
+    use pdf::primitive::Primitive;
+
+    for name in ["example.pdf", "xelatex.pdf"] {
+        let file = run!(FileOptions::cached().open(file_path(name)));
+        let resolver = file.resolver();
+        let mut refs: Vec<Ref<Primitive>> = file.objects()
+            .filter(|(_, p)| p.is_ok())
+            .map(|(r, _)| Ref::new(r))
+            .collect();
+        refs.reverse();
+
+        let many = run!(resolver.get_many(&refs));
+        assert_eq!(many.len(), refs.len());
+        for (r, obj) in refs.iter().zip(&many) {
+            assert_eq!(obj.get_ref(), *r);
+            let single = run!(resolver.get(*r));
+            assert_eq!(format!("{:?}", **obj), format!("{:?}", *single));
+        }
+    }
+}
+
+#[test]
+fn identity_crypt_filter() {
+    use pdf::build::*;
+    use pdf::enc::{CryptFilterParams, StreamFilter};
+    use pdf::primitive::Primitive;
+
+    let xmp = b"<x:xmpmeta xmlns:x='adobe:ns:meta/'/>";
+    let mut builder = PdfBuilder::new(FileOptions::uncached());
+    let metadata = Stream::from_compressed((), &xmp[..], vec![StreamFilter::Crypt(CryptFilterParams::default())]);
+    let metadata = run!(builder.storage.create(metadata));
+
+    let mut page = PageBuilder::default().media_box(Rectangle { left: 0., bottom: 0., right: 100., top: 100. });
+    page.metadata = Some(Primitive::Reference(metadata.get_ref().get_inner()));
+    let data = run!(builder.encrypt(b"", b"owner", -4).build(CatalogBuilder::from_pages(vec![page])));
+    assert!(data.windows(xmp.len()).any(|w| w == xmp));
+
+    let file = run!(FileOptions::uncached().load(&data[..]));
+    let resolver = file.resolver();
+    let page = run!(file.get_page(0));
+    let stream: Stream<()> = run!(Stream::from_primitive(page.metadata.clone().unwrap(), &resolver));
+    assert_eq!(&*run!(stream.data(&resolver)), &xmp[..]);
+    assert_eq!(&*run!(stream.raw_data(&resolver)), &xmp[..]);
+}
+
+#[test]
+fn edit_page_tree() {
+    use pdf::build::*;
+
+    let page = |width: f32| PageBuilder::default().media_box(Rectangle { left: 0., bottom: 0., right: width, top: 100. });
+    let widths = |file: &pdf::file::File<Vec<u8>, _, _, _>| -> Vec<f32> {
+        file.pages().map(|p| run!(p).media_box().unwrap().right).collect()
+    };
+    let data = run!(PdfBuilder::new(FileOptions::uncached())
+        .build(CatalogBuilder::from_pages(vec![page(1.), page(2.), page(3.)])));
+    let mut file = run!(FileOptions::uncached().load(data));
+
+    // move the last two pages into a subtree
+    let root = file.get_root().pages.clone();
+    let kids = root.kids.clone();
+    let sub = run!(PagesRc::create(PageTree { parent: Some(root.clone()), kids: kids[1 ..].to_vec(), count: 2, ..PageTree::default() }, &mut file));
+    for (i, &kid) in kids.iter().enumerate().skip(1) {
+        let mut page = (*run!(file.get_page(i as u32))).clone();
+        page.parent = sub.clone();
+        // the (empty) content streams are in the file and can't be written inline
+        page.contents = None;
+        run!(file.update(kid.get_inner(), PagesNode::Leaf(page)));
+    }
+    run!(file.update(root.get_ref().get_inner(), PagesNode::Tree(PageTree { kids: vec![kids[0], sub.get_ref()], ..(*root).clone() })));
+    let path = std::env::temp_dir().join("pdf_edit_page_tree.pdf");
+    run!(file.save_to(&path));
+    let mut file = run!(FileOptions::uncached().open(&path));
+    assert_eq!(widths(&file), [1., 2., 3.]);
+
+    let inserted = run!(file.insert_page(2, page(4.)));
+    assert_eq!(inserted.parent.get_ref(), sub.get_ref());
+    assert_eq!(widths(&file), [1., 2., 4., 3.]);
+    run!(file.remove_page(1));
+    assert_eq!(widths(&file), [1., 4., 3.]);
+    run!(file.replace_page(0, page(5.)));
+    run!(file.insert_page(3, page(6.)));
+    assert_eq!(widths(&file), [5., 4., 3., 6.]);
+    assert!(file.insert_page(5, page(7.)).is_err());
+    assert!(file.remove_page(4).is_err());
+
+    // emptying the subtree removes it
+    run!(file.remove_page(1));
+    run!(file.remove_page(1));
+    assert_eq!(file.get_root().pages.kids.len(), 2);
+    assert_eq!(file.get_root().pages.count, 2);
+
+    run!(file.save_to(&path));
+    let file = run!(FileOptions::uncached().open(&path));
+    assert_eq!(widths(&file), [5., 6.]);
+}
+
+#[test]
+fn annotation_appearance() {
+    use pdf::build::*;
+    use pdf::content::FormXObject;
+    use pdf::primitive::{Dictionary, Primitive};
+
+    let file = run!(FileOptions::cached().open(file_path("example_annotation.pdf")));
+    let resolver = file.resolver();
+    let annots = run!(run!(file.get_page(0)).annotations.load(&resolver));
+    let form = run!(annots[0].normal_appearance(&resolver)).unwrap();
+    assert_eq!(form.dict().bbox.left, 103.5);
+    assert!(!run!(form.operations(&resolver)).is_empty());
+
+    // appearance states, selected by /AS
+    let mut builder = PdfBuilder::new(FileOptions::uncached());
+    let form = |width: f32| AppearanceStreamEntry::Single(FormXObject {
+        stream: Stream::new(FormDict { bbox: Rectangle { left: 0., bottom: 0., right: width, top: 10. }, ..FormDict::default() }, vec![])
+    });
+    let states = vec![("On".into(), form(1.)), ("Off".into(), form(2.))].into_iter().collect();
+    let normal = run!(builder.storage.create(AppearanceStreamEntry::Dict(states)));
+    let annot = |state: Option<&str>| {
+        let mut ap = Dictionary::new();
+        ap.insert("N", Primitive::Reference(normal.get_ref().get_inner()));
+        let mut dict = Dictionary::new();
+        dict.insert("Subtype", Primitive::name("Widget"));
+        dict.insert("AP", ap);
+        if let Some(state) = state {
+            dict.insert("AS", Primitive::name(state));
+        }
+        run!(Annot::from_primitive(dict.into(), &builder.storage.resolver()))
+    };
+    let resolver = builder.storage.resolver();
+    let width = |state| run!(annot(state).normal_appearance(&resolver)).map(|f| f.dict().bbox.right);
+    assert_eq!(width(Some("Off")), Some(2.));
+    assert_eq!(width(Some("On")), Some(1.));
+    assert_eq!(width(Some("Unknown")), None);
+    assert_eq!(width(None), None);
+}
+
+#[test]
+fn page_inheritance() {
+    use pdf::build::*;
+
+    let mut builder = PdfBuilder::new(FileOptions::uncached());
+    let mut root = PageTree { rotate: Some(90), media_box: Some(Rectangle { left: 0., bottom: 0., right: 10., top: 20. }), ..PageTree::default() };
+    root.count = 1;
+    let root = run!(PagesRc::create(root, &mut builder.storage));
+    let mid = run!(PagesRc::create(PageTree { parent: Some(root.clone()), count: 1, ..PageTree::default() }, &mut builder.storage));
+    let mut page = Page::new(mid.clone());
+    assert_eq!(page.rotate(), 90);
+    assert_eq!(run!(page.media_box()).top, 20.);
+    assert_eq!(run!(page.physical_size()), (20., 10.));
+    page.rotate = 180;
+    assert_eq!(page.rotate(), 180);
+
+    // the page's own entries come first, then the raw dictionaries of the ancestors
+    let resolver = builder.storage.resolver();
+    assert_eq!(run!(page.inherited::<i32>("Rotate", &resolver)), Some(180));
+    page.rotate = 0;
+    assert_eq!(run!(page.inherited::<i32>("Rotate", &resolver)), Some(90));
+    assert_eq!(run!(page.inherited::<Rectangle>("MediaBox", &resolver)).map(|b| b.top), Some(20.));
+    assert_eq!(run!(page.inherited::<i32>("Foo", &resolver)), None);
+    page.other.insert("Foo", 3);
+    assert_eq!(run!(page.inherited::<i32>("Foo", &resolver)), Some(3));
+}
+
+#[test]
+fn catalog_metadata() {
+    for name in ["jpeg.pdf", "pdf-sample.pdf"] {
+        let file = run!(FileOptions::cached().open(file_path(name)));
+        let xmp = run!(file.get_root().metadata(&file.resolver())).unwrap();
+        assert!(xmp.starts_with(b"<?xpacket begin="), "{}", name);
+        assert!(xmp.windows(10).any(|w| w == b"x:xmpmeta "), "{}", name);
+    }
+    let file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    assert!(run!(file.get_root().metadata(&file.resolver())).is_none());
+}
+
+#[test]
+fn linearized_first_page() {
+    let file = run!(FileOptions::cached().open(file_path("pdf-sample.pdf")));
+    let linearization = file.linearization().unwrap();
+    assert_eq!(linearization.first_page_object, 9);
+    assert_eq!(linearization.num_pages, 1);
+    assert_eq!(linearization.hint_streams, [451, 137]);
+    assert_eq!(linearization.main_xref_offset, 7656);
+    let page = run!(file.open_first_page_fast());
+    assert_eq!(page.get_ref().get_inner().id, 9);
+    assert_eq!(page.get_ref(), run!(file.get_page(0)).get_ref());
+
+    let file = run!(FileOptions::cached().open(file_path("example.pdf")));
+    assert!(file.linearization().is_none());
+    assert_eq!(run!(file.open_first_page_fast()).get_ref(), run!(file.get_page(0)).get_ref());
+
+    // an incremental update invalidates the linearization
+    let mut file = run!(FileOptions::cached().open(file_path("pdf-sample.pdf")));
+    run!(file.create(pdf::primitive::Primitive::Integer(1)));
+    let path = std::env::temp_dir().join("pdf_linearized_update.pdf");
+    run!(file.save_to(&path));
+    let file = run!(FileOptions::cached().open(&path));
+    assert!(file.linearization().is_none());
+    assert_eq!(run!(file.open_first_page_fast()).get_ref().get_inner().id, 9);
+}
+
+#[test]
+fn ranged_reads() {
+    use std::ops::Range;
+    use std::sync::{Arc, Mutex};
+    use pdf::backend::{Backend, IndexRange};
+
+    struct Recording {
+        data: Vec<u8>,
+        reads: Arc<Mutex<Vec<Range<usize>>>>,
+    }
+    impl Backend for Recording {
+        fn read<T: IndexRange>(&self, range: T) -> pdf::error::Result<&[u8]> {
+            let range = range.to_range(self.data.len())?;
+            self.reads.lock().unwrap().push(range.clone());
+            Ok(&self.data[range])
+        }
+        fn len(&self) -> usize {
+            self.data.len()
+        }
+    }
+
+    let data = std::fs::read(file_path("ep.pdf")).unwrap();
+    let len = data.len();
+    let reads = Arc::new(Mutex::new(vec![]));
+    let file = run!(FileOptions::cached().load(Recording { data, reads: reads.clone() }));
+    run!(file.open_first_page_fast());
+
+    // only parts of the file are read, not everything from some offset to the end
+    let reads = reads.lock().unwrap();
+    assert!(reads.iter().all(|r| r.len() < len / 4), "{:?}", reads);
+    assert!(reads.iter().map(|r| r.len()).sum::<usize>() < len / 2);
+}
+
+#[test]
+fn extract_text() {
+    let file = FileOptions::cached().open(file_path("pdf-sample.pdf")).unwrap();
+    let page = file.get_page(0).unwrap();
+    let text = page.extract_text(&file.resolver(), &pdf::text::TextExtractOptions::default()).unwrap();
+    let mut lines = text.lines();
+    assert_eq!(lines.next(), Some("Adobe Acrobat PDF Files"));
+    assert_eq!(lines.next(), Some("Adobe® Portable Document Format (PDF) is a universal file format that preserves all"));
+
+    // form XObjects are included
+    let file = FileOptions::cached().open(file_path("formxobject.pdf")).unwrap();
+    let page = file.get_page(0).unwrap();
+    let text = page.extract_text(&file.resolver(), &pdf::text::TextExtractOptions::default()).unwrap();
+    assert_eq!(text, "Hello World!");
+}
+
+#[test]
+fn content_bbox() {
+    for name in ["pdf-sample.pdf", "formxobject.pdf", "xelatex.pdf"] {
+        let file = run!(FileOptions::cached().open(file_path(name)));
+        let page = run!(file.get_page(0));
+        let bbox = run!(page.content_bbox(&file.resolver())).expect("page has content");
+        let media_box = run!(page.media_box()).normalized();
+        assert_eq!(media_box.intersection(&bbox), Some(bbox));
+        assert!(bbox.width() > 0. && bbox.height() > 0. && bbox.width() < media_box.width());
+    }
+}
+
+#[test]
+fn embedded_fonts() {
+    let file = run!(FileOptions::cached().open(file_path("xelatex.pdf")));
+    let resolver = file.resolver();
+    let page = run!(file.get_page(0));
+    let resources = run!(page.resources());
+    assert!(!resources.fonts.is_empty());
+    for font in resources.fonts.values() {
+        let font = run!(font.load(&resolver));
+        let name = font.name.as_ref().unwrap().as_str();
+        assert!(font.is_embedded());
+        // all fonts are subsets
+        assert_eq!(font.substitute_name(), &name[7..]);
+    }
+}
+
+#[test]
+fn max_file_size() {
+    let limited = |max_file_size| FileOptions::cached().parse_options(ParseOptions { max_file_size, ..ParseOptions::strict() });
+    let len = std::fs::metadata(file_path("example.pdf")).unwrap().len() as usize;
+    assert!(limited(Some(len - 1)).open(file_path("example.pdf")).is_err());
+    run!(limited(Some(len)).open(file_path("example.pdf")));
+}
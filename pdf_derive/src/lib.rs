@@ -62,6 +62,15 @@
 //! ```
 //!
 //!
+//! When writing, entries whose value is `Primitive::Null` (like `None` or an empty `HashMap`) are
+//! left out. A field with the `emit_empty` attribute is written as an empty dictionary instead,
+//! for dictionaries that are required even if they are empty:
+//!
+//! ```ignore
+//! #[pdf(key = "Resources", emit_empty)]
+//! resources: HashMap<Name, Primitive>,
+//! ```
+//!
 //! ## 2. Struct from PDF Stream
 //! PDF Streams consist of a stream dictionary along with the stream itself. It is assumed that all
 //! structs that want to derive Object where the primitive it  converts from is a stream,
@@ -134,6 +143,14 @@ pub fn deepclone(input: TokenStream) -> TokenStream {
     impl_deepclone(&ast)
 }
 
+#[proc_macro_derive(Trace, attributes(pdf))]
+pub fn trace(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+
+    // Build the impl
+    impl_trace(&ast)
+}
+
 
 #[derive(Default)]
 struct FieldAttrs {
@@ -143,6 +160,7 @@ struct FieldAttrs {
     skip: bool,
     other: bool,
     indirect: bool,
+    emit_empty: bool,
 }
 impl FieldAttrs {
     fn new() -> FieldAttrs {
@@ -153,6 +171,7 @@ impl FieldAttrs {
             skip: false,
             other: false,
             indirect: false,
+            emit_empty: false,
         }
     }
     fn key(&self) -> &LitStr {
@@ -198,6 +217,11 @@ impl FieldAttrs {
                     return Ok(());
                 }
 
+                if meta.path.is_ident("emit_empty") {
+                    attrs.emit_empty = true;
+                    return Ok(());
+                }
+
                 Err(meta.error("unsupported key"))
             }).expect("parse error");
         }
@@ -299,6 +323,14 @@ fn impl_deepclone(ast: &DeriveInput) -> TokenStream {
     }
 }
 
+fn impl_trace(ast: &DeriveInput) -> TokenStream {
+    match &ast.data {
+        Data::Struct(ref data) => impl_trace_for_struct(ast, &data.fields).into(),
+        Data::Enum(ref variants) => impl_trace_for_enum(ast, variants).into(),
+        _ => unimplemented!()
+    }
+}
+
 fn enum_pairs(ast: &DeriveInput, data: &DataEnum) -> (Vec<(String, TokenStream2)>, Option<TokenStream2>) {
     let id = &ast.ident;
 
@@ -507,6 +539,67 @@ fn impl_deepclone_for_enum(ast: &DeriveInput, data: &DataEnum) -> SynStream {
     }
 }
 
+fn impl_trace_for_enum(ast: &DeriveInput, data: &DataEnum) -> SynStream {
+    let id = &ast.ident;
+    let mut generics = ast.generics.clone();
+    for g in generics.params.iter_mut() {
+        if let GenericParam::Type(p) = g {
+            p.bounds.push(
+                parse_quote!(pdf::object::Trace)
+            );
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let parts = data.variants.iter().map(|var| {
+        let var_ident = &var.ident;
+        match var.fields {
+            Fields::Unnamed(ref fields) => {
+                let (labels, calls): (Vec<_>, Vec<_>) = fields.unnamed.iter().enumerate().map(|(i, f)| {
+                    let label = Ident::new(&format!("f_{i}"), Span::mixed_site());
+                    let call = if FieldAttrs::parse(&f.attrs).skip {
+                        quote! {}
+                    } else {
+                        quote! { pdf::object::Trace::trace(#label, cb); }
+                    };
+                    (label, call)
+                }).unzip();
+                quote! {
+                    #id::#var_ident( #( ref #labels, )* ) => { #( #calls )* }
+                }
+            }
+            Fields::Named(ref fields) => {
+                let names: Vec<_> = fields.named.iter().map(|f| f.ident.as_ref().unwrap()).collect();
+                let calls = fields.named.iter()
+                    .filter(|f| !FieldAttrs::parse(&f.attrs).skip)
+                    .map(|f| {
+                        let name = f.ident.as_ref().unwrap();
+                        quote! { pdf::object::Trace::trace(#name, cb); }
+                    });
+                quote! {
+                    #id::#var_ident { #( ref #names ),* } => { #( #calls )* }
+                }
+            }
+            Fields::Unit => {
+                quote! {
+                    #id::#var_ident => {}
+                }
+            }
+        }
+    });
+
+    quote! {
+        impl #impl_generics pdf::object::Trace for #id #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn trace(&self, cb: &mut impl FnMut(pdf::object::PlainRef)) {
+                match *self {
+                    #( #parts, )*
+                }
+            }
+        }
+    }
+}
+
 fn impl_enum_from_stream(ast: &DeriveInput, data: &DataEnum, attrs: &GlobalAttrs) -> SynStream {
     let id = &ast.ident;
     let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
@@ -725,8 +818,20 @@ fn impl_objectwrite_for_struct(ast: &DeriveInput, fields: &Fields) -> SynStream
                 quote! { val }
             };
 
+            let empty = if attrs.emit_empty {
+                quote! {
+                    let val = match val {
+                        pdf::primitive::Primitive::Null => pdf::primitive::Primitive::Dictionary(pdf::primitive::Dictionary::new()),
+                        val => val
+                    };
+                }
+            } else {
+                quote!()
+            };
+
             quote! {
                 let val = pdf::object::ObjectWrite::to_primitive(&self.#field, updater)?;
+                #empty
                 if !matches!(val, pdf::primitive::Primitive::Null) {
                     let val2 = #tr;
                     dict.insert(#key, val2);
@@ -811,6 +916,40 @@ fn impl_deepclone_for_struct(ast: &DeriveInput, fields: &Fields) -> SynStream {
     }
 }
 
+fn impl_trace_for_struct(ast: &DeriveInput, fields: &Fields) -> SynStream {
+    let id = &ast.ident;
+    let mut generics = ast.generics.clone();
+    for g in generics.params.iter_mut() {
+        if let GenericParam::Type(p) = g {
+            p.bounds.push(
+                parse_quote!(pdf::object::Trace)
+            );
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let field_parts = fields.iter().enumerate()
+    .filter(|(_, field)| !FieldAttrs::parse(&field.attrs).skip)
+    .map(|(i, field)| {
+        let member = match field.ident {
+            Some(ref name) => Member::Named(name.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        };
+        quote! {
+            pdf::object::Trace::trace(&self.#member, cb);
+        }
+    });
+
+    quote! {
+        impl #impl_generics pdf::object::Trace for #id #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn trace(&self, cb: &mut impl FnMut(pdf::object::PlainRef)) {
+                #( #field_parts )*
+            }
+        }
+    }
+}
+
 /// Note: must have info and dict (TODO explain in docs)
 fn impl_object_for_stream(ast: &DeriveInput, fields: &Fields) -> SynStream {
     let id = &ast.ident;